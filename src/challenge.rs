@@ -1,7 +1,279 @@
+use crate::config::Config;
+use crate::cooldown::{
+    clear_cooldown_at, remaining_cooldown_at, start_cooldown_at, CooldownSettings,
+};
 use crate::error::{AppError, Result};
+use crate::terminal::{read_hidden_line, HiddenInput};
+use crate::timing::format_duration;
+use clap::ValueEnum;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
-use std::io::{self, Write}; // Import Write trait for flush
+use rand::{thread_rng, Rng};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal, Write}; // Import Write trait for flush
+use std::path::Path;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// The kind of challenge a user must pass before unblocking
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChallengeKind {
+    /// Type a sequence of random words exactly.
+    #[default]
+    Words,
+    /// Solve a series of arithmetic problems.
+    Math,
+}
+
+// Settings for the unblock challenge
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChallengeOptions {
+    pub kind: ChallengeKind,
+    // Number of words (or math problems); 0 disables the challenge
+    pub length: usize,
+    // Show the prompts on stderr so stdout only carries machine-readable output (e.g. --json)
+    pub prompt_on_stderr: bool,
+    // Custom words for the words challenge (--wordlist); None uses CHALLENGE_WORDS
+    pub words: Option<Vec<String>>,
+    // Time allowed for the whole challenge (--challenge-timeout); None means no limit
+    pub timeout: Option<Duration>,
+    // Don't echo the answers, and flash the challenge words one at a time instead of leaving
+    // them on screen to copy (--challenge-hidden)
+    pub hidden: bool,
+    // How many times the challenge may be retried before it fails
+    pub attempts: usize,
+    // Where and how long to refuse new challenges once the attempts are used up;
+    // None keeps no cooldown state
+    pub cooldown: Option<CooldownSettings>,
+    // Sentence that must be typed after the challenge is passed (--emergency); None skips the step
+    pub confirmation_phrase: Option<String>,
+    // Phrase from config.toml that must be typed before the challenge, whatever its length;
+    // None skips the step
+    pub commitment_phrase: Option<CommitmentPhrase>,
+    // Lengths config.toml sets for particular domains or labels; they replace `length` for
+    // unblocks that touch them (see for_targets)
+    pub lengths: ChallengeLengths,
+    // Replaces the terminal challenge with the caller's own check (e.g. a dialog in a GUI);
+    // None uses the prompts configured above
+    pub verifier: Option<ChallengeVerifier>,
+}
+
+// A caller-supplied challenge for embedders of the library. It gets the subject (e.g.
+// "example.com") and returns Ok(()) to allow the unblock, or an error (usually
+// AppError::ChallengeFailed) to refuse it.
+#[derive(Clone)]
+pub struct ChallengeVerifier(Arc<VerifyFn>);
+
+type VerifyFn = dyn Fn(&str) -> Result<()> + Send + Sync;
+
+impl ChallengeVerifier {
+    pub fn new(verify: impl Fn(&str) -> Result<()> + Send + Sync + 'static) -> Self {
+        ChallengeVerifier(Arc::new(verify))
+    }
+
+    fn verify(&self, subject: &str) -> Result<()> {
+        (self.0)(subject)
+    }
+}
+
+impl fmt::Debug for ChallengeVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ChallengeVerifier(..)")
+    }
+}
+
+// Two verifiers are equal only if they are the same closure
+impl PartialEq for ChallengeVerifier {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for ChallengeVerifier {}
+
+impl Default for ChallengeOptions {
+    fn default() -> Self {
+        ChallengeOptions {
+            kind: ChallengeKind::default(),
+            length: DEFAULT_CHALLENGE_LENGTH,
+            prompt_on_stderr: false,
+            words: None,
+            timeout: None,
+            hidden: false,
+            attempts: 3,
+            cooldown: None,
+            confirmation_phrase: None,
+            commitment_phrase: None,
+            lengths: ChallengeLengths::default(),
+            verifier: None,
+        }
+    }
+}
+
+impl ChallengeOptions {
+    // Unblocks without any challenge or output. For embedders that have already made sure the
+    // user means it: the caller takes over the responsibility the challenge normally carries.
+    pub fn skip_challenge() -> Self {
+        ChallengeOptions {
+            verifier: Some(ChallengeVerifier::new(|_| Ok(()))),
+            ..Default::default()
+        }
+    }
+
+    // Turns these options into an emergency challenge (unblock --emergency): a fixed, much longer
+    // challenge that --challenge-length can't shorten, followed by a typed confirmation phrase
+    pub fn emergency(mut self) -> Self {
+        self.length = self.length.max(EMERGENCY_CHALLENGE_LENGTH);
+        self.confirmation_phrase = Some(EMERGENCY_CONFIRMATION_PHRASE.to_string());
+        self
+    }
+
+    // These options with the challenge length configured for the (hostname, label) pairs being
+    // unblocked, if any; an emergency challenge still can't get shorter than usual
+    pub fn for_targets<'a>(
+        &self,
+        targets: impl IntoIterator<Item = (&'a str, Option<&'a str>)>,
+    ) -> ChallengeOptions {
+        let mut options = self.clone();
+        if let Some(length) = self.lengths.resolve(targets) {
+            options.length = if self.confirmation_phrase.is_some() {
+                length.max(EMERGENCY_CHALLENGE_LENGTH)
+            } else {
+                length
+            };
+        }
+        options
+    }
+}
+
+// Challenge lengths for particular domains and labels, from config.toml:
+//   [domain_challenge_lengths]
+//   "reddit.com" = 20
+//   [label_challenge_lengths]
+//   social = 12
+// A domain's length also covers its subdomains (www.reddit.com, old.reddit.com).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChallengeLengths {
+    pub domains: BTreeMap<String, usize>,
+    pub labels: BTreeMap<String, usize>,
+}
+
+impl ChallengeLengths {
+    pub fn from_config(config: &Config) -> ChallengeLengths {
+        ChallengeLengths {
+            domains: config
+                .domain_challenge_lengths
+                .iter()
+                .map(|(domain, length)| {
+                    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+                    let domain = domain.strip_prefix("www.").unwrap_or(&domain).to_string();
+                    (domain, usize::from(*length))
+                })
+                .collect(),
+            labels: config
+                .label_challenge_lengths
+                .iter()
+                .map(|(label, length)| (label.clone(), usize::from(*length)))
+                .collect(),
+        }
+    }
+
+    // The longest length that applies to any of the (hostname, label) pairs: a pair takes its
+    // domain's length if one is set, else its label's. None when nothing is configured for them.
+    pub fn resolve<'a>(
+        &self,
+        targets: impl IntoIterator<Item = (&'a str, Option<&'a str>)>,
+    ) -> Option<usize> {
+        targets
+            .into_iter()
+            .filter_map(|(hostname, label)| {
+                self.domain_length(hostname)
+                    .or_else(|| label.and_then(|label| self.labels.get(label).copied()))
+            })
+            .max()
+    }
+
+    // The longest configured length, so a --wordlist can be checked against it up front
+    pub fn longest(&self) -> Option<usize> {
+        self.domains
+            .values()
+            .chain(self.labels.values())
+            .copied()
+            .max()
+    }
+
+    fn domain_length(&self, hostname: &str) -> Option<usize> {
+        let hostname = hostname.to_lowercase();
+        self.domains
+            .iter()
+            .filter(|(domain, _)| {
+                hostname == **domain
+                    || hostname
+                        .strip_suffix(domain.as_str())
+                        .is_some_and(|prefix| prefix.ends_with('.'))
+            })
+            // The most specific domain wins (old.reddit.com over reddit.com)
+            .max_by_key(|(domain, _)| domain.len())
+            .map(|(_, length)| *length)
+    }
+}
+
+// A fixed phrase to type before every unblock, as a personal commitment device. From config.toml:
+//   unblock_confirmation_phrase = "I accept the consequences"
+//   unblock_confirmation_case_sensitive = false
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitmentPhrase {
+    pub phrase: String,
+    pub case_sensitive: bool,
+}
+
+impl CommitmentPhrase {
+    // None when no phrase (or only whitespace) is configured
+    pub fn from_config(config: &Config) -> Option<CommitmentPhrase> {
+        let phrase = config.unblock_confirmation_phrase.as_deref()?.trim();
+        (!phrase.is_empty()).then(|| CommitmentPhrase {
+            phrase: phrase.to_string(),
+            case_sensitive: config.unblock_confirmation_case_sensitive.unwrap_or(true),
+        })
+    }
+
+    // Whether a typed line is the phrase: surrounding whitespace doesn't count, and neither does
+    // case unless the phrase is case-sensitive
+    fn matches(&self, typed: &str) -> bool {
+        let typed = typed.trim();
+        if self.case_sensitive {
+            typed == self.phrase
+        } else {
+            typed.to_lowercase() == self.phrase.to_lowercase()
+        }
+    }
+}
+
+// Words (or math problems) in a challenge unless --challenge-length or config.toml says otherwise
+pub const DEFAULT_CHALLENGE_LENGTH: usize = 5;
+// Words (or math problems) in an emergency challenge
+pub const EMERGENCY_CHALLENGE_LENGTH: usize = 30;
+const EMERGENCY_CONFIRMATION_PHRASE: &str = "I am choosing to break my focus";
+// How long each word of a --challenge-hidden challenge stays on screen
+const HIDDEN_WORD_TIME: Duration = Duration::from_millis(1500);
+
+// Environment variable that lets `unblock --force` skip the challenge from a terminal
+pub const ALLOW_FORCE_ENV_VAR: &str = "GWD_ALLOW_FORCE";
+
+// Whether unblock --force may skip the challenge: only for scripts (stdin isn't a terminal) or
+// when GWD_ALLOW_FORCE=1 is set, so it isn't a casual way around the challenge
+pub fn force_allowed() -> bool {
+    force_allowed_with(
+        io::stdin().is_terminal(),
+        std::env::var(ALLOW_FORCE_ENV_VAR).ok().as_deref(),
+    )
+}
+
+fn force_allowed_with(stdin_is_terminal: bool, allow_force: Option<&str>) -> bool {
+    !stdin_is_terminal || allow_force == Some("1")
+}
 
 // Same word list as the PowerShell script
 const CHALLENGE_WORDS: &[&str] = &[
@@ -519,42 +791,597 @@ const CHALLENGE_WORDS: &[&str] = &[
     "yourself",
 ];
 
-pub fn run_challenge(domain: &str, word_count: usize) -> Result<()> {
-    if word_count == 0 {
+pub fn run_challenge(domain: &str, options: &ChallengeOptions) -> Result<()> {
+    // A caller-supplied check replaces everything below, prompts and cooldowns alike
+    if let Some(verifier) = &options.verifier {
+        return verifier.verify(domain);
+    }
+    let mut out: Box<dyn Write> = if options.prompt_on_stderr {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    };
+    if options.length == 0 && options.commitment_phrase.is_none() {
         // Skip challenge if count is zero
-        println!("Challenge length set to 0, skipping challenge.");
+        writeln!(out, "Challenge length set to 0, skipping challenge.")?;
         return Ok(());
     }
-    if word_count > CHALLENGE_WORDS.len() {
-        eprintln!("Warning: Challenge length ({}) is greater than the number of available words ({}). Using all words.", word_count, CHALLENGE_WORDS.len());
+    let input = ChallengeInput {
+        deadline: options.timeout.map(|timeout| Instant::now() + timeout),
+        hidden: options.hidden,
+    };
+    if let Some(timeout) = options.timeout {
+        writeln!(
+            out,
+            "Time limit: {}s for the whole challenge.",
+            timeout.as_secs()
+        )?;
+    }
+    // A subject that used up its attempts recently is refused before any prompt is shown
+    if let Some(cooldown) = &options.cooldown {
+        if let Some(remaining) = remaining_cooldown_at(&cooldown.path, domain)? {
+            return Err(AppError::CoolingDown {
+                subject: domain.to_string(),
+                remaining,
+            });
+        }
+    }
+    // The phrase comes first and has a single try; a mismatch doesn't start a cooldown
+    if let Some(commitment) = &options.commitment_phrase {
+        let result = run_commitment(domain, commitment, &input, &mut out);
+        if matches!(result, Err(AppError::ChallengeTimedOut)) {
+            writeln!(out)?;
+        }
+        result?;
+        if options.length == 0 {
+            writeln!(out, "Challenge length set to 0, skipping challenge.")?;
+            return Ok(());
+        }
+    }
+
+    let attempts = options.attempts.max(1);
+    // The words themselves are never logged; the log must not give the answer away
+    tracing::debug!(
+        subject = domain,
+        kind = ?options.kind,
+        length = options.length,
+        attempts,
+        "starting the challenge"
+    );
+    let mut result = Err(AppError::ChallengeFailed);
+    for attempt in 1..=attempts {
+        // Every attempt gets a fresh sequence (or fresh problems)
+        result = match options.kind {
+            ChallengeKind::Words => match &options.words {
+                Some(words) => {
+                    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+                    run_words_challenge(domain, options.length, &words, &input, &mut out)
+                }
+                None => {
+                    run_words_challenge(domain, options.length, CHALLENGE_WORDS, &input, &mut out)
+                }
+            },
+            ChallengeKind::Math => run_math_challenge(domain, options.length, &input, &mut out),
+        };
+        match &result {
+            Err(AppError::ChallengeFailed) if attempt < attempts => {
+                writeln!(out, "Incorrect. Attempts left: {}.", attempts - attempt)?;
+            }
+            _ => break,
+        }
+    }
+    if let (Ok(()), Some(phrase)) = (&result, &options.confirmation_phrase) {
+        result = run_confirmation(domain, phrase, &input, &mut out);
+    }
+    if matches!(result, Err(AppError::ChallengeTimedOut)) {
+        // The prompt line was never finished; end it so the shell prompt starts on a fresh line
+        writeln!(out)?;
+    }
+
+    tracing::debug!(subject = domain, passed = result.is_ok(), "challenge over");
+
+    if let Some(cooldown) = &options.cooldown {
+        match &result {
+            Ok(()) => clear_cooldown_at(&cooldown.path, domain)?,
+            // Ctrl-C gives up like killing gwd would, without using up the attempts
+            Err(AppError::Interrupted) => {}
+            Err(_) => {
+                let duration = start_cooldown_at(&cooldown.path, domain, cooldown.base)?;
+                writeln!(
+                    out,
+                    "No attempts left; unblocking {} is refused for the next {}.",
+                    domain,
+                    format_duration(&duration)
+                )?;
+            }
+        }
+    }
+    result
+}
+
+// Reads the user's answers from stdin, enforcing the challenge's deadline if it has one
+struct ChallengeInput {
+    deadline: Option<Instant>,
+    // Read without echo (--challenge-hidden)
+    hidden: bool,
+}
+
+impl ChallengeInput {
+    // Reads one line. With a deadline, stdin is read on a helper thread so the wait can be cut
+    // short; whatever was typed when time runs out is discarded and the challenge fails.
+    fn read_line(&self) -> Result<String> {
+        let remaining = match self.deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(AppError::ChallengeTimedOut);
+                }
+                Some(remaining)
+            }
+            None => None,
+        };
+        // The guard stays on this thread, so echo comes back even when time runs out while the
+        // helper thread is still waiting for input
+        let hidden_input = if self.hidden {
+            HiddenInput::enable()?
+        } else {
+            None
+        };
+        let hidden = hidden_input.is_some();
+        let read = move || -> Result<String> {
+            if hidden {
+                let line = read_hidden_line(&mut io::stdin().lock());
+                // The Enter key wasn't echoed either
+                eprintln!();
+                return line;
+            }
+            let mut line = String::new();
+            io::stdin().read_line(&mut line)?;
+            Ok(line)
+        };
+        let Some(remaining) = remaining else {
+            return read();
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        // If time runs out the thread stays blocked on stdin; gwd exits right after, ending it
+        thread::spawn(move || {
+            let _ = sender.send(read());
+        });
+        match receiver.recv_timeout(remaining) {
+            Ok(result) => result,
+            Err(_) => Err(AppError::ChallengeTimedOut),
+        }
+    }
+}
+
+// Reads a custom challenge wordlist: one word (or phrase) per line, blank lines ignored.
+// Fails if the file has fewer distinct entries than the challenge needs.
+pub fn load_wordlist(path: &Path, word_count: usize) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| AppError::Config(format!("Failed to read wordlist {:?}: {}", path, e)))?;
+    let mut words: Vec<String> = Vec::new();
+    for line in content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        if !words.iter().any(|word| word == line) {
+            words.push(line.to_string());
+        }
+    }
+    if words.is_empty() {
+        return Err(AppError::Config(format!("Wordlist {:?} is empty", path)));
+    }
+    if words.len() < word_count {
+        return Err(AppError::Config(format!(
+            "Wordlist {:?} has {} distinct entries, but the challenge needs {}",
+            path,
+            words.len(),
+            word_count
+        )));
+    }
+    Ok(words)
+}
+
+// First step of every unblock when config.toml sets unblock_confirmation_phrase
+fn run_commitment(
+    domain: &str,
+    commitment: &CommitmentPhrase,
+    input: &ChallengeInput,
+    out: &mut dyn Write,
+) -> Result<()> {
+    writeln!(
+        out,
+        "Before unblocking '{}', type your commitment phrase:",
+        domain
+    )?;
+    writeln!(out, "{}", commitment.phrase)?;
+    write!(out, "Enter the phrase: ")?;
+    out.flush()?;
+
+    if commitment.matches(&input.read_line()?) {
+        Ok(())
+    } else {
+        Err(AppError::ChallengeFailed)
+    }
+}
+
+// Final step of an emergency unblock: the user has to spell out what they are doing
+fn run_confirmation(
+    domain: &str,
+    phrase: &str,
+    input: &ChallengeInput,
+    out: &mut dyn Write,
+) -> Result<()> {
+    writeln!(
+        out,
+        "To confirm unblocking '{}', type the following sentence EXACTLY:",
+        domain
+    )?;
+    writeln!(out, "{}", phrase)?;
+    write!(out, "Enter the sentence: ")?;
+    out.flush()?;
+
+    if input.read_line()?.trim() == phrase {
+        Ok(())
+    } else {
+        Err(AppError::ChallengeFailed)
+    }
+}
+
+fn run_words_challenge(
+    domain: &str,
+    word_count: usize,
+    words: &[&str],
+    input: &ChallengeInput,
+    out: &mut dyn Write,
+) -> Result<()> {
+    if word_count > words.len() {
+        eprintln!("Warning: Challenge length ({}) is greater than the number of available words ({}). Using all words.", word_count, words.len());
         // Proceed with the maximum number of words instead of erroring out
     }
 
     let mut rng = thread_rng();
-    // Use min to avoid panic if word_count > CHALLENGE_WORDS.len()
-    let challenge_sequence: Vec<&str> = CHALLENGE_WORDS
-        .choose_multiple(&mut rng, word_count.min(CHALLENGE_WORDS.len()))
+    // Use min to avoid panic if word_count > words.len()
+    let challenge_sequence: Vec<&str> = words
+        .choose_multiple(&mut rng, word_count.min(words.len()))
         .cloned()
         .collect();
     let challenge_string = challenge_sequence.join(" ");
 
-    println!("--- Unblock Challenge ---");
-    println!(
-        "To proceed with unblocking '{}', please type the following sequence EXACTLY:",
-        domain
-    );
-    println!("{}", challenge_string);
-    println!("-------------------------");
-    print!("Enter the sequence: ");
-    io::stdout().flush()?; // Ensure the prompt is displayed before reading input
+    writeln!(out, "--- Unblock Challenge ---")?;
+    if input.hidden {
+        writeln!(
+            out,
+            "To proceed with unblocking '{}', memorize the following {} words; each is shown only briefly:",
+            domain,
+            challenge_sequence.len()
+        )?;
+        flash_words(&challenge_sequence, out)?;
+        writeln!(out, "-------------------------")?;
+        write!(out, "Enter the sequence (your typing stays hidden): ")?;
+    } else {
+        writeln!(
+            out,
+            "To proceed with unblocking '{}', please type the following sequence EXACTLY:",
+            domain
+        )?;
+        writeln!(out, "{}", challenge_string)?;
+        writeln!(out, "-------------------------")?;
+        write!(out, "Enter the sequence: ")?;
+    }
+    out.flush()?; // Ensure the prompt is displayed before reading input
 
-    let mut user_input = String::new();
-    io::stdin().read_line(&mut user_input)?;
+    let user_input = input.read_line()?;
 
     if user_input.trim() == challenge_string {
-        println!("Challenge passed!");
+        writeln!(out, "Challenge passed!")?;
         Ok(())
     } else {
         Err(AppError::ChallengeFailed)
     }
 }
+
+// Shows the words of a hidden challenge one at a time on the same line, each replacing the last,
+// and clears the line after the final one
+fn flash_words(words: &[&str], out: &mut dyn Write) -> Result<()> {
+    for (index, word) in words.iter().enumerate() {
+        write!(out, "\r\x1b[2K{}/{}: {}", index + 1, words.len(), word)?;
+        out.flush()?;
+        thread::sleep(HIDDEN_WORD_TIME);
+    }
+    write!(out, "\r\x1b[2K")?;
+    Ok(())
+}
+
+fn run_math_challenge(
+    domain: &str,
+    problem_count: usize,
+    input: &ChallengeInput,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let mut rng = thread_rng();
+
+    writeln!(out, "--- Unblock Challenge ---")?;
+    writeln!(
+        out,
+        "To proceed with unblocking '{}', please solve the following {} problems:",
+        domain, problem_count
+    )?;
+    writeln!(out, "-------------------------")?;
+
+    for index in 1..=problem_count {
+        let (problem, answer) = generate_math_problem(&mut rng, problem_count);
+        write!(out, "Problem {}/{}: {} = ", index, problem_count, problem)?;
+        out.flush()?; // Ensure the prompt is displayed before reading input
+
+        let user_input = input.read_line()?;
+
+        // Any wrong (or unparseable) answer fails the whole challenge
+        if user_input.trim().parse::<i64>().ok() != Some(answer) {
+            return Err(AppError::ChallengeFailed);
+        }
+    }
+
+    writeln!(out, "Challenge passed!")?;
+    Ok(())
+}
+
+// Generates one arithmetic problem and its answer.
+// Longer challenges get larger operands, and multiplication from 3 problems upwards.
+fn generate_math_problem(rng: &mut impl Rng, difficulty: usize) -> (String, i64) {
+    let difficulty = difficulty.max(1) as i64;
+    let max_operand = 10 * (difficulty + 1);
+    let max_factor = 5 + difficulty;
+    let operation_count = if difficulty >= 3 { 3 } else { 2 };
+
+    match rng.gen_range(0..operation_count) {
+        0 => {
+            let (a, b) = (
+                rng.gen_range(1..=max_operand),
+                rng.gen_range(1..=max_operand),
+            );
+            (format!("{} + {}", a, b), a + b)
+        }
+        1 => {
+            let (a, b) = (
+                rng.gen_range(1..=max_operand),
+                rng.gen_range(1..=max_operand),
+            );
+            // Keep the result non-negative
+            let (a, b) = (a.max(b), a.min(b));
+            (format!("{} - {}", a, b), a - b)
+        }
+        _ => {
+            let (a, b) = (rng.gen_range(2..=max_factor), rng.gen_range(2..=max_factor));
+            (format!("{} * {}", a, b), a * b)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_challenge_length_precedence() {
+        let config = Config {
+            challenge_length: Some(5),
+            domain_challenge_lengths: [
+                ("www.Reddit.com".to_string(), 20),
+                ("old.reddit.com".to_string(), 30),
+                ("localhost-test.dev".to_string(), 2),
+            ]
+            .into_iter()
+            .collect(),
+            label_challenge_lengths: [("social".to_string(), 12)].into_iter().collect(),
+            ..Default::default()
+        };
+        let options = ChallengeOptions {
+            length: 5,
+            lengths: ChallengeLengths::from_config(&config),
+            ..Default::default()
+        };
+        let length =
+            |targets: &[(&str, Option<&str>)]| options.for_targets(targets.iter().copied()).length;
+
+        // Per-domain config beats per-label config, and covers subdomains
+        assert_eq!(length(&[("reddit.com", Some("social"))]), 20);
+        assert_eq!(length(&[("www.reddit.com", None)]), 20);
+        assert_eq!(length(&[("old.reddit.com", None)]), 30);
+        assert_eq!(length(&[("localhost-test.dev", Some("social"))]), 2);
+        // Per-label config beats the global default
+        assert_eq!(length(&[("x.com", Some("social"))]), 12);
+        assert_eq!(length(&[("x.com", Some("work")), ("example.com", None)]), 5);
+        // Unblocking several sites at once takes the longest
+        assert_eq!(
+            length(&[("localhost-test.dev", None), ("x.com", Some("social"))]),
+            12
+        );
+        assert_eq!(
+            ChallengeLengths::default().resolve([("reddit.com", None)]),
+            None
+        );
+        assert_eq!(options.lengths.longest(), Some(30));
+
+        // An emergency challenge never gets shorter than usual
+        let emergency = options.clone().emergency();
+        assert_eq!(
+            emergency.for_targets([("localhost-test.dev", None)]).length,
+            EMERGENCY_CHALLENGE_LENGTH
+        );
+    }
+
+    #[test]
+    fn test_commitment_phrase() {
+        let config = Config {
+            unblock_confirmation_phrase: Some("  I accept the consequences \n".to_string()),
+            ..Default::default()
+        };
+        let phrase = CommitmentPhrase::from_config(&config).unwrap();
+        assert_eq!(phrase.phrase, "I accept the consequences");
+        assert!(phrase.case_sensitive);
+        assert!(phrase.matches("I accept the consequences"));
+        // Surrounding whitespace (and the line ending) is ignored, but not what's in between
+        assert!(phrase.matches("\t I accept the consequences  \r\n"));
+        assert!(!phrase.matches("I  accept the consequences"));
+        assert!(!phrase.matches("i accept the consequences"));
+        assert!(!phrase.matches("I accept the consequence"));
+        assert!(!phrase.matches(""));
+
+        let config = Config {
+            unblock_confirmation_case_sensitive: Some(false),
+            ..config
+        };
+        let phrase = CommitmentPhrase::from_config(&config).unwrap();
+        assert!(phrase.matches("i ACCEPT the consequences\n"));
+        assert!(!phrase.matches("i accept"));
+
+        let blank = Config {
+            unblock_confirmation_phrase: Some("   ".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(CommitmentPhrase::from_config(&blank), None);
+        assert_eq!(CommitmentPhrase::from_config(&Config::default()), None);
+    }
+
+    #[test]
+    fn test_force_needs_a_script_or_the_env_var() {
+        assert!(force_allowed_with(false, None));
+        assert!(force_allowed_with(true, Some("1")));
+        assert!(!force_allowed_with(true, None));
+        assert!(!force_allowed_with(true, Some("yes")));
+    }
+
+    // Evaluates a generated "a op b" problem independently of the generator
+    fn evaluate(problem: &str) -> i64 {
+        let parts: Vec<&str> = problem.split_whitespace().collect();
+        let (a, b): (i64, i64) = (parts[0].parse().unwrap(), parts[2].parse().unwrap());
+        match parts[1] {
+            "+" => a + b,
+            "-" => a - b,
+            "*" => a * b,
+            op => panic!("unexpected operator {}", op),
+        }
+    }
+
+    #[test]
+    fn test_generate_math_problem_answer_is_correct() {
+        let mut rng = thread_rng();
+        for difficulty in [1, 5, 20] {
+            for _ in 0..100 {
+                let (problem, answer) = generate_math_problem(&mut rng, difficulty);
+                assert_eq!(evaluate(&problem), answer, "{}", problem);
+                assert!(answer >= 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_challenge_input_fails_once_deadline_passed() {
+        let input = ChallengeInput {
+            deadline: Some(Instant::now()),
+            hidden: false,
+        };
+        assert!(matches!(
+            input.read_line(),
+            Err(AppError::ChallengeTimedOut)
+        ));
+    }
+
+    #[test]
+    fn test_run_challenge_refuses_during_cooldown() {
+        let dir = tempfile::tempdir().unwrap();
+        let cooldown = CooldownSettings {
+            path: dir.path().join("cooldowns.json"),
+            base: Duration::from_secs(300),
+        };
+        start_cooldown_at(&cooldown.path, "a.com", cooldown.base).unwrap();
+        let options = ChallengeOptions {
+            prompt_on_stderr: true,
+            cooldown: Some(cooldown),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            run_challenge("a.com", &options),
+            Err(AppError::CoolingDown { .. })
+        ));
+    }
+
+    #[test]
+    fn test_verifier_replaces_the_prompts() {
+        let refuse = ChallengeOptions {
+            verifier: Some(ChallengeVerifier::new(|subject| {
+                assert_eq!(subject, "a.com");
+                Err(AppError::ChallengeFailed)
+            })),
+            ..Default::default()
+        };
+        assert!(matches!(
+            run_challenge("a.com", &refuse),
+            Err(AppError::ChallengeFailed)
+        ));
+        assert!(run_challenge("a.com", &ChallengeOptions::skip_challenge()).is_ok());
+    }
+
+    #[test]
+    fn test_emergency_options() {
+        let options = ChallengeOptions {
+            length: 0,
+            ..Default::default()
+        }
+        .emergency();
+        assert_eq!(options.length, EMERGENCY_CHALLENGE_LENGTH);
+        assert_eq!(
+            options.confirmation_phrase.as_deref(),
+            Some(EMERGENCY_CONFIRMATION_PHRASE)
+        );
+
+        let options = ChallengeOptions {
+            length: 50,
+            ..Default::default()
+        }
+        .emergency();
+        assert_eq!(options.length, 50);
+    }
+
+    #[test]
+    fn test_load_wordlist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("words.txt");
+
+        fs::write(&path, "focus\n\n  ship it  \nfocus\nrest\n").unwrap();
+        assert_eq!(
+            load_wordlist(&path, 3).unwrap(),
+            vec![
+                "focus".to_string(),
+                "ship it".to_string(),
+                "rest".to_string()
+            ]
+        );
+        assert!(matches!(load_wordlist(&path, 4), Err(AppError::Config(_))));
+
+        fs::write(&path, "\n  \n").unwrap();
+        assert!(matches!(load_wordlist(&path, 1), Err(AppError::Config(_))));
+        assert!(matches!(
+            load_wordlist(&dir.path().join("missing.txt"), 1),
+            Err(AppError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_generate_math_problem_scales_with_difficulty() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let (problem, _) = generate_math_problem(&mut rng, 1);
+            assert!(!problem.contains('*'), "{}", problem);
+            let operands: Vec<i64> = problem
+                .split_whitespace()
+                .filter_map(|part| part.parse().ok())
+                .collect();
+            assert!(operands.iter().all(|&n| n <= 20), "{}", problem);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use crate::error::{AppError, Result};
+use crate::hooks::Hooks;
+use crate::hosts::CommentStyle;
+use crate::profile::config_dir;
+use crate::schedule::Period;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+// Defaults from config.toml, e.g.
+//   challenge_length = 8
+//   redirect_ip = "127.0.0.1"
+//   add_www = false
+//   flush_dns = false
+//   dns_flush_command = "sudo systemctl restart nscd"
+//   history_limit = 1000
+//   comment_style = "json"
+//   unblock_confirmation_phrase = "I accept the consequences"
+//   unblock_confirmation_case_sensitive = false
+//   extra_hosts_files = ["/etc/hosts.d/blocklist"]
+//   protected_hostnames = ["nas.lan"]
+//   sinkhole_ips = ["127.0.0.1", "192.168.1.2"]
+//   max_entries = 20000
+//   [domain_challenge_lengths]
+//   "reddit.com" = 20
+//   [label_challenge_lengths]
+//   social = 12
+//   [[no_unblock]]
+//   days = "mon-fri"
+//   start = "00:00"
+//   end = "18:00"
+//   [hooks]
+//   post_block = ["notify-send", "Blocked {domain}"]
+// A value left out falls back to the built-in default; command-line flags override both.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Words (or math problems) in the unblock challenge (--challenge-length)
+    pub challenge_length: Option<u16>,
+    // IP blocked domains are redirected to (--redirect)
+    pub redirect_ip: Option<String>,
+    // Whether blocking also adds the 'www.' variant (false acts like --no-www)
+    pub add_www: Option<bool>,
+    // Whether to flush the DNS cache after a change (false acts like --no-flush)
+    pub flush_dns: Option<bool>,
+    // Shell command run to flush the DNS cache instead of the built-in one for this OS
+    pub dns_flush_command: Option<String>,
+    // Records kept in history.jsonl before the oldest are rotated out (DEFAULT_HISTORY_LIMIT)
+    pub history_limit: Option<usize>,
+    // How new entries record their metadata in the hosts file (--comment-style)
+    pub comment_style: Option<CommentStyle>,
+    // Phrase to type before every unblock's challenge, and whether its case matters (it does
+    // unless set to false)
+    pub unblock_confirmation_phrase: Option<String>,
+    pub unblock_confirmation_case_sensitive: Option<bool>,
+    // Further hosts files that list and status also read, e.g. ones merged into the hosts file by
+    // other tooling. gwd never writes to them: block and unblock only edit the main hosts file.
+    pub extra_hosts_files: Vec<PathBuf>,
+    // Hostnames block refuses without --force, on top of localhost and the like
+    pub protected_hostnames: Vec<String>,
+    // Addresses besides 0.0.0.0 and :: that mean a line gwd didn't write blocks its hostnames
+    // (another blocker's sinkhole); they only change what counts as blocked, never what gwd writes
+    pub sinkhole_ips: Vec<IpAddr>,
+    // Most domains one block or import may add (--max-entries); 0 means no cap
+    pub max_entries: Option<usize>,
+    // Challenge lengths for unblocking particular domains (and their subdomains), or entries with
+    // a particular label; --challenge-length still overrides them
+    pub domain_challenge_lengths: BTreeMap<String, u16>,
+    pub label_challenge_lengths: BTreeMap<String, u16>,
+    // Times of the week during which nothing may be unblocked (unblock --emergency excepted)
+    pub no_unblock: Vec<Period>,
+    // Commands to run around blocking and unblocking
+    pub hooks: Hooks,
+}
+
+// Location of the config file, next to profiles.toml
+pub fn config_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join(CONFIG_FILE_NAME))
+}
+
+// Loads the config file; a missing file means every built-in default applies
+pub fn load_config() -> Result<Config> {
+    let path = config_path()?;
+    match fs::read_to_string(&path) {
+        Ok(content) => {
+            tracing::debug!(path = ?path, "loading the config file");
+            parse_config(&content)
+                .map_err(|e| AppError::Config(format!("Invalid config file {:?}: {}", path, e)))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            tracing::debug!(path = ?path, "no config file; using the defaults");
+            Ok(Config::default())
+        }
+        Err(e) => Err(AppError::Io(format!(
+            "Failed to read config file {:?}: {}",
+            path, e
+        ))),
+    }
+}
+
+fn parse_config(content: &str) -> std::result::Result<Config, String> {
+    toml::from_str(content).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        assert_eq!(parse_config("").unwrap(), Config::default());
+        assert_eq!(
+            parse_config("challenge_length = 8\nredirect_ip = \"127.0.0.1\"\nadd_www = false\n")
+                .unwrap(),
+            Config {
+                challenge_length: Some(8),
+                redirect_ip: Some("127.0.0.1".to_string()),
+                add_www: Some(false),
+                flush_dns: None,
+                dns_flush_command: None,
+                history_limit: None,
+                comment_style: None,
+                unblock_confirmation_phrase: None,
+                unblock_confirmation_case_sensitive: None,
+                extra_hosts_files: Vec::new(),
+                protected_hostnames: Vec::new(),
+                sinkhole_ips: Vec::new(),
+                max_entries: None,
+                domain_challenge_lengths: BTreeMap::new(),
+                label_challenge_lengths: BTreeMap::new(),
+                no_unblock: Vec::new(),
+                hooks: Hooks::default(),
+            }
+        );
+        assert_eq!(
+            parse_config("[hooks]\npre_block = [\"start-timer\", \"{domain}\"]\n")
+                .unwrap()
+                .hooks
+                .pre_block,
+            Some(vec!["start-timer".to_string(), "{domain}".to_string()])
+        );
+        assert_eq!(
+            parse_config("history_limit = 1000\n")
+                .unwrap()
+                .history_limit,
+            Some(1000)
+        );
+        let config = parse_config(
+            "[domain_challenge_lengths]\n\"reddit.com\" = 20\n[label_challenge_lengths]\nsocial = 12\n",
+        )
+        .unwrap();
+        assert_eq!(config.domain_challenge_lengths["reddit.com"], 20);
+        assert_eq!(config.label_challenge_lengths["social"], 12);
+        let config = parse_config(
+            "[[no_unblock]]\ndays = \"mon-fri\"\nstart = \"00:00\"\nend = \"18:00\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.no_unblock[0].days.len(), 5);
+        assert!(parse_config("[[no_unblock]]\nstart = \"6pm\"\nend = \"18:00\"\n").is_err());
+        assert_eq!(
+            parse_config("dns_flush_command = \"sudo systemctl restart nscd\"\n")
+                .unwrap()
+                .dns_flush_command
+                .as_deref(),
+            Some("sudo systemctl restart nscd")
+        );
+        assert_eq!(
+            parse_config("comment_style = \"json\"\n")
+                .unwrap()
+                .comment_style,
+            Some(CommentStyle::Json)
+        );
+        assert!(parse_config("comment_style = \"yaml\"\n").is_err());
+        assert_eq!(
+            parse_config("extra_hosts_files = [\"/etc/hosts.d/blocklist\"]\n")
+                .unwrap()
+                .extra_hosts_files,
+            vec![PathBuf::from("/etc/hosts.d/blocklist")]
+        );
+        assert_eq!(
+            parse_config("sinkhole_ips = [\"127.0.0.1\", \"::1\"]\n")
+                .unwrap()
+                .sinkhole_ips,
+            vec![
+                IpAddr::from([127, 0, 0, 1]),
+                IpAddr::from(std::net::Ipv6Addr::LOCALHOST)
+            ]
+        );
+        assert!(parse_config("sinkhole_ips = [\"pi.hole\"]\n").is_err());
+        assert_eq!(
+            parse_config("max_entries = 20000\n").unwrap().max_entries,
+            Some(20000)
+        );
+        assert!(parse_config("max_entries = -1\n").is_err());
+        // A typo shouldn't be silently ignored
+        assert!(parse_config("challenge_lenght = 8\n").is_err());
+        assert!(parse_config("add_www = \"no\"\n").is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use crate::error::{AppError, Result};
+use crate::history::state_dir;
+use crate::timing::now_timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const COOLDOWN_FILE_NAME: &str = "cooldowns.json";
+// Escalation stops doubling here, so a bad streak never locks a site away for good
+const MAX_COOLDOWN: Duration = Duration::from_secs(24 * 3_600);
+// Unblocks of a domain this recent make its next challenge longer ("cooling")
+const COOLING_WINDOW: i64 = 24 * 3_600;
+// Challenge words (or math problems) added for every recent unblock of the same domain
+pub const COOLING_STEP: usize = 5;
+
+// Where cooldowns are kept and how long the first one lasts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CooldownSettings {
+    pub path: PathBuf,
+    pub base: Duration,
+}
+
+// The cooldown state of one challenge subject (e.g. "example.com")
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct CooldownRecord {
+    // Unix timestamp the cooldown ends at
+    #[serde(default)]
+    until: i64,
+    // How many times in a row the challenge attempts were used up; each one doubles the cooldown
+    #[serde(default)]
+    strikes: u32,
+    // Unix timestamps of the domain's unblocks within COOLING_WINDOW, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    unblocks: Vec<i64>,
+}
+
+// Location of the cooldown state file, next to the history journal
+pub fn cooldown_path() -> Result<PathBuf> {
+    Ok(state_dir()?.join(COOLDOWN_FILE_NAME))
+}
+
+// Time left before `subject` may be challenged again, or None if it isn't cooling down
+pub fn remaining_cooldown_at(path: &Path, subject: &str) -> Result<Option<Duration>> {
+    let now = now_timestamp();
+    Ok(read_records(path)?
+        .get(subject)
+        .filter(|record| record.until > now)
+        .map(|record| Duration::from_secs((record.until - now) as u64)))
+}
+
+// Starts a cooldown for `subject` after its attempts ran out: `base` the first time, doubling for
+// every further strike up to MAX_COOLDOWN. Returns the cooldown applied.
+pub fn start_cooldown_at(path: &Path, subject: &str, base: Duration) -> Result<Duration> {
+    let mut records = read_records(path)?;
+    let record = records.entry(subject.to_string()).or_default();
+    let factor = 2u32.saturating_pow(record.strikes);
+    let cooldown = base.saturating_mul(factor).min(MAX_COOLDOWN);
+    record.strikes += 1;
+    record.until = now_timestamp() + cooldown.as_secs() as i64;
+    write_records(path, &records)?;
+    Ok(cooldown)
+}
+
+// Forgets `subject`'s cooldown and strikes once it passes the challenge. Its recent unblocks
+// still count towards cooling.
+pub fn clear_cooldown_at(path: &Path, subject: &str) -> Result<()> {
+    let mut records = read_records(path)?;
+    let Some(record) = records.get_mut(subject) else {
+        return Ok(());
+    };
+    if record.unblocks.is_empty() {
+        records.remove(subject);
+    } else if record.until != 0 || record.strikes != 0 {
+        record.until = 0;
+        record.strikes = 0;
+    } else {
+        return Ok(());
+    }
+    write_records(path, &records)
+}
+
+// How many times `domain` was unblocked within COOLING_WINDOW
+pub fn recent_unblocks_at(path: &Path, domain: &str) -> Result<usize> {
+    let since = now_timestamp() - COOLING_WINDOW;
+    Ok(read_records(path)?.get(domain).map_or(0, |record| {
+        record.unblocks.iter().filter(|time| **time > since).count()
+    }))
+}
+
+// Notes an unblock of `domain` for cooling, forgetting the ones that no longer count
+pub fn record_unblock_at(path: &Path, domain: &str) -> Result<()> {
+    let now = now_timestamp();
+    let mut records = read_records(path)?;
+    let record = records.entry(domain.to_string()).or_default();
+    record.unblocks.retain(|time| *time > now - COOLING_WINDOW);
+    record.unblocks.push(now);
+    write_records(path, &records)
+}
+
+// Forgets `domain`'s recent unblocks (gwd reset-cooling), leaving any cooldown running. Returns
+// whether there were any.
+pub fn reset_cooling_at(path: &Path, domain: &str) -> Result<bool> {
+    let mut records = read_records(path)?;
+    let Some(record) = records.get_mut(domain) else {
+        return Ok(false);
+    };
+    if record.unblocks.is_empty() {
+        return Ok(false);
+    }
+    record.unblocks.clear();
+    if record.until == 0 && record.strikes == 0 {
+        records.remove(domain);
+    }
+    write_records(path, &records)?;
+    Ok(true)
+}
+
+// The challenge length for unblocking a domain that was unblocked `recent_unblocks` times within
+// COOLING_WINDOW: COOLING_STEP more for each. A challenge turned off (0) stays off.
+pub fn cooled_length(length: usize, recent_unblocks: usize) -> usize {
+    if length == 0 {
+        return 0;
+    }
+    length.saturating_add(COOLING_STEP.saturating_mul(recent_unblocks))
+}
+
+// A missing file means nothing is cooling down
+fn read_records(path: &Path) -> Result<BTreeMap<String, CooldownRecord>> {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|e| AppError::Config(format!("Invalid cooldown file {:?}: {}", path, e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(AppError::Io(format!(
+            "Failed to read cooldown file {:?}: {}",
+            path, e
+        ))),
+    }
+}
+
+fn write_records(path: &Path, records: &BTreeMap<String, CooldownRecord>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content = serde_json::to_string_pretty(records)
+        .map_err(|e| AppError::Config(format!("Failed to serialize cooldowns: {}", e)))?;
+    fs::write(path, content)
+        .map_err(|e| AppError::Io(format!("Failed to write cooldown file {:?}: {}", path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cooldown_escalates_and_clears() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join(COOLDOWN_FILE_NAME);
+        let base = Duration::from_secs(300);
+
+        assert_eq!(remaining_cooldown_at(&path, "a.com").unwrap(), None);
+
+        assert_eq!(start_cooldown_at(&path, "a.com", base).unwrap(), base);
+        assert_eq!(
+            start_cooldown_at(&path, "a.com", base).unwrap(),
+            Duration::from_secs(600)
+        );
+        let remaining = remaining_cooldown_at(&path, "a.com").unwrap().unwrap();
+        assert!(remaining > Duration::from_secs(590));
+        assert_eq!(remaining_cooldown_at(&path, "b.com").unwrap(), None);
+
+        clear_cooldown_at(&path, "a.com").unwrap();
+        assert_eq!(remaining_cooldown_at(&path, "a.com").unwrap(), None);
+        assert_eq!(start_cooldown_at(&path, "a.com", base).unwrap(), base);
+    }
+
+    #[test]
+    fn test_cooled_length() {
+        assert_eq!(cooled_length(5, 0), 5);
+        assert_eq!(cooled_length(5, 1), 10);
+        assert_eq!(cooled_length(5, 3), 20);
+        assert_eq!(cooled_length(30, 2), 40);
+        assert_eq!(cooled_length(0, 4), 0);
+        assert_eq!(cooled_length(usize::MAX, 1), usize::MAX);
+    }
+
+    #[test]
+    fn test_cooling_counts_recent_unblocks_until_reset() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(COOLDOWN_FILE_NAME);
+
+        assert_eq!(recent_unblocks_at(&path, "a.com").unwrap(), 0);
+        record_unblock_at(&path, "a.com").unwrap();
+        record_unblock_at(&path, "a.com").unwrap();
+        assert_eq!(recent_unblocks_at(&path, "a.com").unwrap(), 2);
+        assert_eq!(recent_unblocks_at(&path, "b.com").unwrap(), 0);
+
+        // Passing a challenge ends a cooldown but not the cooling
+        start_cooldown_at(&path, "a.com", Duration::from_secs(300)).unwrap();
+        clear_cooldown_at(&path, "a.com").unwrap();
+        assert_eq!(remaining_cooldown_at(&path, "a.com").unwrap(), None);
+        assert_eq!(recent_unblocks_at(&path, "a.com").unwrap(), 2);
+
+        // Unblocks from before the window are dropped on the next one
+        let mut records = read_records(&path).unwrap();
+        records.get_mut("a.com").unwrap().unblocks[0] = now_timestamp() - COOLING_WINDOW - 1;
+        write_records(&path, &records).unwrap();
+        assert_eq!(recent_unblocks_at(&path, "a.com").unwrap(), 1);
+        record_unblock_at(&path, "a.com").unwrap();
+        assert_eq!(read_records(&path).unwrap()["a.com"].unblocks.len(), 2);
+
+        assert!(reset_cooling_at(&path, "a.com").unwrap());
+        assert_eq!(recent_unblocks_at(&path, "a.com").unwrap(), 0);
+        assert!(!reset_cooling_at(&path, "a.com").unwrap());
+        assert!(read_records(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cooldown_is_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(COOLDOWN_FILE_NAME);
+
+        for _ in 0..40 {
+            start_cooldown_at(&path, "a.com", Duration::from_secs(3_600)).unwrap();
+        }
+
+        assert_eq!(
+            start_cooldown_at(&path, "a.com", Duration::from_secs(3_600)).unwrap(),
+            MAX_COOLDOWN
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use crate::error::{AppError, Result};
+use similar::TextDiff;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Lines of unchanged context around each change, as in `diff -u`
+const CONTEXT_LINES: usize = 3;
+
+// Scratch copies made by this process so far, so concurrent previews each get their own
+static SCRATCH_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+// A hosts file before and after a change that was only previewed (gwd diff)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Preview {
+    pub before: String,
+    pub after: String,
+}
+
+impl Preview {
+    pub fn changed(&self) -> bool {
+        self.before != self.after
+    }
+
+    // The change as a unified diff, with `path` in the ---/+++ header; empty when nothing changes
+    pub fn unified_diff(&self, path: &Path) -> String {
+        if !self.changed() {
+            return String::new();
+        }
+        let name = path.display().to_string();
+        TextDiff::from_lines(&self.before, &self.after)
+            .unified_diff()
+            .context_radius(CONTEXT_LINES)
+            .header(&name, &name)
+            .to_string()
+    }
+}
+
+// Runs `change` against a scratch copy of the hosts file at `hosts_path` and returns the content
+// before and after. The change goes through the same read-modify-write and atomic rewrite as the
+// real one, so the preview is exactly what it would write, but the real file is only read. A
+// missing hosts file previews as empty.
+pub fn preview_change<T>(
+    hosts_path: &Path,
+    change: impl FnOnce(&Path) -> Result<T>,
+) -> Result<Preview> {
+    let before = match fs::read_to_string(hosts_path) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            return Err(AppError::Io(format!(
+                "Failed to read {:?}: {}",
+                hosts_path, e
+            )))
+        }
+    };
+    let scratch = ScratchDir::create()?;
+    let copy = scratch
+        .0
+        .join(hosts_path.file_name().unwrap_or("hosts".as_ref()));
+    if let Some(content) = &before {
+        fs::write(&copy, content).map_err(|e| scratch_error(&copy, e))?;
+    }
+    change(&copy)?;
+    let after = match fs::read_to_string(&copy) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(scratch_error(&copy, e)),
+    };
+    Ok(Preview {
+        before: before.unwrap_or_default(),
+        after,
+    })
+}
+
+// A directory of its own in the system temp directory, removed with everything in it (the copy,
+// its lock and temp files) when dropped
+struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    fn create() -> Result<ScratchDir> {
+        let path = std::env::temp_dir().join(format!(
+            "gwd-diff-{}-{}",
+            std::process::id(),
+            SCRATCH_COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir(&path).map_err(|e| scratch_error(&path, e))?;
+        Ok(ScratchDir(path))
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.0) {
+            eprintln!("Warning: could not remove {:?}: {}", self.0, e);
+        }
+    }
+}
+
+fn scratch_error(path: &Path, e: io::Error) -> AppError {
+    AppError::Io(format!(
+        "Failed to prepare the preview in {:?}: {}",
+        path, e
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hosts::{block_websites_at, BlockOptions};
+
+    #[test]
+    fn test_preview_change_leaves_the_hosts_file_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts");
+        let original = "127.0.0.1 localhost\n::1 localhost\n";
+        fs::write(&path, original).unwrap();
+
+        let options = BlockOptions {
+            prefixes: Vec::new(),
+            ..Default::default()
+        };
+        let preview = preview_change(&path, |copy| {
+            assert_ne!(copy, path);
+            block_websites_at(&["example.com".to_string()], &options, copy)
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+        assert_eq!(preview.before, original);
+        assert!(preview.after.contains("0.0.0.0 example.com"));
+
+        let diff = preview.unified_diff(&path);
+        let name = path.display().to_string();
+        assert!(diff.starts_with(&format!("--- {}\n+++ {}\n@@ -1,2 +1,6 @@\n", name, name)));
+        assert!(diff.contains("\n 127.0.0.1 localhost\n"));
+        assert!(diff.contains("\n+# === gwd START ===\n"));
+        assert!(diff.contains("\n+0.0.0.0 example.com # Blocked by gwd"));
+        assert!(!diff
+            .lines()
+            .any(|line| line.starts_with('-') && !line.starts_with("---")));
+
+        // Nothing to block twice: no diff at all
+        fs::write(&path, &preview.after).unwrap();
+        let again = preview_change(&path, |copy| {
+            block_websites_at(&["example.com".to_string()], &options, copy)
+        })
+        .unwrap();
+        assert!(!again.changed());
+        assert_eq!(again.unified_diff(&path), "");
+
+        // A change that fails fails the preview, and its scratch copy is still cleaned up
+        let missing = dir.path().join("missing");
+        let mut scratch = PathBuf::new();
+        let result = preview_change(&missing, |copy| {
+            assert!(!copy.exists());
+            scratch = copy.to_path_buf();
+            Err::<(), _>(AppError::Io("refused".to_string()))
+        });
+        assert!(matches!(result, Err(AppError::Io(message)) if message == "refused"));
+        assert!(!scratch.parent().unwrap().exists());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::hosts::{
+    check_permissions, get_hosts_path, gwd_entry_count_at, hosts_path_source,
+    malformed_gwd_lines_at, temp_path_for, HostsPathSource, HOSTS_PATH_ENV_VAR,
+};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// One line of the `gwd doctor` checklist
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Check {
+    pub label: String,
+    pub passed: bool,
+    // What to do about a failed check
+    pub advice: Option<String>,
+}
+
+impl Check {
+    fn pass(label: impl Into<String>) -> Check {
+        Check {
+            label: label.into(),
+            passed: true,
+            advice: None,
+        }
+    }
+
+    fn fail(label: impl Into<String>, advice: impl Into<String>) -> Check {
+        Check {
+            label: label.into(),
+            passed: false,
+            advice: Some(advice.into()),
+        }
+    }
+}
+
+// Runs every check. Checks that need the hosts file are skipped if its path doesn't resolve.
+pub fn run_diagnostics() -> Vec<Check> {
+    let hosts_path = match get_hosts_path() {
+        Ok(path) => path,
+        Err(e) => {
+            return vec![Check::fail(
+                format!("Hosts file path resolves ({})", e),
+                format!(
+                    "Set {} to the hosts file location, or create its directory.",
+                    HOSTS_PATH_ENV_VAR
+                ),
+            )]
+        }
+    };
+    let mut checks = vec![Check::pass(format!(
+        "Hosts file path resolves to {:?}",
+        hosts_path
+    ))];
+    checks.extend(check_hosts_file(&hosts_path));
+    checks
+}
+
+// The checks on a resolved hosts file
+fn check_hosts_file(hosts_path: &Path) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    if let Err(e) = fs::read_to_string(hosts_path) {
+        checks.push(Check::fail(
+            format!("Hosts file exists and is readable ({})", e),
+            "Check that the file exists and that you may read it.",
+        ));
+        return checks;
+    }
+    checks.push(Check::pass("Hosts file exists and is readable"));
+
+    let writable = check_permissions(hosts_path).is_ok();
+    if writable {
+        checks.push(Check::pass("Hosts file is writable"));
+    } else {
+        checks.push(Check::fail("Hosts file is writable", elevation_advice()));
+    }
+
+    if is_elevated() {
+        checks.push(Check::pass("Running with root/administrator privileges"));
+    } else if writable {
+        // e.g. a scratch hosts file selected through GWD_HOSTS_PATH
+        checks.push(Check::pass(
+            "Running without root/administrator privileges (not needed for this hosts file)",
+        ));
+    } else {
+        checks.push(Check::fail(
+            "Running with root/administrator privileges",
+            elevation_advice(),
+        ));
+    }
+
+    let temp_path = temp_path_for(hosts_path);
+    if temp_path.exists() {
+        checks.push(Check::fail(
+            format!("No leftover temp file ({:?} exists)", temp_path),
+            "An earlier gwd run was interrupted. Check the hosts file is intact, then run 'gwd tidy'.",
+        ));
+    } else {
+        checks.push(Check::pass("No leftover temp file"));
+    }
+
+    match malformed_gwd_lines_at(hosts_path) {
+        Ok(problems) if problems.is_empty() => {
+            checks.push(Check::pass("gwd entries are well-formed"));
+        }
+        Ok(problems) => {
+            let details: Vec<String> = problems
+                .iter()
+                .map(|(line, problem)| format!("line {}: {}", line, problem))
+                .collect();
+            checks.push(Check::fail(
+                format!("gwd entries are well-formed ({})", details.join("; ")),
+                "Run 'gwd verify --remove' to delete them, or fix them by hand.",
+            ));
+        }
+        Err(e) => checks.push(Check::fail(
+            format!("gwd entries are well-formed ({})", e),
+            "Check that the hosts file is valid UTF-8 text.",
+        )),
+    }
+
+    checks
+}
+
+// Facts about the environment for bug reports (`gwd info`). Gathering them never changes
+// anything, and a problem (e.g. an unreadable hosts file) is reported rather than returned.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct EnvironmentInfo {
+    pub version: String,
+    // e.g. "linux (x86_64)"
+    pub os: String,
+    pub hosts_path: Option<PathBuf>,
+    pub hosts_path_source: HostsPathSource,
+    pub hosts_file_exists: bool,
+    pub writable: bool,
+    // The effective user's name, if it can be found
+    pub user: Option<String>,
+    pub elevated: bool,
+    // None if the hosts file can't be read; see `problem`
+    pub gwd_entries: Option<usize>,
+    // Why the path didn't resolve or the file couldn't be read
+    pub problem: Option<String>,
+}
+
+pub fn collect_info() -> EnvironmentInfo {
+    let mut info = EnvironmentInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        os: format!("{} ({})", std::env::consts::OS, std::env::consts::ARCH),
+        hosts_path: None,
+        hosts_path_source: hosts_path_source(),
+        hosts_file_exists: false,
+        writable: false,
+        user: effective_user(),
+        elevated: is_elevated(),
+        gwd_entries: None,
+        problem: None,
+    };
+    match get_hosts_path() {
+        Ok(hosts_path) => {
+            info.hosts_file_exists = hosts_path.exists();
+            info.writable = check_permissions(&hosts_path).is_ok();
+            match gwd_entry_count_at(&hosts_path) {
+                Ok(count) => info.gwd_entries = Some(count),
+                Err(e) => info.problem = Some(e.to_string()),
+            }
+            info.hosts_path = Some(hosts_path);
+        }
+        Err(e) => info.problem = Some(e.to_string()),
+    }
+    info
+}
+
+fn effective_user() -> Option<String> {
+    #[cfg(unix)]
+    {
+        nix::unistd::User::from_uid(nix::unistd::Uid::effective())
+            .ok()
+            .flatten()
+            .map(|user| user.name)
+    }
+    #[cfg(not(unix))]
+    {
+        std::env::var("USERNAME").ok()
+    }
+}
+
+// How to get write access to the hosts file on this platform
+fn elevation_advice() -> &'static str {
+    if cfg!(windows) {
+        "Run gwd from a terminal opened with 'Run as administrator'."
+    } else {
+        "Run gwd with sudo (e.g. 'sudo gwd block example.com')."
+    }
+}
+
+// Whether gwd runs as root (Unix). Windows has no cheap check, so it relies on the write check.
+fn is_elevated() -> bool {
+    #[cfg(unix)]
+    {
+        nix::unistd::Uid::effective().is_root()
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_hosts_file_healthy_and_unhealthy() {
+        let dir = tempfile::tempdir().unwrap();
+        let hosts_path = dir.path().join("hosts");
+        fs::write(&hosts_path, "0.0.0.0 ok.com # Blocked by gwd\n").unwrap();
+
+        assert!(check_hosts_file(&hosts_path)
+            .iter()
+            .all(|check| check.passed));
+
+        fs::write(hosts_path.with_extension("tmp"), "").unwrap();
+        fs::write(&hosts_path, "nonsense # Blocked by gwd\n").unwrap();
+        let failed: Vec<Check> = check_hosts_file(&hosts_path)
+            .into_iter()
+            .filter(|check| !check.passed)
+            .collect();
+        assert_eq!(failed.len(), 2);
+        assert!(failed.iter().all(|check| check.advice.is_some()));
+    }
+
+    #[test]
+    fn test_collect_info_reads_without_writing() {
+        let info = collect_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(info.os.starts_with(std::env::consts::OS));
+        // Whatever the hosts file is, either it was counted or the reason is given
+        assert!(info.gwd_entries.is_some() || info.problem.is_some());
+    }
+
+    #[test]
+    fn test_check_hosts_file_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let checks = check_hosts_file(&dir.path().join("hosts"));
+
+        assert_eq!(checks.len(), 1);
+        assert!(!checks[0].passed);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::error::{AppError, Result};
+use crate::output::Verbosity;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+// Marks the relaunched process, so a run that still lacks privileges fails instead of
+// relaunching itself again
+pub const ELEVATED_FLAG: &str = "--elevated";
+
+// Runs this gwd invocation again with administrator rights (--elevate) and waits for it.
+// Returns the exit code of the elevated run.
+pub fn relaunch_elevated(verbosity: Verbosity) -> Result<i32> {
+    let exe = std::env::current_exe()
+        .map_err(|e| AppError::Io(format!("Could not locate the gwd executable: {}", e)))?;
+    // Global flags are accepted before the subcommand, so the marker can go first
+    let mut args: Vec<OsString> = vec![ELEVATED_FLAG.into()];
+    args.extend(std::env::args_os().skip(1));
+    platform::relaunch(exe, args, verbosity)
+}
+
+// Re-runs gwd through sudo in the same terminal. sudo resets the environment, and root's config
+// and state directories differ from the user's, so the directories this gwd resolves to (and a
+// chosen hosts file) are passed along explicitly.
+#[cfg(unix)]
+mod platform {
+    use super::*;
+    use crate::history::{state_dir, STATE_DIR_ENV_VAR};
+    use crate::hosts::{hosts_path_override, HOSTS_PATH_ENV_VAR};
+    use crate::profile::{config_dir, CONFIG_DIR_ENV_VAR};
+    use std::process::Command;
+
+    pub fn relaunch(exe: PathBuf, args: Vec<OsString>, verbosity: Verbosity) -> Result<i32> {
+        let mut env: Vec<OsString> = vec![
+            format!("{}={}", CONFIG_DIR_ENV_VAR, config_dir()?.display()).into(),
+            format!("{}={}", STATE_DIR_ENV_VAR, state_dir()?.display()).into(),
+        ];
+        if let Some(hosts_path) = hosts_path_override() {
+            let mut assignment = OsString::from(format!("{}=", HOSTS_PATH_ENV_VAR));
+            assignment.push(hosts_path);
+            env.push(assignment);
+        }
+
+        verbosity.info(format_args!("Not running as root; re-running with sudo..."));
+        tracing::debug!(exe = ?exe, ?args, "relaunching through sudo");
+        let status = Command::new("sudo")
+            .arg("--")
+            .arg("env")
+            .args(env)
+            .arg(exe)
+            .args(args)
+            .status()
+            .map_err(|e| AppError::Io(format!("Could not run sudo: {}", e)))?;
+        // A run killed by a signal has no exit code
+        Ok(status.code().unwrap_or(1))
+    }
+}
+
+// Relaunches gwd through a UAC prompt. The elevated run opens its own console window and
+// starts with a fresh environment, so only the arguments are passed along.
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use std::process::Command;
+
+    pub fn relaunch(exe: PathBuf, args: Vec<OsString>, verbosity: Verbosity) -> Result<i32> {
+        let command_line = args
+            .iter()
+            .map(|arg| quote_windows_arg(&arg.to_string_lossy()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let script = format!(
+            "$p = Start-Process -FilePath {} -ArgumentList {} -Verb RunAs -Wait -PassThru; exit $p.ExitCode",
+            quote_powershell(&exe.display().to_string()),
+            quote_powershell(&command_line)
+        );
+
+        verbosity.info(format_args!(
+            "Not running as administrator; asking for elevation..."
+        ));
+        tracing::debug!(exe = ?exe, ?args, "relaunching through a UAC prompt");
+        let status = Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+            .status()
+            .map_err(|e| AppError::Io(format!("Could not run powershell: {}", e)))?;
+        Ok(status.code().unwrap_or(1))
+    }
+
+    // A PowerShell single-quoted string, in which only the quote itself needs escaping
+    fn quote_powershell(text: &str) -> String {
+        format!("'{}'", text.replace('\'', "''"))
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use super::*;
+
+    pub fn relaunch(_exe: PathBuf, _args: Vec<OsString>, _verbosity: Verbosity) -> Result<i32> {
+        Err(AppError::UnsupportedOS(std::env::consts::OS.to_string()))
+    }
+}
+
+// Quotes one argument so the standard Windows command-line parser (CommandLineToArgvW) reads it
+// back unchanged: backslashes are only special when they precede a double quote.
+#[cfg(any(windows, test))]
+fn quote_windows_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    // Backslashes before the closing quote would escape it
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_windows_arg() {
+        assert_eq!(quote_windows_arg("example.com"), "example.com");
+        assert_eq!(quote_windows_arg(""), "\"\"");
+        assert_eq!(quote_windows_arg("my words.txt"), "\"my words.txt\"");
+        assert_eq!(quote_windows_arg("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(quote_windows_arg("C:\\My Lists\\"), "\"C:\\My Lists\\\\\"");
+        assert_eq!(quote_windows_arg("C:\\lists\\a.txt"), "C:\\lists\\a.txt");
+    }
+}
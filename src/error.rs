@@ -1,5 +1,7 @@
+use crate::timing::format_duration;
 use std::io;
 use std::path::PathBuf;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug, Clone)] // Added Clone
@@ -17,14 +19,85 @@ pub enum AppError {
     #[error("Invalid domain name: {0}")]
     InvalidDomain(String),
 
+    #[error("Invalid redirect IP address: {0}")]
+    InvalidRedirectIp(String),
+
+    #[error("Invalid time or duration: '{0}' (expected e.g. 17:00, 30m, 2h or 1h30m)")]
+    InvalidDuration(String),
+
+    #[error("'{domain}' is locked for another {}; it can't be unblocked yet.", format_duration(.remaining))]
+    LockActive { domain: String, remaining: Duration },
+
+    #[error("Too many failed challenges for {subject}; try again in {}.", format_duration(.remaining))]
+    CoolingDown {
+        subject: String,
+        remaining: Duration,
+    },
+
+    #[error("'{0}' is not blocked by gwd.")]
+    NotBlocked(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Unknown profile '{0}'. Create it with 'gwd save-profile {0} <domains>...'.")]
+    UnknownProfile(String),
+
+    #[error(
+        "Another gwd process is modifying the hosts file (lock '{0}' is held); try again shortly."
+    )]
+    Busy(PathBuf),
+
+    #[error("Could not flush the DNS cache: {0}")]
+    DnsFlush(String),
+
+    #[error("Could not set up the scheduled job: {0}")]
+    Scheduler(String),
+
     #[error("Challenge failed: Incorrect sequence entered.")]
     ChallengeFailed,
 
+    #[error("Challenge failed: Time ran out before the challenge was completed.")]
+    ChallengeTimedOut,
+
+    #[cfg_attr(not(feature = "remote-lists"), allow(dead_code))] // Only the download constructs it
+    #[error("Failed to download blocklist: {0}")]
+    Http(String),
+
+    #[error("The downloaded blocklist is not usable: {0}")]
+    MalformedList(String),
+
+    #[error("The list has {count} domains, more than the cap of {max}; nothing was changed. Raise the cap with --max-entries or max_entries in config.toml (0 removes it).")]
+    TooManyEntries { count: usize, max: usize },
+
+    #[error("The pre_block hook refused the block: {0}")]
+    HookVetoed(String),
+
+    #[error("Cancelled; nothing was changed. Pass --yes to skip the confirmation.")]
+    Cancelled,
+
+    #[error("Interrupted; nothing was changed.")]
+    Interrupted,
+
     #[error(
-        "Permission denied accessing hosts file at '{0}'. This application requires root/administrator privileges."
+        "Permission denied accessing '{0}'. This application requires root/administrator privileges. Run 'gwd doctor' for details."
     )]
     PermissionDenied(PathBuf), // Include path for context
 
+    #[error(
+        "'{0}' is on a read-only filesystem. Remount it read-write (e.g. 'mount -o remount,rw /'), or in a container bind-mount a writable hosts file over it."
+    )]
+    ReadOnlyHosts(PathBuf),
+
+    #[error(
+        "gwd's section of '{0}' was edited outside gwd, and --strict refuses to change it. Check the section, then run 'gwd normalize' to accept it."
+    )]
+    Tampered(PathBuf),
+
+    #[error("Directory for hosts file '{0}' does not exist")]
+    MissingHostsDir(PathBuf),
+
+    #[allow(dead_code)] // Only constructed on platforms without a known hosts path
     #[error("Could not determine hosts file path for this operating system: {0}")]
     UnsupportedOS(String), // Renamed from UnknownHostsPath and added OS string
 
@@ -47,4 +120,79 @@ impl From<io::Error> for AppError {
 
 // Removed manual From<regex::Error> implementation
 
+impl AppError {
+    // Process exit code for this error, so wrapper scripts can tell failures apart
+    // without parsing stderr. 1 stays the catch-all for anything unexpected.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AppError::PermissionDenied(_) => 2,
+            AppError::InvalidDomain(_)
+            | AppError::InvalidRedirectIp(_)
+            | AppError::InvalidDuration(_)
+            | AppError::MalformedList(_)
+            | AppError::TooManyEntries { .. } => 3,
+            AppError::ChallengeFailed | AppError::ChallengeTimedOut => 4,
+            AppError::UnsupportedOS(_) => 5,
+            AppError::Io(_)
+            | AppError::ReadHosts { .. }
+            | AppError::WriteHosts { .. }
+            | AppError::MissingHostsDir(_)
+            | AppError::ReadOnlyHosts(_)
+            | AppError::DnsFlush(_)
+            | AppError::Scheduler(_)
+            | AppError::Http(_)
+            | AppError::Utf8Error(_) => 6,
+            AppError::LockActive { .. } | AppError::CoolingDown { .. } => 7,
+            AppError::Config(_) | AppError::UnknownProfile(_) => 8,
+            AppError::Busy(_) => 9,
+            AppError::HookVetoed(_) => 10,
+            AppError::Cancelled => 11,
+            AppError::Tampered(_) => 12,
+            // What a shell reports for a process ended by Ctrl-C
+            AppError::Interrupted => 130,
+            AppError::Regex(_) | AppError::NotBlocked(_) => 1,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, AppError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_are_distinct_per_kind() {
+        assert_eq!(
+            AppError::PermissionDenied(PathBuf::from("/etc/hosts")).exit_code(),
+            2
+        );
+        assert_eq!(AppError::InvalidDomain("x".to_string()).exit_code(), 3);
+        assert_eq!(AppError::ChallengeFailed.exit_code(), 4);
+        assert_eq!(AppError::ChallengeTimedOut.exit_code(), 4);
+        assert_eq!(AppError::UnsupportedOS("plan9".to_string()).exit_code(), 5);
+        assert_eq!(AppError::Io("disk full".to_string()).exit_code(), 6);
+        assert_eq!(
+            AppError::ReadOnlyHosts(PathBuf::from("/etc/hosts")).exit_code(),
+            6
+        );
+        assert_eq!(
+            AppError::LockActive {
+                domain: "x.com".to_string(),
+                remaining: Duration::from_secs(60)
+            }
+            .exit_code(),
+            7
+        );
+        assert_eq!(AppError::UnknownProfile("x".to_string()).exit_code(), 8);
+        assert_eq!(AppError::MalformedList("x".to_string()).exit_code(), 3);
+        assert_eq!(AppError::HookVetoed("exit 1".to_string()).exit_code(), 10);
+        assert_eq!(AppError::Cancelled.exit_code(), 11);
+        assert_eq!(
+            AppError::Tampered(PathBuf::from("/etc/hosts")).exit_code(),
+            12
+        );
+        assert_eq!(AppError::Interrupted.exit_code(), 130);
+        assert_eq!(AppError::NotBlocked("x.com".to_string()).exit_code(), 1);
+    }
+}
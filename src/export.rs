@@ -0,0 +1,278 @@
+use crate::error::{AppError, Result};
+use crate::hosts::{parse_domain_list, BlockedEntry, DomainFile};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+// File formats for export and import
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    // [[block]] tables keeping each domain's label and lock
+    #[default]
+    Toml,
+    // One domain per line, like a --from-file blocklist; soft blocks are left out
+    List,
+    // For other blockers; like List, these leave soft blocks out and import can't read them.
+    // Hosts file lines, e.g. "0.0.0.0 example.com # [social]"
+    Hosts,
+    // dnsmasq configuration, e.g. "address=/example.com/0.0.0.0"
+    Dnsmasq,
+    // unbound configuration: a local-zone redirecting each domain to a local-data address
+    Unbound,
+}
+
+// Address the other blockers' formats send blocked domains to, as gwd does by default
+const EXPORT_ADDRESS: &str = "0.0.0.0";
+
+// One exported block. In TOML:
+//   [[block]]
+//   domain = "reddit.com"
+//   label = "social"
+//   until = 1699999999
+//   blocked_at = 1699990000
+//   reason = "doomscrolling"
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportedBlock {
+    pub domain: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    // Unix timestamp of the --until/--for lock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<i64>,
+    // Unix timestamp it was blocked at, kept by import so `list` still shows its age
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_at: Option<i64>,
+    // Why it was blocked (--reason)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    // A soft block (block --soft)
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub soft: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExportFile {
+    #[serde(default)]
+    block: Vec<ExportedBlock>,
+}
+
+// Renders the current blocks (from list_blocked_entries_at) in `format`
+pub fn render_export(entries: &[BlockedEntry], format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::List => Ok(entries
+            .iter()
+            .filter(|entry| !entry.soft)
+            .map(|entry| format!("{}\n", entry.domain))
+            .collect()),
+        ExportFormat::Toml => {
+            let file = ExportFile {
+                block: entries
+                    .iter()
+                    .map(|entry| ExportedBlock {
+                        domain: entry.domain.clone(),
+                        label: entry.label.clone(),
+                        until: entry.until,
+                        blocked_at: entry.blocked_at,
+                        reason: entry.reason.clone(),
+                        soft: entry.soft,
+                    })
+                    .collect(),
+            };
+            toml::to_string(&file)
+                .map_err(|e| AppError::Config(format!("Failed to serialize the export: {}", e)))
+        }
+        ExportFormat::Hosts => Ok(render_for_blocker(entries, |hostname, label| {
+            let comment = label.map_or(String::new(), |label| format!(" # [{}]", label));
+            format!("{} {}{}\n", EXPORT_ADDRESS, hostname, comment)
+        })),
+        // dnsmasq only takes comments on lines of their own
+        ExportFormat::Dnsmasq => Ok(render_for_blocker(entries, |hostname, label| {
+            let comment = label.map_or(String::new(), |label| format!("# [{}]\n", label));
+            format!("{}address=/{}/{}\n", comment, hostname, EXPORT_ADDRESS)
+        })),
+        ExportFormat::Unbound => Ok(render_for_blocker(entries, |hostname, label| {
+            let comment = label.map_or(String::new(), |label| format!(" # [{}]", label));
+            format!(
+                "local-zone: \"{0}\" redirect{1}\nlocal-data: \"{0} A {2}\"\n",
+                hostname, comment, EXPORT_ADDRESS
+            )
+        })),
+    }
+}
+
+// Renders the real blocks for another blocker, with `lines` giving the lines for one hostname and
+// its label. Each domain comes with its www. variant, which list_blocked_entries_at collapses into
+// the domain.
+fn render_for_blocker(
+    entries: &[BlockedEntry],
+    lines: impl Fn(&str, Option<&str>) -> String,
+) -> String {
+    let mut rendered = String::new();
+    for entry in entries.iter().filter(|entry| !entry.soft) {
+        rendered.push_str(&lines(&entry.domain, entry.label.as_deref()));
+        if !entry.domain.starts_with("www.") {
+            let www = format!("www.{}", entry.domain);
+            rendered.push_str(&lines(&www, entry.label.as_deref()));
+        }
+    }
+    rendered
+}
+
+// The blocks read from an import file
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Import {
+    pub blocks: Vec<ExportedBlock>,
+    // (line number, line) for every line of a list that isn't a valid domain
+    pub invalid: Vec<(usize, String)>,
+}
+
+// Reads an export in either format: content with a [[block]] table is TOML, anything else is a
+// plain list, whose invalid lines are collected as with --from-file
+pub fn parse_import(content: &str) -> Result<Import> {
+    let is_toml = content
+        .lines()
+        .any(|line| line.trim_start().starts_with("[[block]]"));
+    if is_toml {
+        let file: ExportFile = toml::from_str(content)
+            .map_err(|e| AppError::Config(format!("Invalid export file: {}", e)))?;
+        return Ok(Import {
+            blocks: file.block,
+            invalid: Vec::new(),
+        });
+    }
+    let DomainFile { domains, invalid } = parse_domain_list(content, false);
+    let blocks = domains
+        .into_iter()
+        .map(|domain| ExportedBlock {
+            domain,
+            label: None,
+            until: None,
+            blocked_at: None,
+            reason: None,
+            soft: false,
+        })
+        .collect();
+    Ok(Import { blocks, invalid })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<BlockedEntry> {
+        vec![
+            BlockedEntry {
+                domain: "reddit.com".to_string(),
+                label: Some("social".to_string()),
+                until: Some(1_699_999_999),
+                until_reboot: false,
+                blocked_at: Some(1_600_000_000),
+                reason: Some("too much \"news\"".to_string()),
+                soft: false,
+            },
+            BlockedEntry {
+                domain: "example.com".to_string(),
+                label: None,
+                until: None,
+                until_reboot: false,
+                blocked_at: None,
+                reason: None,
+                soft: false,
+            },
+            BlockedEntry {
+                domain: "later.com".to_string(),
+                label: None,
+                until: None,
+                until_reboot: false,
+                blocked_at: None,
+                reason: None,
+                soft: true,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_toml_export_round_trips() {
+        let content = render_export(&entries(), ExportFormat::Toml).unwrap();
+        let import = parse_import(&content).unwrap();
+        assert!(import.invalid.is_empty());
+        assert_eq!(
+            import.blocks,
+            vec![
+                ExportedBlock {
+                    domain: "reddit.com".to_string(),
+                    label: Some("social".to_string()),
+                    until: Some(1_699_999_999),
+                    blocked_at: Some(1_600_000_000),
+                    reason: Some("too much \"news\"".to_string()),
+                    soft: false,
+                },
+                ExportedBlock {
+                    domain: "example.com".to_string(),
+                    label: None,
+                    until: None,
+                    blocked_at: None,
+                    reason: None,
+                    soft: false,
+                },
+                ExportedBlock {
+                    domain: "later.com".to_string(),
+                    label: None,
+                    until: None,
+                    blocked_at: None,
+                    reason: None,
+                    soft: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exports_for_other_blockers() {
+        assert_eq!(
+            render_export(&entries(), ExportFormat::Hosts).unwrap(),
+            "0.0.0.0 reddit.com # [social]\n\
+             0.0.0.0 www.reddit.com # [social]\n\
+             0.0.0.0 example.com\n\
+             0.0.0.0 www.example.com\n"
+        );
+        assert_eq!(
+            render_export(&entries(), ExportFormat::Dnsmasq).unwrap(),
+            "# [social]\n\
+             address=/reddit.com/0.0.0.0\n\
+             # [social]\n\
+             address=/www.reddit.com/0.0.0.0\n\
+             address=/example.com/0.0.0.0\n\
+             address=/www.example.com/0.0.0.0\n"
+        );
+        let unbound = render_export(&entries()[1..], ExportFormat::Unbound).unwrap();
+        assert_eq!(
+            unbound,
+            "local-zone: \"example.com\" redirect\n\
+             local-data: \"example.com A 0.0.0.0\"\n\
+             local-zone: \"www.example.com\" redirect\n\
+             local-data: \"www.example.com A 0.0.0.0\"\n"
+        );
+        assert!(render_export(&entries()[..1], ExportFormat::Unbound)
+            .unwrap()
+            .starts_with("local-zone: \"reddit.com\" redirect # [social]\n"));
+    }
+
+    #[test]
+    fn test_list_export_round_trips() {
+        let content = render_export(&entries(), ExportFormat::List).unwrap();
+        assert_eq!(content, "reddit.com\nexample.com\n");
+        let import = parse_import(&format!("{}not a domain\n", content)).unwrap();
+        assert_eq!(import.blocks.len(), 2);
+        assert_eq!(import.blocks[0].label, None);
+        assert_eq!(import.invalid, vec![(3, "not a domain".to_string())]);
+
+        assert!(parse_import("[[block]]\nname = \"x.com\"\n").is_err());
+        assert!(parse_import("").unwrap().blocks.is_empty());
+    }
+}
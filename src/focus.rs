@@ -0,0 +1,139 @@
+use crate::error::{AppError, Result};
+use crate::history::state_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FOCUS_FILE_NAME: &str = "focus.json";
+
+// A detached focus session on one profile
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FocusSession {
+    // Unix timestamp the session ends at
+    pub ends_at: i64,
+    // The exact lines the session added to the hosts file. Only these are removed when it ends,
+    // so blocks made under the profile's label outside the session keep their challenge.
+    #[serde(default)]
+    pub entries: Vec<String>,
+}
+
+// Location of the detached focus sessions, next to the history journal. Each one maps a profile
+// to its session; apply-schedule removes the session's entries after it ends.
+pub fn focus_path() -> Result<PathBuf> {
+    Ok(state_dir()?.join(FOCUS_FILE_NAME))
+}
+
+// Registers a detached session. One replacing an earlier session on the same profile takes over
+// its end time, and its entries too, so ending it removes what both added.
+pub fn start_session_at(
+    path: &Path,
+    profile: &str,
+    ends_at: i64,
+    entries: &[String],
+) -> Result<()> {
+    let mut sessions = read_sessions(path)?;
+    let session = sessions.entry(profile.to_string()).or_default();
+    session.ends_at = ends_at;
+    for entry in entries {
+        if !session.entries.contains(entry) {
+            session.entries.push(entry.clone());
+        }
+    }
+    write_sessions(path, &sessions)
+}
+
+// Profiles whose session is still running at `now`; apply-schedule leaves these blocked
+pub fn active_sessions_at(path: &Path, now: i64) -> Result<Vec<String>> {
+    Ok(read_sessions(path)?
+        .into_iter()
+        .filter(|(_, session)| session.ends_at > now)
+        .map(|(profile, _)| profile)
+        .collect())
+}
+
+// Sessions that are over at `now` and whose entries still have to be removed, by profile
+pub fn ended_sessions_at(path: &Path, now: i64) -> Result<Vec<(String, FocusSession)>> {
+    Ok(read_sessions(path)?
+        .into_iter()
+        .filter(|(_, session)| session.ends_at <= now)
+        .collect())
+}
+
+// Forgets a session once its entries have been removed
+pub fn end_session_at(path: &Path, profile: &str) -> Result<()> {
+    let mut sessions = read_sessions(path)?;
+    if sessions.remove(profile).is_some() {
+        write_sessions(path, &sessions)?;
+    }
+    Ok(())
+}
+
+// A missing file means no session is running
+fn read_sessions(path: &Path) -> Result<BTreeMap<String, FocusSession>> {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|e| AppError::Config(format!("Invalid focus file {:?}: {}", path, e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(AppError::Io(format!(
+            "Failed to read focus file {:?}: {}",
+            path, e
+        ))),
+    }
+}
+
+fn write_sessions(path: &Path, sessions: &BTreeMap<String, FocusSession>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content = serde_json::to_string_pretty(sessions)
+        .map_err(|e| AppError::Config(format!("Failed to serialize focus sessions: {}", e)))?;
+    fs::write(path, content)
+        .map_err(|e| AppError::Io(format!("Failed to write focus file {:?}: {}", path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_end_in_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join(FOCUS_FILE_NAME);
+
+        assert!(ended_sessions_at(&path, 100).unwrap().is_empty());
+
+        let names = |sessions: Vec<(String, FocusSession)>| -> Vec<String> {
+            sessions.into_iter().map(|(profile, _)| profile).collect()
+        };
+        let social = ["0.0.0.0 x.com # Blocked by gwd [social]".to_string()];
+        start_session_at(&path, "social", 200, &social).unwrap();
+        start_session_at(&path, "news", 400, &[]).unwrap();
+        assert_eq!(
+            active_sessions_at(&path, 100).unwrap(),
+            vec!["news", "social"]
+        );
+        assert!(ended_sessions_at(&path, 100).unwrap().is_empty());
+        assert_eq!(
+            ended_sessions_at(&path, 200).unwrap(),
+            vec![(
+                "social".to_string(),
+                FocusSession {
+                    ends_at: 200,
+                    entries: social.to_vec(),
+                }
+            )]
+        );
+        assert_eq!(active_sessions_at(&path, 200).unwrap(), vec!["news"]);
+
+        end_session_at(&path, "social").unwrap();
+        assert!(ended_sessions_at(&path, 300).unwrap().is_empty());
+
+        // Starting again replaces the end time and keeps the entries of both
+        let news = ["0.0.0.0 a.com # Blocked by gwd [news]".to_string()];
+        start_session_at(&path, "news", 250, &news).unwrap();
+        assert_eq!(names(ended_sessions_at(&path, 300).unwrap()), vec!["news"]);
+        start_session_at(&path, "news", 260, &[]).unwrap();
+        assert_eq!(ended_sessions_at(&path, 300).unwrap()[0].1.entries, news);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::error::Result;
+use crate::hosts::format_domain_for_hosts;
+
+// Label of the entries written by `focus-mode on`. It's reserved, so profiles and --label can't
+// mix their entries in with focus mode's.
+pub const FOCUS_MODE_LABEL: &str = "focusmode";
+
+// Well-known distractions blocked by focus mode: social media, video, news, shopping and games
+const DISTRACTIONS: &[&str] = &[
+    // Social media and forums
+    "facebook.com",
+    "instagram.com",
+    "x.com",
+    "twitter.com",
+    "reddit.com",
+    "tiktok.com",
+    "snapchat.com",
+    "pinterest.com",
+    "tumblr.com",
+    "linkedin.com",
+    "threads.net",
+    "bsky.app",
+    "mastodon.social",
+    "9gag.com",
+    "quora.com",
+    "news.ycombinator.com",
+    "discord.com",
+    // Video and streaming
+    "youtube.com",
+    "twitch.tv",
+    "netflix.com",
+    "hulu.com",
+    "disneyplus.com",
+    "primevideo.com",
+    "vimeo.com",
+    "dailymotion.com",
+    // News
+    "cnn.com",
+    "bbc.com",
+    "bbc.co.uk",
+    "nytimes.com",
+    "theguardian.com",
+    "foxnews.com",
+    "washingtonpost.com",
+    "buzzfeed.com",
+    "huffpost.com",
+    // Shopping
+    "amazon.com",
+    "ebay.com",
+    "etsy.com",
+    "aliexpress.com",
+    "temu.com",
+    // Games
+    "store.steampowered.com",
+    "steamcommunity.com",
+    "epicgames.com",
+    "roblox.com",
+    "chess.com",
+    "lichess.org",
+];
+
+// The built-in distraction list minus the allowed domains. Allowing a domain also keeps its
+// subdomains on the list out (allowing "ycombinator.com" keeps "news.ycombinator.com").
+pub fn focus_mode_domains(allow: &[String]) -> Result<Vec<String>> {
+    let allowed = allow
+        .iter()
+        .map(|domain| format_domain_for_hosts(domain))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(DISTRACTIONS
+        .iter()
+        .filter(|domain| !allowed.iter().any(|allowed| covers(allowed, domain)))
+        .map(|domain| domain.to_string())
+        .collect())
+}
+
+fn covers(allowed: &str, domain: &str) -> bool {
+    let allowed = allowed.strip_prefix("www.").unwrap_or(allowed);
+    domain == allowed
+        || domain
+            .strip_suffix(allowed)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_focus_mode_domains_skip_allowed() {
+        let all = focus_mode_domains(&[]).unwrap();
+        assert_eq!(all.len(), DISTRACTIONS.len());
+
+        let allowed = [
+            "https://www.YouTube.com/watch".to_string(),
+            "ycombinator.com".to_string(),
+        ];
+        let domains = focus_mode_domains(&allowed).unwrap();
+        assert!(!domains.contains(&"youtube.com".to_string()));
+        assert!(!domains.contains(&"news.ycombinator.com".to_string()));
+        assert!(domains.contains(&"reddit.com".to_string()));
+        // A name that merely ends the same way isn't covered
+        assert!(!covers("x.com", "dropbox.com"));
+        assert!(focus_mode_domains(&["".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,435 @@
+use crate::error::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const HISTORY_FILE_NAME: &str = "history.jsonl";
+// Records pushed out of history.jsonl move here, so `gwd log` can still show them
+const ROTATED_FILE_NAME: &str = "history.1.jsonl";
+// Records kept in each journal file unless config.toml sets history_limit
+pub const DEFAULT_HISTORY_LIMIT: usize = 500;
+// Environment variable that overrides the directory gwd keeps its state (history) in
+pub const STATE_DIR_ENV_VAR: &str = "GWD_STATE_DIR";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryAction {
+    Block,
+    Unblock,
+}
+
+// One block or unblock, as recorded in history.jsonl (one JSON object per line)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub action: HistoryAction,
+    // Unix timestamp of the action
+    pub timestamp: i64,
+    // The domains the user asked for (empty for unblock --all)
+    pub domains: Vec<String>,
+    // The exact hosts file lines that were added or removed
+    pub entries: Vec<String>,
+}
+
+// Directory holding gwd's state files (e.g. ~/.local/state/gwd)
+pub fn state_dir() -> Result<PathBuf> {
+    match std::env::var_os(STATE_DIR_ENV_VAR) {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => dirs::state_dir()
+            .or_else(dirs::data_local_dir)
+            .map(|dir| dir.join("gwd"))
+            .ok_or_else(|| AppError::Config("Could not determine the state directory".to_string())),
+    }
+}
+
+// Location of the action journal
+pub fn history_path() -> Result<PathBuf> {
+    Ok(state_dir()?.join(HISTORY_FILE_NAME))
+}
+
+// Appends a record. Beyond `limit` records, the oldest ones are rotated into history.1.jsonl,
+// which keeps at most `limit` records of its own.
+pub fn append_record(record: &HistoryRecord, limit: usize) -> Result<()> {
+    append_record_at(&history_path()?, record, limit)
+}
+
+// Same as append_record, but against an explicit journal path. The journal stays locked from
+// reading to writing, so concurrent gwd runs can't drop each other's records.
+pub fn append_record_at(path: &Path, record: &HistoryRecord, limit: usize) -> Result<()> {
+    let line = serde_json::to_string(record)
+        .map_err(|e| AppError::Config(format!("Failed to serialize history: {}", e)))?;
+    let mut journal = LockedJournal::open(path)?;
+    let mut lines = journal.lines()?;
+    lines.push(line);
+    let excess = lines.len().saturating_sub(limit.max(1));
+    if excess > 0 {
+        rotate(&rotated_path(path), &lines[..excess], limit.max(1))?;
+    }
+    journal.replace(&lines[excess..])
+}
+// The most recent record, if any
+pub fn last_record() -> Result<Option<HistoryRecord>> {
+    last_record_at(&history_path()?)
+}
+
+// Same as last_record, but against an explicit journal path
+pub fn last_record_at(path: &Path) -> Result<Option<HistoryRecord>> {
+    match read_journal_lines(path)?.last() {
+        Some(line) => serde_json::from_str(line)
+            .map(Some)
+            .map_err(|e| AppError::Config(format!("Invalid history entry in {:?}: {}", path, e))),
+        None => Ok(None),
+    }
+}
+
+// Removes the most recent record once it has been undone
+pub fn drop_last_record() -> Result<()> {
+    drop_last_record_at(&history_path()?)
+}
+
+// Same as drop_last_record, but against an explicit journal path
+pub fn drop_last_record_at(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let mut journal = LockedJournal::open(path)?;
+    let mut lines = journal.lines()?;
+    if lines.pop().is_some() {
+        journal.replace(&lines)?;
+    }
+    Ok(())
+}
+
+// Every record still on disk, oldest first: the rotated file followed by the journal
+pub fn all_records() -> Result<Vec<HistoryRecord>> {
+    all_records_at(&history_path()?)
+}
+
+// Same as all_records, but against an explicit journal path
+pub fn all_records_at(path: &Path) -> Result<Vec<HistoryRecord>> {
+    let mut lines = read_journal_lines(&rotated_path(path))?;
+    lines.extend(read_journal_lines(path)?);
+    lines
+        .iter()
+        .map(|line| {
+            serde_json::from_str(line).map_err(|e| {
+                AppError::Config(format!("Invalid history entry in {:?}: {}", path, e))
+            })
+        })
+        .collect()
+}
+
+// One line of `gwd log`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LogEntry {
+    pub action: HistoryAction,
+    pub timestamp: i64,
+    // The domains affected, taken from the hosts lines when the command named none (unblock --all)
+    pub domains: Vec<String>,
+    // For an unblock: how long each domain had been blocked, in seconds, if its block is on record
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub blocked_for: BTreeMap<String, i64>,
+}
+
+// Turns the journal (oldest first) into log entries, newest first, leaving out those before
+// `since`. Each unblocked domain is paired with the latest earlier block of the same domain.
+pub fn build_log(records: &[HistoryRecord], since: Option<i64>) -> Vec<LogEntry> {
+    let mut log = Vec::new();
+    for (index, record) in records.iter().enumerate() {
+        if since.is_some_and(|since| record.timestamp < since) {
+            continue;
+        }
+        let domains = affected_domains(record);
+        let mut blocked_for = BTreeMap::new();
+        if record.action == HistoryAction::Unblock {
+            for domain in &domains {
+                let block = records[..index].iter().rev().find(|earlier| {
+                    earlier.action == HistoryAction::Block
+                        && entry_domains(earlier).contains(domain)
+                });
+                if let Some(block) = block {
+                    blocked_for.insert(domain.clone(), record.timestamp - block.timestamp);
+                }
+            }
+        }
+        log.push(LogEntry {
+            action: record.action,
+            timestamp: record.timestamp,
+            domains,
+            blocked_for,
+        });
+    }
+    log.reverse();
+    log
+}
+
+// The domains a record is about: those the command named, else those of its lines (unblock --all)
+pub(crate) fn affected_domains(record: &HistoryRecord) -> Vec<String> {
+    if record.domains.is_empty() {
+        entry_domains(record)
+    } else {
+        record.domains.clone()
+    }
+}
+
+// Domains of the hosts lines a record added or removed, without 'www.' and without duplicates
+fn entry_domains(record: &HistoryRecord) -> Vec<String> {
+    let mut domains: Vec<String> = Vec::new();
+    for entry in &record.entries {
+        if let Some(hostname) = entry.split_whitespace().nth(1) {
+            let domain = hostname.strip_prefix("www.").unwrap_or(hostname);
+            if !domains.iter().any(|known| known == domain) {
+                domains.push(domain.to_string());
+            }
+        }
+    }
+    domains
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    path.with_file_name(ROTATED_FILE_NAME)
+}
+
+// Adds `lines` to the end of the rotated file, keeping its newest `limit` records
+fn rotate(rotated: &Path, lines: &[String], limit: usize) -> Result<()> {
+    let mut old = read_journal_lines(rotated)?;
+    old.extend_from_slice(lines);
+    let excess = old.len().saturating_sub(limit);
+    let mut content = old[excess..].join("\n");
+    content.push('\n');
+    fs::write(rotated, content)
+        .map_err(|e| AppError::Io(format!("Failed to write history file {:?}: {}", rotated, e)))
+}
+
+// The journal, opened and exclusively locked until dropped. It is rewritten in place rather than
+// replaced, so the lock stays on the file other gwd processes open.
+struct LockedJournal {
+    path: PathBuf,
+    file: File,
+}
+
+impl LockedJournal {
+    fn open(path: &Path) -> Result<LockedJournal> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let io_error = |e: std::io::Error| {
+            AppError::Io(format!("Failed to open history file {:?}: {}", path, e))
+        };
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(io_error)?;
+        file.lock().map_err(io_error)?;
+        Ok(LockedJournal {
+            path: path.to_path_buf(),
+            file,
+        })
+    }
+
+    fn lines(&mut self) -> Result<Vec<String>> {
+        let mut content = String::new();
+        self.file.read_to_string(&mut content).map_err(|e| {
+            AppError::Io(format!(
+                "Failed to read history file {:?}: {}",
+                self.path, e
+            ))
+        })?;
+        Ok(journal_lines(&content))
+    }
+
+    fn replace(&mut self, lines: &[String]) -> Result<()> {
+        let mut content = lines.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        let file = &mut self.file;
+        file.set_len(0)
+            .and_then(|_| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| file.write_all(content.as_bytes()))
+            .map_err(|e| {
+                AppError::Io(format!(
+                    "Failed to write history file {:?}: {}",
+                    self.path, e
+                ))
+            })
+    }
+}
+
+// Non-empty lines of the journal; a missing file is an empty journal
+fn read_journal_lines(path: &Path) -> Result<Vec<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(journal_lines(&content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(AppError::Io(format!(
+            "Failed to read history file {:?}: {}",
+            path, e
+        ))),
+    }
+}
+
+fn journal_lines(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(action: HistoryAction, domain: &str) -> HistoryRecord {
+        HistoryRecord {
+            action,
+            timestamp: 1_700_000_000,
+            domains: vec![domain.to_string()],
+            entries: vec![format!("0.0.0.0 {} # Blocked by gwd", domain)],
+        }
+    }
+
+    #[test]
+    fn test_missing_journal_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join(HISTORY_FILE_NAME);
+
+        assert_eq!(last_record_at(&path).unwrap(), None);
+        drop_last_record_at(&path).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_append_and_drop_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join(HISTORY_FILE_NAME);
+
+        append_record_at(&path, &record(HistoryAction::Block, "a.com"), 10).unwrap();
+        append_record_at(&path, &record(HistoryAction::Unblock, "b.com"), 10).unwrap();
+        assert_eq!(
+            last_record_at(&path).unwrap(),
+            Some(record(HistoryAction::Unblock, "b.com"))
+        );
+
+        drop_last_record_at(&path).unwrap();
+        assert_eq!(
+            last_record_at(&path).unwrap(),
+            Some(record(HistoryAction::Block, "a.com"))
+        );
+    }
+
+    #[test]
+    fn test_journal_is_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(HISTORY_FILE_NAME);
+
+        let limit = 50;
+        for i in 0..limit + 5 {
+            let record = record(HistoryAction::Block, &format!("{}.com", i));
+            append_record_at(&path, &record, limit).unwrap();
+        }
+
+        assert_eq!(read_journal_lines(&path).unwrap().len(), limit);
+        assert_eq!(
+            last_record_at(&path).unwrap().unwrap().domains,
+            vec![format!("{}.com", limit + 4)]
+        );
+
+        // The oldest records were rotated out, not lost, and the rotated file is capped as well
+        let rotated = read_journal_lines(&rotated_path(&path)).unwrap();
+        assert_eq!(rotated.len(), 5);
+        assert_eq!(all_records_at(&path).unwrap()[0].domains, vec!["0.com"]);
+        for i in 0..limit * 2 {
+            let record = record(HistoryAction::Block, &format!("more{}.com", i));
+            append_record_at(&path, &record, limit).unwrap();
+        }
+        assert_eq!(all_records_at(&path).unwrap().len(), limit * 2);
+    }
+
+    #[test]
+    fn test_concurrent_appends_keep_every_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(HISTORY_FILE_NAME);
+
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for j in 0..10 {
+                        let record = record(HistoryAction::Block, &format!("{}-{}.com", i, j));
+                        append_record_at(&path, &record, 1_000).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(all_records_at(&path).unwrap().len(), 80);
+    }
+
+    #[test]
+    fn test_log_pairs_unblocks_with_blocks() {
+        let at = |action, timestamp, domains: &[&str], entries: &[&str]| HistoryRecord {
+            action,
+            timestamp,
+            domains: domains.iter().map(|d| d.to_string()).collect(),
+            entries: entries.iter().map(|e| e.to_string()).collect(),
+        };
+        let records = vec![
+            at(
+                HistoryAction::Block,
+                100,
+                &["a.com"],
+                &[
+                    "0.0.0.0 a.com # Blocked by gwd",
+                    "0.0.0.0 www.a.com # Blocked by gwd",
+                ],
+            ),
+            at(
+                HistoryAction::Block,
+                200,
+                &["b.com"],
+                &["0.0.0.0 b.com # Blocked by gwd"],
+            ),
+            at(
+                HistoryAction::Unblock,
+                400,
+                &["a.com"],
+                &["0.0.0.0 a.com # Blocked by gwd"],
+            ),
+            // unblock --all names no domains; they come from the removed lines
+            at(
+                HistoryAction::Unblock,
+                1_000,
+                &[],
+                &[
+                    "0.0.0.0 b.com # Blocked by gwd",
+                    "0.0.0.0 c.com # Blocked by gwd",
+                ],
+            ),
+        ];
+
+        let log = build_log(&records, None);
+        assert_eq!(
+            log.iter().map(|entry| entry.timestamp).collect::<Vec<_>>(),
+            vec![1_000, 400, 200, 100]
+        );
+        assert_eq!(log[0].domains, vec!["b.com", "c.com"]);
+        assert_eq!(
+            log[0].blocked_for,
+            BTreeMap::from([("b.com".to_string(), 800)])
+        );
+        assert_eq!(
+            log[1].blocked_for,
+            BTreeMap::from([("a.com".to_string(), 300)])
+        );
+        assert!(log[2].blocked_for.is_empty());
+
+        assert_eq!(build_log(&records, Some(300)).len(), 2);
+    }
+}
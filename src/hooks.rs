@@ -0,0 +1,193 @@
+use crate::error::{AppError, Result};
+use crate::hosts::{get_hosts_path, HOSTS_PATH_ENV_VAR};
+use crate::output::Verbosity;
+use serde::Deserialize;
+use std::process::Command;
+
+// Replaced in hook arguments by the affected domains, separated by spaces
+const DOMAIN_PLACEHOLDER: &str = "{domain}";
+
+// Commands from the [hooks] table of config.toml, each given as a program and its arguments, e.g.
+//   [hooks]
+//   post_block = ["notify-send", "Blocked {domain}"]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    // Runs before blocking; a failure cancels the block
+    pub pre_block: Option<Vec<String>>,
+    pub post_block: Option<Vec<String>>,
+    // Runs before the unblock challenge
+    pub pre_unblock: Option<Vec<String>>,
+    pub post_unblock: Option<Vec<String>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HookEvent {
+    PreBlock,
+    PostBlock,
+    PreUnblock,
+    PostUnblock,
+}
+
+impl HookEvent {
+    fn name(self) -> &'static str {
+        match self {
+            HookEvent::PreBlock => "pre_block",
+            HookEvent::PostBlock => "post_block",
+            HookEvent::PreUnblock => "pre_unblock",
+            HookEvent::PostUnblock => "post_unblock",
+        }
+    }
+
+    // Passed to the hook as GWD_ACTION
+    fn action(self) -> &'static str {
+        match self {
+            HookEvent::PreBlock | HookEvent::PostBlock => "block",
+            HookEvent::PreUnblock | HookEvent::PostUnblock => "unblock",
+        }
+    }
+}
+
+impl Hooks {
+    fn command(&self, event: HookEvent) -> Option<&[String]> {
+        match event {
+            HookEvent::PreBlock => self.pre_block.as_deref(),
+            HookEvent::PostBlock => self.post_block.as_deref(),
+            HookEvent::PreUnblock => self.pre_unblock.as_deref(),
+            HookEvent::PostUnblock => self.post_unblock.as_deref(),
+        }
+    }
+}
+
+// Runs the hook configured for `event`, if any, and waits for it. The hook gets GWD_ACTION,
+// GWD_HOOK, GWD_DOMAIN (the domains, separated by spaces) and GWD_HOSTS_PATH in its environment.
+// Only a failing pre_block hook is an error (it vetoes the block); any other failure is a warning,
+// since it mustn't stand in the way of the hosts file change itself.
+pub fn run_hook(
+    hooks: &Hooks,
+    event: HookEvent,
+    domains: &[String],
+    verbosity: Verbosity,
+) -> Result<()> {
+    let Some(command) = hooks.command(event) else {
+        return Ok(());
+    };
+    match run_command(command, event, domains, verbosity) {
+        Ok(()) => Ok(()),
+        Err(reason) if event == HookEvent::PreBlock => Err(AppError::HookVetoed(reason)),
+        Err(reason) => {
+            eprintln!("Warning: the {} hook failed: {}", event.name(), reason);
+            Ok(())
+        }
+    }
+}
+
+fn run_command(
+    command: &[String],
+    event: HookEvent,
+    domains: &[String],
+    verbosity: Verbosity,
+) -> std::result::Result<(), String> {
+    let domain_list = domains.join(" ");
+    let argv = expand_placeholders(command, &domain_list);
+    let (program, args) = argv
+        .split_first()
+        .ok_or_else(|| "the command is empty".to_string())?;
+
+    verbosity.debug(format_args!(
+        "Running {} hook: {}",
+        event.name(),
+        argv.join(" ")
+    ));
+    let mut process = Command::new(program);
+    process
+        .args(args)
+        .env("GWD_ACTION", event.action())
+        .env("GWD_HOOK", event.name())
+        .env("GWD_DOMAIN", &domain_list);
+    if let Ok(hosts_path) = get_hosts_path() {
+        process.env(HOSTS_PATH_ENV_VAR, hosts_path);
+    }
+    let status = process
+        .status()
+        .map_err(|e| format!("could not run '{}': {}", program, e))?;
+    tracing::debug!(hook = event.name(), %status, "hook finished");
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("'{}' exited with {}", program, status))
+    }
+}
+
+fn expand_placeholders(command: &[String], domain_list: &str) -> Vec<String> {
+    command
+        .iter()
+        .map(|arg| arg.replace(DOMAIN_PLACEHOLDER, domain_list))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domains(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_expand_placeholders() {
+        let command = domains(&["notify-send", "Blocked {domain}", "--app=gwd"]);
+        assert_eq!(
+            expand_placeholders(&command, "a.com b.com"),
+            vec!["notify-send", "Blocked a.com b.com", "--app=gwd"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_only_pre_block_failure_vetoes() {
+        let failing = Some(domains(&["false"]));
+        let hooks = Hooks {
+            pre_block: failing.clone(),
+            post_block: failing.clone(),
+            pre_unblock: failing.clone(),
+            post_unblock: failing,
+        };
+        let targets = domains(&["a.com"]);
+
+        assert!(matches!(
+            run_hook(&hooks, HookEvent::PreBlock, &targets, Verbosity::Quiet),
+            Err(AppError::HookVetoed(_))
+        ));
+        for event in [
+            HookEvent::PostBlock,
+            HookEvent::PreUnblock,
+            HookEvent::PostUnblock,
+        ] {
+            assert!(run_hook(&hooks, event, &targets, Verbosity::Quiet).is_ok());
+        }
+        assert!(run_hook(
+            &Hooks::default(),
+            HookEvent::PreBlock,
+            &targets,
+            Verbosity::Quiet
+        )
+        .is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hook_gets_domain_in_environment() {
+        let hooks = Hooks {
+            pre_block: Some(domains(&["sh", "-c", "test \"$GWD_DOMAIN\" = a.com"])),
+            ..Default::default()
+        };
+        assert!(run_hook(
+            &hooks,
+            HookEvent::PreBlock,
+            &domains(&["a.com"]),
+            Verbosity::Quiet
+        )
+        .is_ok());
+    }
+}
@@ -1,28 +1,138 @@
-use crate::challenge::run_challenge;
+use crate::challenge::{run_challenge, ChallengeOptions};
+use crate::cooldown::{cooled_length, recent_unblocks_at, record_unblock_at, reset_cooling_at};
 use crate::error::{AppError, Result};
+use crate::interrupt::InterruptGuard;
+use crate::output::{paint, paint_stderr, Progress, Tone, Verbosity, PROGRESS_THRESHOLD};
+use crate::schedule::{no_unblock_remaining, Period};
+use crate::timing::{boot_time, format_rfc3339, now_timestamp, parse_rfc3339};
+use chrono::Local;
+use clap::ValueEnum;
 use lazy_static::lazy_static;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::fs::{self, File, OpenOptions}; // Added fs
-use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write}; // Added Read trait
-use std::path::PathBuf; // Keep PathBuf
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf}; // Keep PathBuf
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
 
-const REDIRECT_IP: &str = "0.0.0.0";
+pub const DEFAULT_REDIRECT_IP: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED); // 0.0.0.0
+const DEFAULT_REDIRECT_IPV6: IpAddr = IpAddr::V6(Ipv6Addr::UNSPECIFIED); // ::
 const BLOCK_COMMENT_TAG: &str = "# Blocked by gwd";
+// Tag of a soft block: an entry written commented out, so it's recorded without taking effect,
+// e.g. "# 0.0.0.0 example.com # Soft-blocked by gwd"
+const SOFT_BLOCK_TAG: &str = "# Soft-blocked by gwd";
+// Tags of the JSON comment style (block --comment-style json), each followed by a JSON object
+// with the metadata, e.g. "0.0.0.0 example.com # gwd {"label":"work","until":1699999999}"
+const JSON_COMMENT_TAG: &str = "# gwd";
+const JSON_SOFT_TAG: &str = "# gwd-soft";
+// Field of the plain comment style marking a block --until-reboot entry,
+// e.g. "# Blocked by gwd until-reboot @2024-01-15T09:30:00Z"
+const UNTIL_REBOOT_MARKER: &str = "until-reboot";
+// Prefix of an entry turned off by gwd pause, followed by "until <Unix timestamp> " and the entry
+// as it was, e.g. "# PAUSED until 1699999999 0.0.0.0 example.com # Blocked by gwd"
+const PAUSED_PREFIX: &str = "# PAUSED ";
+// Markers delimiting the contiguous region of the hosts file that gwd manages
+const SECTION_START_MARKER: &str = "# === gwd START ===";
+const SECTION_END_MARKER: &str = "# === gwd END ===";
+// Line at the top of gwd's section holding the SHA-256 of its entries, so an edit made outside
+// gwd (e.g. deleting a block mid-focus-session) shows up the next time gwd changes the file
+const CHECKSUM_PREFIX: &str = "# gwd-checksum:";
+// Header of a named sub-section of gwd's section (block --section), e.g. "# gwd:work". Once the
+// section has any, entries without a sub-section of their own go under DEFAULT_SECTION.
+const SUBSECTION_PREFIX: &str = "# gwd:";
+pub const DEFAULT_SECTION: &str = "default";
+// Longest allowed label (the part between dots) of a hostname, per RFC 1035
+const MAX_LABEL_LENGTH: usize = 63;
+// What block writes when it has to create a missing hosts file, so localhost keeps resolving
+#[cfg(not(windows))]
+const DEFAULT_HOSTS_CONTENT: &str = "127.0.0.1 localhost\n::1 localhost\n";
+#[cfg(windows)]
+const DEFAULT_HOSTS_CONTENT: &str = "127.0.0.1 localhost\r\n::1 localhost\r\n";
+// How long to wait for another gwd process to finish with the hosts file
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+// How often to try replacing the hosts file on Windows, where antivirus or the DNS Client service
+// can briefly hold it open, and the wait before the first retry (doubled after each one)
+#[cfg(windows)]
+const RENAME_ATTEMPTS: u32 = 5;
+#[cfg(windows)]
+const RENAME_RETRY_DELAY: Duration = Duration::from_millis(50);
+// Environment variable that overrides the OS default hosts file location
+pub const HOSTS_PATH_ENV_VAR: &str = "GWD_HOSTS_PATH";
 
 lazy_static! {
-    // Regex to clean domain names (remove http/https, trailing slashes), case-insensitive protocol
-    static ref DOMAIN_CLEANUP_REGEX: Result<Regex> = Regex::new(r"(?i)^(?:https?://)?(.*?)/?$").map_err(AppError::from);
-    // Regex to find existing block entries more precisely
-    // Matches start of line, optional whitespace, redirect IP, one or more spaces,
-    // the domain, then either whitespace/comment or end of line.
-    static ref HOSTS_ENTRY_REGEX: Result<Regex> = Regex::new(r"^\s*0\.0\.0\.0\s+").map_err(AppError::from); // Simplified for now, needs domain added dynamically
+    // Regex to clean domain names (remove http/https, and any path, query string or fragment), case-insensitive protocol
+    static ref DOMAIN_CLEANUP_REGEX: Result<Regex> = Regex::new(r"(?i)^(?:https?://)?([^/?#]*)").map_err(AppError::from);
+    // Regex to find any active (not commented out) line written by gwd, capturing the hostnames
+    // and the tag. Matches optional whitespace, an IP, whitespace, one or more hostnames separated
+    // by blanks, then the gwd comment tag of either style.
+    static ref GWD_ENTRY_REGEX: Result<Regex> = Regex::new(&format!(
+        r"^\s*[^\s#]+\s+([^\s#]+(?:[ \t]+[^\s#]+)*)\s*({}|{}[ \t]*\{{)",
+        regex::escape(BLOCK_COMMENT_TAG),
+        regex::escape(JSON_COMMENT_TAG)
+    ))
+    .map_err(AppError::from);
+    // Regex to parse the metadata in a gwd comment tag, e.g.
+    // "# Blocked by gwd [social] until 1699999999 @2024-01-15T09:30:00Z reason=too%20much%20news".
+    // Captures the optional label (profile name), the optional lock expiry (Unix timestamp), the
+    // optional until-reboot marker, the optional time the entry was added (RFC 3339), the
+    // optional percent-encoded --reason and, on a variant added with --also, its prefix; older
+    // entries may have none of them.
+    static ref GWD_TAG_REGEX: Result<Regex> = Regex::new(&format!(
+        r"{}(?:[ \t]+\[([^\]]*)\])?(?:[ \t]+until[ \t]+(\d+))?(?:[ \t]+({}))?(?:[ \t]+@(\S+))?(?:[ \t]+reason=(\S+))?(?:[ \t]+variant=(\S+))?",
+        regex::escape(BLOCK_COMMENT_TAG),
+        UNTIL_REBOOT_MARKER
+    ))
+    .map_err(AppError::from);
 
     // Determine hosts file path based on OS
     static ref HOSTS_PATH: Result<PathBuf> = get_hosts_path_internal();
 }
 
+// Hosts file chosen with --hosts-path for this run, if any
+static HOSTS_PATH_FLAG: OnceLock<PathBuf> = OnceLock::new();
+
+// Whether a section edited outside gwd stops every change until `gwd normalize` (--strict),
+// rather than only printing a warning
+static STRICT_CHECKSUM: AtomicBool = AtomicBool::new(false);
+
+pub fn set_strict_checksum(strict: bool) {
+    STRICT_CHECKSUM.store(strict, Ordering::Relaxed);
+}
+
 // Internal function to determine the path, called by lazy_static
 fn get_hosts_path_internal() -> Result<PathBuf> {
+    resolve_hosts_path(std::env::var_os(HOSTS_PATH_ENV_VAR))
+}
+
+// Resolves the hosts path from an optional GWD_HOSTS_PATH value, falling back to the OS default
+fn resolve_hosts_path(env_override: Option<OsString>) -> Result<PathBuf> {
+    match env_override {
+        Some(value) if !value.is_empty() => validate_hosts_path(PathBuf::from(value)),
+        _ => default_hosts_path(),
+    }
+}
+
+// Checks that a user-supplied hosts path lives in an existing directory
+fn validate_hosts_path(path: PathBuf) -> Result<PathBuf> {
+    match path.parent() {
+        // A bare file name (or root) refers to the current directory, which always exists
+        Some(parent) if !parent.as_os_str().is_empty() && !parent.is_dir() => {
+            Err(AppError::MissingHostsDir(path))
+        }
+        _ => Ok(path),
+    }
+}
+
+// The standard hosts file location for the current OS
+fn default_hosts_path() -> Result<PathBuf> {
     #[cfg(target_os = "windows")]
     {
         Ok(PathBuf::from(r"C:\Windows\System32\drivers\etc\hosts"))
@@ -52,367 +162,6961 @@ fn get_hosts_path_internal() -> Result<PathBuf> {
     }
 }
 
+// Selects the hosts file for the rest of this run (--hosts-path). It takes precedence over
+// GWD_HOSTS_PATH and the OS default; its directory must already exist.
+pub fn set_hosts_path_override(path: PathBuf) -> Result<()> {
+    let path = validate_hosts_path(path)?;
+    // Only the first call counts; main makes it once, before anything reads the path
+    let _ = HOSTS_PATH_FLAG.set(path);
+    Ok(())
+}
+
+// The explicitly chosen hosts file (--hosts-path, then GWD_HOSTS_PATH), or None for the OS default
+pub fn hosts_path_override() -> Option<PathBuf> {
+    HOSTS_PATH_FLAG.get().cloned().or_else(|| {
+        std::env::var_os(HOSTS_PATH_ENV_VAR)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    })
+}
+
+// Where the hosts path in use was chosen
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostsPathSource {
+    // --hosts-path
+    Flag,
+    // GWD_HOSTS_PATH
+    Env,
+    // The OS default
+    Default,
+}
+
+pub fn hosts_path_source() -> HostsPathSource {
+    if HOSTS_PATH_FLAG.get().is_some() {
+        HostsPathSource::Flag
+    } else if std::env::var_os(HOSTS_PATH_ENV_VAR).is_some_and(|value| !value.is_empty()) {
+        HostsPathSource::Env
+    } else {
+        HostsPathSource::Default
+    }
+}
+
 // Public function to get the cached hosts path
 pub fn get_hosts_path() -> Result<PathBuf> {
+    if let Some(path) = HOSTS_PATH_FLAG.get() {
+        return Ok(path.clone());
+    }
     // Clone the Result itself. If it's Ok, the PathBuf inside is cloned.
     // If it's Err, the AppError inside is cloned (requires AppError to be Clone).
     HOSTS_PATH.clone()
 }
 
+// Turns a domain, or a URL pasted in its place, into the form gwd writes to the hosts file:
+// scheme, path, port and trailing dot dropped, lowercased, and an internationalized name in its
+// ASCII (punycode, "xn--") form, since hosts files only match ASCII names
+pub fn canonicalize_domain(domain: &str) -> Result<String> {
+    format_domain_for_hosts(domain)
+}
+
 // Function to format domain names consistently
-fn format_domain_for_hosts(domain: &str) -> Result<String> {
+pub(crate) fn format_domain_for_hosts(domain: &str) -> Result<String> {
     // Access the regex result, propagating errors using ?
     let regex = DOMAIN_CLEANUP_REGEX.as_ref().map_err(|e| e.clone())?;
+    // The trailing dot of a fully qualified name ("example.com.") names the same host
     let cleaned = regex
         .captures(domain)
         .and_then(|cap| cap.get(1))
-        .map(|m| m.as_str().to_lowercase())
+        .map(|m| {
+            let host = strip_port(m.as_str());
+            host.strip_suffix('.').unwrap_or(host).to_lowercase()
+        })
         .ok_or_else(|| AppError::InvalidDomain(domain.to_string()))?;
 
     if cleaned.is_empty() {
         Err(AppError::InvalidDomain(domain.to_string()))
-    } else {
+    } else if cleaned.is_ascii() {
         Ok(cleaned)
+    } else {
+        // idna's error says no more than that the conversion failed
+        idna::domain_to_ascii_strict(&cleaned).map_err(|_| {
+            AppError::InvalidDomain(format!("'{}' (not a valid international name)", domain))
+        })
     }
 }
 
-// Function to add block entries to the hosts file
-pub fn block_website(domain: &str) -> Result<()> {
-    let hosts_path = get_hosts_path()?;
-    let clean_domain = format_domain_for_hosts(domain)?;
-    let domain_www = format!("www.{}", clean_domain);
-
-    let entry1 = format!("{} {} {}", REDIRECT_IP, clean_domain, BLOCK_COMMENT_TAG);
-    let entry2 = format!("{} {} {}", REDIRECT_IP, domain_www, BLOCK_COMMENT_TAG);
-
-    // Regex for checking existing entries for *this specific domain*
-    // Needs to be created dynamically inside the function.
-    // We need to escape the domain string in case it contains regex metacharacters.
-    let escaped_clean_domain = regex::escape(&clean_domain);
-    let escaped_domain_www = regex::escape(&domain_www);
-    let check_regex1_str = format!(
-        r"^\s*{}\s+{}\s*(?:#.*)?$",
-        regex::escape(REDIRECT_IP),
-        escaped_clean_domain
-    );
-    let check_regex2_str = format!(
-        r"^\s*{}\s+{}\s*(?:#.*)?$",
-        regex::escape(REDIRECT_IP),
-        escaped_domain_www
-    );
-    let check_regex1 = Regex::new(&check_regex1_str)?;
-    let check_regex2 = Regex::new(&check_regex2_str)?;
-
-    // Use a specific error mapping function
-    let map_io_error = |e: io::Error, path: &PathBuf| match e.kind() {
-        io::ErrorKind::PermissionDenied => AppError::PermissionDenied(path.clone()),
-        // Use ReadHosts/WriteHosts specific variants if needed, otherwise generic Io
-        _ => AppError::Io(format!("Failed access hosts file at {:?}: {}", path, e)),
-    };
-
-    let mut file = OpenOptions::new()
-        .read(true)
-        .append(true) // Use append mode for adding lines
-        .open(&hosts_path)
-        .map_err(|e| map_io_error(e, &hosts_path))?; // Use specific error mapping
+// Drops a ":port" suffix from a URL host ("example.com:8080", "[::1]:80"); a bare IPv6
+// address has several colons and is left alone
+fn strip_port(host: &str) -> &str {
+    if let Some(inner) = host.strip_prefix('[') {
+        return inner.split(']').next().unwrap_or(inner);
+    }
+    match host.split_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
 
-    // Read existing content to check for duplicates
-    // We need to rewind the file cursor because we opened in append mode initially
-    file.seek(SeekFrom::Start(0))?;
-    let reader = BufReader::new(&file);
-    let mut lines_to_add = Vec::new();
-    let mut exists1 = false;
-    let mut exists2 = false;
+// Formats a domain that is about to be written to the hosts file, rejecting anything that
+// isn't a plausible hostname. IP literals are only accepted with `allow_ip` (--allow-ip).
+// Lookups and removals stay lenient and use format_domain_for_hosts directly.
+pub(crate) fn validate_domain(domain: &str, allow_ip: bool) -> Result<String> {
+    let cleaned = format_domain_for_hosts(domain)?;
+    let invalid = |reason: &str| AppError::InvalidDomain(format!("'{}' ({})", domain, reason));
 
-    for line_result in reader.lines() {
-        // Iterate over Result<String>
-        let line = line_result?; // Handle potential IO error during read
-        if check_regex1.is_match(&line) {
-            exists1 = true;
-        }
-        if check_regex2.is_match(&line) {
-            exists2 = true;
+    if cleaned.parse::<IpAddr>().is_ok() {
+        return if allow_ip {
+            Ok(cleaned)
+        } else {
+            Err(invalid("IP addresses need --allow-ip"))
+        };
+    }
+    if let Some(c) = cleaned
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '.'))
+    {
+        return Err(invalid(&format!(
+            "'{}' is not allowed; use letters, digits, '-' and '.'",
+            c
+        )));
+    }
+    if !cleaned.contains('.') {
+        return Err(invalid("a domain needs at least one dot, e.g. example.com"));
+    }
+    for label in cleaned.split('.') {
+        if label.is_empty() {
+            return Err(invalid("empty label between dots"));
         }
-        if exists1 && exists2 {
-            break; // No need to read further
+        if label.len() > MAX_LABEL_LENGTH {
+            return Err(invalid(&format!(
+                "label '{}' is longer than {} characters",
+                label, MAX_LABEL_LENGTH
+            )));
         }
     }
+    Ok(cleaned)
+}
 
-    if !exists1 {
-        lines_to_add.push(entry1);
-        println!("Adding entry for: {}", clean_domain);
-    } else {
-        println!("Block entry for {} already exists.", clean_domain);
-    }
+// Names the machine itself relies on, which block refuses without --force: sinking them breaks
+// local services rather than blocking a distraction
+#[cfg(target_os = "linux")]
+const PROTECTED_HOSTNAMES: &[&str] = &[
+    "localhost",
+    "localhost.localdomain",
+    "ip6-localhost",
+    "ip6-loopback",
+    "ip6-allnodes",
+    "ip6-allrouters",
+];
+#[cfg(target_os = "macos")]
+const PROTECTED_HOSTNAMES: &[&str] = &["localhost", "broadcasthost"];
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const PROTECTED_HOSTNAMES: &[&str] = &["localhost"];
 
-    if !exists2 {
-        lines_to_add.push(entry2);
-        println!("Adding entry for: {}", domain_www);
-    } else {
-        println!("Block entry for {} already exists.", domain_www);
-    }
-
-    if !lines_to_add.is_empty() {
-        // Ensure the file ends with a newline before appending
-        // Check the last byte of the file
-        let mut last_char = [0; 1];
-        // Check if file has content before seeking
-        if file.metadata()?.len() > 0 {
-            if file.seek(SeekFrom::End(-1)).is_ok() && file.read(&mut last_char).is_ok() {
-                if last_char[0] != b'\n' {
-                    // Removed unnecessary parentheses
-                    file.seek(SeekFrom::End(0))?;
-                    writeln!(file)?;
-                }
-            } else {
-                // Error seeking or reading last byte, assume newline needed or proceed
-                file.seek(SeekFrom::End(0))?;
+// The hostnames block refuses without --force: PROTECTED_HOSTNAMES, this machine's own name and
+// `extra` (protected_hostnames in config.toml)
+pub fn protected_hostnames(extra: &[String]) -> Vec<String> {
+    let mut protected: Vec<String> = PROTECTED_HOSTNAMES
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    for name in machine_hostname().into_iter().chain(extra.iter().cloned()) {
+        if let Ok(name) = format_domain_for_hosts(&name) {
+            if !protected.contains(&name) {
+                protected.push(name);
             }
-        } else {
-            // File is empty, ensure cursor is at the start/end (which is the same)
-            file.seek(SeekFrom::Start(0))?; // Or SeekFrom::End(0)
         }
+    }
+    protected
+}
 
-        for line in lines_to_add {
-            writeln!(file, "{}", line)?;
+// The addresses that mark a line gwd didn't write as a block of its hostnames: 0.0.0.0, :: and
+// `extra` (sinkhole_ips in config.toml, e.g. 127.0.0.1 or a Pi-hole's address)
+pub fn sinkhole_ips(extra: &[IpAddr]) -> Vec<IpAddr> {
+    let mut sinkholes = vec![DEFAULT_REDIRECT_IP, DEFAULT_REDIRECT_IPV6];
+    for ip in extra {
+        if !sinkholes.contains(ip) {
+            sinkholes.push(*ip);
         }
-        println!(
-            "Successfully updated hosts file to block '{}'.",
-            clean_domain
-        );
-        // Consider adding platform-specific flush DNS instructions here
-        #[cfg(target_os = "windows")]
-        println!("Run 'ipconfig /flushdns' if the block doesn't take effect immediately.");
-        #[cfg(any(target_os = "macos", target_os = "linux"))]
-        println!("DNS cache might need flushing (e.g., systemd-resolve --flush-caches or dscacheutil -flushcache).");
-    } else {
-        println!("'{}' already configured for blocking.", clean_domain);
     }
+    sinkholes
+}
 
-    Ok(())
+#[cfg(unix)]
+fn machine_hostname() -> Option<String> {
+    nix::unistd::gethostname().ok()?.into_string().ok()
 }
 
-// Function to remove block entries from the hosts file
-pub fn unblock_website(domain: &str, challenge_word_count: usize) -> Result<()> {
-    let clean_domain = format_domain_for_hosts(domain)?;
+#[cfg(not(unix))]
+fn machine_hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
 
-    // Run the challenge first
-    run_challenge(&clean_domain, challenge_word_count)?;
+// Domains read from a blocklist file, plus the lines that couldn't be used
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DomainFile {
+    // Cleaned-up domains in file order, without duplicates
+    pub domains: Vec<String>,
+    // (line number, line) for every line that isn't a valid domain
+    pub invalid: Vec<(usize, String)>,
+}
 
-    let hosts_path = get_hosts_path()?;
-    let domain_www = format!("www.{}", clean_domain);
+// Function to read a blocklist file: one domain per line, '#' starts a comment.
+// Invalid lines are collected rather than aborting, so one typo doesn't sink the whole list.
+pub fn read_domain_file(path: &Path, allow_ip: bool) -> Result<DomainFile> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| AppError::Io(format!("Failed to read domain file {:?}: {}", path, e)))?;
+    Ok(parse_domain_list(&content, allow_ip))
+}
 
-    // Regex for finding the lines to remove
-    // Needs to be created dynamically inside the function.
-    let escaped_clean_domain = regex::escape(&clean_domain);
-    let escaped_domain_www = regex::escape(&domain_www);
-    // Match lines starting with the redirect IP, spaces, the domain (or www.domain), and then optional space/comment or end of line
-    let remove_regex_str = format!(
-        r"^\s*{}\s+({}|{})\s*(?:#.*)?$",
-        regex::escape(REDIRECT_IP),
-        escaped_clean_domain,
-        escaped_domain_www
-    );
-    let remove_regex = Regex::new(&remove_regex_str)?;
+// Parses blocklist content in the format read_domain_file expects (also used for --list-url)
+pub fn parse_domain_list(content: &str, allow_ip: bool) -> DomainFile {
+    let mut domain_file = DomainFile::default();
+    for (index, line) in content.lines().enumerate() {
+        let entry = line.split('#').next().unwrap_or("").trim();
+        if entry.is_empty() {
+            continue;
+        }
+        match validate_domain(entry, allow_ip) {
+            Ok(domain) => {
+                if !domain_file.domains.contains(&domain) {
+                    domain_file.domains.push(domain);
+                }
+            }
+            Err(_) => domain_file.invalid.push((index + 1, entry.to_string())),
+        }
+    }
+    domain_file
+}
 
-    let temp_file_path = hosts_path.with_extension("tmp");
+// Domains one block or import may add unless --max-entries (or max_entries in config.toml) says
+// otherwise, so a broken or hostile blocklist can't bloat the hosts file
+pub const DEFAULT_MAX_ENTRIES: usize = 5000;
 
-    // Use the same error mapping helper
-    let map_io_error = |e: io::Error, path: &PathBuf| match e.kind() {
-        io::ErrorKind::PermissionDenied => AppError::PermissionDenied(path.clone()),
-        _ => AppError::Io(format!("Failed access hosts file at {:?}: {}", path, e)),
-    };
+// Refuses a batch of `count` domains when it is over `max`; a `max` of 0 means no cap
+pub fn check_max_entries(count: usize, max: usize) -> Result<()> {
+    if max != 0 && count > max {
+        Err(AppError::TooManyEntries { count, max })
+    } else {
+        Ok(())
+    }
+}
 
-    // Read from original, write non-matching lines to temp
-    {
-        // Scope for file handles
-        let original_file = File::open(&hosts_path).map_err(|e| map_io_error(e, &hosts_path))?; // Use helper
-        let reader = BufReader::new(original_file);
+// Function to parse a user-supplied redirect address (e.g. from --redirect)
+pub fn parse_redirect_ip(ip: &str) -> Result<IpAddr> {
+    ip.trim()
+        .parse()
+        .map_err(|_| AppError::InvalidRedirectIp(ip.to_string()))
+}
 
-        let mut temp_file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&temp_file_path)
-            .map_err(|e| AppError::Io(format!("Failed to create temp file: {}", e)))?;
-
-        let mut removed_count = 0;
-        for line_result in reader.lines() {
-            // Iterate over Result<String>
-            let line = line_result?; // Handle potential IO error during read
-            if remove_regex.is_match(&line) {
-                println!("Removing line: {}", line);
-                removed_count += 1;
-            } else {
-                writeln!(temp_file, "{}", line)?;
-            }
-        }
+// Picks the IPv6 sinkhole matching an IPv4 redirect: loopback stays loopback (::1), anything else becomes ::
+fn ipv6_redirect_for(redirect_ip: &IpAddr) -> IpAddr {
+    match redirect_ip {
+        IpAddr::V6(_) => *redirect_ip,
+        IpAddr::V4(v4) if v4.is_loopback() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        IpAddr::V4(_) => DEFAULT_REDIRECT_IPV6,
+    }
+}
 
-        if removed_count == 0 {
-            println!("No active blocking entries found for '{}'.", clean_domain);
-            // Clean up temp file if nothing was removed
-            drop(temp_file); // Close the file handle before removing
-            fs::remove_file(&temp_file_path)?; // Use fs::remove_file
-            return Ok(());
+// How the metadata comment of a new entry is written (block --comment-style, comment_style in
+// config.toml). Entries in either style are read back whichever is selected.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentStyle {
+    // "# Blocked by gwd [work] until 1699999999 @2024-01-15T09:30:00Z reason=..."
+    #[default]
+    Plain,
+    // "# gwd {"label":"work","until":1699999999}", for tools that read the hosts file
+    Json,
+}
+
+// Options controlling how block entries are written
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockOptions {
+    // Address blocked hostnames are redirected to
+    pub redirect_ip: IpAddr,
+    // Also write matching IPv6 sinkhole entries so AAAA lookups are blocked as well
+    pub ipv6: bool,
+    // Unix timestamp before which gwd refuses to unblock the domains
+    pub lock_until: Option<i64>,
+    // Mark the entries to be removed by the first gwd run after the next reboot (--until-reboot)
+    pub until_reboot: bool,
+    // Label (e.g. a profile name) recorded in the comment so the entries can be grouped
+    pub label: Option<String>,
+    // Why the user blocked the domains (--reason), shown again when they unblock
+    pub reason: Option<String>,
+    // Unix timestamp recorded in the comment as the time the entries were added
+    pub blocked_at: Option<i64>,
+    // Accept IP literals as "domains" (--allow-ip)
+    pub allow_ip: bool,
+    // Subdomain variants blocked alongside each domain, e.g. "www" for www.example.com
+    // (--no-www drops it, --also adds more)
+    pub prefixes: Vec<String>,
+    // Add gwd's own entry even where a non-gwd line already blocks the hostname, and block
+    // protected hostnames (--force)
+    pub force: bool,
+    // Hostnames refused unless `force` is set (see protected_hostnames)
+    pub protected: Vec<String>,
+    // Addresses that make a line gwd didn't write count as blocking its hostnames (see
+    // sinkhole_ips), besides one to the address being written
+    pub sinkhole_ips: Vec<IpAddr>,
+    // Write the entries commented out, as soft blocks that `harden` can turn into real ones
+    // (--soft)
+    pub soft: bool,
+    // Sub-section of gwd's section the entries are written under (--section)
+    pub section: Option<String>,
+    // How the comment holding the label, lock, time and reason is written
+    pub comment_style: CommentStyle,
+    // Only print the entries that would be added, without writing anything
+    pub dry_run: bool,
+    // How much progress to print (Quiet e.g. when the caller reports the result as JSON)
+    pub verbosity: Verbosity,
+}
+
+impl Default for BlockOptions {
+    fn default() -> Self {
+        BlockOptions {
+            redirect_ip: DEFAULT_REDIRECT_IP,
+            ipv6: false,
+            lock_until: None,
+            until_reboot: false,
+            label: None,
+            reason: None,
+            blocked_at: None,
+            allow_ip: false,
+            prefixes: vec!["www".to_string()],
+            force: false,
+            protected: protected_hostnames(&[]),
+            sinkhole_ips: sinkhole_ips(&[]),
+            soft: false,
+            section: None,
+            comment_style: CommentStyle::default(),
+            dry_run: false,
+            verbosity: Verbosity::default(),
         }
-    } // Files are closed here
+    }
+}
 
-    // Replace original with temp file
-    fs::rename(&temp_file_path, &hosts_path).map_err(|e| {
-        // Use fs::rename
-        AppError::Io(format!(
-            "Failed to replace hosts file with updated version: {}. Temp file at: {:?}",
-            e, temp_file_path
-        ))
-    })?;
+// Options controlling how block entries are removed
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnblockOptions {
+    // Challenge the user must pass before anything is removed
+    pub challenge: ChallengeOptions,
+    // Only print the entries that would be removed, without the challenge or writing anything
+    pub dry_run: bool,
+    // How much progress to print (Quiet e.g. when the caller reports the result as JSON)
+    pub verbosity: Verbosity,
+    // Skip the challenge whatever its length, for scripted teardown (unblock --force). Timed
+    // locks still apply.
+    pub force: bool,
+    // Times of the week during which every unblock that would need a challenge is refused
+    // (no_unblock in config.toml), whatever the per-domain locks say
+    pub no_unblock: Vec<Period>,
+    // Only remove entries from this sub-section of gwd's section (--section)
+    pub section: Option<String>,
+    // Also take the domains off 0.0.0.0/:: sinkhole lines gwd didn't write, anywhere in the file
+    // (--include-foreign)
+    pub include_foreign: bool,
+}
 
-    println!(
-        "Successfully removed blocking entries for '{}'.",
-        clean_domain
-    );
-    // Consider adding platform-specific flush DNS instructions here
-    #[cfg(target_os = "windows")]
-    println!("Run 'ipconfig /flushdns' if you still cannot access the site.");
-    #[cfg(any(target_os = "macos", target_os = "linux"))]
-    println!("DNS cache might need flushing (e.g., systemd-resolve --flush-caches or dscacheutil -flushcache).");
+// What a block run did, for callers that report results themselves (e.g. --json)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BlockReport {
+    // The cleaned-up domains that were requested
+    pub domains: Vec<String>,
+    // Hostnames that got a new entry (or would, in a dry run)
+    pub added: Vec<String>,
+    // Hostnames that were already blocked
+    pub already_present: Vec<String>,
+    // Hostnames found blocked by a line gwd didn't write (e.g. another blocklist); they are in
+    // `already_present` too, unless --force added gwd's own entry
+    pub foreign: Vec<String>,
+    // The exact lines written to the hosts file, so the block can be undone
+    pub entries: Vec<String>,
+}
 
-    Ok(())
+// What an unblock run did
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct UnblockReport {
+    // The cleaned-up domains that were requested (empty for --all)
+    pub domains: Vec<String>,
+    // The hosts file lines that were removed (or would be, in a dry run)
+    pub removed: Vec<String>,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use tempfile::NamedTempFile; // Now should resolve
+// What a pause (gwd pause) did
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PauseReport {
+    // The entry lines that were paused (or would be, in a dry run)
+    pub paused: Vec<String>,
+    // When the paused entries come back by themselves (Unix timestamp)
+    pub resume_at: i64,
+}
 
-    // Helper to create a mock hosts file
-    fn create_mock_hosts(content: &str) -> io::Result<NamedTempFile> {
-        let file = NamedTempFile::new()?;
-        fs::write(file.path(), content)?;
-        Ok(file)
-    }
+// What the read pass of a block found for one (address, hostname) it wants to write
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Existing {
+    Nothing,
+    // A line written by gwd
+    Gwd,
+    // A sinkhole line written by hand or another tool
+    Foreign,
+    // A soft block, which doesn't block anything yet
+    Soft,
+}
+
+// Totals of a block or unblock batch, for the one-line summary printed at the end of a command
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BatchSummary {
+    // Hostnames that got an entry (block) or lost their entries (unblock)
+    pub changed: usize,
+    // Hostnames that were already blocked (block), or requested domains that weren't (unblock)
+    pub skipped: usize,
+    // Lines of a domain file or downloaded list that were left out, and why
+    pub invalid: Vec<InvalidInput>,
+}
+
+// One unusable line of a domain file or list
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct InvalidInput {
+    pub line: usize,
+    pub input: String,
+    pub reason: String,
+}
 
-    // Helper function to override the HOSTS_PATH for testing
-    #[allow(unused_variables)] // Silence warning for unused path
-    fn set_test_hosts_path(path: PathBuf) {
-        // This is tricky with lazy_static. A common approach is conditional compilation
-        // or using a different mechanism for tests, like dependency injection.
-        // For simplicity here, we'll rely on the functions accepting the path directly
-        // if we refactor them, or acknowledge this limitation.
-        // A more robust solution might involve an Arc<Mutex<Option<PathBuf>>>
-        // or a testing-specific configuration setup.
-        // Currently, the functions use the lazy_static directly, making this hard.
-        // We will test the logic assuming the path resolution works.
-        panic!("set_test_hosts_path is not implemented due to lazy_static limitations in test context. Refactor needed for full path mocking.");
+impl BatchSummary {
+    // Adds the invalid lines collected while reading a domain file or list (DomainFile::invalid)
+    pub fn with_invalid(mut self, invalid: &[(usize, String)], allow_ip: bool) -> BatchSummary {
+        self.invalid = invalid
+            .iter()
+            .map(|(line, input)| InvalidInput {
+                line: *line,
+                input: input.clone(),
+                reason: validate_domain(input, allow_ip)
+                    .err()
+                    .map_or_else(String::new, |e| e.to_string()),
+            })
+            .collect();
+        self
     }
 
-    #[test]
-    fn test_format_domain_for_hosts_simple() {
-        assert_eq!(
-            format_domain_for_hosts("example.com").unwrap(),
-            "example.com"
-        );
+    // e.g. "Blocked 7, skipped 3 already present, 1 invalid." Zero counts after the first are left
+    // out; `skipped_as` says why something was skipped.
+    pub fn describe(&self, action: &str, skipped_as: &str) -> String {
+        let mut parts = vec![format!("{} {}", action, self.changed)];
+        if self.skipped > 0 {
+            parts.push(format!("skipped {} {}", self.skipped, skipped_as));
+        }
+        if !self.invalid.is_empty() {
+            parts.push(format!("{} invalid", self.invalid.len()));
+        }
+        format!("{}.", parts.join(", "))
     }
+}
 
-    #[test]
-    fn test_format_domain_for_hosts_http() {
-        assert_eq!(
-            format_domain_for_hosts("http://example.com").unwrap(),
-            "example.com"
-        );
+impl BlockReport {
+    // Folds in the report of another block run, e.g. one label group of an import
+    pub fn merge(&mut self, other: BlockReport) {
+        self.domains.extend(other.domains);
+        self.added.extend(other.added);
+        self.already_present.extend(other.already_present);
+        self.foreign.extend(other.foreign);
+        self.entries.extend(other.entries);
     }
 
-    #[test]
-    fn test_format_domain_for_hosts_https() {
-        assert_eq!(
-            format_domain_for_hosts("https://example.com").unwrap(),
-            "example.com"
-        );
+    pub fn summary(&self) -> BatchSummary {
+        BatchSummary {
+            changed: self.added.len(),
+            skipped: self.already_present.len(),
+            invalid: Vec::new(),
+        }
     }
+}
 
-    #[test]
-    fn test_format_domain_for_hosts_trailing_slash() {
-        assert_eq!(
-            format_domain_for_hosts("example.com/").unwrap(),
-            "example.com"
-        );
+impl UnblockReport {
+    pub fn summary(&self) -> BatchSummary {
+        let hostnames = self.removed_hostnames();
+        BatchSummary {
+            changed: hostnames.len(),
+            skipped: self.not_blocked().len(),
+            invalid: Vec::new(),
+        }
     }
-    #[test]
-    fn test_format_domain_for_hosts_https_trailing_slash() {
-        assert_eq!(
-            format_domain_for_hosts("https://example.com/").unwrap(),
-            "example.com"
-        );
+
+    // The requested domains that lost no entry. A domain counts as unblocked if it or one of
+    // its variants lost an entry.
+    pub fn not_blocked(&self) -> Vec<&str> {
+        let hostnames = self.removed_hostnames();
+        self.domains
+            .iter()
+            .filter(|domain| {
+                !hostnames
+                    .iter()
+                    .any(|hostname| hostname == *domain || is_subdomain_of(hostname, domain))
+            })
+            .map(String::as_str)
+            .collect()
     }
 
-    #[test]
-    fn test_format_domain_for_hosts_mixed_case() {
-        assert_eq!(
-            format_domain_for_hosts("HTTPS://Example.Com/").unwrap(),
-            "example.com"
-        );
+    // The hostnames of the removed lines, in order and without repeats
+    fn removed_hostnames(&self) -> Vec<String> {
+        let mut hostnames: Vec<String> = Vec::new();
+        for line in &self.removed {
+            // A removed soft block names its hostname behind the leading '#'
+            let line = harden_line(line).unwrap_or_else(|| line.clone());
+            let fields = line.split('#').next().unwrap_or_default();
+            for hostname in fields.split_whitespace().skip(1) {
+                let hostname = normalize_hostname(hostname);
+                if !hostnames.contains(&hostname) {
+                    hostnames.push(hostname);
+                }
+            }
+        }
+        hostnames
     }
+}
 
-    #[test]
-    fn test_format_domain_for_hosts_with_www() {
-        // format_domain should NOT strip www
-        assert_eq!(
-            format_domain_for_hosts("www.example.com").unwrap(),
-            "www.example.com"
-        );
-        assert_eq!(
-            format_domain_for_hosts("http://www.example.com").unwrap(),
-            "www.example.com"
+// Builds the trailing comment for a new entry,
+// e.g. "# Blocked by gwd [social] until 1699999999 @2024-01-15T09:30:00Z reason=doomscrolling".
+// `variant` is the --also prefix the entry's hostname was made with, if any.
+fn block_comment(options: &BlockOptions, variant: Option<&str>) -> String {
+    format_block_comment(
+        options.comment_style,
+        options.label.as_deref(),
+        options.lock_until,
+        options.until_reboot,
+        // The sweep after a reboot goes by when an --until-reboot entry was added
+        options
+            .blocked_at
+            .or_else(|| options.until_reboot.then(now_timestamp)),
+        options.reason.as_deref(),
+        variant,
+    )
+}
+
+fn format_block_comment(
+    style: CommentStyle,
+    label: Option<&str>,
+    lock_until: Option<i64>,
+    until_reboot: bool,
+    blocked_at: Option<i64>,
+    reason: Option<&str>,
+    variant: Option<&str>,
+) -> String {
+    let reason = reason.map(str::trim).filter(|reason| !reason.is_empty());
+    if style == CommentStyle::Json {
+        let mut metadata = serde_json::Map::new();
+        if let Some(label) = label {
+            metadata.insert("label".to_string(), label.into());
+        }
+        if let Some(until) = lock_until {
+            metadata.insert("until".to_string(), until.into());
+        }
+        if until_reboot {
+            metadata.insert("until_reboot".to_string(), true.into());
+        }
+        if let Some(time) = blocked_at.and_then(format_rfc3339) {
+            metadata.insert("blocked_at".to_string(), time.into());
+        }
+        if let Some(reason) = reason {
+            metadata.insert("reason".to_string(), reason.into());
+        }
+        if let Some(variant) = variant {
+            metadata.insert("variant".to_string(), variant.into());
+        }
+        return format!(
+            "{} {}",
+            JSON_COMMENT_TAG,
+            serde_json::Value::Object(metadata)
         );
     }
-
-    #[test]
-    fn test_format_domain_for_hosts_invalid() {
-        assert!(format_domain_for_hosts("").is_err());
-        assert!(format_domain_for_hosts("http://").is_err());
-        assert!(format_domain_for_hosts("https://").is_err());
-        // Consider adding more invalid cases if needed
+    let mut comment = BLOCK_COMMENT_TAG.to_string();
+    if let Some(label) = label {
+        comment.push_str(&format!(" [{}]", label));
+    }
+    if let Some(until) = lock_until {
+        comment.push_str(&format!(" until {}", until));
+    }
+    if until_reboot {
+        comment.push_str(&format!(" {}", UNTIL_REBOOT_MARKER));
+    }
+    if let Some(time) = blocked_at.and_then(format_rfc3339) {
+        comment.push_str(&format!(" @{}", time));
     }
+    if let Some(reason) = reason {
+        comment.push_str(&format!(" reason={}", encode_reason(reason)));
+    }
+    if let Some(variant) = variant {
+        comment.push_str(&format!(" variant={}", variant));
+    }
+    comment
+}
 
-    // --- Tests for block_website and unblock_website ---
-    // These tests are more complex due to file I/O and lazy_static HOSTS_PATH.
-    // A better approach would be to refactor block/unblock to accept a PathBuf
-    // argument, allowing easy testing with temp files.
+// The metadata of a JSON-style comment. Keys gwd doesn't know are ignored, so entries written by
+// a newer version still read.
+#[derive(Debug, Default, Deserialize)]
+struct JsonTag {
+    label: Option<String>,
+    until: Option<i64>,
+    #[serde(default)]
+    until_reboot: bool,
+    // RFC 3339, like the "@" field of the plain style
+    blocked_at: Option<String>,
+    reason: Option<String>,
+    variant: Option<String>,
+}
 
-    // Example test structure (assuming refactor to pass path):
-    /*
-    fn block_website_testable(domain: &str, hosts_path: &PathBuf) -> Result<()> { ... }
-    fn unblock_website_testable(domain: &str, challenge_word_count: usize, hosts_path: &PathBuf) -> Result<()> { ... }
+// Byte offset of `tag` followed by a JSON object in `line`, e.g. of "# gwd" in "... # gwd {...}"
+fn find_json_tag(line: &str, tag: &str) -> Option<usize> {
+    line.match_indices(tag)
+        .map(|(start, _)| start)
+        .find(|start| line[start + tag.len()..].trim_start().starts_with('{'))
+}
 
-    #[test]
-    fn test_block_website_new_entry() {
-        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
-        let hosts_path = mock_hosts.path().to_path_buf();
+// `line` with the JSON tag at `start` swapped for `to` (see soften_line and harden_line)
+fn swap_json_tag(line: &str, start: usize, from: &str, to: &str) -> String {
+    format!("{}{}{}", &line[..start], to, &line[start + from.len()..])
+}
+
+// Whether `line` carries the tag of an active gwd entry, in either comment style
+fn has_gwd_tag(line: &str) -> bool {
+    line.contains(BLOCK_COMMENT_TAG) || find_json_tag(line, JSON_COMMENT_TAG).is_some()
+}
+
+// Whether `line` carries the tag of a soft block, in either comment style
+fn has_soft_tag(line: &str) -> bool {
+    line.contains(SOFT_BLOCK_TAG) || find_json_tag(line, JSON_SOFT_TAG).is_some()
+}
 
-        block_website_testable("example.com", &hosts_path).unwrap();
+// Percent-encodes a --reason so it is a single field free of '#' and whitespace, keeping the tag
+// parseable whatever the user typed
+fn encode_reason(reason: &str) -> String {
+    reason
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
 
-        let content = fs::read_to_string(&hosts_path).unwrap();
-        assert!(content.contains("0.0.0.0 example.com # Blocked by gwd"));
-        assert!(content.contains("0.0.0.0 www.example.com # Blocked by gwd"));
+// Reverses encode_reason; a malformed escape is kept as it is
+fn decode_reason(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| encoded.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
     }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
 
-     #[test]
-    fn test_unblock_website_removes_entries() {
-        let initial_content = "127.0.0.1 localhost\n0.0.0.0 example.com # Blocked by gwd\n0.0.0.0 www.example.com # Blocked by gwd\n";
-        let mock_hosts = create_mock_hosts(initial_content).unwrap();
-        let hosts_path = mock_hosts.path().to_path_buf();
+// Function to block a single website (and its variants); see block_websites
+pub fn block_website(domain: &str, options: &BlockOptions) -> Result<BlockReport> {
+    block_websites(&[domain.to_string()], options)
+}
 
-        // Mocking the challenge is needed here, or setting word_count to 0
-        unblock_website_testable("example.com", 0, &hosts_path).unwrap(); // Skip challenge
+// Function to add block entries to the hosts file for one or more domains.
+// The hosts file is opened and scanned once for the whole batch.
+pub fn block_websites(domains: &[String], options: &BlockOptions) -> Result<BlockReport> {
+    let hosts_path = get_hosts_path()?;
+    block_websites_at(domains, options, &hosts_path)
+}
 
-        let content = fs::read_to_string(&hosts_path).unwrap();
-        assert!(!content.contains("0.0.0.0 example.com"));
-        assert!(!content.contains("0.0.0.0 www.example.com"));
-        assert!(content.contains("127.0.0.1 localhost")); // Ensure other lines remain
+// Same as block_websites, but against an explicit hosts file path
+pub fn block_websites_at(
+    domains: &[String],
+    options: &BlockOptions,
+    hosts_path: &Path,
+) -> Result<BlockReport> {
+    let redirect_ip = options.redirect_ip;
+    if let Some(section) = &options.section {
+        validate_section_name(section)?;
+    }
+    // Clean every domain up front so an invalid one aborts before we touch the file,
+    // and drop duplicates (e.g. "example.com" and "https://Example.com/") while keeping order.
+    let mut clean_domains: Vec<String> = Vec::new();
+    for domain in domains {
+        let clean_domain = match format_domain_for_hosts(domain) {
+            // A protected name such as 'localhost' needn't look like a domain to be forced
+            Ok(name) if options.protected.contains(&name) => {
+                if !options.force {
+                    return Err(AppError::InvalidDomain(format!(
+                        "'{}' (this machine relies on it; pass --force to block it anyway)",
+                        domain
+                    )));
+                }
+                name
+            }
+            _ => validate_domain(domain, options.allow_ip)?,
+        };
+        if !clean_domains.contains(&clean_domain) {
+            clean_domains.push(clean_domain);
+        }
     }
-    */
 
-    // Since refactoring is out of scope for this step, we acknowledge the limitation
-    // that testing block/unblock directly against the real hosts file is risky
-    // and mocking lazy_static is complex. Manual testing after build is needed.
+    let mut redirect_ips = vec![redirect_ip];
+    if options.ipv6 && !redirect_ips.contains(&ipv6_redirect_for(&redirect_ip)) {
+        redirect_ips.push(ipv6_redirect_for(&redirect_ip));
+    }
+
+    // Each (address, hostname) we want blocked, with what the file already has for it
+    let mut targets: Vec<(IpAddr, String, Existing)> = Vec::new();
+    // The --also variants among them, with their prefix, which their tag records so unblocking
+    // the domain removes them too (www. always goes with its domain, so it isn't recorded)
+    let mut variants: Vec<(String, &str)> = Vec::new();
+    for clean_domain in &clean_domains {
+        // An IP literal has no 'www.' (or other) variants
+        let mut hostnames = vec![clean_domain.clone()];
+        if clean_domain.parse::<IpAddr>().is_err() {
+            for prefix in &options.prefixes {
+                let hostname = validate_domain(&format!("{}.{}", prefix, clean_domain), false)?;
+                if !hostnames.contains(&hostname) {
+                    if prefix != "www" {
+                        variants.push((hostname.clone(), prefix));
+                    }
+                    hostnames.push(hostname);
+                }
+            }
+        }
+        for hostname in hostnames {
+            for ip in &redirect_ips {
+                targets.push((*ip, hostname.clone(), Existing::Nothing));
+            }
+        }
+    }
+
+    options
+        .verbosity
+        .debug(format_args!("Using hosts file {:?}", hosts_path));
+    // Hold the lock across the whole read-modify-write so concurrent runs can't lose entries.
+    // A missing hosts file (e.g. in a fresh container) is created with just the localhost entries.
+    let mut hosts = load_for_edit(hosts_path, options.dry_run)?;
+    if !hosts.exists() {
+        options.verbosity.info(format_args!(
+            "Hosts file {:?} not found; it will be created.",
+            hosts_path
+        ));
+        hosts.fill_if_missing();
+    }
+    // A long batch (e.g. a blocklist) shows its progress through the file and the new entries
+    // instead of a note per hostname
+    let line_count = hosts.lines().count();
+    let mut progress = (clean_domains.len() >= PROGRESS_THRESHOLD && !options.dry_run)
+        .then(|| Progress::start("Blocking", line_count + targets.len(), options.verbosity));
+
+    // Duplicates are checked across the whole file, not just gwd's section, so entries
+    // added by hand (or by another tool) still count as a block, unless --force asks for gwd's own.
+    // A soft block is settled by anything already there; a real block replaces a soft one.
+    let settled = |existing: Existing| {
+        existing == Existing::Gwd
+            || (existing == Existing::Foreign && !options.force)
+            || (existing != Existing::Nothing && options.soft)
+    };
+    // Another blocker's line only settles an entry of the same address family: a line sending a
+    // hostname to 127.0.0.1 doesn't stop its IPv6 lookups
+    let (ipv6_sinkholes, ipv4_sinkholes): (Vec<IpAddr>, Vec<IpAddr>) = options
+        .sinkhole_ips
+        .iter()
+        .partition(|sinkhole| sinkhole.is_ipv6());
+    let sinkholes_like = |ip: &IpAddr| {
+        if ip.is_ipv6() {
+            &ipv6_sinkholes[..]
+        } else {
+            &ipv4_sinkholes[..]
+        }
+    };
+    let mut examined = 0;
+    for line in hosts.lines() {
+        examined += 1;
+        if let Some(progress) = progress.as_mut() {
+            progress.advance(1);
+        }
+        options
+            .verbosity
+            .debug(format_args!("Examining: {}", line.text()));
+        for (ip, hostname, existing) in targets.iter_mut() {
+            if *existing != Existing::Gwd && line.sinks(ip, hostname) {
+                *existing = if !line.gwd_entries().is_empty() {
+                    Existing::Gwd
+                } else {
+                    Existing::Foreign
+                };
+            } else if *existing == Existing::Nothing
+                && line.sinks_foreign(sinkholes_like(ip), hostname)
+            {
+                *existing = Existing::Foreign;
+            } else if *existing == Existing::Nothing && line.soft_blocks(ip, hostname) {
+                *existing = Existing::Soft;
+            }
+        }
+        if targets.iter().all(|(_, _, existing)| settled(*existing)) {
+            break; // No need to read further
+        }
+    }
+    if let Some(progress) = progress.as_mut() {
+        progress.advance(line_count - examined);
+    }
+
+    let mut report = BlockReport::default();
+    let mut lines_to_add = Vec::new();
+    for (ip, hostname, existing) in &targets {
+        if let Some(progress) = progress.as_mut() {
+            progress.advance(1);
+        }
+        // Only mention the address family when both are being written
+        let family = if redirect_ips.len() > 1 && ip.is_ipv6() {
+            "IPv6 "
+        } else {
+            ""
+        };
+        if existing == &Existing::Foreign && progress.is_none() {
+            options.verbosity.info(paint(
+                format_args!(
+                    "{} is already blocked by a non-gwd entry{}.",
+                    hostname,
+                    if options.force {
+                        "; adding gwd's own anyway (--force)"
+                    } else {
+                        ""
+                    }
+                ),
+                Tone::Note,
+            ));
+        }
+        if existing == &Existing::Foreign && !report.foreign.contains(hostname) {
+            report.foreign.push(hostname.clone());
+        }
+        if !settled(*existing) {
+            let variant = variants
+                .iter()
+                .find(|(variant, _)| variant == hostname)
+                .map(|(_, prefix)| *prefix);
+            let entry = format!("{} {} {}", ip, hostname, block_comment(options, variant));
+            let entry = if options.soft {
+                soften_line(&entry)
+            } else {
+                entry
+            };
+            if options.dry_run {
+                options
+                    .verbosity
+                    .info(paint(format_args!("+ {}", entry), Tone::Added));
+            } else {
+                options.verbosity.debug(paint(
+                    format_args!("Adding {}entry for: {}", family, hostname),
+                    Tone::Added,
+                ));
+            }
+            if !report.added.contains(hostname) {
+                report.added.push(hostname.clone());
+            }
+            report.entries.push(entry.clone());
+            lines_to_add.push(entry);
+        } else {
+            options.verbosity.debug(paint(
+                format_args!("{}Block entry for {} already exists.", family, hostname),
+                Tone::Note,
+            ));
+            if !report.already_present.contains(hostname) {
+                report.already_present.push(hostname.clone());
+            }
+        }
+    }
+
+    if options.dry_run {
+        options.verbosity.info(format_args!(
+            "Dry run: no changes written to {:?}.",
+            hosts_path
+        ));
+    } else if !lines_to_add.is_empty() {
+        // A real block takes the place of a soft block of the same hostname
+        let replaced = hosts.remove_hostnames(|line, hostname| {
+            !options.soft
+                && matches!(line, HostsLine::Soft { .. })
+                && report.added.iter().any(|added| added == hostname)
+        });
+        for line in replaced {
+            options
+                .verbosity
+                .debug(format_args!("Replacing soft block: {}", line));
+        }
+        // New entries go at the end of gwd's section (or of its --section), which is created if
+        // missing
+        for line in &lines_to_add {
+            hosts.add_block_to(line, options.section.as_deref());
+        }
+        hosts.save()?;
+        options.verbosity.debug(format_args!(
+            "Successfully updated hosts file to block '{}'.",
+            clean_domains.join("', '")
+        ));
+    } else {
+        options.verbosity.debug(paint(
+            format_args!(
+                "'{}' already configured for blocking.",
+                clean_domains.join("', '")
+            ),
+            Tone::Note,
+        ));
+    }
+
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    report.domains = clean_domains;
+    Ok(report)
+}
+
+// Function to remove block entries from the hosts file
+pub fn unblock_website(domain: &str, options: &UnblockOptions) -> Result<UnblockReport> {
+    let hosts_path = get_hosts_path()?;
+    unblock_website_at(domain, options, &hosts_path)
+}
+
+// Same as unblock_website, but against an explicit hosts file path
+pub fn unblock_website_at(
+    domain: &str,
+    options: &UnblockOptions,
+    hosts_path: &Path,
+) -> Result<UnblockReport> {
+    unblock_websites_at(&[domain.to_string()], options, hosts_path)
+}
+
+// Function to remove block entries for several domains after a single challenge
+pub fn unblock_websites(domains: &[String], options: &UnblockOptions) -> Result<UnblockReport> {
+    let hosts_path = get_hosts_path()?;
+    unblock_websites_at(domains, options, &hosts_path)
+}
+
+// Same as unblock_websites, but against an explicit hosts file path
+pub fn unblock_websites_at(
+    domains: &[String],
+    options: &UnblockOptions,
+    hosts_path: &Path,
+) -> Result<UnblockReport> {
+    let mut clean_domains: Vec<String> = Vec::new();
+    for domain in domains {
+        let clean_domain = format_domain_for_hosts(domain)?;
+        if !clean_domains.contains(&clean_domain) {
+            clean_domains.push(clean_domain);
+        }
+    }
+    // The bare and www forms, plus any other variants gwd wrote for these domains
+    let hosts = HostsFile::load(hosts_path)?;
+    let mut hostnames: Vec<String> = Vec::new();
+    for domain in &clean_domains {
+        hostnames.push(domain.clone());
+        hostnames.push(format!("www.{}", domain));
+        for variant in gwd_variants_of(domain, &hosts) {
+            if !hostnames.contains(&variant) {
+                hostnames.push(variant);
+            }
+        }
+    }
+    let hostname_refs: Vec<&str> = hostnames.iter().map(String::as_str).collect();
+    let domain_list = clean_domains.join("', '");
+    // With --section, lines in the other sub-sections are left alone
+    let scope = options
+        .section
+        .as_deref()
+        .map(|section| hosts.section_texts(section));
+    let in_scope = |line: &HostsLine| {
+        scope
+            .as_ref()
+            .is_none_or(|texts| texts.iter().any(|text| text == line.text()))
+    };
+
+    // A timed lock wins over the challenge, so refuse before asking anything
+    if let Some((domain, remaining)) = active_lock(&hosts, Some(&hostname_refs)) {
+        return Err(AppError::LockActive { domain, remaining });
+    }
+
+    // Run the challenge first (a dry run changes nothing, so it doesn't need one). Soft blocks
+    // don't block anything, so when they are all there is to remove, there's no challenge.
+    let sinks_foreign = |line: &HostsLine, hostname: &str| {
+        options.include_foreign
+            && matches!(line, HostsLine::Other { .. })
+            && (line.sinks(&DEFAULT_REDIRECT_IP, hostname)
+                || line.sinks(&DEFAULT_REDIRECT_IPV6, hostname))
+    };
+    let only_soft = !hosts
+        .list_gwd_in(options.section.as_deref())
+        .iter()
+        .any(|entry| hostnames.contains(&entry.hostname))
+        && !hosts.lines().any(|line| {
+            hostnames
+                .iter()
+                .any(|hostname| sinks_foreign(&line, hostname))
+        })
+        && hosts
+            .list_soft_in(options.section.as_deref())
+            .iter()
+            .any(|entry| hostnames.contains(&entry.hostname));
+    if !options.dry_run && !only_soft {
+        let entries = hosts.list_gwd_in(options.section.as_deref());
+        let entries: Vec<&GwdEntry> = entries
+            .iter()
+            .filter(|entry| hostnames.contains(&entry.hostname))
+            .collect();
+        remind_reasons(entries.iter().copied(), options);
+        // The requested domains count even where only a line gwd didn't write blocks them
+        let targets = clean_domains
+            .iter()
+            .map(|domain| (domain.as_str(), None))
+            .chain(entries.iter().map(|entry| entry.target()));
+        require_challenge(&domain_list, targets, options)?;
+    }
+
+    let verbosity = options.verbosity;
+    verbosity.debug(format_args!("Using hosts file {:?}", hosts_path));
+
+    // Remove gwd's entries (and soft blocks) for the domains and their variants, whatever
+    // redirect IP they were written with. Only lines carrying gwd's tag go: a sinkhole line gwd
+    // didn't write stays, even inside gwd's section, unless --include-foreign asks for it too.
+    // A gwd line that also lists other hostnames keeps them.
+    let mut hosts = load_for_edit(hosts_path, options.dry_run)?;
+    let mut removed = hosts.remove_hostnames(|line, hostname| {
+        verbosity.debug(format_args!("Examining {} on: {}", hostname, line.text()));
+        hostnames.iter().any(|wanted| wanted == hostname) && in_scope(line)
+    });
+    if options.include_foreign {
+        removed.extend(hosts.remove_foreign(|line, hostname| {
+            hostnames.iter().any(|wanted| wanted == hostname) && sinks_foreign(line, hostname)
+        }));
+    }
+    if !removed.is_empty() && !options.dry_run {
+        hosts.save()?;
+    }
+    // Say which hostnames still resolve to a sinkhole, so a leftover block isn't a mystery
+    for hostname in &hostnames {
+        let foreign = hosts.lines().any(|line| {
+            matches!(line, HostsLine::Other { .. })
+                && (line.sinks(&DEFAULT_REDIRECT_IP, hostname)
+                    || line.sinks(&DEFAULT_REDIRECT_IPV6, hostname))
+        });
+        if foreign && !options.include_foreign {
+            verbosity.info(paint(
+                format_args!(
+                    "{} is still blocked by a line gwd didn't write; --include-foreign removes it too.",
+                    hostname
+                ),
+                Tone::Note,
+            ));
+        }
+    }
+    drop(hosts);
+    let report = UnblockReport {
+        domains: clean_domains,
+        removed,
+    };
+    print_removed_lines(&report.removed, options);
+    if report.removed.is_empty() {
+        verbosity.debug(format_args!(
+            "No {} found for '{}'.",
+            if options.include_foreign {
+                "gwd entries or other sinkhole lines"
+            } else {
+                "gwd entries"
+            },
+            domain_list
+        ));
+        return Ok(report);
+    }
+    if options.dry_run {
+        verbosity.info(format_args!(
+            "Dry run: no changes written to {:?}.",
+            hosts_path
+        ));
+        return Ok(report);
+    }
+
+    verbosity.debug(format_args!(
+        "Successfully removed blocking entries for '{}'.",
+        domain_list
+    ));
+
+    Ok(report)
+}
+
+// Number of hosts file lines tagged by gwd, i.e. what unblock --all would remove
+pub fn gwd_entry_count_at(hosts_path: &Path) -> Result<usize> {
+    Ok(HostsFile::load(hosts_path)?
+        .lines()
+        .filter(|line| has_gwd_tag(line.text()))
+        .count())
+}
+
+// Function to list the entries paused by gwd pause, with the times they resume
+pub fn list_paused_at(hosts_path: &Path) -> Result<Vec<(i64, GwdEntry)>> {
+    Ok(HostsFile::load(hosts_path)?.list_paused())
+}
+
+// Function to remove every gwd-managed entry from the hosts file after a single challenge
+pub fn unblock_all_websites(options: &UnblockOptions) -> Result<UnblockReport> {
+    let hosts_path = get_hosts_path()?;
+    unblock_all_websites_at(options, &hosts_path)
+}
+
+// Same as unblock_all_websites, but against an explicit hosts file path
+pub fn unblock_all_websites_at(
+    options: &UnblockOptions,
+    hosts_path: &Path,
+) -> Result<UnblockReport> {
+    // Count gwd entries first so we neither run the challenge nor touch the file if there are none
+    let entry_count = gwd_entry_count_at(hosts_path)?;
+    let hosts = HostsFile::load(hosts_path)?;
+    if entry_count == 0 && hosts.list_soft().is_empty() {
+        options.verbosity.info("No active blocking entries found.");
+        return Ok(UnblockReport::default());
+    }
+
+    // Any still-locked domain keeps the whole batch blocked
+    if let Some((domain, remaining)) = active_lock(&hosts, None) {
+        return Err(AppError::LockActive { domain, remaining });
+    }
+
+    // Run the challenge once for the whole batch; soft blocks alone don't need one
+    if !options.dry_run && entry_count > 0 {
+        remind_reasons(hosts.list_gwd().iter(), options);
+        let entries = hosts.list_gwd();
+        require_challenge(
+            "all websites blocked by gwd",
+            entries.iter().map(GwdEntry::target),
+            options,
+        )?;
+    }
+
+    let verbosity = options.verbosity;
+    verbosity.debug(format_args!("Using hosts file {:?}", hosts_path));
+    let removed = remove_matching_lines(hosts_path, options.dry_run, |line| {
+        verbosity.debug(format_args!("Examining: {}", line.text()));
+        has_gwd_tag(line.text()) || !line.soft_entries().is_empty()
+    })?;
+    let report = UnblockReport {
+        domains: Vec::new(),
+        removed,
+    };
+    print_removed_lines(&report.removed, options);
+    if options.dry_run {
+        verbosity.info(format_args!(
+            "Dry run: no changes written to {:?}.",
+            hosts_path
+        ));
+        return Ok(report);
+    }
+
+    verbosity.debug(format_args!(
+        "Successfully removed {} blocking entries.",
+        report.removed.len()
+    ));
+
+    Ok(report)
+}
+
+// Function to turn the soft blocks of some domains (and of their www. and other variants) into
+// real blocks, keeping their label and reason. No challenge: this only makes blocking stricter.
+pub fn harden_websites(
+    domains: &[String],
+    dry_run: bool,
+    verbosity: Verbosity,
+) -> Result<BlockReport> {
+    let hosts_path = get_hosts_path()?;
+    harden_websites_at(domains, dry_run, verbosity, &hosts_path)
+}
+
+// Same as harden_websites, but against an explicit hosts file path. The report's `added` lists
+// the hardened hostnames and `entries` the lines they now have.
+pub fn harden_websites_at(
+    domains: &[String],
+    dry_run: bool,
+    verbosity: Verbosity,
+    hosts_path: &Path,
+) -> Result<BlockReport> {
+    let mut clean_domains: Vec<String> = Vec::new();
+    for domain in domains {
+        let clean_domain = format_domain_for_hosts(domain)?;
+        if !clean_domains.contains(&clean_domain) {
+            clean_domains.push(clean_domain);
+        }
+    }
+    let wanted = |hostname: &str| {
+        clean_domains
+            .iter()
+            .any(|domain| hostname == domain || is_subdomain_of(hostname, domain))
+    };
+
+    let mut hosts = load_for_edit(hosts_path, dry_run)?;
+    // A soft block listing several hostnames is hardened whole, as its hostnames share one tag
+    let replaced = hosts.replace_lines(|line| match line {
+        HostsLine::Soft { text, entries }
+            if entries.iter().any(|entry| wanted(&entry.hostname)) =>
+        {
+            harden_line(text)
+        }
+        _ => None,
+    });
+    let mut report = BlockReport::default();
+    for (old, new) in replaced {
+        if dry_run {
+            verbosity.info(paint(format_args!("- {}", old), Tone::Removed));
+            verbosity.info(paint(format_args!("+ {}", new), Tone::Added));
+        } else {
+            verbosity.debug(paint(format_args!("Hardening: {}", new), Tone::Added));
+        }
+        for entry in parse_gwd_entries(&new) {
+            if !report.added.contains(&entry.hostname) {
+                report.added.push(entry.hostname);
+            }
+        }
+        report.entries.push(new);
+    }
+    for domain in &clean_domains {
+        if !report
+            .added
+            .iter()
+            .any(|hostname| hostname == domain || is_subdomain_of(hostname, domain))
+        {
+            verbosity.info(paint(
+                format_args!("{} has no soft block to harden.", domain),
+                Tone::Note,
+            ));
+        }
+    }
+    if !dry_run && !report.entries.is_empty() {
+        hosts.save()?;
+    }
+    report.domains = clean_domains;
+    Ok(report)
+}
+
+// Function to give the existing gwd entries of some domains (and of their www. and other
+// variants) a new label, soft blocks included (gwd move). Only the comments change: what is
+// blocked, and any lock, stays as it is, so no challenge is needed.
+pub fn move_websites(
+    domains: &[String],
+    label: &str,
+    dry_run: bool,
+    verbosity: Verbosity,
+) -> Result<BlockReport> {
+    let hosts_path = get_hosts_path()?;
+    move_websites_at(domains, label, dry_run, verbosity, &hosts_path)
+}
+
+// Same as move_websites, but against an explicit hosts file path. Fails with
+// AppError::NotBlocked, changing nothing, if a domain has no gwd entry. The report's `added`
+// lists the relabelled hostnames and `already_present` those that already had the label.
+pub fn move_websites_at(
+    domains: &[String],
+    label: &str,
+    dry_run: bool,
+    verbosity: Verbosity,
+    hosts_path: &Path,
+) -> Result<BlockReport> {
+    let mut clean_domains: Vec<String> = Vec::new();
+    for domain in domains {
+        let clean_domain = format_domain_for_hosts(domain)?;
+        if !clean_domains.contains(&clean_domain) {
+            clean_domains.push(clean_domain);
+        }
+    }
+    let wanted = |hostname: &str| {
+        clean_domains
+            .iter()
+            .any(|domain| hostname == domain || is_subdomain_of(hostname, domain))
+    };
+    // The line of a gwd entry with the label in its comment swapped for `label`
+    let relabel = |text: &str, entry: &GwdEntry| {
+        let comment = format_block_comment(
+            entry.style,
+            Some(label),
+            entry.until,
+            entry.until_reboot,
+            entry.blocked_at,
+            entry.reason.as_deref(),
+            entry.variant.as_deref(),
+        );
+        format!("{}{}", &text[..text.len() - entry.comment.len()], comment)
+    };
+
+    let mut hosts = load_for_edit(hosts_path, dry_run)?;
+    let blocked: Vec<GwdEntry> = hosts
+        .list_gwd()
+        .into_iter()
+        .chain(hosts.list_soft())
+        .collect();
+    for domain in &clean_domains {
+        if !blocked
+            .iter()
+            .any(|entry| entry.hostname == *domain || is_subdomain_of(&entry.hostname, domain))
+        {
+            return Err(AppError::NotBlocked(domain.clone()));
+        }
+    }
+
+    let mut report = BlockReport::default();
+    // The hostnames of a line share its comment, so a line listing several moves whole
+    let replaced = hosts.replace_lines(|line| {
+        let entries = line.tagged_entries();
+        let entry = entries.first()?;
+        if !entries.iter().any(|entry| wanted(&entry.hostname)) {
+            return None;
+        }
+        if entry.label.as_deref() == Some(label) {
+            for entry in entries {
+                if !report.already_present.contains(&entry.hostname) {
+                    report.already_present.push(entry.hostname.clone());
+                }
+            }
+            return None;
+        }
+        match line {
+            HostsLine::Gwd { text, .. } => Some(relabel(text, entry)),
+            HostsLine::Soft { text, .. } => Some(soften_line(&relabel(&harden_line(text)?, entry))),
+            _ => None,
+        }
+    });
+    for (old, new) in replaced {
+        if dry_run {
+            verbosity.info(paint(format_args!("- {}", old), Tone::Removed));
+            verbosity.info(paint(format_args!("+ {}", new), Tone::Added));
+        } else {
+            verbosity.debug(format_args!("Relabelling: {}", new));
+        }
+        for entry in HostsLine::parse(&new).tagged_entries() {
+            if !report.added.contains(&entry.hostname) {
+                report.added.push(entry.hostname.clone());
+            }
+        }
+        report.entries.push(new);
+    }
+    if !dry_run && !report.entries.is_empty() {
+        hosts.save()?;
+    }
+    report.domains = clean_domains;
+    Ok(report)
+}
+
+// Function to turn off every gwd block until `resume_at` (gwd pause), after a single challenge as
+// for unblock --all. The entries are commented out with PAUSED_PREFIX and the resume time, so
+// resume_blocks_at (or the sweep every command runs first, once the time is up) can put them
+// back as they were. Entries that are already paused get the new resume time.
+pub fn pause_blocks_at(
+    resume_at: i64,
+    options: &UnblockOptions,
+    hosts_path: &Path,
+) -> Result<PauseReport> {
+    let hosts = HostsFile::load(hosts_path)?;
+    let entries = hosts.list_gwd();
+    let already_paused = hosts.list_paused();
+    if entries.is_empty() && already_paused.is_empty() {
+        options.verbosity.info("No active blocking entries found.");
+        return Ok(PauseReport {
+            paused: Vec::new(),
+            resume_at,
+        });
+    }
+
+    // A timed lock is a promise not to unblock, and a pause is an unblock for a while
+    if let Some((domain, remaining)) = active_lock(&hosts, None) {
+        return Err(AppError::LockActive { domain, remaining });
+    }
+    if !options.dry_run {
+        remind_reasons(entries.iter(), options);
+        require_challenge(
+            "pausing all websites blocked by gwd",
+            entries
+                .iter()
+                .chain(already_paused.iter().map(|(_, entry)| entry))
+                .map(GwdEntry::target),
+            options,
+        )?;
+    }
+
+    let mut hosts = load_for_edit(hosts_path, options.dry_run)?;
+    let replaced = hosts.replace_lines(|line| match line {
+        HostsLine::Gwd { text, .. } => Some(pause_line(text, resume_at)),
+        HostsLine::Raw(text) => unpause_line(text).map(|(_, entry)| pause_line(&entry, resume_at)),
+        _ => None,
+    });
+    let mut report = PauseReport {
+        paused: Vec::new(),
+        resume_at,
+    };
+    for (old, new) in replaced {
+        if options.dry_run {
+            options
+                .verbosity
+                .info(paint(format_args!("- {}", old), Tone::Removed));
+            options
+                .verbosity
+                .info(paint(format_args!("+ {}", new), Tone::Added));
+        } else {
+            options
+                .verbosity
+                .debug(paint(format_args!("Pausing: {}", new), Tone::Removed));
+        }
+        report
+            .paused
+            .extend(unpause_line(&new).map(|(_, entry)| entry));
+    }
+    if options.dry_run {
+        options.verbosity.info(format_args!(
+            "Dry run: no changes written to {:?}.",
+            hosts_path
+        ));
+    } else {
+        hosts.save()?;
+    }
+    Ok(report)
+}
+
+// Function to turn paused entries (gwd pause) back on now (gwd resume). No challenge: this only
+// restores blocks. Returns the restored entry lines.
+pub fn resume_blocks_at(
+    dry_run: bool,
+    verbosity: Verbosity,
+    hosts_path: &Path,
+) -> Result<Vec<String>> {
+    resume_paused_lines(hosts_path, dry_run, verbosity, |_| true)
+}
+
+// Resumes the paused entries whose time is up, like sweep_expired: the file is only locked and
+// rewritten when there is one. Returns the restored entry lines.
+pub fn sweep_paused(hosts_path: &Path) -> Result<Vec<String>> {
+    let now = now_timestamp();
+    let due = |resume_at: i64| resume_at <= now;
+    let any_due = HostsFile::load(hosts_path)?
+        .list_paused()
+        .iter()
+        .any(|(resume_at, _)| due(*resume_at));
+    if !any_due {
+        return Ok(Vec::new());
+    }
+    resume_paused_lines(hosts_path, false, Verbosity::Quiet, due)
+}
+
+// Uncomments the paused entries whose resume time `due` accepts. A hostname blocked again while
+// paused keeps just the new entry for its address.
+fn resume_paused_lines(
+    hosts_path: &Path,
+    dry_run: bool,
+    verbosity: Verbosity,
+    due: impl Fn(i64) -> bool,
+) -> Result<Vec<String>> {
+    let mut hosts = load_for_edit(hosts_path, dry_run)?;
+    let active: Vec<(String, String)> = hosts
+        .list_gwd()
+        .into_iter()
+        .map(|entry| (entry.ip, entry.hostname))
+        .collect();
+    let resumable = |line: &HostsLine| match line {
+        HostsLine::Raw(text) => unpause_line(text)
+            .filter(|(resume_at, _)| due(*resume_at))
+            .map(|(_, entry)| entry),
+        _ => None,
+    };
+    let mut resumed: Vec<String> = Vec::new();
+    let duplicates = hosts.remove_block(|line| {
+        resumable(line).is_some_and(|entry| {
+            let entries = parse_gwd_entries(&entry);
+            !entries.is_empty()
+                && entries
+                    .into_iter()
+                    .all(|entry| active.contains(&(entry.ip, entry.hostname)))
+        })
+    });
+    for line in duplicates {
+        if let Some((_, entry)) = unpause_line(&line) {
+            resumed.push(entry);
+        }
+    }
+    for (_, entry) in hosts.replace_lines(resumable) {
+        resumed.push(entry);
+    }
+    for entry in &resumed {
+        if dry_run {
+            verbosity.info(paint(format_args!("+ {}", entry), Tone::Added));
+        } else {
+            verbosity.debug(paint(format_args!("Resuming: {}", entry), Tone::Added));
+        }
+    }
+    if !dry_run && !resumed.is_empty() {
+        hosts.save()?;
+    }
+    Ok(resumed)
+}
+
+// Function to remove every entry blocked under one label (block --label, or a profile) after a
+// single challenge, leaving entries for the same domains blocked without that label alone
+pub fn unblock_label(label: &str, options: &UnblockOptions) -> Result<UnblockReport> {
+    let hosts_path = get_hosts_path()?;
+    remove_label_at(label, options, true, &hosts_path)
+}
+
+// Same as unblock_label, but without a challenge: this is for non-interactive callers such as
+// apply-schedule
+pub fn unblock_label_at(
+    label: &str,
+    dry_run: bool,
+    verbosity: Verbosity,
+    hosts_path: &Path,
+) -> Result<UnblockReport> {
+    let options = UnblockOptions {
+        dry_run,
+        verbosity,
+        ..Default::default()
+    };
+    remove_label_at(label, &options, false, hosts_path)
+}
+
+fn remove_label_at(
+    label: &str,
+    options: &UnblockOptions,
+    challenge: bool,
+    hosts_path: &Path,
+) -> Result<UnblockReport> {
+    let has_label = |entry: &GwdEntry| entry.label.as_deref() == Some(label);
+
+    let hosts = HostsFile::load(hosts_path)?;
+    let mut hostnames: Vec<String> = Vec::new();
+    for entry in hosts
+        .list_gwd()
+        .into_iter()
+        .chain(hosts.list_soft())
+        .filter(has_label)
+    {
+        if !hostnames.contains(&entry.hostname) {
+            hostnames.push(entry.hostname);
+        }
+    }
+    if hostnames.is_empty() {
+        options
+            .verbosity
+            .info(format_args!("No entries blocked as '{}' found.", label));
+        return Ok(UnblockReport::default());
+    }
+
+    let hostname_refs: Vec<&str> = hostnames.iter().map(String::as_str).collect();
+    if let Some((domain, remaining)) = active_lock(&hosts, Some(&hostname_refs)) {
+        return Err(AppError::LockActive { domain, remaining });
+    }
+
+    // Soft blocks alone don't need a challenge
+    let entries = hosts.list_gwd();
+    if challenge && !options.dry_run && entries.iter().any(has_label) {
+        remind_reasons(entries.iter().filter(|entry| has_label(entry)), options);
+        require_challenge(
+            &format!("everything blocked as '{}'", label),
+            entries
+                .iter()
+                .filter(|entry| has_label(entry))
+                .map(GwdEntry::target),
+            options,
+        )?;
+    }
+
+    let removed = remove_matching_lines(hosts_path, options.dry_run, |line| {
+        line.tagged_entries().first().is_some_and(has_label)
+    })?;
+    print_removed_lines(&removed, options);
+    if !options.dry_run {
+        options.verbosity.debug(format_args!(
+            "Successfully removed {} entries blocked as '{}'.",
+            removed.len(),
+            label
+        ));
+    }
+    Ok(UnblockReport {
+        domains: hostnames,
+        removed,
+    })
+}
+
+// Function to remove every entry in one sub-section of gwd's section (block --section) after a
+// single challenge
+pub fn unblock_section(section: &str, options: &UnblockOptions) -> Result<UnblockReport> {
+    let hosts_path = get_hosts_path()?;
+    unblock_section_at(section, options, &hosts_path)
+}
+
+// Same as unblock_section, but against an explicit hosts file path
+pub fn unblock_section_at(
+    section: &str,
+    options: &UnblockOptions,
+    hosts_path: &Path,
+) -> Result<UnblockReport> {
+    let hosts = HostsFile::load(hosts_path)?;
+    let texts = hosts.section_texts(section);
+    let entries = hosts.list_gwd_in(Some(section));
+    let mut hostnames: Vec<String> = Vec::new();
+    for entry in entries.iter().chain(&hosts.list_soft_in(Some(section))) {
+        if !hostnames.contains(&entry.hostname) {
+            hostnames.push(entry.hostname.clone());
+        }
+    }
+    if hostnames.is_empty() {
+        options
+            .verbosity
+            .info(format_args!("No entries in section '{}' found.", section));
+        return Ok(UnblockReport::default());
+    }
+
+    let hostname_refs: Vec<&str> = hostnames.iter().map(String::as_str).collect();
+    if let Some((domain, remaining)) = active_lock(&hosts, Some(&hostname_refs)) {
+        return Err(AppError::LockActive { domain, remaining });
+    }
+
+    // Soft blocks alone don't need a challenge
+    if !options.dry_run && !entries.is_empty() {
+        remind_reasons(entries.iter(), options);
+        require_challenge(
+            &format!("everything in section '{}'", section),
+            entries.iter().map(GwdEntry::target),
+            options,
+        )?;
+    }
+
+    let removed = remove_matching_lines(hosts_path, options.dry_run, |line| {
+        !line.tagged_entries().is_empty() && texts.iter().any(|text| text == line.text())
+    })?;
+    print_removed_lines(&removed, options);
+    Ok(UnblockReport {
+        domains: hostnames,
+        removed,
+    })
+}
+
+// Before an unblock's challenge, repeats why the user blocked the domains (block --reason), e.g.
+// "You blocked example.com because: too much doomscrolling". Prompts are on stderr with --json,
+// and so is the reminder.
+fn remind_reasons<'a>(entries: impl Iterator<Item = &'a GwdEntry>, options: &UnblockOptions) {
+    let mut reminded: Vec<(&str, &str)> = Vec::new();
+    for entry in entries {
+        let Some(reason) = entry.reason.as_deref() else {
+            continue;
+        };
+        let domain = entry
+            .hostname
+            .strip_prefix("www.")
+            .unwrap_or(&entry.hostname);
+        if reminded.contains(&(domain, reason)) {
+            continue;
+        }
+        reminded.push((domain, reason));
+        let message = format!("You blocked {} because: {}", domain, reason);
+        if options.challenge.prompt_on_stderr {
+            eprintln!("{}", paint_stderr(message, Tone::Note));
+        } else {
+            options.verbosity.info(paint(message, Tone::Note));
+        }
+    }
+}
+
+// Runs the unblock challenge unless the caller forced the unblock
+fn require_challenge<'a>(
+    subject: &str,
+    targets: impl IntoIterator<Item = (&'a str, Option<&'a str>)>,
+    options: &UnblockOptions,
+) -> Result<()> {
+    // The global commitment wins over --force and the challenge alike
+    if let Some(remaining) = no_unblock_remaining(&options.no_unblock, Local::now().naive_local()) {
+        return Err(AppError::LockActive {
+            domain: subject.to_string(),
+            remaining,
+        });
+    }
+    if options.force {
+        options.verbosity.debug("Skipping the challenge (--force)");
+        return Ok(());
+    }
+    let targets: Vec<(&str, Option<&str>)> = targets.into_iter().collect();
+    let mut challenge = options.challenge.for_targets(targets.iter().copied());
+    // Cooling: every recent unblock of one of the domains makes this challenge longer. It is
+    // kept with the cooldowns, so a challenge without them (e.g. from a library caller) has none.
+    let mut domains: Vec<&str> = Vec::new();
+    for (hostname, _) in &targets {
+        let domain = hostname.strip_prefix("www.").unwrap_or(hostname);
+        if !domains.contains(&domain) {
+            domains.push(domain);
+        }
+    }
+    // Variants such as m.example.com cool down with their domain
+    let variants: Vec<&str> = domains
+        .iter()
+        .copied()
+        .filter(|hostname| {
+            domains
+                .iter()
+                .any(|domain| is_subdomain_of(hostname, domain))
+        })
+        .collect();
+    domains.retain(|domain| !variants.contains(domain));
+    let cooling_path = options
+        .challenge
+        .cooldown
+        .as_ref()
+        .filter(|_| challenge.verifier.is_none())
+        .map(|cooldown| cooldown.path.clone());
+    if let Some(path) = &cooling_path {
+        let mut coolest = ("", 0);
+        for domain in &domains {
+            let recent = recent_unblocks_at(path, domain)?;
+            if recent > coolest.1 {
+                coolest = (domain, recent);
+            }
+        }
+        let (domain, recent) = coolest;
+        if recent > 0 && challenge.length > 0 {
+            let length = cooled_length(challenge.length, recent);
+            let message = format!(
+                "{} was unblocked {} time(s) in the last 24 hours, so the challenge is {} \
+                 longer ('gwd reset-cooling {}' resets this).",
+                domain,
+                recent,
+                length - challenge.length,
+                domain
+            );
+            challenge.length = length;
+            if challenge.prompt_on_stderr {
+                eprintln!("{}", paint_stderr(message, Tone::Note));
+            } else {
+                options.verbosity.info(paint(message, Tone::Note));
+            }
+        }
+    }
+    run_challenge(subject, &challenge)?;
+    if let Some(path) = &cooling_path {
+        for domain in &domains {
+            record_unblock_at(path, domain)?;
+        }
+    }
+    Ok(())
+}
+
+// Forgets the recent unblocks of a domain (and its www. form) that make its challenge longer
+// (gwd reset-cooling). Returns whether there were any.
+pub fn reset_cooling(domain: &str, cooldown_path: &Path) -> Result<bool> {
+    let clean_domain = format_domain_for_hosts(domain)?;
+    let domain = clean_domain.strip_prefix("www.").unwrap_or(&clean_domain);
+    reset_cooling_at(cooldown_path, domain)
+}
+
+// Echoes the lines an unblock removed (or would remove, in a dry run)
+fn print_removed_lines(lines: &[String], options: &UnblockOptions) {
+    for line in lines {
+        if options.dry_run {
+            options
+                .verbosity
+                .info(paint(format_args!("- {}", line), Tone::Removed));
+        } else {
+            options.verbosity.debug(paint(
+                format_args!("Removing line: {}", line),
+                Tone::Removed,
+            ));
+        }
+    }
+}
+
+// Function to undo a block: removes exactly the entries it wrote, after the usual lock check
+// and challenge (undoing a block is an unblock, so it mustn't be a way around them)
+pub fn undo_block_at(
+    entries: &[String],
+    options: &UnblockOptions,
+    hosts_path: &Path,
+) -> Result<UnblockReport> {
+    let hostnames: Vec<&str> = entries
+        .iter()
+        .filter_map(|entry| entry.split_whitespace().nth(1))
+        .collect();
+    if let Some((domain, remaining)) = active_lock(&HostsFile::load(hosts_path)?, Some(&hostnames))
+    {
+        return Err(AppError::LockActive { domain, remaining });
+    }
+    if !options.dry_run {
+        let entries: Vec<GwdEntry> = entries
+            .iter()
+            .filter_map(|entry| parse_gwd_entry(entry))
+            .collect();
+        require_challenge(
+            "the last block",
+            entries.iter().map(GwdEntry::target),
+            options,
+        )?;
+    }
+
+    let removed = remove_matching_lines(hosts_path, options.dry_run, |line| {
+        entries.iter().any(|entry| entry == line.text())
+    })?;
+    print_removed_lines(&removed, options);
+    Ok(UnblockReport {
+        domains: Vec::new(),
+        removed,
+    })
+}
+
+// Function to end a focus session: removes exactly the entries the session wrote, with no
+// challenge since the session ran its course. Blocks made outside the session stay, even under
+// the same label.
+pub fn end_focus_session_at(
+    entries: &[String],
+    dry_run: bool,
+    verbosity: Verbosity,
+    hosts_path: &Path,
+) -> Result<UnblockReport> {
+    let options = UnblockOptions {
+        dry_run,
+        verbosity,
+        ..Default::default()
+    };
+    let removed = remove_matching_lines(hosts_path, dry_run, |line| {
+        entries.iter().any(|entry| entry == line.text())
+    })?;
+    print_removed_lines(&removed, &options);
+    let mut domains: Vec<String> = Vec::new();
+    for entry in removed.iter().flat_map(|line| parse_gwd_entries(line)) {
+        if !domains.contains(&entry.hostname) {
+            domains.push(entry.hostname);
+        }
+    }
+    Ok(UnblockReport { domains, removed })
+}
+
+// Function to undo an unblock: puts the removed entries back into gwd's section, skipping any
+// that are already there. No challenge, since the user passed one to unblock in the first place.
+// Returns the restored entries.
+pub fn undo_unblock_at(
+    entries: &[String],
+    dry_run: bool,
+    verbosity: Verbosity,
+    hosts_path: &Path,
+) -> Result<Vec<String>> {
+    let mut hosts = load_for_edit(hosts_path, dry_run)?;
+    hosts.fill_if_missing();
+    let existing: Vec<String> = hosts.lines().map(|line| line.text().to_string()).collect();
+    let restored: Vec<String> = entries
+        .iter()
+        .filter(|entry| !existing.contains(entry))
+        .cloned()
+        .collect();
+    for entry in &restored {
+        if dry_run {
+            verbosity.info(paint(format_args!("+ {}", entry), Tone::Added));
+        } else {
+            verbosity.info(paint(
+                format_args!("Restoring line: {}", entry),
+                Tone::Added,
+            ));
+        }
+    }
+    if dry_run || restored.is_empty() {
+        return Ok(restored);
+    }
+
+    for entry in &restored {
+        hosts.add_block(entry);
+    }
+    hosts.save()?;
+    Ok(restored)
+}
+
+// What a normalize run did
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct NormalizeReport {
+    // Entries dropped because an earlier entry already blocks the same hostname at the same address
+    pub duplicates_removed: usize,
+    // Entries rewritten into the canonical "<ip> <hostname> # Blocked by gwd ..." form
+    pub reformatted: usize,
+    // Whether the section had been edited outside gwd, so its checksum was recomputed
+    pub checksum_reset: bool,
+}
+
+// A gwd entry parsed into its parts, for normalize
+struct ParsedEntry {
+    ip: IpAddr,
+    hostname: String,
+    label: Option<String>,
+    until: Option<i64>,
+    until_reboot: bool,
+    blocked_at: Option<i64>,
+    reason: Option<String>,
+    variant: Option<String>,
+    style: CommentStyle,
+}
+
+impl ParsedEntry {
+    // One per hostname, as gwd writes them; empty for lines that aren't well-formed gwd entries,
+    // which normalize leaves alone
+    fn parse(line: &str) -> Vec<ParsedEntry> {
+        parse_gwd_entries(line)
+            .into_iter()
+            .map(ParsedEntry::from_entry)
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default()
+    }
+
+    fn from_entry(entry: GwdEntry) -> Option<ParsedEntry> {
+        Some(ParsedEntry {
+            ip: entry.ip.parse::<IpAddr>().ok()?,
+            hostname: entry.hostname,
+            label: entry.label,
+            until: entry.until,
+            until_reboot: entry.until_reboot,
+            blocked_at: entry.blocked_at,
+            reason: entry.reason,
+            variant: entry.variant,
+            style: entry.style,
+        })
+    }
+
+    fn render(&self) -> String {
+        format!(
+            "{} {} {}",
+            self.ip,
+            self.hostname,
+            format_block_comment(
+                self.style,
+                self.label.as_deref(),
+                self.until,
+                self.until_reboot,
+                self.blocked_at,
+                self.reason.as_deref(),
+                self.variant.as_deref()
+            )
+        )
+    }
+}
+
+// Function to clean up gwd's section: drops duplicate entries (the same hostname blocked twice at
+// the same address, however it is spelled or spaced) and rewrites the rest in the form gwd writes
+// new entries. Lines gwd doesn't recognise are kept verbatim and in order, so running it again
+// changes nothing.
+pub fn normalize_hosts(dry_run: bool, verbosity: Verbosity) -> Result<NormalizeReport> {
+    let hosts_path = get_hosts_path()?;
+    normalize_hosts_at(dry_run, verbosity, &hosts_path)
+}
+
+// Same as normalize_hosts, but against an explicit hosts file path
+pub fn normalize_hosts_at(
+    dry_run: bool,
+    verbosity: Verbosity,
+    hosts_path: &Path,
+) -> Result<NormalizeReport> {
+    // normalize is how an outside edit gets accepted, so it never refuses over the checksum
+    let mut hosts = load_unchecked(hosts_path, dry_run)?;
+
+    let mut report = NormalizeReport {
+        checksum_reset: hosts.layout.tampered(),
+        ..Default::default()
+    };
+    // Each kept line, with the parsed entry (if any) so later duplicates can update it. A line
+    // listing several hostnames becomes one line per hostname.
+    let mut kept: Vec<(String, Option<ParsedEntry>)> = Vec::new();
+    for raw_line in &hosts.layout.entries {
+        let line = trim_line_ending(raw_line);
+        let entries = ParsedEntry::parse(line);
+        if entries.is_empty() {
+            kept.push((raw_line.clone(), None));
+            continue;
+        }
+        for entry in entries {
+            let duplicate_of = kept.iter_mut().find_map(|(_, existing)| {
+                existing.as_mut().filter(|existing| {
+                    existing.ip == entry.ip && existing.hostname == entry.hostname
+                })
+            });
+            match duplicate_of {
+                Some(existing) => {
+                    if dry_run {
+                        verbosity.info(paint(format_args!("- {}", line), Tone::Removed));
+                    } else {
+                        verbosity.info(paint(
+                            format_args!("Removing duplicate entry: {}", line),
+                            Tone::Removed,
+                        ));
+                    }
+                    report.duplicates_removed += 1;
+                    // Never weaken a block: keep the later lock and any label
+                    if entry.until > existing.until {
+                        existing.until = entry.until;
+                    }
+                    existing.until_reboot &= entry.until_reboot;
+                    if existing.label.is_none() {
+                        existing.label = entry.label;
+                    }
+                    if existing.reason.is_none() {
+                        existing.reason = entry.reason;
+                    }
+                    // The site has been blocked since the earliest of the two
+                    existing.blocked_at = earliest(existing.blocked_at, entry.blocked_at);
+                }
+                None => kept.push((line.to_string(), Some(entry))),
+            }
+        }
+    }
+
+    let line_ending = hosts.layout.line_ending;
+    hosts.layout.entries = kept
+        .into_iter()
+        .map(|(line, entry)| match entry {
+            Some(entry) => {
+                let canonical = entry.render();
+                if canonical != line {
+                    verbosity.debug(format_args!("Reformatting: {} -> {}", line, canonical));
+                    report.reformatted += 1;
+                }
+                format!("{}{}", canonical, line_ending)
+            }
+            None => line,
+        })
+        .collect();
+
+    if !dry_run {
+        hosts.save()?;
+    }
+    Ok(report)
+}
+
+// Flushes the OS DNS cache so hosts file changes take effect immediately.
+// Returns false when there was no cache to flush. Failures are returned as DnsFlush errors;
+// callers treat them as warnings since the hosts file itself has already been updated.
+pub fn flush_dns() -> Result<bool> {
+    #[cfg(target_os = "windows")]
+    {
+        run_flush_command("ipconfig", &["/flushdns"]).map(|_| true)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        run_flush_command("dscacheutil", &["-flushcache"])?;
+        run_flush_command("killall", &["-HUP", "mDNSResponder"]).map(|_| true)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        // Best effort: without systemd-resolved there is usually no local cache to flush
+        match run_flush_command("resolvectl", &["flush-caches"]) {
+            Ok(()) => Ok(true),
+            Err(AppError::DnsFlush(msg)) if msg.contains("not found") => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    {
+        Ok(false)
+    }
+}
+
+// Manual flush instructions, for when gwd is told not to flush the DNS cache itself
+pub fn flush_dns_hint() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "Run 'ipconfig /flushdns' if the change doesn't take effect immediately."
+    } else {
+        "DNS cache might need flushing (e.g., resolvectl flush-caches or dscacheutil -flushcache)."
+    }
+}
+
+// Runs dns_flush_command from config.toml through the shell, so it can use sudo, pipes or
+// several commands. A non-zero exit status becomes DnsFlush.
+pub fn run_dns_flush_command(command: &str) -> Result<()> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    tracing::debug!(command, "flushing the DNS cache with dns_flush_command");
+    let output = std::process::Command::new(shell)
+        .args([flag, command])
+        .output()
+        .map_err(|e| AppError::DnsFlush(format!("failed to run '{}': {}", command, e)))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let status = match output.status.code() {
+        Some(code) => format!("exit status {}", code),
+        None => "a signal".to_string(),
+    };
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.trim();
+    Err(AppError::DnsFlush(if stderr.is_empty() {
+        format!("'{}' failed with {}", command, status)
+    } else {
+        format!("'{}' failed with {}: {}", command, status, stderr)
+    }))
+}
+
+// Runs one DNS flush command, turning a missing binary or non-zero exit into DnsFlush
+#[allow(dead_code)] // Unused on platforms without a known flush command
+fn run_flush_command(program: &str, args: &[&str]) -> Result<()> {
+    tracing::debug!(program, ?args, "flushing the DNS cache");
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => AppError::DnsFlush(format!("'{}' not found", program)),
+            _ => AppError::DnsFlush(format!("failed to run '{}': {}", program, e)),
+        })?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(AppError::DnsFlush(format!(
+            "'{} {}' failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+// Returns the dominant line ending of `content` ("\r\n" or "\n"), defaulting to the platform's
+fn detect_line_ending(content: &str) -> &'static str {
+    let newlines = content.matches('\n').count();
+    let crlfs = content.matches("\r\n").count();
+    if newlines == 0 {
+        if cfg!(windows) {
+            "\r\n"
+        } else {
+            "\n"
+        }
+    } else if crlfs * 2 > newlines {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+// Reads all lines of the hosts file
+fn read_hosts_lines(hosts_path: &Path) -> Result<Vec<String>> {
+    let file = match File::open(hosts_path) {
+        // No hosts file means nothing is blocked
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        result => result,
+    };
+    let file = file.map_err(|e| map_io_error(e, hosts_path, IoPhase::Read))?;
+    BufReader::new(file)
+        .lines()
+        .collect::<io::Result<_>>()
+        .map_err(|e| map_io_error(e, hosts_path, IoPhase::Read))
+}
+
+// Finds the longest-running timed lock among gwd entries for `hostnames` (or all entries if None).
+// Returns the locked domain (without 'www.') and the time remaining, or None if nothing is locked.
+fn active_lock(hosts: &HostsFile, hostnames: Option<&[&str]>) -> Option<(String, Duration)> {
+    let now = now_timestamp();
+    let mut latest: Option<(String, i64)> = None;
+    for entry in hosts.list_gwd() {
+        if hostnames.is_some_and(|wanted| !wanted.contains(&entry.hostname.as_str())) {
+            continue;
+        }
+        if let Some(until) = entry.until.filter(|until| *until > now) {
+            if latest
+                .as_ref()
+                .is_none_or(|(_, latest_until)| until > *latest_until)
+            {
+                let domain = entry
+                    .hostname
+                    .strip_prefix("www.")
+                    .unwrap_or(&entry.hostname)
+                    .to_string();
+                latest = Some((domain, until));
+            }
+        }
+    }
+    latest.map(|(domain, until)| (domain, Duration::from_secs((until - now) as u64)))
+}
+
+// Strips a trailing "\n" or "\r\n" from a raw line
+fn trim_line_ending(raw_line: &str) -> &str {
+    raw_line.trim_end_matches('\n').trim_end_matches('\r')
+}
+
+// The hosts file split around gwd's managed section. Lines keep their original terminators.
+struct ManagedLayout {
+    // Lines before the section (or the whole file if there is no section yet)
+    before: Vec<String>,
+    // Entries inside the section, without the markers and the checksum line
+    entries: Vec<String>,
+    // The checksum stored in the section, if it has one (older versions of gwd wrote none)
+    checksum: Option<String>,
+    // Lines after the section
+    after: Vec<String>,
+    // Line ending used for anything gwd writes
+    line_ending: &'static str,
+}
+
+impl ManagedLayout {
+    // Splits `content` into the parts around gwd's section, repairing it along the way:
+    // - a missing END marker means the section runs to the end of the file
+    // - duplicated sections are merged into the first one, and stray END markers are dropped
+    // - gwd entries found outside the section (e.g. written by older versions) are moved into it
+    fn parse(content: &str) -> ManagedLayout {
+        let mut layout = ManagedLayout {
+            before: Vec::new(),
+            entries: Vec::new(),
+            checksum: None,
+            after: Vec::new(),
+            line_ending: detect_line_ending(content),
+        };
+        let mut seen_start = false;
+        let mut in_section = false;
+
+        for raw_line in content.split_inclusive('\n') {
+            let line = trim_line_ending(raw_line);
+            match line.trim() {
+                SECTION_START_MARKER => {
+                    seen_start = true;
+                    in_section = true;
+                }
+                SECTION_END_MARKER => in_section = false,
+                trimmed if in_section && trimmed.starts_with(CHECKSUM_PREFIX) => {
+                    layout.checksum = Some(trimmed[CHECKSUM_PREFIX.len()..].trim().to_string())
+                }
+                _ if in_section || has_gwd_tag(line) || has_soft_tag(line) => {
+                    layout.entries.push(raw_line.to_string())
+                }
+                _ if seen_start => layout.after.push(raw_line.to_string()),
+                _ => layout.before.push(raw_line.to_string()),
+            }
+        }
+        layout
+    }
+
+    // Whether the section changed since gwd last wrote it. A section without a checksum (or
+    // none at all) counts as unchanged.
+    fn tampered(&self) -> bool {
+        self.checksum
+            .as_ref()
+            .is_some_and(|stored| *stored != section_checksum(&self.entries))
+    }
+
+    // Reassembles the file. The section is omitted entirely once it has no entries left.
+    fn render(&self) -> String {
+        let mut output = String::new();
+        // Appends a line, first terminating the previous one if it lacked a line ending
+        let mut push = |line: &str| {
+            if !output.is_empty() && !output.ends_with('\n') {
+                output.push_str(self.line_ending);
+            }
+            output.push_str(line);
+        };
+
+        for line in &self.before {
+            push(line);
+        }
+        if !self.entries.is_empty() {
+            push(&format!("{}{}", SECTION_START_MARKER, self.line_ending));
+            push(&format!(
+                "{} {}{}",
+                CHECKSUM_PREFIX,
+                section_checksum(&self.entries),
+                self.line_ending
+            ));
+            for line in &self.entries {
+                push(line);
+            }
+            push(&format!("{}{}", SECTION_END_MARKER, self.line_ending));
+        }
+        for line in &self.after {
+            push(line);
+        }
+        output
+    }
+}
+
+// The name in a sub-section header ("# gwd:work" -> "work"), or None if the line isn't one
+fn subsection_name(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix(SUBSECTION_PREFIX)
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
+// Drops the headers of sub-sections left without lines, and the default header once it is the
+// only one left, so a section that stops using sub-sections looks like it never did
+fn prune_subsections(entries: &mut Vec<String>) {
+    let mut index = 0;
+    while index < entries.len() {
+        let empty = subsection_name(&entries[index]).is_some()
+            && entries
+                .get(index + 1)
+                .is_none_or(|next| subsection_name(next).is_some());
+        if empty {
+            entries.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+    let mut headers = entries.iter().filter_map(|line| subsection_name(line));
+    if let (Some(DEFAULT_SECTION), None) = (headers.next(), headers.next()) {
+        entries.retain(|line| subsection_name(line).is_none());
+    }
+}
+
+// Checks a --section name: letters, digits, '-' and '_', like profile names
+pub fn validate_section_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::Config(format!(
+            "Invalid section name '{}': use letters, digits, '-' and '_' only",
+            name
+        )))
+    }
+}
+
+// Hex SHA-256 of the section's entries, one per line. Line endings don't count, so a file
+// converted between CRLF and LF still matches.
+fn section_checksum(entries: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    for line in entries {
+        hasher.update(trim_line_ending(line).as_bytes());
+        hasher.update(b"\n");
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// A gwd entry split into its parts, e.g.
+// "0.0.0.0 example.com # Blocked by gwd [social] until 1699999999 @2024-01-15T09:30:00Z"
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GwdEntry {
+    // The address as written; a hand-edited entry may not hold a valid IP
+    pub ip: String,
+    // Lower-cased, without the trailing dot of a fully qualified name
+    pub hostname: String,
+    // Label (e.g. profile name) the entry was blocked under
+    pub label: Option<String>,
+    // Unix timestamp of the --until/--for lock
+    pub until: Option<i64>,
+    // Removed by the first gwd run after a reboot (block --until-reboot)
+    pub until_reboot: bool,
+    // Unix timestamp the entry was added at
+    pub blocked_at: Option<i64>,
+    // Why the domain was blocked (--reason), decoded
+    pub reason: Option<String>,
+    // The --also prefix of a variant, e.g. "m" for m.example.com blocked along with example.com
+    pub variant: Option<String>,
+    // Which style the comment is written in
+    pub style: CommentStyle,
+    // The comment, from gwd's tag to the end of the line
+    pub comment: String,
+}
+
+impl GwdEntry {
+    // What the challenge length is looked up by (see ChallengeLengths::resolve)
+    pub fn target(&self) -> (&str, Option<&str>) {
+        (&self.hostname, self.label.as_deref())
+    }
+}
+
+// One line of the hosts file, without its line ending
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum HostsLine {
+    // Blank lines, comments, section markers and anything else that isn't an entry
+    Raw(String),
+    // An entry written by gwd, with one GwdEntry per hostname on the line
+    Gwd {
+        text: String,
+        entries: Vec<GwdEntry>,
+    },
+    // A soft block: a gwd entry commented out. `entries` describe it as it will read once hardened.
+    Soft {
+        text: String,
+        entries: Vec<GwdEntry>,
+    },
+    // An "<ip> <hostname>..." entry written by hand or by another tool
+    Other {
+        text: String,
+        ip: IpAddr,
+        // Lower-cased, without trailing dots
+        hostnames: Vec<String>,
+    },
+}
+
+impl HostsLine {
+    pub fn parse(line: &str) -> HostsLine {
+        let entries = parse_gwd_entries(line);
+        if !entries.is_empty() {
+            return HostsLine::Gwd {
+                text: line.to_string(),
+                entries,
+            };
+        }
+        let entries = harden_line(line).map_or_else(Vec::new, |line| parse_gwd_entries(&line));
+        if !entries.is_empty() {
+            return HostsLine::Soft {
+                text: line.to_string(),
+                entries,
+            };
+        }
+        // Everything after a '#' is a comment
+        let mut fields = line.split('#').next().unwrap_or("").split_whitespace();
+        let ip = fields.next().and_then(|ip| ip.parse::<IpAddr>().ok());
+        let hostnames: Vec<String> = fields.map(normalize_hostname).collect();
+        match ip {
+            Some(ip) if !hostnames.is_empty() => HostsLine::Other {
+                text: line.to_string(),
+                ip,
+                hostnames,
+            },
+            _ => HostsLine::Raw(line.to_string()),
+        }
+    }
+
+    // The line as it appears in the file
+    pub fn text(&self) -> &str {
+        match self {
+            HostsLine::Raw(text)
+            | HostsLine::Gwd { text, .. }
+            | HostsLine::Soft { text, .. }
+            | HostsLine::Other { text, .. } => text,
+        }
+    }
+
+    // The entries of a line gwd wrote, one per hostname; empty for any other line
+    pub fn gwd_entries(&self) -> &[GwdEntry] {
+        match self {
+            HostsLine::Gwd { entries, .. } => entries,
+            _ => &[],
+        }
+    }
+
+    // The entries of a soft block, one per hostname; empty for any other line
+    pub fn soft_entries(&self) -> &[GwdEntry] {
+        match self {
+            HostsLine::Soft { entries, .. } => entries,
+            _ => &[],
+        }
+    }
+
+    // The entries of a gwd line, soft or not. Its hostnames share one comment, so the first
+    // entry has the line's label, lock and the rest of its metadata.
+    pub fn tagged_entries(&self) -> &[GwdEntry] {
+        match self {
+            HostsLine::Gwd { entries, .. } | HostsLine::Soft { entries, .. } => entries,
+            _ => &[],
+        }
+    }
+
+    // Whether this is a soft block that would sink `hostname` like an entry to `redirect_ip`
+    // once hardened
+    pub fn soft_blocks(&self, redirect_ip: &IpAddr, hostname: &str) -> bool {
+        self.soft_entries()
+            .iter()
+            .any(|entry| same_target(entry, redirect_ip, hostname))
+    }
+
+    // Whether this line already blocks `hostname` the way an entry to `redirect_ip` would: a line
+    // sinking that hostname (alone or among others) to `redirect_ip`, or one gwd wrote with an
+    // address of the same family, whatever IP was in use at the time
+    pub fn sinks(&self, redirect_ip: &IpAddr, hostname: &str) -> bool {
+        match self {
+            // A soft block is commented out, so it sinks nothing
+            HostsLine::Raw(_) | HostsLine::Soft { .. } => false,
+            HostsLine::Gwd { entries, .. } => entries
+                .iter()
+                .any(|entry| same_target(entry, redirect_ip, hostname)),
+            HostsLine::Other { ip, hostnames, .. } => {
+                ip == redirect_ip && hostnames.iter().any(|listed| listed == hostname)
+            }
+        }
+    }
+
+    // Whether this is a line gwd didn't write sending `hostname` to one of `sinkholes`
+    pub fn sinks_foreign(&self, sinkholes: &[IpAddr], hostname: &str) -> bool {
+        match self {
+            HostsLine::Other { ip, hostnames, .. } => {
+                sinkholes.contains(ip) && hostnames.iter().any(|listed| listed == hostname)
+            }
+            _ => false,
+        }
+    }
+}
+
+// Whether a gwd entry is for `hostname` with an address of the same family as `redirect_ip`,
+// whatever IP was in use at the time. IPv6 addresses always contain a colon, IPv4 addresses
+// never do.
+fn same_target(entry: &GwdEntry, redirect_ip: &IpAddr, hostname: &str) -> bool {
+    entry.hostname == hostname && entry.ip.contains(':') == redirect_ip.is_ipv6()
+}
+
+// The soft-block form of an entry line: commented out, with the soft tag of its style
+fn soften_line(line: &str) -> String {
+    let softened = match find_json_tag(line, JSON_COMMENT_TAG) {
+        Some(start) => swap_json_tag(line, start, JSON_COMMENT_TAG, JSON_SOFT_TAG),
+        None => line.replacen(BLOCK_COMMENT_TAG, SOFT_BLOCK_TAG, 1),
+    };
+    format!("# {}", softened)
+}
+
+// The paused form of an entry line (gwd pause)
+fn pause_line(line: &str, resume_at: i64) -> String {
+    format!("{}until {} {}", PAUSED_PREFIX, resume_at, line.trim_start())
+}
+
+// The resume time and entry line of a paused entry, or None if `line` isn't one
+fn unpause_line(line: &str) -> Option<(i64, String)> {
+    let rest = line
+        .trim_start()
+        .strip_prefix(PAUSED_PREFIX)?
+        .strip_prefix("until ")?;
+    let (resume_at, entry) = rest.split_once(' ')?;
+    let resume_at = resume_at.parse::<i64>().ok()?;
+    parse_gwd_entry(entry)?;
+    Some((resume_at, entry.to_string()))
+}
+
+// The entry line a soft block turns into when hardened, or None if `line` isn't a soft block
+fn harden_line(line: &str) -> Option<String> {
+    let entry = line.trim_start().strip_prefix('#')?.trim_start();
+    if let Some(start) = find_json_tag(entry, JSON_SOFT_TAG) {
+        return Some(swap_json_tag(entry, start, JSON_SOFT_TAG, JSON_COMMENT_TAG));
+    }
+    entry
+        .contains(SOFT_BLOCK_TAG)
+        .then(|| entry.replacen(SOFT_BLOCK_TAG, BLOCK_COMMENT_TAG, 1))
+}
+
+fn normalize_hostname(hostname: &str) -> String {
+    hostname.trim_end_matches('.').to_lowercase()
+}
+
+// Splits a shared "<ip> <hostname> <hostname>... # comment" line into the line without the
+// hostnames `remove` picks, and an "<ip> <hostname>" line for each one it picked
+fn split_shared_line(text: &str, remove: impl Fn(&str) -> bool) -> (String, Vec<String>) {
+    let (fields, comment) = match text.find('#') {
+        Some(start) => text.split_at(start),
+        None => (text, ""),
+    };
+    let mut tokens = fields.split_whitespace();
+    let ip = tokens.next().unwrap_or_default();
+    let (taken, kept): (Vec<&str>, Vec<&str>) = tokens.partition(|hostname| remove(hostname));
+    let mut rest = format!("{} {}", ip, kept.join(" "));
+    if !comment.is_empty() {
+        rest.push(' ');
+        rest.push_str(comment);
+    }
+    let taken = taken
+        .iter()
+        .map(|hostname| format!("{} {}", ip, hostname))
+        .collect();
+    (rest, taken)
+}
+
+// Splits a gwd line listing several hostnames into the line without the hostnames `remove`
+// picks, and a line with the same tag for each one it picked. Soft blocks stay soft.
+fn split_tagged_line(
+    line: &HostsLine,
+    remove: impl Fn(&str) -> bool,
+) -> Option<(String, Vec<String>)> {
+    let (entry_line, soft) = match line {
+        HostsLine::Gwd { text, .. } => (text.clone(), false),
+        HostsLine::Soft { text, .. } => (harden_line(text)?, true),
+        _ => return None,
+    };
+    let comment = &line.tagged_entries().first()?.comment;
+    let mut fields = entry_line[..entry_line.len() - comment.len()].split_whitespace();
+    let ip = fields.next()?;
+    let (taken, kept): (Vec<&str>, Vec<&str>) = fields.partition(|hostname| remove(hostname));
+    let render = |hostnames: &[&str]| {
+        let line = format!("{} {} {}", ip, hostnames.join(" "), comment);
+        if soft {
+            soften_line(&line)
+        } else {
+            line
+        }
+    };
+    let taken = taken.iter().map(|hostname| render(&[hostname])).collect();
+    Some((render(&kept), taken))
+}
+
+// Parses a line gwd wrote itself, which has a single hostname (see parse_gwd_entries)
+fn parse_gwd_entry(line: &str) -> Option<GwdEntry> {
+    parse_gwd_entries(line).into_iter().next()
+}
+
+// Parses a gwd entry in either comment style, into one GwdEntry per hostname: a tagged line may
+// list several, e.g. "0.0.0.0 x.com y.com # Blocked by gwd" after an edit by hand. Empty for a
+// line that isn't a gwd entry; a JSON comment that doesn't parse makes the line no gwd entry at
+// all (verify reports it as malformed).
+fn parse_gwd_entries(line: &str) -> Vec<GwdEntry> {
+    let Some(entry) = parse_gwd_tag(line) else {
+        return Vec::new();
+    };
+    let hostnames = GWD_ENTRY_REGEX
+        .as_ref()
+        .ok()
+        .and_then(|entry_regex| entry_regex.captures(line))
+        .and_then(|captures| captures.get(1))
+        .map_or("", |hostnames| hostnames.as_str());
+    hostnames
+        .split_whitespace()
+        .map(|hostname| GwdEntry {
+            hostname: normalize_hostname(hostname),
+            ..entry.clone()
+        })
+        .collect()
+}
+
+// The address and metadata of a gwd entry line, with `hostname` left empty
+fn parse_gwd_tag(line: &str) -> Option<GwdEntry> {
+    let entry_regex = GWD_ENTRY_REGEX.as_ref().ok()?;
+    let captures = entry_regex.captures(line)?;
+    let comment = &line[captures.get(2)?.start()..];
+    let mut entry = GwdEntry {
+        ip: line.split_whitespace().next()?.to_string(),
+        hostname: String::new(),
+        label: None,
+        until: None,
+        until_reboot: false,
+        blocked_at: None,
+        reason: None,
+        variant: None,
+        style: CommentStyle::Plain,
+        comment: comment.to_string(),
+    };
+    if let Some(json) = comment.strip_prefix(JSON_COMMENT_TAG) {
+        let tag: JsonTag = serde_json::from_str(json).ok()?;
+        entry.label = tag.label;
+        entry.until = tag.until;
+        entry.until_reboot = tag.until_reboot;
+        entry.blocked_at = tag.blocked_at.as_deref().and_then(parse_rfc3339);
+        entry.reason = tag.reason;
+        entry.variant = tag.variant;
+        entry.style = CommentStyle::Json;
+    } else {
+        let tag = GWD_TAG_REGEX.as_ref().ok()?.captures(comment)?;
+        entry.label = tag.get(1).map(|m| m.as_str().to_string());
+        entry.until = tag.get(2).and_then(|m| m.as_str().parse::<i64>().ok());
+        entry.until_reboot = tag.get(3).is_some();
+        entry.blocked_at = tag.get(4).and_then(|m| parse_rfc3339(m.as_str()));
+        entry.reason = tag.get(5).map(|m| decode_reason(m.as_str()));
+        entry.variant = tag.get(6).map(|m| m.as_str().to_string());
+    }
+    Some(entry)
+}
+
+// The hosts file, loaded once for reading or editing. Lines keep their original bytes and
+// terminators, so save() only changes what add_block and remove_block changed.
+pub(crate) struct HostsFile {
+    path: PathBuf,
+    // The content as loaded, to tell whether there is anything to save
+    content: String,
+    layout: ManagedLayout,
+    exists: bool,
+    // Held from loading to saving when the file was loaded for editing
+    lock: Option<HostsLock>,
+}
+
+impl HostsFile {
+    // Loads the file for reading. A missing file loads as empty.
+    pub fn load(path: &Path) -> Result<HostsFile> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(map_io_error(e, path, IoPhase::Read)),
+        };
+        tracing::debug!(
+            path = ?path,
+            bytes = content.as_ref().map(String::len),
+            "read the hosts file"
+        );
+        Ok(HostsFile {
+            path: path.to_path_buf(),
+            layout: ManagedLayout::parse(content.as_deref().unwrap_or("")),
+            exists: content.is_some(),
+            content: content.unwrap_or_default(),
+            lock: None,
+        })
+    }
+
+    // Loads the file for editing: other gwd processes wait until this one is dropped, so
+    // concurrent runs can't lose each other's changes
+    pub fn load_locked(path: &Path) -> Result<HostsFile> {
+        let lock = HostsLock::acquire(path)?;
+        let mut hosts = HostsFile::load(path)?;
+        hosts.lock = Some(lock);
+        Ok(hosts)
+    }
+
+    // False if the file didn't exist when it was loaded
+    pub fn exists(&self) -> bool {
+        self.exists
+    }
+
+    // Starts a missing file with the localhost entries (DEFAULT_HOSTS_CONTENT), so they keep
+    // resolving once save() creates it
+    pub fn fill_if_missing(&mut self) {
+        if !self.exists {
+            self.layout = ManagedLayout::parse(DEFAULT_HOSTS_CONTENT);
+        }
+    }
+
+    // Every line, in the order save() writes them (gwd entries gathered into its section)
+    pub fn lines(&self) -> impl Iterator<Item = HostsLine> + '_ {
+        self.layout
+            .before
+            .iter()
+            .chain(&self.layout.entries)
+            .chain(&self.layout.after)
+            .map(|raw_line| HostsLine::parse(trim_line_ending(raw_line)))
+    }
+
+    // gwd's entries in file order
+    pub fn list_gwd(&self) -> Vec<GwdEntry> {
+        self.list_gwd_in(None)
+    }
+
+    // gwd's entries in one sub-section (block --section) in file order, or all of them for None
+    pub fn list_gwd_in(&self, section: Option<&str>) -> Vec<GwdEntry> {
+        self.section_lines(section)
+            .flat_map(|raw_line| parse_gwd_entries(trim_line_ending(raw_line)))
+            .collect()
+    }
+
+    // gwd's paused entries (gwd pause) in file order, with their resume times
+    pub fn list_paused(&self) -> Vec<(i64, GwdEntry)> {
+        self.section_lines(None)
+            .filter_map(|raw_line| unpause_line(trim_line_ending(raw_line)))
+            .flat_map(|(resume_at, line)| {
+                parse_gwd_entries(&line)
+                    .into_iter()
+                    .map(move |entry| (resume_at, entry))
+            })
+            .collect()
+    }
+
+    // gwd's soft blocks in file order
+    pub fn list_soft(&self) -> Vec<GwdEntry> {
+        self.list_soft_in(None)
+    }
+
+    // gwd's soft blocks in one sub-section in file order, or all of them for None
+    pub fn list_soft_in(&self, section: Option<&str>) -> Vec<GwdEntry> {
+        self.section_lines(section)
+            .flat_map(
+                |raw_line| match HostsLine::parse(trim_line_ending(raw_line)) {
+                    HostsLine::Soft { entries, .. } => entries,
+                    _ => Vec::new(),
+                },
+            )
+            .collect()
+    }
+
+    // The lines of gwd's section that belong to a sub-section, without their line endings: those
+    // under its "# gwd:<name>" header, plus for DEFAULT_SECTION any before the first header
+    pub fn section_texts(&self, section: &str) -> Vec<String> {
+        self.section_lines(Some(section))
+            .map(|raw_line| trim_line_ending(raw_line).to_string())
+            .collect()
+    }
+
+    // Lines of gwd's section in one sub-section, or all of them for None
+    fn section_lines<'a>(&'a self, section: Option<&'a str>) -> impl Iterator<Item = &'a String> {
+        let mut current = DEFAULT_SECTION;
+        self.layout.entries.iter().filter(move |raw_line| {
+            if let Some(name) = subsection_name(raw_line) {
+                current = name;
+            }
+            section.is_none_or(|wanted| current == wanted)
+        })
+    }
+
+    // Appends a line to the end of gwd's section, which is created if missing
+    pub fn add_block(&mut self, line: &str) {
+        self.add_block_to(line, None);
+    }
+
+    // Appends a line to the end of a sub-section of gwd's section (block --section), adding its
+    // "# gwd:<name>" header if needed. While the section has no headers, a line without a
+    // sub-section simply goes last; once it has some, the entries that had none are put under
+    // the default header.
+    pub fn add_block_to(&mut self, line: &str, section: Option<&str>) {
+        let line_ending = self.layout.line_ending;
+        let entries = &mut self.layout.entries;
+        let has_headers = entries.iter().any(|line| subsection_name(line).is_some());
+        let header = |name: &str| format!("{}{}{}", SUBSECTION_PREFIX, name, line_ending);
+        let index = match section {
+            None if !has_headers => entries.len(),
+            _ => {
+                let name = section.unwrap_or(DEFAULT_SECTION);
+                if !has_headers && !entries.is_empty() {
+                    entries.insert(0, header(DEFAULT_SECTION));
+                }
+                // An entry lacking a line ending can only have been the last line of the file
+                if let Some(last) = entries.last_mut().filter(|last| !last.ends_with('\n')) {
+                    last.push_str(line_ending);
+                }
+                match entries
+                    .iter()
+                    .position(|line| subsection_name(line) == Some(name))
+                {
+                    Some(start) => entries[start + 1..]
+                        .iter()
+                        .position(|line| subsection_name(line).is_some())
+                        .map_or(entries.len(), |offset| start + 1 + offset),
+                    None => {
+                        entries.push(header(name));
+                        entries.len()
+                    }
+                }
+            }
+        };
+        entries.insert(index, format!("{}{}", line, line_ending));
+        // Whatever follows the section ends up last; make sure the file still ends in a newline
+        if let Some(last) = self.layout.after.last_mut() {
+            if !last.ends_with('\n') {
+                last.push_str(line_ending);
+            }
+        }
+    }
+
+    // Rewrites the lines of gwd's section for which `replace` returns a new line, keeping their
+    // terminators. Returns (old line, new line) pairs.
+    pub fn replace_lines(
+        &mut self,
+        mut replace: impl FnMut(&HostsLine) -> Option<String>,
+    ) -> Vec<(String, String)> {
+        let mut replaced = Vec::new();
+        for raw_line in self.layout.entries.iter_mut() {
+            let text = trim_line_ending(raw_line);
+            let line = HostsLine::parse(text);
+            if let Some(new_line) = replace(&line) {
+                let ending = raw_line[text.len()..].to_string();
+                replaced.push((text.to_string(), new_line.clone()));
+                *raw_line = format!("{}{}", new_line, ending);
+            }
+        }
+        replaced
+    }
+
+    // Drops the lines of gwd's section for which `should_remove` returns true; lines outside the
+    // section are never touched. Returns the removed lines.
+    pub fn remove_block(
+        &mut self,
+        mut should_remove: impl FnMut(&HostsLine) -> bool,
+    ) -> Vec<String> {
+        let mut removed = Vec::new();
+        self.layout.entries.retain(|raw_line| {
+            let line = HostsLine::parse(trim_line_ending(raw_line));
+            if should_remove(&line) {
+                removed.push(line.text().to_string());
+                false
+            } else {
+                true
+            }
+        });
+        prune_subsections(&mut self.layout.entries);
+        removed
+    }
+
+    // Takes the hostnames for which `should_remove` returns true off gwd's lines (soft blocks
+    // included) in its section. A line left without hostnames is dropped; one that also lists
+    // other hostnames is rewritten without the removed ones. Returns the removed lines, plus a
+    // line with the same tag for each hostname taken off a shared line.
+    pub fn remove_hostnames(
+        &mut self,
+        mut should_remove: impl FnMut(&HostsLine, &str) -> bool,
+    ) -> Vec<String> {
+        let mut removed = Vec::new();
+        let mut kept = Vec::new();
+        for raw_line in std::mem::take(&mut self.layout.entries) {
+            let text = trim_line_ending(&raw_line);
+            let line = HostsLine::parse(text);
+            let listed = line.tagged_entries();
+            let gone: Vec<&str> = listed
+                .iter()
+                .map(|entry| entry.hostname.as_str())
+                .filter(|hostname| should_remove(&line, hostname))
+                .collect();
+            if gone.is_empty() {
+                kept.push(raw_line);
+            } else if gone.len() == listed.len() {
+                removed.push(text.to_string());
+            } else if let Some((rest, taken)) = split_tagged_line(&line, |hostname| {
+                gone.contains(&normalize_hostname(hostname).as_str())
+            }) {
+                removed.extend(taken);
+                kept.push(format!("{}{}", rest, &raw_line[text.len()..]));
+            } else {
+                kept.push(raw_line);
+            }
+        }
+        self.layout.entries = kept;
+        prune_subsections(&mut self.layout.entries);
+        removed
+    }
+
+    // Takes the hostnames for which `should_remove` returns true off lines gwd didn't write,
+    // anywhere in the file (unblock --include-foreign). A line left without hostnames is dropped;
+    // one that also lists other hostnames is rewritten without the removed ones. Returns the
+    // removed lines, plus an "<ip> <hostname>" line for each hostname taken off a shared line.
+    pub fn remove_foreign(
+        &mut self,
+        mut should_remove: impl FnMut(&HostsLine, &str) -> bool,
+    ) -> Vec<String> {
+        let mut removed = Vec::new();
+        let layout = &mut self.layout;
+        for part in [&mut layout.before, &mut layout.entries, &mut layout.after] {
+            let mut kept = Vec::new();
+            for raw_line in std::mem::take(part) {
+                let text = trim_line_ending(&raw_line);
+                let line = HostsLine::parse(text);
+                let listed: &[String] = match &line {
+                    HostsLine::Other { hostnames, .. } => hostnames,
+                    _ => &[],
+                };
+                let gone: Vec<String> = listed
+                    .iter()
+                    .filter(|hostname| should_remove(&line, hostname))
+                    .cloned()
+                    .collect();
+                if gone.is_empty() {
+                    kept.push(raw_line);
+                } else if gone.len() == listed.len() {
+                    removed.push(text.to_string());
+                } else {
+                    let (rest, taken) = split_shared_line(text, |hostname| {
+                        gone.contains(&normalize_hostname(hostname))
+                    });
+                    removed.extend(taken);
+                    kept.push(format!("{}{}", rest, &raw_line[text.len()..]));
+                }
+            }
+            *part = kept;
+        }
+        prune_subsections(&mut self.layout.entries);
+        removed
+    }
+
+    // Writes the changes, if any, by renaming a temp file over the original. Only a file loaded
+    // with load_locked can be saved.
+    pub fn save(&self) -> Result<()> {
+        if self.lock.is_none() {
+            return Err(AppError::Io(format!(
+                "{:?} was loaded read-only and can't be saved",
+                self.path
+            )));
+        }
+        self.save_staged(&Staging::system())
+    }
+
+    // save with the temp file staged as `staging` says
+    fn save_staged(&self, staging: &Staging) -> Result<()> {
+        let new_content = self.layout.render();
+        if new_content == self.content {
+            return Ok(());
+        }
+        write_hosts_atomically(&self.path, &new_content, staging)
+    }
+}
+
+// Loads the hosts file for a change that may be a dry run, which doesn't need the lock. If
+// gwd's section was edited outside gwd, this warns (the change goes ahead and stores a new
+// checksum), or with --strict refuses until `gwd normalize` accepts the edit.
+fn load_for_edit(hosts_path: &Path, dry_run: bool) -> Result<HostsFile> {
+    let hosts = load_unchecked(hosts_path, dry_run)?;
+    check_checksum(&hosts, STRICT_CHECKSUM.load(Ordering::Relaxed))?;
+    Ok(hosts)
+}
+
+fn check_checksum(hosts: &HostsFile, strict: bool) -> Result<()> {
+    if hosts.layout.tampered() {
+        if strict {
+            return Err(AppError::Tampered(hosts.path.clone()));
+        }
+        eprintln!(
+            "{}",
+            paint_stderr(
+                format_args!(
+                    "Warning: gwd's section of {:?} was edited outside gwd since gwd last \
+                     changed it; its checksum no longer matches.",
+                    hosts.path
+                ),
+                Tone::Removed,
+            )
+        );
+    }
+    Ok(())
+}
+
+fn load_unchecked(hosts_path: &Path, dry_run: bool) -> Result<HostsFile> {
+    if dry_run {
+        HostsFile::load(hosts_path)
+    } else {
+        HostsFile::load_locked(hosts_path)
+    }
+}
+
+// Where write_hosts_atomically stages the new hosts file before renaming it into place
+pub fn temp_path_for(hosts_path: &Path) -> PathBuf {
+    hosts_path.with_extension("tmp")
+}
+
+// Where a write may leave its temp file: next to the hosts file, or in the system temp directory
+// (see create_temp_file)
+fn temp_file_candidates(hosts_path: &Path, staging: &Staging) -> [PathBuf; 2] {
+    [
+        temp_path_for(hosts_path),
+        fallback_path_for(&staging.fallback_dir, hosts_path, "tmp"),
+    ]
+}
+
+// Deletes the temp files orphaned by an interrupted write, next to the hosts file or in the
+// system temp directory (see create_temp_file), and returns their paths. The caller must hold the
+// HostsLock, otherwise this could delete another run's temp file.
+fn discard_stale_temp_files(hosts_path: &Path, staging: &Staging) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for temp_file_path in temp_file_candidates(hosts_path, staging) {
+        match fs::remove_file(&temp_file_path) {
+            Ok(()) => removed.push(temp_file_path),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(map_io_error(
+                    e,
+                    hosts_path,
+                    IoPhase::Write(&format!(
+                        "removing the stale temp file {:?}",
+                        temp_file_path
+                    )),
+                ))
+            }
+        }
+    }
+    Ok(removed)
+}
+
+// Function to remove gwd entries whose --until/--for lock has run out, or that were blocked
+// --until-reboot before the system last booted (gwd tidy). Returns the removed lines.
+pub fn remove_expired_entries_at(dry_run: bool, hosts_path: &Path) -> Result<Vec<String>> {
+    let now = now_timestamp();
+    let boot = OnceCell::new();
+    remove_matching_lines(hosts_path, dry_run, |line| {
+        line.gwd_entries()
+            .first()
+            .is_some_and(|entry| has_expired(entry, now, || *boot.get_or_init(boot_time)))
+    })
+}
+
+// Whether a gwd entry's lock has run out, or it was blocked --until-reboot before `boot` (the
+// last boot, looked up only for such entries). Without a boot time, or a record of when the entry
+// was added, an --until-reboot entry stays.
+fn has_expired(entry: &GwdEntry, now: i64, boot: impl FnOnce() -> Option<i64>) -> bool {
+    entry.until.is_some_and(|until| until <= now)
+        || (entry.until_reboot
+            && entry
+                .blocked_at
+                .zip(boot())
+                .is_some_and(|(blocked_at, boot)| blocked_at < boot))
+}
+
+// Removes expired blocks like remove_expired_entries_at, but only takes the lock and rewrites the
+// file when one has actually run out. Every gwd command runs this first (unless --no-sweep), so
+// it must stay a plain read when there is nothing to do, e.g. for `gwd list` without root.
+pub fn sweep_expired(hosts_path: &Path) -> Result<Vec<String>> {
+    let now = now_timestamp();
+    let boot = OnceCell::new();
+    let any_expired = HostsFile::load(hosts_path)?
+        .list_gwd()
+        .iter()
+        .any(|entry| has_expired(entry, now, || *boot.get_or_init(boot_time)));
+    if !any_expired {
+        return Ok(Vec::new());
+    }
+    remove_expired_entries_at(false, hosts_path)
+}
+
+// Function to remove orphaned temp files (gwd tidy). Returns the paths removed, or with `dry_run`
+// the ones that would be.
+pub fn tidy_temp_files_at(dry_run: bool, hosts_path: &Path) -> Result<Vec<PathBuf>> {
+    let staging = Staging::system();
+    if dry_run {
+        return Ok(temp_file_candidates(hosts_path, &staging)
+            .into_iter()
+            .filter(|path| fs::symlink_metadata(path).is_ok())
+            .collect());
+    }
+    let _lock = HostsLock::acquire(hosts_path)?;
+    discard_stale_temp_files(hosts_path, &staging)
+}
+
+// Exclusive advisory lock serializing gwd's read-modify-write cycles on one hosts file.
+// It lives in a separate lockfile because every write replaces the hosts file (and its inode) by
+// renaming a temp file over it. Dropping the guard closes the file, which releases the lock.
+struct HostsLock {
+    _file: File,
+}
+
+impl HostsLock {
+    // Waits up to LOCK_TIMEOUT for other gwd processes, then gives up with AppError::Busy
+    fn acquire(hosts_path: &Path) -> Result<HostsLock> {
+        let open = |path: &Path| {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+        };
+        let mut lock_path = hosts_path.with_extension("gwd.lock");
+        let mut opened = open(&lock_path);
+        // Like the temp file, the lock moves to the system temp directory when the hosts file's
+        // directory doesn't let gwd create it (see create_temp_file)
+        if matches!(&opened, Err(e) if e.kind() == io::ErrorKind::PermissionDenied)
+            && hosts_path.exists()
+        {
+            lock_path = fallback_path_for(&std::env::temp_dir(), hosts_path, "gwd.lock");
+            opened = open_shared_lock(&lock_path);
+        }
+        let file = opened.map_err(|e| {
+            map_io_error(
+                e,
+                &lock_path,
+                IoPhase::Other(&format!("Failed to open lock file {:?}", lock_path)),
+            )
+        })?;
+        let deadline = std::time::Instant::now() + LOCK_TIMEOUT;
+        loop {
+            match file.try_lock() {
+                Ok(()) => {
+                    tracing::debug!(lock = ?lock_path, "locked the hosts file");
+                    return Ok(HostsLock { _file: file });
+                }
+                Err(std::fs::TryLockError::WouldBlock) if std::time::Instant::now() < deadline => {
+                    std::thread::sleep(LOCK_POLL_INTERVAL);
+                }
+                Err(std::fs::TryLockError::WouldBlock) => return Err(AppError::Busy(lock_path)),
+                Err(std::fs::TryLockError::Error(e)) => {
+                    return Err(AppError::Io(format!(
+                        "Failed to lock {:?}: {}",
+                        lock_path, e
+                    )))
+                }
+            }
+        }
+    }
+}
+
+// Rewrites the hosts file without the gwd entries for which `should_remove` returns true.
+// Returns the removed lines; if there are none (or `dry_run` is set) the original file is
+// left untouched and no temp file is created.
+fn remove_matching_lines(
+    hosts_path: &Path,
+    dry_run: bool,
+    should_remove: impl FnMut(&HostsLine) -> bool,
+) -> Result<Vec<String>> {
+    // Hold the lock until the new content has been renamed into place
+    let mut hosts = load_for_edit(hosts_path, dry_run)?;
+    // Only entries inside gwd's section are candidates for removal;
+    // surviving lines keep their original bytes and terminators
+    let removed = hosts.remove_block(should_remove);
+    if !removed.is_empty() && !dry_run {
+        hosts.save()?;
+    }
+    Ok(removed)
+}
+
+// Replaces the hosts file with `content` without ever leaving it half-written: the content goes
+// to a temp file next to the original, which is then renamed over it. Where the hosts file's
+// directory doesn't let gwd create files, the temp file goes to the system temp directory
+// instead, and is copied into place when it can't be renamed there (see ReplaceStrategy). The
+// caller must hold the HostsLock.
+fn write_hosts_atomically(hosts_path: &Path, content: &str, staging: &Staging) -> Result<()> {
+    // With the lock held, an existing temp file can only be left over from an interrupted run;
+    // never build on it
+    for stale in discard_stale_temp_files(hosts_path, staging)? {
+        eprintln!(
+            "Warning: removed stale temp file {:?} left by an interrupted gwd run.",
+            stale
+        );
+    }
+    // A backup, on the other hand, means a copy into place was cut short, and may be the only
+    // complete version of the hosts file (see copy_over)
+    let backup_path = fallback_path_for(&staging.fallback_dir, hosts_path, "bak");
+    if fs::symlink_metadata(&backup_path).is_ok() {
+        return Err(AppError::Io(format!(
+            "{:?} holds the hosts file as it was before an interrupted gwd write. Check {:?}, \
+             copy the backup over it if it is incomplete, then delete the backup and try again.",
+            backup_path, hosts_path
+        )));
+    }
+
+    // Ctrl-C from here on rolls the write back rather than killing gwd with the temp file in place
+    let interrupt = InterruptGuard::install()?;
+    let (temp_file_path, mut temp_file) = create_temp_file(hosts_path, staging)?;
+    tracing::debug!(
+        path = ?hosts_path,
+        temp = ?temp_file_path,
+        bytes = content.len(),
+        "writing the hosts file"
+    );
+    temp_file
+        .write_all(content.as_bytes())
+        // Make sure the data is on disk before the rename makes it the hosts file
+        .and_then(|()| temp_file.sync_all())
+        .map_err(|e| map_io_error(e, hosts_path, IoPhase::Write("writing the temp file")))?;
+    drop(temp_file);
+
+    let next_to_hosts = temp_file_path.parent() == hosts_path.parent();
+    let mut strategy = replace_strategy(
+        next_to_hosts,
+        hosts_path
+            .parent()
+            .and_then(|dir| (staging.same_filesystem)(&temp_file_path, dir)),
+    );
+    // The temp file got default permissions; give it the original's before it takes its place
+    // (a hosts file being created keeps the defaults, and a copy keeps the original's own)
+    if strategy == ReplaceStrategy::Rename && hosts_path.exists() {
+        if let Some(e) = copy_file_attributes(hosts_path, &temp_file_path)? {
+            if next_to_hosts {
+                // Changing ownership needs root; without it, keep going rather than abort the edit
+                eprintln!(
+                    "Warning: Could not preserve the owner of {:?}: {}",
+                    hosts_path, e
+                );
+            } else {
+                // A copy keeps the owner that the renamed temp file wouldn't have
+                strategy = ReplaceStrategy::Copy;
+            }
+        }
+    }
+    if interrupt.interrupted() {
+        fs::remove_file(&temp_file_path)
+            .map_err(|e| map_io_error(e, hosts_path, IoPhase::Write("removing the temp file")))?;
+        return Err(AppError::Interrupted);
+    }
+
+    tracing::debug!(temp = ?temp_file_path, ?strategy, "replacing the hosts file");
+    let result = match strategy {
+        ReplaceStrategy::Rename => match rename_with_retry(&temp_file_path, hosts_path) {
+            // A temp file from the system temp directory can still fail to move into a directory
+            // gwd can't write to, or across filesystems where gwd couldn't tell beforehand
+            Err(e)
+                if !next_to_hosts
+                    && matches!(
+                        e.kind(),
+                        io::ErrorKind::PermissionDenied | io::ErrorKind::CrossesDevices
+                    ) =>
+            {
+                tracing::debug!(error = %e, "rename failed; copying the temp file instead");
+                copy_over(&temp_file_path, hosts_path, &backup_path)
+            }
+            result => result,
+        },
+        ReplaceStrategy::Copy => copy_over(&temp_file_path, hosts_path, &backup_path),
+    };
+    result.map_err(|e| {
+        // Don't leave the temp file behind in the system temp directory, where tidy won't look
+        if !next_to_hosts {
+            let _ = fs::remove_file(&temp_file_path);
+        }
+        map_io_error(
+            e,
+            hosts_path,
+            IoPhase::Write(&format!(
+                "replacing it with the temp file {:?}",
+                temp_file_path
+            )),
+        )
+    })
+}
+
+// Creates the temp file for a write: next to the hosts file (temp_path_for), or, when the hosts
+// file exists but its directory doesn't let gwd create files (unusual ACLs), in the system temp
+// directory (fallback_path_for)
+fn create_temp_file(hosts_path: &Path, staging: &Staging) -> Result<(PathBuf, File)> {
+    let temp_file_path = temp_path_for(hosts_path);
+    let error = match (staging.create_next_to_hosts)(&temp_file_path) {
+        Ok(file) => return Ok((temp_file_path, file)),
+        Err(e) => e,
+    };
+    if error.kind() != io::ErrorKind::PermissionDenied || !hosts_path.exists() {
+        return Err(map_io_error(
+            error,
+            hosts_path,
+            IoPhase::Write("creating the temp file"),
+        ));
+    }
+    let fallback = fallback_path_for(&staging.fallback_dir, hosts_path, "tmp");
+    tracing::debug!(
+        denied = ?temp_file_path,
+        temp = ?fallback,
+        "can't create files next to the hosts file; using the system temp directory"
+    );
+    let file = shared_dir_options()
+        .create_new(true)
+        .open(&fallback)
+        .map_err(|e| {
+            map_io_error(
+                e,
+                hosts_path,
+                IoPhase::Write(&format!("creating the temp file {:?}", fallback)),
+            )
+        })?;
+    Ok((fallback, file))
+}
+
+// Where gwd keeps a file for `hosts_path` (its temp file, backup or lock) when the hosts file's
+// directory doesn't let it create one there: `dir`, the system temp directory, under a name made
+// from the hosts path, so each hosts file gets its own, e.g. /tmp/gwd_etc_hosts.tmp for /etc/hosts
+fn fallback_path_for(dir: &Path, hosts_path: &Path, extension: &str) -> PathBuf {
+    let name: String = hosts_path
+        .to_string_lossy()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    dir.join(format!("gwd{}.{}", name, extension))
+}
+
+// Options for creating gwd's files in the system temp directory, where any user can create files
+// and its names are easy to guess: a file is created readable by the current user only, and a
+// symlink planted in its place is never followed
+fn shared_dir_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600).custom_flags(nix::libc::O_NOFOLLOW);
+    }
+    options
+}
+
+// Opens the lock file in the system temp directory. Unlike the temp file it outlives each run, so
+// an existing one is reused, but only if it is a regular file of the current user's; one that
+// another user put there is refused rather than locked (or written through).
+fn open_shared_lock(lock_path: &Path) -> io::Result<File> {
+    match shared_dir_options().create_new(true).open(lock_path) {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        created => return created,
+    }
+    let file = shared_dir_options().open(lock_path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = file.metadata()?;
+        if !metadata.is_file() || metadata.uid() != nix::unistd::geteuid().as_raw() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{:?} isn't a file of the current user's", lock_path),
+            ));
+        }
+    }
+    Ok(file)
+}
+
+// Where write_hosts_atomically stages the new hosts file, and how it finds out whether that is on
+// the hosts file's filesystem. Tests swap these to take the fallback without unusual ACLs or a
+// second filesystem.
+struct Staging {
+    // Creates the temp file next to the hosts file; fails with PermissionDenied where the
+    // directory doesn't let gwd create files
+    create_next_to_hosts: fn(&Path) -> io::Result<File>,
+    // Where the temp file and backup go instead
+    fallback_dir: PathBuf,
+    same_filesystem: fn(&Path, &Path) -> Option<bool>,
+}
+
+impl Staging {
+    fn system() -> Staging {
+        Staging {
+            create_next_to_hosts: |path| OpenOptions::new().write(true).create_new(true).open(path),
+            fallback_dir: std::env::temp_dir(),
+            same_filesystem,
+        }
+    }
+}
+
+// How the finished temp file takes the hosts file's place
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ReplaceStrategy {
+    // Rename it over the hosts file, which is atomic: readers see either the old file or the new
+    Rename,
+    // Copy its content into the hosts file and delete it. rename can't move a file to another
+    // filesystem, so this is the way in from the system temp directory when that is elsewhere.
+    Copy,
+}
+
+// Renames where rename works: always for a temp file next to the hosts file, and for one
+// elsewhere unless it is known to be on another filesystem (`same_filesystem`, None where gwd
+// can't tell, in which case a failed rename still falls back to a copy)
+fn replace_strategy(next_to_hosts: bool, same_filesystem: Option<bool>) -> ReplaceStrategy {
+    if next_to_hosts || same_filesystem != Some(false) {
+        ReplaceStrategy::Rename
+    } else {
+        ReplaceStrategy::Copy
+    }
+}
+
+// Whether two paths are on the same filesystem (device), or None where that can't be told
+#[cfg(unix)]
+fn same_filesystem(a: &Path, b: &Path) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
+    Some(fs::metadata(a).ok()?.dev() == fs::metadata(b).ok()?.dev())
+}
+
+#[cfg(not(unix))]
+fn same_filesystem(_a: &Path, _b: &Path) -> Option<bool> {
+    None
+}
+
+// Copies the temp file's content into the hosts file in place, then deletes the temp file. This
+// can't be atomic like a rename, so the original content first goes to a synced backup at
+// `backup_path`, and without that backup the hosts file isn't touched. A write that fails puts
+// the original back; only a finished one deletes the backup, so a crash part-way through leaves
+// it for the next write to find (see write_hosts_atomically). Writing in place also keeps the
+// hosts file's owner and permissions.
+fn copy_over(temp_file_path: &Path, hosts_path: &Path, backup_path: &Path) -> io::Result<()> {
+    let content = fs::read(temp_file_path)?;
+    let original = match fs::read(hosts_path) {
+        Ok(original) => Some(original),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    if let Some(original) = &original {
+        let backed_up = shared_dir_options()
+            .create_new(true)
+            .open(backup_path)
+            .and_then(|mut backup| {
+                backup.write_all(original)?;
+                backup.sync_all()
+            });
+        if let Err(e) = backed_up {
+            let _ = fs::remove_file(backup_path);
+            return Err(io::Error::new(
+                e.kind(),
+                format!("could not back it up to {:?} first: {}", backup_path, e),
+            ));
+        }
+    }
+    let write = |bytes: &[u8]| -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(hosts_path)?;
+        file.write_all(bytes)?;
+        file.sync_all()
+    };
+    if let Err(e) = write(&content) {
+        if let Some(original) = original {
+            match write(&original) {
+                Ok(()) => {
+                    let _ = fs::remove_file(backup_path);
+                }
+                Err(restore) => eprintln!(
+                    "Warning: could not restore {:?} after a failed write: {}. Its previous \
+                     content is in {:?}.",
+                    hosts_path, restore, backup_path
+                ),
+            }
+        }
+        return Err(e);
+    }
+    if original.is_some() {
+        if let Err(e) = fs::remove_file(backup_path) {
+            eprintln!(
+                "Warning: could not remove the backup {:?}: {}",
+                backup_path, e
+            );
+        }
+    }
+    fs::remove_file(temp_file_path)
+}
+
+// Moves the finished temp file over the hosts file. On Windows the rename fails while another
+// process has the file open, which antivirus scanners do for a moment after every write, so
+// access-denied and sharing errors are retried a few times before giving up.
+#[cfg(windows)]
+fn rename_with_retry(from: &Path, to: &Path) -> io::Result<()> {
+    retry_while_busy(RENAME_ATTEMPTS, RENAME_RETRY_DELAY, || fs::rename(from, to))
+}
+
+#[cfg(not(windows))]
+fn rename_with_retry(from: &Path, to: &Path) -> io::Result<()> {
+    fs::rename(from, to)
+}
+
+// Runs `op` up to `attempts` times while it fails because the file is in use, sleeping `delay`
+// (doubled each time) in between. Any other error, or the last one, is returned as is.
+#[cfg(windows)]
+fn retry_while_busy(
+    attempts: u32,
+    delay: Duration,
+    mut op: impl FnMut() -> io::Result<()>,
+) -> io::Result<()> {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+    const BUSY_OS_ERRORS: [i32; 2] = [32, 33];
+    let mut delay = delay;
+    for attempt in 1.. {
+        match op() {
+            Err(e)
+                if attempt < attempts
+                    && (e.kind() == io::ErrorKind::PermissionDenied
+                        || e.raw_os_error()
+                            .is_some_and(|code| BUSY_OS_ERRORS.contains(&code))) =>
+            {
+                tracing::debug!(attempt, error = %e, ?delay, "the file is in use; retrying");
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+    unreachable!("the loop only ends by returning")
+}
+
+// Which part of a hosts file update an I/O error happened in
+#[derive(Clone, Copy, Debug)]
+enum IoPhase<'a> {
+    // Loading the hosts file
+    Read,
+    // Replacing it with the new content; names the step that failed, e.g. "writing the temp file"
+    Write(&'a str),
+    // A file gwd keeps next to it, such as the lock; the message says which
+    Other(&'a str),
+}
+
+// Maps an I/O error on the hosts file (or a file gwd keeps next to it) to an error users can act
+// on: missing privileges and read-only filesystems get their own advice, anything else says
+// whether reading or writing the hosts file failed
+fn map_io_error(e: io::Error, path: &Path, phase: IoPhase) -> AppError {
+    let path = path.to_path_buf();
+    match (e.kind(), phase) {
+        (io::ErrorKind::PermissionDenied, _) => AppError::PermissionDenied(path),
+        (io::ErrorKind::ReadOnlyFilesystem, _) => AppError::ReadOnlyHosts(path),
+        (_, IoPhase::Read) => AppError::ReadHosts {
+            path,
+            source_str: e.to_string(),
+        },
+        (_, IoPhase::Write(step)) => AppError::WriteHosts {
+            path,
+            source_str: format!("{}: {}", step, e),
+        },
+        (_, IoPhase::Other(message)) => AppError::Io(format!("{}: {}", message, e)),
+    }
+}
+
+// How a single domain is currently blocked in the hosts file, strongest first
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockState {
+    // At least one gwd-managed entry exists for the domain (or its www. form)
+    Gwd,
+    // Only entries added by hand or by another tool sink the domain
+    Foreign,
+    // gwd's entries for the domain are paused (gwd pause) until they resume
+    Paused,
+    // Only a soft block (commented out, so not in effect) exists for the domain
+    Soft,
+    // Nothing blocks the domain
+    NotBlocked,
+}
+
+// Function to report whether gwd currently blocks a domain
+pub fn is_blocked(domain: &str, hosts_path: &Path) -> Result<bool> {
+    Ok(block_state(domain, hosts_path)? == BlockState::Gwd)
+}
+
+// Function to work out how (if at all) a domain is blocked in the hosts file
+pub fn block_state(domain: &str, hosts_path: &Path) -> Result<BlockState> {
+    block_state_among(domain, hosts_path, &sinkhole_ips(&[]))
+}
+
+// Same as block_state, with a line gwd didn't write counting as a block when it sends the
+// domain to one of `sinkholes` (see sinkhole_ips)
+fn block_state_among(domain: &str, hosts_path: &Path, sinkholes: &[IpAddr]) -> Result<BlockState> {
+    let clean_domain = format_domain_for_hosts(domain)?;
+    let domain_www = format!("www.{}", clean_domain);
+    let hostnames = [clean_domain.as_str(), domain_www.as_str()];
+
+    let mut state = BlockState::NotBlocked;
+    for line in HostsFile::load(hosts_path)?.lines() {
+        let listed = |entry: &GwdEntry| hostnames.contains(&entry.hostname.as_str());
+        if line.gwd_entries().iter().any(listed) {
+            return Ok(BlockState::Gwd);
+        }
+        if hostnames
+            .iter()
+            .any(|hostname| line.sinks_foreign(sinkholes, hostname))
+        {
+            state = BlockState::Foreign;
+        } else if unpause_line(line.text())
+            .is_some_and(|(_, entry)| parse_gwd_entries(&entry).iter().any(listed))
+        {
+            state = state.min(BlockState::Paused);
+        } else if state == BlockState::NotBlocked && line.soft_entries().iter().any(listed) {
+            state = BlockState::Soft;
+        }
+    }
+    Ok(state)
+}
+
+// Same as block_state, but across several hosts files (see list_section_entries_in) and with
+// the given sinkhole addresses: the strongest state any of the files gives the domain
+pub fn block_state_in(
+    domain: &str,
+    hosts_paths: &[PathBuf],
+    sinkholes: &[IpAddr],
+) -> Result<BlockState> {
+    let mut state = BlockState::NotBlocked;
+    for hosts_path in hosts_paths {
+        state = state.min(block_state_among(domain, hosts_path, sinkholes)?);
+    }
+    Ok(state)
+}
+
+// The latest --until/--for lock on gwd's entries for a domain (or its www. form), if any.
+// The timestamp may already be in the past.
+pub fn lock_until(domain: &str, hosts_path: &Path) -> Result<Option<i64>> {
+    let clean_domain = format_domain_for_hosts(domain)?;
+    let domain_www = format!("www.{}", clean_domain);
+    Ok(HostsFile::load(hosts_path)?
+        .list_gwd()
+        .into_iter()
+        .filter(|entry| entry.hostname == clean_domain || entry.hostname == domain_www)
+        .filter_map(|entry| entry.until)
+        .max())
+}
+
+// Same as lock_until, but across several hosts files (see list_section_entries_in)
+pub fn lock_until_in(domain: &str, hosts_paths: &[PathBuf]) -> Result<Option<i64>> {
+    let mut until = None;
+    for hosts_path in hosts_paths {
+        until = until.max(lock_until(domain, hosts_path)?);
+    }
+    Ok(until)
+}
+
+// The earlier of two optional timestamps, ignoring a missing one
+fn earliest(a: Option<i64>, b: Option<i64>) -> Option<i64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+// Whether `hostname` is a strict subdomain of `domain` (e.g. 'm.example.com' of 'example.com')
+fn is_subdomain_of(hostname: &str, domain: &str) -> bool {
+    hostname
+        .strip_suffix(domain)
+        .is_some_and(|prefix| prefix.len() > 1 && prefix.ends_with('.'))
+}
+
+// The domain a variant gwd wrote was for: 'example.com' for 'www.example.com', or for
+// 'm.example.com' when its tag records the --also prefix "m". None for any other hostname, so a
+// subdomain blocked in its own right (e.g. 'cdn.example.com') isn't taken for a variant.
+fn variant_parent<'a>(hostname: &'a str, variant: Option<&str>) -> Option<&'a str> {
+    ["www"].into_iter().chain(variant).find_map(|prefix| {
+        hostname
+            .strip_prefix(prefix)?
+            .strip_prefix('.')
+            .filter(|parent| !parent.is_empty())
+    })
+}
+
+// Hostnames of the gwd entries for the variants of `domain` (see variant_parent), so unblocking
+// removes whatever variants were actually written and leaves other subdomains blocked
+fn gwd_variants_of(domain: &str, hosts: &HostsFile) -> Vec<String> {
+    let mut variants = Vec::new();
+    for entry in hosts.list_gwd().into_iter().chain(hosts.list_soft()) {
+        if variant_parent(&entry.hostname, entry.variant.as_deref()) == Some(domain)
+            && !variants.contains(&entry.hostname)
+        {
+            variants.push(entry.hostname);
+        }
+    }
+    variants
+}
+
+// Finds gwd lines gwd itself can't make sense of: tagged lines that aren't "<ip> <hostname> # tag"
+// entries, and section markers without a partner. Returns (line number, description) pairs.
+pub fn malformed_gwd_lines_at(hosts_path: &Path) -> Result<Vec<(usize, String)>> {
+    let entry_regex = GWD_ENTRY_REGEX.as_ref().map_err(|e| e.clone())?;
+    let mut problems = Vec::new();
+    let mut open_section: Option<usize> = None;
+    for (index, line) in read_hosts_lines(hosts_path)?.iter().enumerate() {
+        let line_number = index + 1;
+        match line.trim() {
+            SECTION_START_MARKER => {
+                if let Some(start) = open_section {
+                    problems.push((start, "section start without an end marker".to_string()));
+                }
+                open_section = Some(line_number);
+            }
+            SECTION_END_MARKER => {
+                let start = open_section.take();
+                if start.is_none() {
+                    problems.push((
+                        line_number,
+                        "section end without a start marker".to_string(),
+                    ));
+                }
+            }
+            _ if is_malformed_gwd_entry(line, entry_regex) => {
+                problems.push((line_number, format!("malformed gwd entry: {}", line.trim())));
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = open_section {
+        problems.push((start, "section start without an end marker".to_string()));
+    }
+    problems.sort();
+    Ok(problems)
+}
+
+// Whether `line` carries gwd's tag without being a usable "<ip> <hostname> # tag" entry,
+// e.g. a hand-edited line with a bad address or no hostname. Such lines block nothing and the
+// exact-match regexes used by block/unblock never touch them.
+fn is_malformed_gwd_entry(line: &str, entry_regex: &Regex) -> bool {
+    let valid = entry_regex.captures(line).is_some_and(|cap| {
+        line.split_whitespace()
+            .next()
+            .is_some_and(|ip| ip.parse::<IpAddr>().is_ok())
+            && cap[1]
+                .split_whitespace()
+                .all(|hostname| validate_domain(hostname, true).is_ok())
+    });
+    has_gwd_tag(line) && !(valid && parse_gwd_entry(line).is_some()) && unpause_line(line).is_none()
+}
+
+// Function to delete malformed gwd entries (gwd verify --remove). Returns the removed lines.
+pub fn remove_malformed_gwd_lines_at(dry_run: bool, hosts_path: &Path) -> Result<Vec<String>> {
+    let entry_regex = GWD_ENTRY_REGEX.as_ref().map_err(|e| e.clone())?;
+    remove_matching_lines(hosts_path, dry_run, |line| {
+        is_malformed_gwd_entry(line.text(), entry_regex)
+    })
+}
+
+// Checks that the current user may modify the hosts file
+pub fn check_permissions(hosts_path: &Path) -> Result<()> {
+    let hosts_path = hosts_path.to_path_buf();
+    #[cfg(windows)]
+    {
+        // Open the file in append mode, as the Unix check does. Windows grants append only to
+        // users the file's ACL lets write (and refuses it for a read-only file), so this proves
+        // the same write access a block needs, without the truncating, exclusive-looking write
+        // handle that other readers (e.g. the DNS Client service) could briefly trip over.
+        // Nothing is written, and the handle is closed before returning.
+        match std::fs::OpenOptions::new().append(true).open(&hosts_path) {
+            Ok(file) => {
+                drop(file);
+                Ok(())
+            }
+            // block creates a missing file; any problem doing so surfaces there
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                Err(AppError::PermissionDenied(hosts_path)) // Pass the path
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::ReadOnlyFilesystem => {
+                Err(AppError::ReadOnlyHosts(hosts_path))
+            }
+            Err(e) => Err(AppError::Io(format!(
+                "Error checking permissions on {:?}: {}",
+                hosts_path, e
+            ))), // Convert error to string
+        }
+    }
+    #[cfg(unix)] // Add cfg attribute for Unix block
+    {
+        // Root can always write; otherwise accept a hosts file the user can write to
+        // (e.g. a scratch file selected through GWD_HOSTS_PATH)
+        // A missing file is writable if it can be created in its directory
+        // A read-only filesystem stops root too, so it's reported as such rather than as a
+        // permission problem
+        let writable = if hosts_path.exists() {
+            match std::fs::OpenOptions::new().append(true).open(&hosts_path) {
+                Ok(_) => true,
+                Err(e) if e.kind() == std::io::ErrorKind::ReadOnlyFilesystem => {
+                    return Err(AppError::ReadOnlyHosts(hosts_path));
+                }
+                Err(_) => false,
+            }
+        } else {
+            hosts_path
+                .parent()
+                .is_some_and(|dir| nix::unistd::access(dir, nix::unistd::AccessFlags::W_OK).is_ok())
+        };
+        if !nix::unistd::Uid::effective().is_root() && !writable {
+            Err(AppError::PermissionDenied(hosts_path)) // Pass the path
+        } else {
+            Ok(())
+        }
+    }
+    #[cfg(not(any(unix, windows)))] // Handle other OSes
+    {
+        // Assume permissions are okay on unknown platforms for now
+        tracing::warn!("unknown platform, cannot reliably check permissions");
+        Ok(())
+    }
+}
+
+// Copies the permissions (and on Unix the owner/group) of `original` onto `replacement`,
+// so renaming the replacement over the original doesn't change who can read the hosts file.
+// Changing the owner needs root; when that fails, the error is returned for the caller to decide.
+fn copy_file_attributes(original: &Path, replacement: &Path) -> Result<Option<io::Error>> {
+    let metadata = fs::metadata(original)?;
+    fs::set_permissions(replacement, metadata.permissions())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let replacement_metadata = fs::metadata(replacement)?;
+        if replacement_metadata.uid() != metadata.uid()
+            || replacement_metadata.gid() != metadata.gid()
+        {
+            if let Err(e) =
+                std::os::unix::fs::chown(replacement, Some(metadata.uid()), Some(metadata.gid()))
+            {
+                return Ok(Some(e));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+// One logical gwd block as shown by `list`: a domain plus the metadata from its comment
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BlockedEntry {
+    pub domain: String,
+    // Label (e.g. profile name) the domain was blocked under, if any
+    pub label: Option<String>,
+    // Unix timestamp of the --until/--for lock, if any (the latest one across the domain's lines)
+    pub until: Option<i64>,
+    // Blocked --until-reboot (all of the domain's lines are)
+    pub until_reboot: bool,
+    // Unix timestamp the domain was blocked at, if recorded (the earliest across its lines)
+    pub blocked_at: Option<i64>,
+    // Why the domain was blocked (--reason), if recorded
+    pub reason: Option<String>,
+    // A soft block, recorded but not in effect until `harden`
+    pub soft: bool,
+}
+
+// Function to list the distinct domains blocked by gwd in the hosts file
+pub fn list_blocked_domains() -> Result<Vec<String>> {
+    let hosts_path = get_hosts_path()?;
+    list_blocked_domains_at(&hosts_path)
+}
+
+// Same as list_blocked_domains, but against an explicit hosts file path
+pub fn list_blocked_domains_at(hosts_path: &Path) -> Result<Vec<String>> {
+    Ok(list_blocked_entries_at(hosts_path)?
+        .into_iter()
+        .map(|entry| entry.domain)
+        .collect())
+}
+
+// Number of blocks `gwd list` shows (list --count), counted in one pass over the file without
+// building the list, for status bars that poll it on hosts files with tens of thousands of lines.
+// Only the hostnames of gwd's entries are kept, to drop repeats and collapse variants the way
+// list_blocked_entries_at does.
+pub fn count_blocked(hosts_path: &Path) -> Result<usize> {
+    let file = match File::open(hosts_path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        result => result.map_err(|e| map_io_error(e, hosts_path, IoPhase::Read))?,
+    };
+    let mut reader = BufReader::new(file);
+    // Hostname -> (whether it is only soft-blocked, its --also prefix if it is such a variant)
+    let mut hostnames: HashMap<String, (bool, Option<String>)> = HashMap::new();
+    let mut raw_line = String::new();
+    loop {
+        raw_line.clear();
+        let read = reader
+            .read_line(&mut raw_line)
+            .map_err(|e| map_io_error(e, hosts_path, IoPhase::Read))?;
+        if read == 0 {
+            break;
+        }
+        let line = trim_line_ending(&raw_line);
+        // Most lines of a big file come from other blocklists; skip them before any parsing
+        if !has_gwd_tag(line) && !has_soft_tag(line) {
+            continue;
+        }
+        // As in HostsLine::parse, and a real block wins over a soft one
+        match HostsLine::parse(line) {
+            HostsLine::Gwd { entries, .. } => {
+                for entry in entries {
+                    hostnames.insert(entry.hostname, (false, entry.variant));
+                }
+            }
+            HostsLine::Soft { entries, .. } => {
+                for entry in entries {
+                    hostnames
+                        .entry(entry.hostname)
+                        .or_insert((true, entry.variant));
+                }
+            }
+            _ => {}
+        }
+    }
+    let collapsed = |hostname: &str, (soft, variant): &(bool, Option<String>)| {
+        variant_parent(hostname, variant.as_deref())
+            .and_then(|parent| hostnames.get(parent))
+            .is_some_and(|(parent_soft, _)| parent_soft == soft)
+    };
+    Ok(hostnames
+        .iter()
+        .filter(|(hostname, state)| !collapsed(hostname, state))
+        .count())
+}
+
+// Function to list the distinct gwd blocks together with their metadata
+pub fn list_blocked_entries_at(hosts_path: &Path) -> Result<Vec<BlockedEntry>> {
+    list_section_entries_at(None, hosts_path)
+}
+
+// Same as list_blocked_entries_at, but only for the blocks in one sub-section of gwd's section
+// (block --section), or all of them for None
+pub fn list_section_entries_at(
+    section: Option<&str>,
+    hosts_path: &Path,
+) -> Result<Vec<BlockedEntry>> {
+    list_section_entries_in(section, &[hosts_path.to_path_buf()])
+}
+
+// Same as list_section_entries_at, but across several hosts files (the one gwd writes, then the
+// read-only extra_hosts_files from the config). A domain blocked in more than one is listed once.
+pub fn list_section_entries_in(
+    section: Option<&str>,
+    hosts_paths: &[PathBuf],
+) -> Result<Vec<BlockedEntry>> {
+    // Collect hostnames in file order, skipping repeats (e.g. the IPv6 twin of an entry). Soft
+    // blocks come after the real ones.
+    let mut gwd = Vec::new();
+    let mut soft = Vec::new();
+    for hosts_path in hosts_paths {
+        let hosts = HostsFile::load(hosts_path)?;
+        gwd.extend(hosts.list_gwd_in(section));
+        soft.extend(hosts.list_soft_in(section));
+    }
+    let soft_from = gwd.len();
+    let mut entries: Vec<BlockedEntry> = Vec::new();
+    // (variant, the domain it was written for) pairs; see variant_parent
+    let mut variants: Vec<(String, String)> = Vec::new();
+    for (index, gwd_entry) in gwd.into_iter().chain(soft).enumerate() {
+        if let Some(parent) = variant_parent(&gwd_entry.hostname, gwd_entry.variant.as_deref()) {
+            variants.push((gwd_entry.hostname.clone(), parent.to_string()));
+        }
+        if let Some(entry) = entries
+            .iter_mut()
+            .find(|entry| entry.domain == gwd_entry.hostname)
+        {
+            entry.until = entry.until.max(gwd_entry.until);
+            entry.until_reboot &= gwd_entry.until_reboot;
+            entry.blocked_at = earliest(entry.blocked_at, gwd_entry.blocked_at);
+            if entry.reason.is_none() {
+                entry.reason = gwd_entry.reason;
+            }
+            continue;
+        }
+        entries.push(BlockedEntry {
+            domain: gwd_entry.hostname,
+            label: gwd_entry.label,
+            until: gwd_entry.until,
+            until_reboot: gwd_entry.until_reboot,
+            blocked_at: gwd_entry.blocked_at,
+            reason: gwd_entry.reason,
+            soft: index >= soft_from,
+        });
+    }
+
+    // Collapse variants like 'www.example.com' or 'm.example.com' into 'example.com' when the
+    // bare form is also blocked the same way (soft or not)
+    let domains: Vec<(String, bool)> = entries
+        .iter()
+        .map(|entry| (entry.domain.clone(), entry.soft))
+        .collect();
+    entries.retain(|entry| {
+        !variants.iter().any(|(variant, parent)| {
+            *variant == entry.domain && domains.contains(&(parent.clone(), entry.soft))
+        })
+    });
+
+    Ok(entries)
+}
+
+// A hostname blocked by a line gwd didn't write (list --foreign)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ForeignBlock {
+    pub hostname: String,
+    // The sinkhole address the line sends it to
+    pub ip: IpAddr,
+}
+
+// The hostnames that lines gwd didn't write send to one of `sinkholes` (see sinkhole_ips), across
+// several hosts files, in file order and each once. Those gwd blocks too are left out, and so are
+// the names in `protected`: with 127.0.0.1 as a sinkhole, "127.0.0.1 localhost" blocks nothing.
+pub fn list_foreign_blocks_in(
+    hosts_paths: &[PathBuf],
+    sinkholes: &[IpAddr],
+    protected: &[String],
+) -> Result<Vec<ForeignBlock>> {
+    let mut gwd = Vec::new();
+    let mut blocks: Vec<ForeignBlock> = Vec::new();
+    for hosts_path in hosts_paths {
+        let hosts = HostsFile::load(hosts_path)?;
+        gwd.extend(hosts.list_gwd().into_iter().map(|entry| entry.hostname));
+        for line in hosts.lines() {
+            let HostsLine::Other { ip, hostnames, .. } = &line else {
+                continue;
+            };
+            if !sinkholes.contains(ip) {
+                continue;
+            }
+            for hostname in hostnames {
+                if !protected.contains(hostname)
+                    && !blocks.iter().any(|block| &block.hostname == hostname)
+                {
+                    blocks.push(ForeignBlock {
+                        hostname: hostname.clone(),
+                        ip: *ip,
+                    });
+                }
+            }
+        }
+    }
+    blocks.retain(|block| !gwd.contains(&block.hostname));
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::challenge::ChallengeLengths;
+    use chrono::{NaiveTime, Weekday};
+    use std::fs;
+    use tempfile::TempDir;
+
+    // Helper to create a mock hosts file
+    // A hosts file in its own temp directory, so the temp and lock files gwd creates next to it
+    // are cleaned up along with it
+    struct MockHosts {
+        _dir: TempDir,
+        path: PathBuf,
+    }
+
+    impl MockHosts {
+        fn path(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    fn create_mock_hosts(content: &str) -> io::Result<MockHosts> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("hosts");
+        fs::write(&path, content)?;
+        Ok(MockHosts { _dir: dir, path })
+    }
+
+    // `content` with the checksum line gwd writes at the top of each of its sections
+    fn sealed(content: &str) -> String {
+        let mut output = String::new();
+        let mut lines = content.split_inclusive('\n').peekable();
+        while let Some(line) = lines.next() {
+            output.push_str(line);
+            if trim_line_ending(line) != SECTION_START_MARKER {
+                continue;
+            }
+            let mut entries = Vec::new();
+            while let Some(entry) = lines.next_if(|l| trim_line_ending(l) != SECTION_END_MARKER) {
+                entries.push(entry.to_string());
+            }
+            let line_ending = &line[SECTION_START_MARKER.len()..];
+            output.push_str(&format!(
+                "{} {}{}",
+                CHECKSUM_PREFIX,
+                section_checksum(&entries),
+                line_ending
+            ));
+            output.extend(entries);
+        }
+        output
+    }
+
+    #[test]
+    fn test_format_domain_for_hosts_simple() {
+        assert_eq!(
+            format_domain_for_hosts("example.com").unwrap(),
+            "example.com"
+        );
+    }
+
+    #[test]
+    fn test_format_domain_for_hosts_http() {
+        assert_eq!(
+            format_domain_for_hosts("http://example.com").unwrap(),
+            "example.com"
+        );
+    }
+
+    #[test]
+    fn test_format_domain_for_hosts_https() {
+        assert_eq!(
+            format_domain_for_hosts("https://example.com").unwrap(),
+            "example.com"
+        );
+    }
+
+    #[test]
+    fn test_format_domain_for_hosts_trailing_slash() {
+        assert_eq!(
+            format_domain_for_hosts("example.com/").unwrap(),
+            "example.com"
+        );
+    }
+    #[test]
+    fn test_format_domain_for_hosts_https_trailing_slash() {
+        assert_eq!(
+            format_domain_for_hosts("https://example.com/").unwrap(),
+            "example.com"
+        );
+    }
+
+    #[test]
+    fn test_format_domain_for_hosts_mixed_case() {
+        assert_eq!(
+            format_domain_for_hosts("HTTPS://Example.Com/").unwrap(),
+            "example.com"
+        );
+    }
+
+    #[test]
+    fn test_format_domain_for_hosts_strips_url_parts() {
+        for (input, expected) in [
+            ("https://www.reddit.com/r/rust/?sort=new", "www.reddit.com"),
+            ("example.com/some/path", "example.com"),
+            ("http://example.com:8080/", "example.com"),
+            ("example.com:443", "example.com"),
+            ("example.com?q=1", "example.com"),
+            ("https://example.com#top", "example.com"),
+            ("http://[::1]:8080/", "::1"),
+            ("::1", "::1"),
+            ("example.com.", "example.com"),
+            ("https://example.com.:443/", "example.com"),
+        ] {
+            assert_eq!(
+                format_domain_for_hosts(input).unwrap(),
+                expected,
+                "{}",
+                input
+            );
+        }
+        assert!(format_domain_for_hosts("https:///path").is_err());
+    }
+
+    #[test]
+    fn test_format_domain_for_hosts_with_www() {
+        // format_domain should NOT strip www
+        assert_eq!(
+            format_domain_for_hosts("www.example.com").unwrap(),
+            "www.example.com"
+        );
+        assert_eq!(
+            format_domain_for_hosts("http://www.example.com").unwrap(),
+            "www.example.com"
+        );
+    }
+
+    #[test]
+    fn test_format_domain_for_hosts_invalid() {
+        assert!(format_domain_for_hosts("").is_err());
+        assert!(format_domain_for_hosts("http://").is_err());
+        assert!(format_domain_for_hosts("https://").is_err());
+        // Consider adding more invalid cases if needed
+    }
+
+    #[test]
+    fn test_canonicalize_domain_converts_international_names() {
+        for (input, expected) in [
+            ("münchen.de", "xn--mnchen-3ya.de"),
+            ("https://MÜNCHEN.de/karte", "xn--mnchen-3ya.de"),
+            ("www.bücher.example", "www.xn--bcher-kva.example"),
+            ("пример.рф", "xn--e1afmkfd.xn--p1ai"),
+            // Already in ASCII form: left as it is
+            ("xn--mnchen-3ya.de", "xn--mnchen-3ya.de"),
+        ] {
+            assert_eq!(canonicalize_domain(input).unwrap(), expected, "{}", input);
+        }
+        assert_eq!(
+            validate_domain("münchen.de", false).unwrap(),
+            "xn--mnchen-3ya.de"
+        );
+        // Characters IDNA doesn't allow in a name
+        for input in ["bad\u{FFFD}name.de", "ex ample.çom"] {
+            assert!(
+                matches!(canonicalize_domain(input), Err(AppError::InvalidDomain(_))),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_list_blocked_domains_collapses_www() {
+        let mock_hosts = create_mock_hosts(
+            "127.0.0.1 localhost\n\
+             0.0.0.0 example.com # Blocked by gwd\n\
+             0.0.0.0 www.example.com # Blocked by gwd\n\
+             0.0.0.0 www.only-www.com # Blocked by gwd\n",
+        )
+        .unwrap();
+        assert_eq!(
+            list_blocked_domains_at(mock_hosts.path()).unwrap(),
+            vec!["example.com", "www.only-www.com"]
+        );
+    }
+
+    #[test]
+    fn test_list_blocked_domains_ignores_manual_and_commented() {
+        let mock_hosts = create_mock_hosts(
+            "0.0.0.0 manual.com\n\
+             0.0.0.0 manual-comment.com # my own block\n\
+             # 0.0.0.0 disabled.com # Blocked by gwd\n\
+             0.0.0.0 reddit.com # Blocked by gwd\n",
+        )
+        .unwrap();
+        assert_eq!(
+            list_blocked_domains_at(mock_hosts.path()).unwrap(),
+            vec!["reddit.com"]
+        );
+    }
+
+    #[test]
+    fn test_list_and_status_read_a_read_only_hosts_file() {
+        let content = "127.0.0.1 localhost\n\
+                       0.0.0.0 reddit.com # Blocked by gwd\n\
+                       0.0.0.0 www.reddit.com # Blocked by gwd\n";
+        let mock_hosts = create_mock_hosts(content).unwrap();
+        let mut permissions = fs::metadata(mock_hosts.path()).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(mock_hosts.path(), permissions).unwrap();
+
+        assert_eq!(
+            list_blocked_domains_at(mock_hosts.path()).unwrap(),
+            vec!["reddit.com"]
+        );
+        assert_eq!(count_blocked(mock_hosts.path()).unwrap(), 1);
+        let hosts_paths = [mock_hosts.path().to_path_buf()];
+        assert_eq!(
+            block_state_in("reddit.com", &hosts_paths, &[]).unwrap(),
+            BlockState::Gwd
+        );
+        assert_eq!(fs::read_to_string(mock_hosts.path()).unwrap(), content);
+    }
+
+    #[test]
+    fn test_count_blocked_matches_list_on_a_large_file() {
+        // 50k lines, mostly from another blocklist, with gwd's entries (and their www. twins, an
+        // IPv6 copy and a few soft blocks) in between
+        let mut content = String::from("127.0.0.1 localhost\n");
+        for index in 0..40_000 {
+            content.push_str(&format!("0.0.0.0 ads{}.example.net\n", index));
+        }
+        for index in 0..2_500 {
+            content.push_str(&format!(
+                "0.0.0.0 site{0}.com # Blocked by gwd [bulk]\n\
+                 0.0.0.0 www.site{0}.com # Blocked by gwd [bulk]\n\
+                 :: site{0}.com # Blocked by gwd [bulk]\n\
+                 # 0.0.0.0 later{0}.com # Soft-blocked by gwd\n",
+                index
+            ));
+        }
+        content
+            .push_str("0.0.0.0 m.site7.com # gwd {\"variant\":\"m\"}\n# 0.0.0.0 site8.com # Soft-blocked by gwd\n");
+        let mock_hosts = create_mock_hosts(&content).unwrap();
+        assert!(content.lines().count() > 50_000);
+
+        let count = count_blocked(mock_hosts.path()).unwrap();
+        assert_eq!(count, 5_000);
+        assert_eq!(
+            count,
+            list_blocked_entries_at(mock_hosts.path()).unwrap().len()
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(count_blocked(&dir.path().join("missing")).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_configured_sinkhole_ips_count_as_foreign_blocks() {
+        let mock_hosts = create_mock_hosts(
+            "127.0.0.1 localhost\n\
+             127.0.0.1 loopback.com\n\
+             ::1 loopback6.com\n\
+             192.168.1.2 pihole.com www.pihole.com\n\
+             0.0.0.0 plain.com\n\
+             10.0.0.5 nas.lan\n\
+             0.0.0.0 ours.com # Blocked by gwd\n",
+        )
+        .unwrap();
+        let path = mock_hosts.path();
+        let paths = [path.to_path_buf()];
+        let pihole = IpAddr::from([192, 168, 1, 2]);
+        let extra = [
+            IpAddr::from([127, 0, 0, 1]),
+            IpAddr::from(Ipv6Addr::LOCALHOST),
+            pihole,
+        ];
+        let sinkholes = sinkhole_ips(&extra);
+        assert_eq!(sinkholes.len(), 5);
+        assert_eq!(sinkhole_ips(&[DEFAULT_REDIRECT_IP]).len(), 2);
+
+        // Without the configuration only 0.0.0.0 and :: count
+        assert_eq!(
+            block_state("loopback.com", path).unwrap(),
+            BlockState::NotBlocked
+        );
+        for domain in ["loopback.com", "loopback6.com", "pihole.com", "plain.com"] {
+            assert_eq!(
+                block_state_in(domain, &paths, &sinkholes).unwrap(),
+                BlockState::Foreign,
+                "{}",
+                domain
+            );
+        }
+        assert_eq!(
+            block_state_in("nas.lan", &paths, &sinkholes).unwrap(),
+            BlockState::NotBlocked
+        );
+
+        let foreign =
+            list_foreign_blocks_in(&paths, &sinkholes, &["localhost".to_string()]).unwrap();
+        let listed: Vec<(&str, IpAddr)> = foreign
+            .iter()
+            .map(|block| (block.hostname.as_str(), block.ip))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                ("loopback.com", extra[0]),
+                ("loopback6.com", extra[1]),
+                ("pihole.com", pihole),
+                ("www.pihole.com", pihole),
+                ("plain.com", DEFAULT_REDIRECT_IP),
+            ]
+        );
+
+        // block reports them as already blocked by another tool, but only for their own address
+        // family: 127.0.0.1 doesn't stop IPv6 lookups
+        let options = BlockOptions {
+            ipv6: true,
+            prefixes: Vec::new(),
+            sinkhole_ips: sinkholes.clone(),
+            ..Default::default()
+        };
+        let report = block_websites_at(&["loopback.com".to_string()], &options, path).unwrap();
+        assert_eq!(report.foreign, vec!["loopback.com"]);
+        assert_eq!(
+            report.entries,
+            vec![":: loopback.com # Blocked by gwd".to_string()]
+        );
+        let report = block_websites_at(&["pihole.com".to_string()], &options, path).unwrap();
+        assert_eq!(report.foreign, vec!["pihole.com"]);
+        assert_eq!(report.entries.len(), 1);
+        assert!(report.entries[0].starts_with(":: pihole.com "));
+        // Without them, a 127.0.0.1 line isn't a block
+        let options = BlockOptions {
+            prefixes: Vec::new(),
+            ..Default::default()
+        };
+        let report = block_websites_at(&["loopback6.com".to_string()], &options, path).unwrap();
+        assert!(report.foreign.is_empty());
+        assert_eq!(report.added, vec!["loopback6.com"]);
+    }
+
+    #[test]
+    fn test_extra_hosts_files_are_read_alongside_the_main_one() {
+        let main = create_mock_hosts(
+            "0.0.0.0 reddit.com # Blocked by gwd [social] until 2000000000\n\
+             # 0.0.0.0 later.com # Soft-blocked by gwd\n",
+        )
+        .unwrap();
+        let extra = create_mock_hosts(
+            "0.0.0.0 reddit.com # Blocked by gwd until 2100000000\n\
+             0.0.0.0 later.com # Blocked by gwd\n\
+             0.0.0.0 ads.example.net\n",
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let paths = [
+            main.path().to_path_buf(),
+            extra.path().to_path_buf(),
+            dir.path().join("missing"),
+        ];
+
+        let entries = list_section_entries_in(None, &paths).unwrap();
+        let listed: Vec<(&str, bool)> = entries
+            .iter()
+            .map(|entry| (entry.domain.as_str(), entry.soft))
+            .collect();
+        assert_eq!(listed, vec![("reddit.com", false), ("later.com", false)]);
+        assert_eq!(entries[0].label.as_deref(), Some("social"));
+        assert_eq!(entries[0].until, Some(2_100_000_000));
+
+        assert_eq!(
+            block_state_in("later.com", &paths, &sinkhole_ips(&[])).unwrap(),
+            BlockState::Gwd
+        );
+        assert_eq!(
+            block_state_in("ads.example.net", &paths, &sinkhole_ips(&[])).unwrap(),
+            BlockState::Foreign
+        );
+        assert_eq!(
+            block_state_in("example.org", &paths, &sinkhole_ips(&[])).unwrap(),
+            BlockState::NotBlocked
+        );
+        assert_eq!(
+            lock_until_in("reddit.com", &paths).unwrap(),
+            Some(2_100_000_000)
+        );
+    }
+
+    #[test]
+    fn test_list_blocked_domains_empty() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+        assert!(list_blocked_domains_at(mock_hosts.path())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_remove_matching_lines_only_gwd_entries() {
+        let mock_hosts = create_mock_hosts(
+            "127.0.0.1 localhost\n\
+             0.0.0.0 manual.com\n\
+             0.0.0.0 example.com # Blocked by gwd\n\
+             0.0.0.0 www.example.com # Blocked by gwd\n",
+        )
+        .unwrap();
+        let removed = remove_matching_lines(mock_hosts.path(), false, |line| {
+            line.text().contains(BLOCK_COMMENT_TAG)
+        })
+        .unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            "127.0.0.1 localhost\n0.0.0.0 manual.com\n"
+        );
+    }
+
+    #[test]
+    fn test_remove_matching_lines_nothing_to_remove() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+        let removed = remove_matching_lines(mock_hosts.path(), false, |line| {
+            line.text().contains(BLOCK_COMMENT_TAG)
+        })
+        .unwrap();
+        assert!(removed.is_empty());
+        assert!(!mock_hosts.path().with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn test_parse_redirect_ip() {
+        assert_eq!(
+            parse_redirect_ip("127.0.0.1").unwrap(),
+            IpAddr::V4(Ipv4Addr::LOCALHOST)
+        );
+        assert!(parse_redirect_ip("::").unwrap().is_ipv6());
+        assert!(matches!(
+            parse_redirect_ip("127.0.0"),
+            Err(AppError::InvalidRedirectIp(_))
+        ));
+        assert!(parse_redirect_ip("localhost").is_err());
+    }
+
+    #[test]
+    fn test_hosts_line_parse() {
+        assert_eq!(
+            HostsLine::parse("  # just a comment"),
+            HostsLine::Raw("  # just a comment".to_string())
+        );
+        assert_eq!(HostsLine::parse(""), HostsLine::Raw(String::new()));
+        assert_eq!(
+            HostsLine::parse("::1 localhost ip6-localhost # loopback"),
+            HostsLine::Other {
+                text: "::1 localhost ip6-localhost # loopback".to_string(),
+                ip: IpAddr::V6(Ipv6Addr::LOCALHOST),
+                hostnames: vec!["localhost".to_string(), "ip6-localhost".to_string()],
+            }
+        );
+        let line = "0.0.0.0 Example.com. # Blocked by gwd [social] until 1699999999";
+        assert_eq!(
+            HostsLine::parse(line).gwd_entries().first(),
+            Some(&GwdEntry {
+                ip: "0.0.0.0".to_string(),
+                hostname: "example.com".to_string(),
+                label: Some("social".to_string()),
+                until: Some(1_699_999_999),
+                until_reboot: false,
+                blocked_at: None,
+                reason: None,
+                variant: None,
+                style: CommentStyle::Plain,
+                comment: "# Blocked by gwd [social] until 1699999999".to_string(),
+            })
+        );
+        assert_eq!(HostsLine::parse(line).text(), line);
+    }
+
+    #[test]
+    fn test_reason_round_trips_through_the_tag() {
+        let reason = "too much #doomscrolling, 100% ✓";
+        assert_eq!(decode_reason(&encode_reason(reason)), reason);
+        assert_eq!(decode_reason("50%zz%"), "50%zz%");
+
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+        let options = BlockOptions {
+            label: Some("social".to_string()),
+            reason: Some(reason.to_string()),
+            ..Default::default()
+        };
+        block_websites_at(&["reddit.com".to_string()], &options, mock_hosts.path()).unwrap();
+
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        assert!(content.contains(
+            "0.0.0.0 reddit.com # Blocked by gwd [social] reason=too%20much%20%23doomscrolling%2C%20100%25%20%E2%9C%93\n"
+        ));
+        let entries = list_blocked_entries_at(mock_hosts.path()).unwrap();
+        assert_eq!(entries[0].reason.as_deref(), Some(reason));
+        assert_eq!(entries[0].label.as_deref(), Some("social"));
+        // The '#' in the reason doesn't confuse the entry or the tag
+        assert_eq!(
+            block_state("reddit.com", mock_hosts.path()).unwrap(),
+            BlockState::Gwd
+        );
+        assert_eq!(
+            normalize_hosts_at(false, Verbosity::Quiet, mock_hosts.path()).unwrap(),
+            NormalizeReport::default()
+        );
+    }
+
+    #[test]
+    fn test_hosts_file_add_remove_and_save() {
+        let content = "127.0.0.1 localhost\n0.0.0.0 a.com # Blocked by gwd\n# trailing";
+        let mock_hosts = create_mock_hosts(content).unwrap();
+
+        // A file loaded read-only can't be saved. Without a section yet, the entry outside one
+        // moves into a new section at the end of the file.
+        let mut hosts = HostsFile::load(mock_hosts.path()).unwrap();
+        assert_eq!(hosts.list_gwd().len(), 1);
+        hosts.add_block("0.0.0.0 b.com # Blocked by gwd");
+        assert!(hosts.save().is_err());
+
+        let mut hosts = HostsFile::load_locked(mock_hosts.path()).unwrap();
+        hosts.add_block("0.0.0.0 b.com # Blocked by gwd");
+        let removed = hosts.remove_block(|line| {
+            line.gwd_entries()
+                .iter()
+                .any(|entry| entry.hostname == "a.com")
+        });
+        assert_eq!(removed, vec!["0.0.0.0 a.com # Blocked by gwd"]);
+        hosts.save().unwrap();
+        drop(hosts);
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            sealed(
+                "127.0.0.1 localhost\n\
+             # trailing\n\
+             # === gwd START ===\n\
+             0.0.0.0 b.com # Blocked by gwd\n\
+             # === gwd END ===\n"
+            )
+        );
+
+        // Loading a missing file gives an empty one that saving leaves uncreated
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("hosts");
+        let hosts = HostsFile::load_locked(&missing).unwrap();
+        assert!(!hosts.exists());
+        assert_eq!(hosts.lines().count(), 0);
+        hosts.save().unwrap();
+        assert!(!missing.exists());
+    }
+
+    // Whether `line` already blocks example.com the way an entry to `ip` would
+    fn sinks_example(ip: &IpAddr, line: &str) -> bool {
+        HostsLine::parse(line).sinks(ip, "example.com")
+    }
+
+    #[test]
+    fn test_sinks_matches_any_ip_for_gwd_entries() {
+        let sinks = |line| sinks_example(&DEFAULT_REDIRECT_IP, line);
+        assert!(sinks("0.0.0.0 example.com"));
+        assert!(sinks("0.0.0.0 example.com # my own block"));
+        assert!(sinks("127.0.0.1 example.com # Blocked by gwd"));
+        assert!(!sinks("127.0.0.1 example.com"));
+        assert!(!sinks("0.0.0.0 www.example.com # Blocked by gwd"));
+        assert!(!sinks("# 0.0.0.0 example.com # Blocked by gwd"));
+        assert!(!sinks(":: example.com # Blocked by gwd"));
+    }
+
+    #[test]
+    fn test_sinks_tolerates_formatting() {
+        let sinks = |line| sinks_example(&DEFAULT_REDIRECT_IP, line);
+        assert!(sinks("0.0.0.0\texample.com"));
+        assert!(sinks("  0.0.0.0 \t  example.com  \t"));
+        assert!(sinks("0.0.0.0   example.com   # blocked manually by me"));
+        assert!(sinks("0.0.0.0 example.com#no space"));
+        assert!(sinks("0.0.0.0 example.com."));
+        assert!(sinks("0.0.0.0 Example.COM. # FQDN"));
+        assert!(sinks("127.0.0.1\texample.com.\t# Blocked by gwd [x]"));
+        assert!(!sinks("0.0.0.0 example.com.au"));
+        assert!(!sinks("0.0.0.0 example.comx"));
+    }
+
+    #[test]
+    fn test_block_skips_differently_formatted_entries() {
+        let content = "127.0.0.1 localhost\n\
+                       0.0.0.0\texample.com.\t# blocked manually by me\n\
+                       0.0.0.0   WWW.Example.com   # another note\n";
+        let mock_hosts = create_mock_hosts(content).unwrap();
+
+        let report = block_at(&["example.com."], mock_hosts.path()).unwrap();
+
+        assert!(report.added.is_empty());
+        assert_eq!(
+            report.already_present,
+            vec!["example.com", "www.example.com"]
+        );
+        assert_eq!(fs::read_to_string(mock_hosts.path()).unwrap(), content);
+    }
+
+    #[test]
+    fn test_sinks_ipv6() {
+        let sinks = |line| sinks_example(&DEFAULT_REDIRECT_IPV6, line);
+        assert!(sinks(":: example.com"));
+        assert!(sinks("::1 example.com # Blocked by gwd"));
+        assert!(!sinks("0.0.0.0 example.com # Blocked by gwd"));
+        assert!(!sinks("::1 example.com"));
+    }
+
+    #[test]
+    fn test_ipv6_redirect_for() {
+        assert_eq!(
+            ipv6_redirect_for(&DEFAULT_REDIRECT_IP),
+            DEFAULT_REDIRECT_IPV6
+        );
+        assert_eq!(
+            ipv6_redirect_for(&IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            IpAddr::V6(Ipv6Addr::LOCALHOST)
+        );
+    }
+
+    #[test]
+    fn test_list_blocked_domains_merges_ipv4_and_ipv6() {
+        let mock_hosts = create_mock_hosts(
+            "0.0.0.0 example.com # Blocked by gwd\n\
+             :: example.com # Blocked by gwd\n\
+             0.0.0.0 www.example.com # Blocked by gwd\n\
+             :: www.example.com # Blocked by gwd\n",
+        )
+        .unwrap();
+        assert_eq!(
+            list_blocked_domains_at(mock_hosts.path()).unwrap(),
+            vec!["example.com"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_hosts_path_env_override() {
+        let dir = tempfile::tempdir().unwrap();
+        let custom = dir.path().join("hosts");
+        assert_eq!(
+            resolve_hosts_path(Some(custom.clone().into_os_string())).unwrap(),
+            custom
+        );
+    }
+
+    #[test]
+    fn test_resolve_hosts_path_env_override_missing_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let custom = dir.path().join("missing").join("hosts");
+        assert!(matches!(
+            resolve_hosts_path(Some(custom.into_os_string())),
+            Err(AppError::MissingHostsDir(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_hosts_path_falls_back_to_default() {
+        assert_eq!(
+            resolve_hosts_path(None).unwrap(),
+            default_hosts_path().unwrap()
+        );
+        assert_eq!(
+            resolve_hosts_path(Some(OsString::new())).unwrap(),
+            default_hosts_path().unwrap()
+        );
+    }
+
+    // --- Tests for block_websites and unblock_website against a temp hosts file ---
+
+    // Unblock settings that skip the interactive challenge
+    fn no_challenge() -> UnblockOptions {
+        UnblockOptions {
+            challenge: ChallengeOptions {
+                length: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn block_at(domains: &[&str], hosts_path: &Path) -> Result<BlockReport> {
+        let domains: Vec<String> = domains.iter().map(|d| d.to_string()).collect();
+        block_websites_at(&domains, &BlockOptions::default(), hosts_path)
+    }
+
+    #[test]
+    fn test_block_detects_hostname_on_shared_line() {
+        let content = "127.0.0.1 localhost\n\
+                       0.0.0.0 cdn.example.com example.com\twww.example.com # my list\n";
+        let mock_hosts = create_mock_hosts(content).unwrap();
+
+        let report = block_at(&["example.com"], mock_hosts.path()).unwrap();
+
+        assert!(report.added.is_empty());
+        assert_eq!(report.foreign, vec!["example.com", "www.example.com"]);
+        assert_eq!(fs::read_to_string(mock_hosts.path()).unwrap(), content);
+        assert_eq!(
+            block_state("www.example.com", mock_hosts.path()).unwrap(),
+            BlockState::Foreign
+        );
+    }
+
+    #[test]
+    fn test_tagged_shared_line_counts_for_each_hostname() {
+        let content = sealed(
+            "127.0.0.1 localhost\n\
+             # === gwd START ===\n\
+             0.0.0.0 x.com y.com # Blocked by gwd [social]\n\
+             # 0.0.0.0 a.com b.com # Soft-blocked by gwd\n\
+             # === gwd END ===\n",
+        );
+        let mock_hosts = create_mock_hosts(&content).unwrap();
+        let path = mock_hosts.path();
+
+        assert_eq!(
+            list_blocked_domains_at(path).unwrap(),
+            vec!["x.com", "y.com", "a.com", "b.com"]
+        );
+        assert_eq!(count_blocked(path).unwrap(), 4);
+        assert_eq!(block_state("y.com", path).unwrap(), BlockState::Gwd);
+        assert_eq!(block_state("b.com", path).unwrap(), BlockState::Soft);
+        assert!(malformed_gwd_lines_at(path).unwrap().is_empty());
+
+        // Blocking a hostname already on the line adds nothing
+        let options = BlockOptions {
+            prefixes: Vec::new(),
+            ..Default::default()
+        };
+        let report = block_websites_at(&["y.com".to_string()], &options, path).unwrap();
+        assert!(report.added.is_empty());
+        assert_eq!(fs::read_to_string(path).unwrap(), content);
+    }
+
+    #[test]
+    fn test_unblock_rewrites_tagged_shared_lines_without_the_target() {
+        let mock_hosts = create_mock_hosts(&sealed(
+            "127.0.0.1 localhost\n\
+             # === gwd START ===\n\
+             0.0.0.0 x.com y.com # Blocked by gwd [social]\n\
+             # 0.0.0.0 a.com b.com # Soft-blocked by gwd\n\
+             # === gwd END ===\n",
+        ))
+        .unwrap();
+        let path = mock_hosts.path();
+
+        let report = unblock_websites_at(
+            &["x.com".to_string(), "a.com".to_string()],
+            &no_challenge(),
+            path,
+        )
+        .unwrap();
+
+        assert_eq!(
+            report.removed,
+            vec![
+                "0.0.0.0 x.com # Blocked by gwd [social]",
+                "# 0.0.0.0 a.com # Soft-blocked by gwd",
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            sealed(
+                "127.0.0.1 localhost\n\
+                 # === gwd START ===\n\
+                 0.0.0.0 y.com # Blocked by gwd [social]\n\
+                 # 0.0.0.0 b.com # Soft-blocked by gwd\n\
+                 # === gwd END ===\n"
+            )
+        );
+        assert_eq!(block_state("y.com", path).unwrap(), BlockState::Gwd);
+        assert_eq!(block_state("x.com", path).unwrap(), BlockState::NotBlocked);
+    }
+
+    #[test]
+    fn test_normalize_splits_tagged_shared_lines() {
+        let mock_hosts = create_mock_hosts(&sealed(
+            "# === gwd START ===\n\
+             0.0.0.0 x.com  y.com # Blocked by gwd\n\
+             0.0.0.0 y.com # Blocked by gwd [social]\n\
+             # === gwd END ===\n",
+        ))
+        .unwrap();
+
+        let report = normalize_hosts_at(false, Verbosity::Quiet, mock_hosts.path()).unwrap();
+
+        assert_eq!(report.duplicates_removed, 1);
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            sealed(
+                "# === gwd START ===\n\
+                 0.0.0.0 x.com # Blocked by gwd\n\
+                 0.0.0.0 y.com # Blocked by gwd [social]\n\
+                 # === gwd END ===\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_unblock_leaves_untagged_shared_lines() {
+        let content = "127.0.0.1 localhost\n\
+                       # === gwd START ===\n\
+                       0.0.0.0 example.com cdn.example.com www.example.com # by hand\n\
+                       0.0.0.0 a.com www.a.com\n\
+                       0.0.0.0 example.com # Blocked by gwd\n\
+                       0.0.0.0 other.com # Blocked by gwd\n\
+                       # === gwd END ===\n";
+        let mock_hosts = create_mock_hosts(content).unwrap();
+
+        let report = unblock_websites_at(
+            &["example.com".to_string(), "a.com".to_string()],
+            &no_challenge(),
+            mock_hosts.path(),
+        )
+        .unwrap();
+
+        // Only gwd's own line goes; the hand-written ones keep blocking their hostnames
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            sealed(&content.replace("0.0.0.0 example.com # Blocked by gwd\n", ""))
+        );
+        assert_eq!(report.removed, vec!["0.0.0.0 example.com # Blocked by gwd"]);
+        assert_eq!(report.summary().changed, 1);
+        assert_eq!(report.not_blocked(), vec!["a.com"]);
+    }
+
+    #[test]
+    fn test_unblock_include_foreign_takes_hostnames_off_any_sinkhole_line() {
+        let mock_hosts = create_mock_hosts(
+            "127.0.0.1 localhost\n\
+             0.0.0.0 example.com # my manual block\n\
+             # === gwd START ===\n\
+             0.0.0.0 example.com cdn.example.com www.example.com # by hand\n\
+             0.0.0.0 example.com # Blocked by gwd\n\
+             0.0.0.0 other.com # Blocked by gwd\n\
+             # === gwd END ===\n\
+             127.0.0.1 example.com\n",
+        )
+        .unwrap();
+        let options = UnblockOptions {
+            include_foreign: true,
+            ..no_challenge()
+        };
+
+        let report = unblock_website_at("example.com", &options, mock_hosts.path()).unwrap();
+
+        // cdn.example.com isn't one of the domain's forms, and 127.0.0.1 isn't a sinkhole
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            sealed(
+                "127.0.0.1 localhost\n\
+                 # === gwd START ===\n\
+                 0.0.0.0 cdn.example.com # by hand\n\
+                 0.0.0.0 other.com # Blocked by gwd\n\
+                 # === gwd END ===\n\
+                 127.0.0.1 example.com\n"
+            )
+        );
+        assert_eq!(
+            report.removed,
+            vec![
+                "0.0.0.0 example.com # Blocked by gwd",
+                "0.0.0.0 example.com # my manual block",
+                "0.0.0.0 example.com",
+                "0.0.0.0 www.example.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_block_website_new_entry() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+
+        block_at(&["example.com"], mock_hosts.path()).unwrap();
+
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        assert_eq!(
+            content,
+            sealed(
+                "127.0.0.1 localhost\n\
+             # === gwd START ===\n\
+             0.0.0.0 example.com # Blocked by gwd\n\
+             0.0.0.0 www.example.com # Blocked by gwd\n\
+             # === gwd END ===\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_block_creates_missing_hosts_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let hosts_path = dir.path().join("hosts");
+
+        block_at(&["example.com"], &hosts_path).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&hosts_path).unwrap(),
+            sealed(&format!(
+                "{}# === gwd START ===\n\
+                 0.0.0.0 example.com # Blocked by gwd\n\
+                 0.0.0.0 www.example.com # Blocked by gwd\n\
+                 # === gwd END ===\n",
+                DEFAULT_HOSTS_CONTENT
+            ))
+        );
+    }
+
+    #[test]
+    fn test_unblock_with_missing_hosts_file_removes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let hosts_path = dir.path().join("hosts");
+
+        let report = unblock_website_at("example.com", &no_challenge(), &hosts_path).unwrap();
+
+        assert!(report.removed.is_empty());
+        assert!(!hosts_path.exists());
+        assert!(list_blocked_domains_at(&hosts_path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_batch_summaries() {
+        let mock_hosts = create_mock_hosts("0.0.0.0 b.com # Blocked by gwd\n").unwrap();
+
+        let report = block_at(&["a.com", "b.com"], mock_hosts.path()).unwrap();
+        let summary = report
+            .summary()
+            .with_invalid(&[(3, "localhost".to_string())], false);
+        assert_eq!((summary.changed, summary.skipped), (3, 1));
+        assert!(summary.invalid[0].reason.contains("at least one dot"));
+        assert_eq!(
+            summary.describe("Blocked", "already present"),
+            "Blocked 3, skipped 1 already present, 1 invalid."
+        );
+
+        let domains = vec!["a.com".to_string(), "c.com".to_string()];
+        let report = unblock_websites_at(&domains, &no_challenge(), mock_hosts.path()).unwrap();
+        assert_eq!(
+            report.summary().describe("Unblocked", "not blocked"),
+            "Unblocked 2, skipped 1 not blocked."
+        );
+        assert_eq!(
+            BatchSummary::default().describe("Blocked", "already present"),
+            "Blocked 0."
+        );
+    }
+
+    #[test]
+    fn test_block_skips_foreign_entries_unless_forced() {
+        let content = "0.0.0.0 example.com # from StevenBlack/hosts\n";
+        let mock_hosts = create_mock_hosts(content).unwrap();
+
+        let report = block_at(&["example.com"], mock_hosts.path()).unwrap();
+        assert_eq!(report.foreign, vec!["example.com"]);
+        assert_eq!(report.already_present, vec!["example.com"]);
+        assert_eq!(report.added, vec!["www.example.com"]);
+
+        let options = BlockOptions {
+            force: true,
+            ..Default::default()
+        };
+        let report =
+            block_websites_at(&["example.com".to_string()], &options, mock_hosts.path()).unwrap();
+        assert_eq!(report.foreign, vec!["example.com"]);
+        assert_eq!(report.added, vec!["example.com"]);
+        assert_eq!(report.already_present, vec!["www.example.com"]);
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        assert!(content.contains("0.0.0.0 example.com # Blocked by gwd\n"));
+
+        // Once gwd has its own entry, --force has nothing left to add
+        let report =
+            block_websites_at(&["example.com".to_string()], &options, mock_hosts.path()).unwrap();
+        assert!(report.added.is_empty());
+    }
+
+    #[test]
+    fn test_block_website_is_idempotent() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+
+        block_at(&["example.com"], mock_hosts.path()).unwrap();
+        let first = fs::read_to_string(mock_hosts.path()).unwrap();
+        block_at(&["https://Example.com/"], mock_hosts.path()).unwrap();
+
+        assert_eq!(fs::read_to_string(mock_hosts.path()).unwrap(), first);
+    }
+
+    #[test]
+    fn test_block_websites_batch_dedups_and_fixes_missing_newline() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost").unwrap();
+
+        block_at(&["a.com", "b.com", "A.com"], mock_hosts.path()).unwrap();
+
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines[..2], ["127.0.0.1 localhost", "# === gwd START ==="]);
+        assert!(lines[2].starts_with(CHECKSUM_PREFIX));
+        assert!(lines[3].starts_with("0.0.0.0 a.com"));
+        assert_eq!(content.matches("# Blocked by gwd").count(), 4);
+        assert_eq!(content.matches(" a.com ").count(), 1);
+    }
+
+    #[test]
+    fn test_block_websites_ipv6() {
+        let mock_hosts = create_mock_hosts("").unwrap();
+        let domains = vec!["example.com".to_string()];
+
+        let options = BlockOptions {
+            ipv6: true,
+            ..Default::default()
+        };
+        block_websites_at(&domains, &options, mock_hosts.path()).unwrap();
+
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        assert!(content.contains(":: example.com # Blocked by gwd\n"));
+        assert!(content.contains(":: www.example.com # Blocked by gwd\n"));
+        // Four entries between the markers and the checksum line
+        assert_eq!(content.lines().count(), 7);
+    }
+
+    #[test]
+    fn test_unblock_website_removes_entries() {
+        let initial_content = "127.0.0.1 localhost\n0.0.0.0 example.com # Blocked by gwd\n0.0.0.0 www.example.com # Blocked by gwd\n";
+        let mock_hosts = create_mock_hosts(initial_content).unwrap();
+
+        unblock_website_at("example.com", &no_challenge(), mock_hosts.path()).unwrap(); // Skip challenge
+
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        assert!(!content.contains("0.0.0.0 example.com"));
+        assert!(!content.contains("0.0.0.0 www.example.com"));
+        assert!(content.contains("127.0.0.1 localhost")); // Ensure other lines remain
+    }
+
+    #[test]
+    fn test_unblock_website_removes_bare_and_www_but_not_untagged_lines() {
+        let mock_hosts = create_mock_hosts(
+            "127.0.0.1 localhost\n\
+             0.0.0.0 example.com\n\
+             # === gwd START ===\n\
+             0.0.0.0 example.com # Blocked by gwd\n\
+             0.0.0.0 www.example.com\n\
+             0.0.0.0 www.example.com # Blocked by gwd [social]\n\
+             # === gwd END ===\n",
+        )
+        .unwrap();
+
+        let report = unblock_website_at("example.com", &no_challenge(), mock_hosts.path()).unwrap();
+
+        assert_eq!(
+            report.removed,
+            vec![
+                "0.0.0.0 example.com # Blocked by gwd",
+                "0.0.0.0 www.example.com # Blocked by gwd [social]"
+            ]
+        );
+        // Sinkhole lines without gwd's tag weren't written by gwd, wherever they are
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            sealed(
+                "127.0.0.1 localhost\n\
+                 0.0.0.0 example.com\n\
+                 # === gwd START ===\n\
+                 0.0.0.0 www.example.com\n\
+                 # === gwd END ===\n"
+            )
+        );
+        assert_eq!(
+            block_state("example.com", mock_hosts.path()).unwrap(),
+            BlockState::Foreign
+        );
+    }
+
+    #[test]
+    fn test_unblock_all_websites_keeps_manual_entries() {
+        let initial_content =
+            "0.0.0.0 manual.com\n0.0.0.0 a.com # Blocked by gwd\n:: b.com # Blocked by gwd\n";
+        let mock_hosts = create_mock_hosts(initial_content).unwrap();
+
+        unblock_all_websites_at(&no_challenge(), mock_hosts.path()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            "0.0.0.0 manual.com\n"
+        );
+    }
+
+    #[test]
+    fn test_block_websites_with_lock_round_trips_through_list() {
+        let mock_hosts = create_mock_hosts("").unwrap();
+        let options = BlockOptions {
+            lock_until: Some(4_102_444_800), // 2100-01-01
+            ..Default::default()
+        };
+        block_websites_at(&["example.com".to_string()], &options, mock_hosts.path()).unwrap();
+
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        assert!(content.contains("0.0.0.0 example.com # Blocked by gwd until 4102444800\n"));
+        assert_eq!(
+            list_blocked_domains_at(mock_hosts.path()).unwrap(),
+            vec!["example.com"]
+        );
+        // Blocking again doesn't add a second (differently tagged) entry
+        block_at(&["example.com"], mock_hosts.path()).unwrap();
+        assert_eq!(fs::read_to_string(mock_hosts.path()).unwrap(), content);
+    }
+
+    #[test]
+    fn test_unblock_website_refuses_while_locked() {
+        let initial_content = "0.0.0.0 example.com # Blocked by gwd until 4102444800\n";
+        let mock_hosts = create_mock_hosts(initial_content).unwrap();
+
+        let result = unblock_website_at("example.com", &no_challenge(), mock_hosts.path());
+
+        assert!(
+            matches!(result, Err(AppError::LockActive { ref domain, .. }) if domain == "example.com")
+        );
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            initial_content
+        );
+        assert!(matches!(
+            unblock_all_websites_at(&no_challenge(), mock_hosts.path()),
+            Err(AppError::LockActive { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unblock_website_after_lock_expired() {
+        let mock_hosts =
+            create_mock_hosts("0.0.0.0 www.example.com # Blocked by gwd until 1000\n").unwrap();
+
+        unblock_website_at("example.com", &no_challenge(), mock_hosts.path()).unwrap();
+
+        assert_eq!(fs::read_to_string(mock_hosts.path()).unwrap(), "");
+    }
+
+    #[test]
+    fn test_interrupted_unblock_leaves_no_temp_file() {
+        let content = "127.0.0.1 localhost\n0.0.0.0 example.com # Blocked by gwd\n";
+        let mock_hosts = create_mock_hosts(content).unwrap();
+
+        crate::interrupt::simulate_interrupt(true);
+        let result = unblock_website_at("example.com", &no_challenge(), mock_hosts.path());
+        crate::interrupt::simulate_interrupt(false);
+
+        assert!(matches!(result, Err(AppError::Interrupted)));
+        assert!(!temp_path_for(mock_hosts.path()).exists());
+        assert_eq!(fs::read_to_string(mock_hosts.path()).unwrap(), content);
+        // The lock was released: the next unblock goes through
+        unblock_website_at("example.com", &no_challenge(), mock_hosts.path()).unwrap();
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            "127.0.0.1 localhost\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unblock_website_preserves_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mock_hosts =
+            create_mock_hosts("127.0.0.1 localhost\n0.0.0.0 example.com # Blocked by gwd\n")
+                .unwrap();
+        fs::set_permissions(mock_hosts.path(), fs::Permissions::from_mode(0o604)).unwrap();
+
+        unblock_website_at("example.com", &no_challenge(), mock_hosts.path()).unwrap();
+
+        let mode = fs::metadata(mock_hosts.path())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o604);
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            "127.0.0.1 localhost\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_block_website_replaces_file_atomically() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mock_hosts = create_mock_hosts(
+            "# === gwd START ===\n0.0.0.0 a.com # Blocked by gwd\n# === gwd END ===\n::1 localhost",
+        )
+        .unwrap();
+        fs::set_permissions(mock_hosts.path(), fs::Permissions::from_mode(0o604)).unwrap();
+        fs::write(temp_path_for(mock_hosts.path()), "half-written garbage\n").unwrap();
+
+        block_at(&["b.com"], mock_hosts.path()).unwrap();
+
+        let mode = fs::metadata(mock_hosts.path())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o604);
+        assert!(!temp_path_for(mock_hosts.path()).exists());
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        assert!(content.contains("0.0.0.0 b.com # Blocked by gwd\n"));
+        assert!(content.ends_with("::1 localhost\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_temp_file_on_another_filesystem_is_copied_into_place() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        assert_eq!(replace_strategy(true, Some(false)), ReplaceStrategy::Rename);
+        assert_eq!(replace_strategy(false, Some(true)), ReplaceStrategy::Rename);
+        assert_eq!(replace_strategy(false, None), ReplaceStrategy::Rename);
+        assert_eq!(replace_strategy(false, Some(false)), ReplaceStrategy::Copy);
+
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+        fs::set_permissions(mock_hosts.path(), fs::Permissions::from_mode(0o604)).unwrap();
+        let inode = fs::metadata(mock_hosts.path()).unwrap().ino();
+        // A directory that refuses the temp file, and a system temp directory that rename can't
+        // move it out of
+        let elsewhere = tempfile::tempdir().unwrap();
+        let staging = Staging {
+            create_next_to_hosts: |_| Err(io::ErrorKind::PermissionDenied.into()),
+            fallback_dir: elsewhere.path().to_path_buf(),
+            same_filesystem: |_, _| Some(false),
+        };
+        let mut hosts = HostsFile::load_locked(mock_hosts.path()).unwrap();
+        hosts.add_block("0.0.0.0 b.com # Blocked by gwd");
+        hosts.save_staged(&staging).unwrap();
+        drop(hosts);
+
+        // The same file, with its permissions, now holding the new content
+        let metadata = fs::metadata(mock_hosts.path()).unwrap();
+        assert_eq!(metadata.ino(), inode);
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o604);
+        assert!(fs::read_to_string(mock_hosts.path())
+            .unwrap()
+            .contains("0.0.0.0 b.com # Blocked by gwd\n"));
+        // Neither the temp file nor the backup is left behind
+        assert_eq!(fs::read_dir(elsewhere.path()).unwrap().count(), 0);
+
+        // A backup left by a copy that was cut short stops the next write until it is dealt with
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        let backup_path = fallback_path_for(elsewhere.path(), mock_hosts.path(), "bak");
+        fs::write(&backup_path, "127.0.0.1 localhost\n").unwrap();
+        let mut hosts = HostsFile::load_locked(mock_hosts.path()).unwrap();
+        hosts.add_block("0.0.0.0 c.com # Blocked by gwd");
+        let error = hosts.save_staged(&staging).unwrap_err().to_string();
+        assert!(error.contains("interrupted gwd write"), "{}", error);
+        assert_eq!(fs::read_to_string(mock_hosts.path()).unwrap(), content);
+        fs::remove_file(&backup_path).unwrap();
+
+        // Without anywhere to put the backup, the hosts file isn't touched either
+        let temp_path = elsewhere.path().join("hosts.tmp");
+        fs::write(&temp_path, "0.0.0.0 c.com # Blocked by gwd\n").unwrap();
+        let unwritable = elsewhere.path().join("missing").join("hosts.bak");
+        let error = copy_over(&temp_path, mock_hosts.path(), &unwritable).unwrap_err();
+        assert!(
+            error.to_string().contains("could not back it up"),
+            "{}",
+            error
+        );
+        assert_eq!(fs::read_to_string(mock_hosts.path()).unwrap(), content);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_lock_in_the_system_temp_directory_follows_no_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        fs::write(&target, "precious\n").unwrap();
+        let planted = dir.path().join("planted.gwd.lock");
+        std::os::unix::fs::symlink(&target, &planted).unwrap();
+        assert!(open_shared_lock(&planted).is_err());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "precious\n");
+
+        // gwd's own lock file is created once and reused
+        let lock_path = dir.path().join("gwd.lock");
+        drop(open_shared_lock(&lock_path).unwrap());
+        assert!(open_shared_lock(&lock_path).is_ok());
+    }
+
+    #[test]
+    fn test_stale_temp_file_in_the_system_temp_directory_is_discarded() {
+        let mock_hosts = create_mock_hosts("0.0.0.0 example.com # Blocked by gwd\n").unwrap();
+        let fallback = fallback_path_for(&std::env::temp_dir(), mock_hosts.path(), "tmp");
+        assert_eq!(fallback.parent(), Some(std::env::temp_dir().as_path()));
+        assert_ne!(fallback, temp_path_for(mock_hosts.path()));
+        fs::write(&fallback, "half-written garbage\n").unwrap();
+
+        // A dry run names the file a real tidy removes
+        assert_eq!(
+            tidy_temp_files_at(true, mock_hosts.path()).unwrap(),
+            vec![fallback.clone()]
+        );
+        assert!(fallback.exists());
+        assert_eq!(
+            tidy_temp_files_at(false, mock_hosts.path()).unwrap(),
+            vec![fallback.clone()]
+        );
+        assert!(!fallback.exists());
+        assert!(tidy_temp_files_at(false, mock_hosts.path())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_detect_line_ending() {
+        assert_eq!(detect_line_ending("a\r\nb\r\n"), "\r\n");
+        assert_eq!(detect_line_ending("a\nb\n"), "\n");
+        assert_eq!(detect_line_ending("a\r\nb\nc\n"), "\n");
+    }
+
+    #[test]
+    fn test_block_website_crlf() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\r\n::1 localhost").unwrap();
+
+        block_at(&["example.com"], mock_hosts.path()).unwrap();
+        // Already-present CRLF entries are detected as duplicates
+        block_at(&["example.com"], mock_hosts.path()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            sealed(
+                "127.0.0.1 localhost\r\n::1 localhost\r\n\
+             # === gwd START ===\r\n\
+             0.0.0.0 example.com # Blocked by gwd\r\n\
+             0.0.0.0 www.example.com # Blocked by gwd\r\n\
+             # === gwd END ===\r\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_unblock_website_crlf_preserves_terminators() {
+        let mock_hosts = create_mock_hosts(
+            "127.0.0.1 localhost\r\n\
+             0.0.0.0 example.com # Blocked by gwd\r\n\
+             0.0.0.0 www.example.com # Blocked by gwd\r\n\
+             # trailing comment\n\
+             ::1 localhost\r\n",
+        )
+        .unwrap();
+
+        unblock_website_at("example.com", &no_challenge(), mock_hosts.path()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            "127.0.0.1 localhost\r\n# trailing comment\n::1 localhost\r\n"
+        );
+    }
+
+    #[test]
+    fn test_block_state() {
+        let mock_hosts = create_mock_hosts(
+            "127.0.0.1 localhost\n\
+             0.0.0.0 www.example.com # Blocked by gwd\n\
+             0.0.0.0 manual.com # my own block\n\
+             0.0.0.0 both.com\n\
+             127.0.0.1 both.com # Blocked by gwd\n",
+        )
+        .unwrap();
+        let path = mock_hosts.path();
+
+        assert_eq!(block_state("example.com", path).unwrap(), BlockState::Gwd);
+        assert_eq!(
+            block_state("https://www.example.com/", path).unwrap(),
+            BlockState::Gwd
+        );
+        assert_eq!(
+            block_state("manual.com", path).unwrap(),
+            BlockState::Foreign
+        );
+        assert_eq!(block_state("both.com", path).unwrap(), BlockState::Gwd);
+        assert_eq!(
+            block_state("other.com", path).unwrap(),
+            BlockState::NotBlocked
+        );
+
+        assert!(is_blocked("example.com", path).unwrap());
+        assert!(!is_blocked("manual.com", path).unwrap());
+        assert!(!is_blocked("localhost", path).unwrap());
+    }
+
+    #[test]
+    fn test_block_websites_appends_inside_existing_section() {
+        let mock_hosts = create_mock_hosts(
+            "127.0.0.1 localhost\n\
+             # === gwd START ===\n\
+             0.0.0.0 a.com # Blocked by gwd\n\
+             # === gwd END ===\n\
+             10.0.0.1 nas.local\n",
+        )
+        .unwrap();
+
+        block_at(&["b.com"], mock_hosts.path()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            sealed(
+                "127.0.0.1 localhost\n\
+             # === gwd START ===\n\
+             0.0.0.0 a.com # Blocked by gwd\n\
+             0.0.0.0 b.com # Blocked by gwd\n\
+             0.0.0.0 www.b.com # Blocked by gwd\n\
+             # === gwd END ===\n\
+             10.0.0.1 nas.local\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_named_sections_group_entries() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+        let block = |domain: &str, section: Option<&str>| {
+            let options = BlockOptions {
+                prefixes: Vec::new(),
+                section: section.map(str::to_string),
+                verbosity: Verbosity::Quiet,
+                ..Default::default()
+            };
+            block_websites_at(&[domain.to_string()], &options, mock_hosts.path())
+        };
+        block("plain.com", None).unwrap();
+        block("slack.com", Some("work")).unwrap();
+        block("reddit.com", Some("personal")).unwrap();
+        block("jira.com", Some("work")).unwrap();
+        block("other.com", None).unwrap();
+        assert!(block("x.com", Some("my work")).is_err());
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            sealed(
+                "127.0.0.1 localhost\n\
+                 # === gwd START ===\n\
+                 # gwd:default\n\
+                 0.0.0.0 plain.com # Blocked by gwd\n\
+                 0.0.0.0 other.com # Blocked by gwd\n\
+                 # gwd:work\n\
+                 0.0.0.0 slack.com # Blocked by gwd\n\
+                 0.0.0.0 jira.com # Blocked by gwd\n\
+                 # gwd:personal\n\
+                 0.0.0.0 reddit.com # Blocked by gwd\n\
+                 # === gwd END ===\n"
+            )
+        );
+        let listed = |section: &str| -> Vec<String> {
+            list_section_entries_at(Some(section), mock_hosts.path())
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.domain)
+                .collect()
+        };
+        assert_eq!(listed("work"), vec!["slack.com", "jira.com"]);
+        assert_eq!(listed("default"), vec!["plain.com", "other.com"]);
+
+        // --section keeps an unblock away from the other sub-sections
+        let in_section = |section: &str| UnblockOptions {
+            section: Some(section.to_string()),
+            ..no_challenge()
+        };
+        let report =
+            unblock_website_at("slack.com", &in_section("personal"), mock_hosts.path()).unwrap();
+        assert!(report.removed.is_empty());
+        unblock_website_at("slack.com", &in_section("work"), mock_hosts.path()).unwrap();
+        let report = unblock_section_at("work", &no_challenge(), mock_hosts.path()).unwrap();
+        assert_eq!(report.removed, vec!["0.0.0.0 jira.com # Blocked by gwd"]);
+        assert!(listed("work").is_empty());
+
+        // Emptied sub-sections lose their headers, and the default one goes with the last of them
+        unblock_website_at("reddit.com", &no_challenge(), mock_hosts.path()).unwrap();
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            sealed(
+                "127.0.0.1 localhost\n\
+                 # === gwd START ===\n\
+                 0.0.0.0 plain.com # Blocked by gwd\n\
+                 0.0.0.0 other.com # Blocked by gwd\n\
+                 # === gwd END ===\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_managed_layout_repairs_missing_and_duplicate_markers() {
+        // Legacy entry outside any section, a duplicated section, a stray END and a missing final END
+        let content = "0.0.0.0 old.com # Blocked by gwd\n\
+                       # === gwd START ===\n\
+                       0.0.0.0 a.com # Blocked by gwd\n\
+                       # === gwd END ===\n\
+                       10.0.0.1 nas.local\n\
+                       # === gwd END ===\n\
+                       # === gwd START ===\n\
+                       0.0.0.0 b.com # Blocked by gwd\n";
+
+        assert_eq!(
+            ManagedLayout::parse(content).render(),
+            sealed(
+                "# === gwd START ===\n\
+             0.0.0.0 old.com # Blocked by gwd\n\
+             0.0.0.0 a.com # Blocked by gwd\n\
+             0.0.0.0 b.com # Blocked by gwd\n\
+             # === gwd END ===\n\
+             10.0.0.1 nas.local\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_unblock_website_only_touches_section() {
+        let mock_hosts = create_mock_hosts(
+            "0.0.0.0 example.com # my own block\n\
+             # === gwd START ===\n\
+             0.0.0.0 example.com # Blocked by gwd\n\
+             0.0.0.0 other.com # Blocked by gwd\n\
+             # === gwd END ===\n",
+        )
+        .unwrap();
+
+        unblock_website_at("example.com", &no_challenge(), mock_hosts.path()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            sealed(
+                "0.0.0.0 example.com # my own block\n\
+             # === gwd START ===\n\
+             0.0.0.0 other.com # Blocked by gwd\n\
+             # === gwd END ===\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_unblock_keeps_surviving_lines_byte_identical() {
+        // Blank lines, comments, odd spacing and a last line without a newline, with the
+        // removed entries both inside gwd's section and left over from before it existed
+        let before = "# Static table lookup for hostnames.\n\
+                      \n\
+                      127.0.0.1\tlocalhost   # loopback \n\
+                      \t\n\
+                      # === gwd START ===\n\
+                      0.0.0.0 example.com # Blocked by gwd\n\
+                      0.0.0.0 other.com # Blocked by gwd\n\
+                      # === gwd END ===\n\
+                      \n\
+                      ::1 localhost ip6-localhost  # trailing comment\n\
+                      0.0.0.0 www.example.com # Blocked by gwd\n\
+                      10.0.0.1 nas.local #no newline";
+        let after = "# Static table lookup for hostnames.\n\
+                     \n\
+                     127.0.0.1\tlocalhost   # loopback \n\
+                     \t\n\
+                     # === gwd START ===\n\
+                     0.0.0.0 other.com # Blocked by gwd\n\
+                     # === gwd END ===\n\
+                     \n\
+                     ::1 localhost ip6-localhost  # trailing comment\n\
+                     10.0.0.1 nas.local #no newline";
+        let mock_hosts = create_mock_hosts(before).unwrap();
+
+        unblock_website_at("example.com", &no_challenge(), mock_hosts.path()).unwrap();
+        assert_eq!(
+            fs::read(mock_hosts.path()).unwrap(),
+            sealed(after).as_bytes()
+        );
+
+        // Removing the last line keeps the newline the line before it already had, and no more
+        let mock_hosts =
+            create_mock_hosts("127.0.0.1 localhost\n0.0.0.0 a.com # Blocked by gwd").unwrap();
+        unblock_website_at("a.com", &no_challenge(), mock_hosts.path()).unwrap();
+        assert_eq!(
+            fs::read(mock_hosts.path()).unwrap(),
+            b"127.0.0.1 localhost\n"
+        );
+    }
+
+    #[test]
+    fn test_block_websites_dry_run_writes_nothing() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+        let options = BlockOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+
+        block_websites_at(&["example.com".to_string()], &options, mock_hosts.path()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            "127.0.0.1 localhost\n"
+        );
+    }
+
+    #[test]
+    fn test_unblock_dry_run_writes_nothing() {
+        let initial_content = "0.0.0.0 example.com # Blocked by gwd\n";
+        let mock_hosts = create_mock_hosts(initial_content).unwrap();
+        // The challenge would block on stdin if it ran, so a dry run must skip it
+        let options = UnblockOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+
+        unblock_website_at("example.com", &options, mock_hosts.path()).unwrap();
+        unblock_all_websites_at(&options, mock_hosts.path()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            initial_content
+        );
+        assert!(!mock_hosts.path().with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn test_read_only_filesystem_gets_its_own_error() {
+        let path = Path::new("/etc/hosts");
+        assert!(matches!(
+            map_io_error(io::ErrorKind::ReadOnlyFilesystem.into(), path, IoPhase::Read),
+            AppError::ReadOnlyHosts(p) if p == path
+        ));
+        assert!(matches!(
+            map_io_error(
+                io::ErrorKind::PermissionDenied.into(),
+                path,
+                IoPhase::Write("writing the temp file")
+            ),
+            AppError::PermissionDenied(_)
+        ));
+        assert!(matches!(
+            map_io_error(
+                io::ErrorKind::StorageFull.into(),
+                path,
+                IoPhase::Write("writing the temp file")
+            ),
+            AppError::WriteHosts { path: p, source_str }
+                if p == path && source_str.starts_with("writing the temp file: ")
+        ));
+        assert!(matches!(
+            map_io_error(io::ErrorKind::StorageFull.into(), path, IoPhase::Other("Failed")),
+            AppError::Io(message) if message.starts_with("Failed: ")
+        ));
+    }
+
+    #[test]
+    fn test_read_and_write_failures_name_the_phase() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = BlockOptions {
+            verbosity: Verbosity::Quiet,
+            ..BlockOptions::default()
+        };
+
+        // A directory where the hosts file should be can't be read
+        let unreadable = dir.path().join("unreadable");
+        fs::create_dir(&unreadable).unwrap();
+        let err =
+            block_websites_at(&["example.com".to_string()], &options, &unreadable).unwrap_err();
+        assert!(
+            matches!(&err, AppError::ReadHosts { path, .. } if path == &unreadable),
+            "{:?}",
+            err
+        );
+        assert!(matches!(
+            list_blocked_entries_at(&unreadable),
+            Err(AppError::ReadHosts { .. })
+        ));
+
+        // ... and one where the temp file should go makes the write fail, leaving the file as is
+        let hosts_path = dir.path().join("hosts");
+        fs::write(&hosts_path, "127.0.0.1 localhost\n").unwrap();
+        fs::create_dir(temp_path_for(&hosts_path)).unwrap();
+        let err =
+            block_websites_at(&["example.com".to_string()], &options, &hosts_path).unwrap_err();
+        assert!(
+            matches!(&err, AppError::WriteHosts { path, .. } if path == &hosts_path),
+            "{:?}",
+            err
+        );
+        assert_eq!(err.exit_code(), 6);
+        assert_eq!(
+            fs::read_to_string(&hosts_path).unwrap(),
+            "127.0.0.1 localhost\n"
+        );
+    }
+
+    #[test]
+    fn test_unblock_uses_the_configured_challenge_length() {
+        let mock_hosts = create_mock_hosts(
+            "0.0.0.0 example.com # Blocked by gwd\n\
+             0.0.0.0 test.dev # Blocked by gwd [scratch]\n",
+        )
+        .unwrap();
+        // The default challenge would block on stdin if it ran; a length of 0 skips it
+        let options = UnblockOptions {
+            challenge: ChallengeOptions {
+                lengths: ChallengeLengths {
+                    domains: [("example.com".to_string(), 0)].into_iter().collect(),
+                    labels: [("scratch".to_string(), 0)].into_iter().collect(),
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        unblock_website_at("example.com", &options, mock_hosts.path()).unwrap();
+        remove_label_at("scratch", &options, true, mock_hosts.path()).unwrap();
+        assert!(list_blocked_entries_at(mock_hosts.path())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_forced_unblock_skips_the_challenge() {
+        let mock_hosts = create_mock_hosts(
+            "0.0.0.0 example.com # Blocked by gwd\n\
+             0.0.0.0 locked.com # Blocked by gwd until 4102444800\n",
+        )
+        .unwrap();
+        // The default challenge would block on stdin if it ran
+        let options = UnblockOptions {
+            force: true,
+            ..Default::default()
+        };
+
+        let report = unblock_website_at("example.com", &options, mock_hosts.path()).unwrap();
+        assert_eq!(report.removed.len(), 1);
+        // --force is about the challenge only; a timed lock still refuses
+        assert!(matches!(
+            unblock_website_at("locked.com", &options, mock_hosts.path()),
+            Err(AppError::LockActive { .. })
+        ));
+    }
+
+    #[test]
+    fn test_soft_block_is_recorded_but_not_in_effect() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+        let options = BlockOptions {
+            label: Some("later".to_string()),
+            soft: true,
+            ..Default::default()
+        };
+
+        let report =
+            block_websites_at(&["example.com".to_string()], &options, mock_hosts.path()).unwrap();
+        assert_eq!(report.added, vec!["example.com", "www.example.com"]);
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        assert!(
+            content.contains("# 0.0.0.0 example.com # Soft-blocked by gwd [later]"),
+            "{}",
+            content
+        );
+        assert_eq!(
+            block_state("example.com", mock_hosts.path()).unwrap(),
+            BlockState::Soft
+        );
+        assert!(!is_blocked("example.com", mock_hosts.path()).unwrap());
+        let entries = list_blocked_entries_at(mock_hosts.path()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].soft);
+        assert_eq!(entries[0].label.as_deref(), Some("later"));
+
+        // Soft-blocking again is a no-op
+        let again =
+            block_websites_at(&["example.com".to_string()], &options, mock_hosts.path()).unwrap();
+        assert!(again.added.is_empty());
+        assert_eq!(again.already_present.len(), 2);
+    }
+
+    #[test]
+    fn test_harden_turns_soft_blocks_into_real_ones() {
+        let mock_hosts = create_mock_hosts(
+            "# 0.0.0.0 example.com # Soft-blocked by gwd [later]\n\
+             # 0.0.0.0 www.example.com # Soft-blocked by gwd [later]\n\
+             # 0.0.0.0 other.com # Soft-blocked by gwd\n",
+        )
+        .unwrap();
+        let domains = vec!["example.com".to_string(), "missing.com".to_string()];
+
+        let preview =
+            harden_websites_at(&domains, true, Verbosity::Quiet, mock_hosts.path()).unwrap();
+        assert_eq!(preview.added, vec!["example.com", "www.example.com"]);
+        assert!(fs::read_to_string(mock_hosts.path())
+            .unwrap()
+            .starts_with("# 0.0.0.0 example.com"));
+
+        let report =
+            harden_websites_at(&domains, false, Verbosity::Quiet, mock_hosts.path()).unwrap();
+        assert_eq!(
+            report.entries[0],
+            "0.0.0.0 example.com # Blocked by gwd [later]"
+        );
+        assert_eq!(
+            block_state("example.com", mock_hosts.path()).unwrap(),
+            BlockState::Gwd
+        );
+        assert_eq!(
+            block_state("other.com", mock_hosts.path()).unwrap(),
+            BlockState::Soft
+        );
+    }
+
+    #[test]
+    fn test_move_relabels_entries_without_unblocking() {
+        let mock_hosts = create_mock_hosts(
+            "0.0.0.0 example.com # Blocked by gwd [social] until 4102444800 reason=news\n\
+             0.0.0.0 www.example.com # Blocked by gwd [deepwork]\n\
+             0.0.0.0 m.example.com # gwd {\"label\":\"social\",\"until_reboot\":true,\"variant\":\"m\"}\n\
+             # 0.0.0.0 later.com # Soft-blocked by gwd\n\
+             0.0.0.0 other.com # Blocked by gwd [social]\n",
+        )
+        .unwrap();
+        let move_to = |domains: &[&str], dry_run: bool| {
+            let domains: Vec<String> = domains.iter().map(|domain| domain.to_string()).collect();
+            move_websites_at(
+                &domains,
+                "deepwork",
+                dry_run,
+                Verbosity::Quiet,
+                mock_hosts.path(),
+            )
+        };
+
+        // A domain gwd doesn't block fails the whole move
+        assert!(matches!(
+            move_to(&["example.com", "missing.com"], false),
+            Err(AppError::NotBlocked(domain)) if domain == "missing.com"
+        ));
+        let before = fs::read_to_string(mock_hosts.path()).unwrap();
+        let preview = move_to(&["example.com"], true).unwrap();
+        assert_eq!(preview.added, vec!["example.com", "m.example.com"]);
+        assert_eq!(fs::read_to_string(mock_hosts.path()).unwrap(), before);
+
+        let report = move_to(&["https://example.com/", "later.com"], false).unwrap();
+        assert_eq!(
+            report.entries,
+            vec![
+                "0.0.0.0 example.com # Blocked by gwd [deepwork] until 4102444800 reason=news",
+                "0.0.0.0 m.example.com # gwd {\"label\":\"deepwork\",\"until_reboot\":true,\"variant\":\"m\"}",
+                "# 0.0.0.0 later.com # Soft-blocked by gwd [deepwork]",
+            ]
+        );
+        assert_eq!(report.already_present, vec!["www.example.com"]);
+        let labels: Vec<(String, Option<String>, bool)> =
+            list_blocked_entries_at(mock_hosts.path())
+                .unwrap()
+                .into_iter()
+                .map(|entry| (entry.domain, entry.label, entry.soft))
+                .collect();
+        assert_eq!(
+            labels,
+            vec![
+                (
+                    "example.com".to_string(),
+                    Some("deepwork".to_string()),
+                    false
+                ),
+                ("other.com".to_string(), Some("social".to_string()), false),
+                ("later.com".to_string(), Some("deepwork".to_string()), true),
+            ]
+        );
+        assert_eq!(
+            lock_until("example.com", mock_hosts.path()).unwrap(),
+            Some(4_102_444_800)
+        );
+    }
+
+    #[test]
+    fn test_pause_and_resume_toggle_every_entry() {
+        let mock_hosts = create_mock_hosts(
+            "127.0.0.1 localhost\n\
+             0.0.0.0 example.com # Blocked by gwd [social]\n\
+             0.0.0.0 www.example.com # gwd {\"label\":\"social\"}\n\
+             # 0.0.0.0 later.com # Soft-blocked by gwd\n",
+        )
+        .unwrap();
+        let path = mock_hosts.path();
+        let original = fs::read_to_string(path).unwrap();
+
+        let dry_run = UnblockOptions {
+            dry_run: true,
+            ..no_challenge()
+        };
+        let preview = pause_blocks_at(2_000_000_000, &dry_run, path).unwrap();
+        assert_eq!(preview.paused.len(), 2);
+        assert_eq!(fs::read_to_string(path).unwrap(), original);
+
+        let report = pause_blocks_at(2_000_000_000, &no_challenge(), path).unwrap();
+        assert_eq!(
+            report.paused,
+            vec![
+                "0.0.0.0 example.com # Blocked by gwd [social]",
+                "0.0.0.0 www.example.com # gwd {\"label\":\"social\"}",
+            ]
+        );
+        let content = fs::read_to_string(path).unwrap();
+        assert!(content.contains(
+            "\n# PAUSED until 2000000000 0.0.0.0 example.com # Blocked by gwd [social]\n"
+        ));
+        assert!(content.contains("\n# 0.0.0.0 later.com # Soft-blocked by gwd\n"));
+        assert!(HostsFile::load(path).unwrap().list_gwd().is_empty());
+        assert_eq!(list_paused_at(path).unwrap().len(), 2);
+        assert_eq!(
+            block_state("example.com", path).unwrap(),
+            BlockState::Paused
+        );
+        assert!(malformed_gwd_lines_at(path).unwrap().is_empty());
+
+        // Blocked again during the pause: the resume doesn't add a second entry
+        let bare = BlockOptions {
+            prefixes: Vec::new(),
+            ..Default::default()
+        };
+        block_websites_at(&["example.com".to_string()], &bare, path).unwrap();
+        assert!(sweep_paused(path).unwrap().is_empty());
+        let resumed = resume_blocks_at(false, Verbosity::Quiet, path).unwrap();
+        assert_eq!(resumed.len(), 2);
+        let entries: Vec<String> = HostsFile::load(path)
+            .unwrap()
+            .list_gwd()
+            .into_iter()
+            .map(|entry| entry.hostname)
+            .collect();
+        assert_eq!(entries, vec!["www.example.com", "example.com"]);
+        assert!(list_paused_at(path).unwrap().is_empty());
+        assert!(resume_blocks_at(false, Verbosity::Quiet, path)
+            .unwrap()
+            .is_empty());
+
+        // A pause that is over ends with the next sweep
+        pause_blocks_at(now_timestamp() - 1, &no_challenge(), path).unwrap();
+        assert_eq!(sweep_paused(path).unwrap().len(), 2);
+        assert_eq!(block_state("example.com", path).unwrap(), BlockState::Gwd);
+
+        // A timed lock can't be paused around
+        let locked =
+            create_mock_hosts("0.0.0.0 locked.com # Blocked by gwd until 4102444800\n").unwrap();
+        assert!(matches!(
+            pause_blocks_at(2_000_000_000, &no_challenge(), locked.path()),
+            Err(AppError::LockActive { domain, .. }) if domain == "locked.com"
+        ));
+    }
+
+    #[test]
+    fn test_json_comment_style_round_trips() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+        let options = BlockOptions {
+            label: Some("work".to_string()),
+            lock_until: Some(1_699_999_999),
+            blocked_at: Some(1_705_311_000),
+            reason: Some("\"deep\" # work".to_string()),
+            prefixes: Vec::new(),
+            comment_style: CommentStyle::Json,
+            verbosity: Verbosity::Quiet,
+            ..BlockOptions::default()
+        };
+        let report =
+            block_websites_at(&["example.com".to_string()], &options, mock_hosts.path()).unwrap();
+        assert_eq!(
+            report.entries,
+            vec![
+                "0.0.0.0 example.com # gwd {\"blocked_at\":\"2024-01-15T09:30:00Z\",\"label\":\"work\",\"reason\":\"\\\"deep\\\" # work\",\"until\":1699999999}"
+            ]
+        );
+        let soft = BlockOptions {
+            soft: true,
+            label: None,
+            lock_until: None,
+            reason: None,
+            ..options.clone()
+        };
+        block_websites_at(&["later.com".to_string()], &soft, mock_hosts.path()).unwrap();
+
+        // Both styles read back the same way
+        fs::OpenOptions::new()
+            .append(true)
+            .open(mock_hosts.path())
+            .unwrap()
+            .write_all(b"0.0.0.0 plain.com # Blocked by gwd [home]\n")
+            .unwrap();
+        let entries = list_blocked_entries_at(mock_hosts.path()).unwrap();
+        let example = entries.iter().find(|e| e.domain == "example.com").unwrap();
+        assert_eq!(example.label.as_deref(), Some("work"));
+        assert_eq!(example.until, Some(1_699_999_999));
+        assert_eq!(example.blocked_at, Some(1_705_311_000));
+        assert_eq!(example.reason.as_deref(), Some("\"deep\" # work"));
+        assert!(entries.iter().any(|e| e.domain == "later.com" && e.soft));
+        assert!(entries
+            .iter()
+            .any(|e| e.domain == "plain.com" && e.label.as_deref() == Some("home")));
+
+        let report = harden_websites_at(
+            &["later.com".to_string()],
+            false,
+            Verbosity::Quiet,
+            mock_hosts.path(),
+        )
+        .unwrap();
+        assert!(report.entries[0].starts_with("0.0.0.0 later.com # gwd {"));
+        assert_eq!(
+            block_state("later.com", mock_hosts.path()).unwrap(),
+            BlockState::Gwd
+        );
+        assert_eq!(gwd_entry_count_at(mock_hosts.path()).unwrap(), 3);
+
+        // normalize keeps each entry in its own style
+        normalize_hosts_at(false, Verbosity::Quiet, mock_hosts.path()).unwrap();
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        assert!(content.contains("0.0.0.0 example.com # gwd {"));
+        assert!(content.contains("0.0.0.0 plain.com # Blocked by gwd [home]"));
+        assert!(malformed_gwd_lines_at(mock_hosts.path())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_unparseable_json_comment_is_malformed() {
+        let mock_hosts = create_mock_hosts(
+            "0.0.0.0 good.com # gwd {}\n\
+             0.0.0.0 bad.com # gwd {\"label\": work}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            list_blocked_entries_at(mock_hosts.path())
+                .unwrap()
+                .iter()
+                .map(|entry| entry.domain.as_str())
+                .collect::<Vec<_>>(),
+            vec!["good.com"]
+        );
+        let problems = malformed_gwd_lines_at(mock_hosts.path()).unwrap();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].1.contains("bad.com"));
+    }
+
+    #[test]
+    fn test_soft_blocks_unblock_without_challenge_and_yield_to_real_blocks() {
+        let mock_hosts = create_mock_hosts(
+            "# 0.0.0.0 example.com # Soft-blocked by gwd\n\
+             # 0.0.0.0 other.com # Soft-blocked by gwd\n",
+        )
+        .unwrap();
+
+        // The default challenge would block on stdin if it ran
+        let report =
+            unblock_website_at("example.com", &UnblockOptions::default(), mock_hosts.path())
+                .unwrap();
+        assert_eq!(
+            report.removed,
+            vec!["# 0.0.0.0 example.com # Soft-blocked by gwd"]
+        );
+
+        // A real block replaces the soft one instead of sitting next to it
+        let report = block_at(&["other.com"], mock_hosts.path()).unwrap();
+        assert!(report.added.contains(&"other.com".to_string()));
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        assert!(!content.contains("Soft-blocked"), "{}", content);
+        assert!(content.contains("0.0.0.0 other.com # Blocked by gwd\n"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_retry_while_busy_gives_up_after_the_last_attempt() {
+        let mut calls = 0;
+        let result = retry_while_busy(3, Duration::from_millis(1), || {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+
+        // Other errors aren't retried, and a success ends the loop
+        let mut calls = 0;
+        let result = retry_while_busy(3, Duration::from_millis(1), || {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::NotFound))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+        let mut calls = 0;
+        retry_while_busy(3, Duration::from_millis(1), || {
+            calls += 1;
+            if calls < 2 {
+                Err(io::Error::from_raw_os_error(32))
+            } else {
+                Ok(())
+            }
+        })
+        .unwrap();
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn test_no_unblock_period_refuses_every_challenge() {
+        let mock_hosts = create_mock_hosts(
+            "0.0.0.0 example.com # Blocked by gwd\n\
+             # 0.0.0.0 later.com # Soft-blocked by gwd\n",
+        )
+        .unwrap();
+        // Every day, around the clock
+        let always = Period {
+            days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+                Weekday::Sat,
+                Weekday::Sun,
+            ],
+            start: NaiveTime::MIN,
+            end: NaiveTime::MIN,
+        };
+        let options = UnblockOptions {
+            force: true,
+            no_unblock: vec![always],
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            unblock_website_at("example.com", &options, mock_hosts.path()),
+            Err(AppError::LockActive { .. })
+        ));
+        assert!(matches!(
+            unblock_all_websites_at(&options, mock_hosts.path()),
+            Err(AppError::LockActive { .. })
+        ));
+        // Soft blocks aren't in effect, so removing them needs no challenge to refuse
+        let report = unblock_website_at("later.com", &options, mock_hosts.path()).unwrap();
+        assert_eq!(report.removed.len(), 1);
+    }
+
+    #[test]
+    fn test_section_checksum_detects_outside_edits() {
+        assert_eq!(
+            section_checksum(&["0.0.0.0 b.com # Blocked by gwd\r\n".to_string()]),
+            "4c42daa90996f2b0987bebd600046f63c60e98270cf55d378e72ed28b709156d"
+        );
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+        block_at(&["a.com", "b.com"], mock_hosts.path()).unwrap();
+        let hosts = HostsFile::load(mock_hosts.path()).unwrap();
+        assert!(!hosts.layout.tampered());
+        check_checksum(&hosts, true).unwrap();
+
+        // Deleting a block by hand breaks the checksum
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        fs::write(
+            mock_hosts.path(),
+            content.replace("0.0.0.0 a.com # Blocked by gwd\n", ""),
+        )
+        .unwrap();
+        let hosts = HostsFile::load(mock_hosts.path()).unwrap();
+        assert!(hosts.layout.tampered());
+        assert!(matches!(
+            check_checksum(&hosts, true),
+            Err(AppError::Tampered(_))
+        ));
+        // Without --strict the change goes ahead and stores a fresh checksum
+        check_checksum(&hosts, false).unwrap();
+        block_at(&["c.com"], mock_hosts.path()).unwrap();
+        assert!(!HostsFile::load(mock_hosts.path())
+            .unwrap()
+            .layout
+            .tampered());
+
+        // normalize accepts an outside edit and says so
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        fs::write(
+            mock_hosts.path(),
+            content.replace("0.0.0.0 b.com # Blocked by gwd\n", ""),
+        )
+        .unwrap();
+        let report = normalize_hosts_at(false, Verbosity::Quiet, mock_hosts.path()).unwrap();
+        assert!(report.checksum_reset);
+        assert!(!HostsFile::load(mock_hosts.path())
+            .unwrap()
+            .layout
+            .tampered());
+    }
+
+    #[test]
+    fn test_concurrent_blocks_keep_both_entries() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+        let path = mock_hosts.path().to_path_buf();
+
+        let handles: Vec<_> = ["first.com", "second.com"]
+            .into_iter()
+            .map(|domain| {
+                let path = path.clone();
+                std::thread::spawn(move || block_at(&[domain], &path).unwrap())
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let content = fs::read_to_string(&path).unwrap();
+        for hostname in ["first.com", "www.first.com", "second.com", "www.second.com"] {
+            let entry = format!("0.0.0.0 {} # Blocked by gwd\n", hostname);
+            assert_eq!(content.matches(&entry).count(), 1, "{}", content);
+        }
+    }
+
+    #[test]
+    fn test_block_with_custom_prefixes() {
+        let mock_hosts = create_mock_hosts("").unwrap();
+        let path = mock_hosts.path();
+        let no_www = BlockOptions {
+            prefixes: Vec::new(),
+            ..Default::default()
+        };
+        let with_mobile = BlockOptions {
+            prefixes: vec!["www".to_string(), "m".to_string()],
+            ..Default::default()
+        };
+
+        block_websites_at(&["intranet.corp".to_string()], &no_www, path).unwrap();
+        block_websites_at(&["example.com".to_string()], &with_mobile, path).unwrap();
+
+        let content = fs::read_to_string(path).unwrap();
+        assert!(content.contains("0.0.0.0 intranet.corp # Blocked by gwd\n"));
+        assert!(!content.contains("www.intranet.corp"));
+        assert!(content.contains("0.0.0.0 m.example.com # Blocked by gwd variant=m\n"));
+        // Variants are listed under their base domain
+        assert_eq!(
+            list_blocked_domains_at(path).unwrap(),
+            vec!["intranet.corp", "example.com"]
+        );
+
+        // Unblocking removes whichever variants were written
+        unblock_website_at("example.com", &no_challenge(), path).unwrap();
+        unblock_website_at("intranet.corp", &no_challenge(), path).unwrap();
+        assert!(list_blocked_domains_at(path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unblock_leaves_subdomains_blocked_in_their_own_right() {
+        let mock_hosts = create_mock_hosts("").unwrap();
+        let path = mock_hosts.path();
+        let with_mobile = BlockOptions {
+            prefixes: vec!["www".to_string(), "m".to_string()],
+            ..Default::default()
+        };
+        block_websites_at(
+            &["example.com".to_string(), "cdn.example.com".to_string()],
+            &with_mobile,
+            path,
+        )
+        .unwrap();
+        assert_eq!(
+            list_blocked_domains_at(path).unwrap(),
+            vec!["example.com", "cdn.example.com"]
+        );
+        assert_eq!(count_blocked(path).unwrap(), 2);
+
+        let report = unblock_website_at("example.com", &no_challenge(), path).unwrap();
+        assert_eq!(
+            report.removed,
+            vec![
+                "0.0.0.0 example.com # Blocked by gwd",
+                "0.0.0.0 www.example.com # Blocked by gwd",
+                "0.0.0.0 m.example.com # Blocked by gwd variant=m",
+            ]
+        );
+        let content = fs::read_to_string(path).unwrap();
+        assert!(content.contains("0.0.0.0 cdn.example.com # Blocked by gwd\n"));
+        assert!(content.contains("0.0.0.0 www.cdn.example.com # Blocked by gwd\n"));
+        assert!(content.contains("0.0.0.0 m.cdn.example.com # Blocked by gwd variant=m\n"));
+        assert_eq!(
+            list_blocked_domains_at(path).unwrap(),
+            vec!["cdn.example.com"]
+        );
+    }
+
+    #[test]
+    fn test_block_rejects_invalid_prefix() {
+        let mock_hosts = create_mock_hosts("").unwrap();
+        let options = BlockOptions {
+            prefixes: vec!["bad prefix".to_string()],
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            block_websites_at(&["example.com".to_string()], &options, mock_hosts.path()),
+            Err(AppError::InvalidDomain(_))
+        ));
+    }
+
+    #[test]
+    fn test_undo_block_and_unblock() {
+        let mock_hosts = create_mock_hosts(&sealed(
+            "127.0.0.1 localhost\n\
+             # === gwd START ===\n\
+             0.0.0.0 keep.com # Blocked by gwd\n\
+             # === gwd END ===\n",
+        ))
+        .unwrap();
+        let path = mock_hosts.path();
+        let original = fs::read_to_string(path).unwrap();
+
+        let report = block_at(&["oops.com"], path).unwrap();
+        assert_eq!(
+            report.entries,
+            vec![
+                "0.0.0.0 oops.com # Blocked by gwd",
+                "0.0.0.0 www.oops.com # Blocked by gwd"
+            ]
+        );
+        undo_block_at(&report.entries, &no_challenge(), path).unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), original);
+
+        let report = unblock_website_at("keep.com", &no_challenge(), path).unwrap();
+        let restored = undo_unblock_at(&report.removed, false, Verbosity::Quiet, path).unwrap();
+        assert_eq!(restored, vec!["0.0.0.0 keep.com # Blocked by gwd"]);
+        assert_eq!(fs::read_to_string(path).unwrap(), original);
+
+        // Restoring again is a no-op
+        assert!(
+            undo_unblock_at(&report.removed, false, Verbosity::Quiet, path)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_undo_block_respects_lock() {
+        let mock_hosts = create_mock_hosts("").unwrap();
+        let options = BlockOptions {
+            lock_until: Some(now_timestamp() + 3600),
+            ..Default::default()
+        };
+        let report =
+            block_websites_at(&["locked.com".to_string()], &options, mock_hosts.path()).unwrap();
+
+        assert!(matches!(
+            undo_block_at(&report.entries, &no_challenge(), mock_hosts.path()),
+            Err(AppError::LockActive { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unblock_discards_stale_temp_file() {
+        let mock_hosts = create_mock_hosts("0.0.0.0 example.com # Blocked by gwd\n").unwrap();
+        let temp_path = temp_path_for(mock_hosts.path());
+        fs::write(&temp_path, "half-written garbage\n").unwrap();
+
+        unblock_website_at("example.com", &no_challenge(), mock_hosts.path()).unwrap();
+
+        assert!(!temp_path.exists());
+        assert_eq!(fs::read_to_string(mock_hosts.path()).unwrap(), "");
+    }
+
+    #[test]
+    fn test_normalize_removes_duplicates_and_reformats() {
+        let mock_hosts = create_mock_hosts(
+            "127.0.0.1   localhost\n\
+             # === gwd START ===\n\
+             0.0.0.0 a.com # Blocked by gwd\n\
+             0.0.0.0\tA.com   # Blocked by gwd until 1999999999\n\
+             # a note\n\
+             ::0  b.com # Blocked by gwd [work]\n\
+             :: b.com # Blocked by gwd\n\
+             0.0.0.0 a.com # Blocked by gwd\n\
+             # === gwd END ===\n",
+        )
+        .unwrap();
+
+        let report = normalize_hosts_at(false, Verbosity::Quiet, mock_hosts.path()).unwrap();
+
+        assert_eq!(
+            report,
+            NormalizeReport {
+                duplicates_removed: 3,
+                reformatted: 2,
+                checksum_reset: false,
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            sealed(
+                "127.0.0.1   localhost\n\
+             # === gwd START ===\n\
+             0.0.0.0 a.com # Blocked by gwd until 1999999999\n\
+             # a note\n\
+             :: b.com # Blocked by gwd [work]\n\
+             # === gwd END ===\n"
+            )
+        );
+
+        // A second run has nothing left to do
+        let before = fs::read_to_string(mock_hosts.path()).unwrap();
+        let report = normalize_hosts_at(false, Verbosity::Quiet, mock_hosts.path()).unwrap();
+        assert_eq!(report, NormalizeReport::default());
+        assert_eq!(fs::read_to_string(mock_hosts.path()).unwrap(), before);
+    }
+
+    #[test]
+    fn test_normalize_dry_run_writes_nothing() {
+        let original = "0.0.0.0 a.com # Blocked by gwd\n0.0.0.0  a.com # Blocked by gwd\n";
+        let mock_hosts = create_mock_hosts(original).unwrap();
+
+        let report = normalize_hosts_at(true, Verbosity::Quiet, mock_hosts.path()).unwrap();
+
+        assert_eq!(report.duplicates_removed, 1);
+        assert_eq!(fs::read_to_string(mock_hosts.path()).unwrap(), original);
+    }
+
+    #[test]
+    fn test_unblock_label_only_removes_labelled_entries() {
+        let mock_hosts = create_mock_hosts(
+            "0.0.0.0 a.com # Blocked by gwd [social]\n\
+             0.0.0.0 b.com # Blocked by gwd\n\
+             0.0.0.0 c.com # Blocked by gwd [news]\n",
+        )
+        .unwrap();
+
+        let report =
+            unblock_label_at("social", false, Verbosity::Quiet, mock_hosts.path()).unwrap();
+
+        assert_eq!(
+            report.removed,
+            vec!["0.0.0.0 a.com # Blocked by gwd [social]"]
+        );
+        assert_eq!(
+            list_blocked_domains_at(mock_hosts.path()).unwrap(),
+            vec!["b.com", "c.com"]
+        );
+        assert!(
+            unblock_label_at("social", false, Verbosity::Quiet, mock_hosts.path())
+                .unwrap()
+                .removed
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_block_with_label_then_unblock_label() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+        let options = BlockOptions {
+            label: Some("deepwork".to_string()),
+            ..Default::default()
+        };
+        block_websites_at(&["a.com".to_string()], &options, mock_hosts.path()).unwrap();
+        block_at(&["b.com"], mock_hosts.path()).unwrap();
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        assert!(content.contains("0.0.0.0 a.com # Blocked by gwd [deepwork]\n"));
+
+        let report = remove_label_at("deepwork", &no_challenge(), true, mock_hosts.path()).unwrap();
+
+        assert_eq!(report.domains, vec!["a.com", "www.a.com"]);
+        assert_eq!(
+            list_blocked_domains_at(mock_hosts.path()).unwrap(),
+            vec!["b.com"]
+        );
+    }
+
+    #[test]
+    fn test_lock_until_and_expired_entries() {
+        let mock_hosts = create_mock_hosts(
+            "0.0.0.0 old.com # Blocked by gwd until 1000\n\
+             0.0.0.0 www.old.com # Blocked by gwd until 1000\n\
+             0.0.0.0 new.com # Blocked by gwd until 4102444800\n\
+             0.0.0.0 plain.com # Blocked by gwd\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            lock_until("old.com", mock_hosts.path()).unwrap(),
+            Some(1000)
+        );
+        assert_eq!(
+            lock_until("https://new.com/", mock_hosts.path()).unwrap(),
+            Some(4_102_444_800)
+        );
+        assert_eq!(lock_until("plain.com", mock_hosts.path()).unwrap(), None);
+
+        let removed = remove_expired_entries_at(false, mock_hosts.path()).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(
+            list_blocked_domains_at(mock_hosts.path()).unwrap(),
+            vec!["new.com", "plain.com"]
+        );
+    }
+
+    #[test]
+    fn test_until_reboot_entries_go_after_a_reboot() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+        let options = BlockOptions {
+            until_reboot: true,
+            label: Some("evening".to_string()),
+            prefixes: Vec::new(),
+            verbosity: Verbosity::Quiet,
+            ..BlockOptions::default()
+        };
+        let report =
+            block_websites_at(&["now.com".to_string()], &options, mock_hosts.path()).unwrap();
+        assert!(report.entries[0].contains("# Blocked by gwd [evening] until-reboot @"));
+        let json = BlockOptions {
+            comment_style: CommentStyle::Json,
+            ..options
+        };
+        let report = block_websites_at(&["now.org".to_string()], &json, mock_hosts.path()).unwrap();
+        assert!(report.entries[0].contains("\"until_reboot\":true"));
+        let entries = list_blocked_entries_at(mock_hosts.path()).unwrap();
+        assert!(entries.iter().all(|entry| entry.until_reboot));
+
+        // Blocked at 1_000, the system booted at 2_000 or 500
+        let entry =
+            parse_gwd_entry("0.0.0.0 old.com # Blocked by gwd until-reboot @1970-01-01T00:16:40Z")
+                .unwrap();
+        assert!(entry.until_reboot);
+        assert_eq!(entry.blocked_at, Some(1_000));
+        assert!(has_expired(&entry, 3_000, || Some(2_000)));
+        assert!(!has_expired(&entry, 3_000, || Some(500)));
+        // Without a boot time the entry stays
+        assert!(!has_expired(&entry, 3_000, || None));
+        let plain =
+            parse_gwd_entry("0.0.0.0 plain.com # Blocked by gwd @1970-01-01T00:16:40Z").unwrap();
+        assert!(!has_expired(&plain, 3_000, || panic!(
+            "no boot time needed"
+        )));
+
+        // Blocked in 2000, long before this system booted
+        if boot_time().is_some() {
+            fs::OpenOptions::new()
+                .append(true)
+                .open(mock_hosts.path())
+                .unwrap()
+                .write_all(b"0.0.0.0 old.com # Blocked by gwd until-reboot @2000-01-01T00:00:00Z\n")
+                .unwrap();
+            assert_eq!(sweep_expired(mock_hosts.path()).unwrap().len(), 1);
+            assert_eq!(
+                list_blocked_domains_at(mock_hosts.path()).unwrap(),
+                vec!["now.com", "now.org"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_sweep_expired_only_writes_when_needed() {
+        let mock_hosts = create_mock_hosts(
+            "0.0.0.0 old.com # Blocked by gwd until 1000
+             0.0.0.0 new.com # Blocked by gwd until 4102444800
+",
+        )
+        .unwrap();
+        let lock_path = mock_hosts.path().with_extension("gwd.lock");
+
+        assert_eq!(
+            sweep_expired(mock_hosts.path()).unwrap(),
+            vec!["0.0.0.0 old.com # Blocked by gwd until 1000"]
+        );
+        assert!(lock_path.exists());
+        fs::remove_file(&lock_path).unwrap();
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+
+        // Nothing left to remove: no lock taken, file untouched
+        assert!(sweep_expired(mock_hosts.path()).unwrap().is_empty());
+        assert!(!lock_path.exists());
+        assert_eq!(fs::read_to_string(mock_hosts.path()).unwrap(), content);
+    }
+
+    #[test]
+    fn test_focus_session_end_leaves_earlier_blocks_under_its_label() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+        let options = BlockOptions {
+            label: Some("social".to_string()),
+            prefixes: Vec::new(),
+            ..Default::default()
+        };
+        // Blocked before the session, then once more by the session, which adds only y.com
+        let earlier =
+            block_websites_at(&["x.com".to_string()], &options, mock_hosts.path()).unwrap();
+        let session = block_websites_at(
+            &["x.com".to_string(), "y.com".to_string()],
+            &options,
+            mock_hosts.path(),
+        )
+        .unwrap();
+        assert_eq!(session.added, vec!["y.com"]);
+
+        let report =
+            end_focus_session_at(&session.entries, false, Verbosity::Quiet, mock_hosts.path())
+                .unwrap();
+        assert_eq!(report.domains, vec!["y.com"]);
+        assert_eq!(report.removed, session.entries);
+        let blocked = list_blocked_domains_at(mock_hosts.path()).unwrap();
+        assert_eq!(blocked, vec!["x.com"]);
+        assert!(fs::read_to_string(mock_hosts.path())
+            .unwrap()
+            .contains(&earlier.entries[0]));
+    }
+
+    #[test]
+    fn test_tidy_temp_files() {
+        let mock_hosts = create_mock_hosts("").unwrap();
+        let temp_path = temp_path_for(mock_hosts.path());
+        fs::write(&temp_path, "").unwrap();
+
+        assert_eq!(
+            tidy_temp_files_at(true, mock_hosts.path()).unwrap(),
+            vec![temp_path.clone()]
+        );
+        assert_eq!(
+            tidy_temp_files_at(false, mock_hosts.path()).unwrap(),
+            vec![temp_path.clone()]
+        );
+        assert!(!temp_path.exists());
+        assert!(tidy_temp_files_at(false, mock_hosts.path())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_malformed_gwd_lines() {
+        let mock_hosts = create_mock_hosts(
+            "127.0.0.1 localhost\n\
+             # === gwd START ===\n\
+             0.0.0.0 ok.com # Blocked by gwd\n\
+             not-an-ip bad.com # Blocked by gwd\n\
+             # Blocked by gwd\n\
+             # === gwd END ===\n\
+             # === gwd END ===\n",
+        )
+        .unwrap();
+
+        let problems = malformed_gwd_lines_at(mock_hosts.path()).unwrap();
+        let line_numbers: Vec<usize> = problems.iter().map(|(line, _)| *line).collect();
+        assert_eq!(line_numbers, vec![4, 5, 7]);
+
+        let healthy = create_mock_hosts("0.0.0.0 ok.com # Blocked by gwd\n").unwrap();
+        assert!(malformed_gwd_lines_at(healthy.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_remove_malformed_gwd_lines() {
+        let mock_hosts = create_mock_hosts(
+            "127.0.0.1 localhost\n\
+             # === gwd START ===\n\
+             0.0.0.0 ok.com # Blocked by gwd\n\
+             not-an-ip bad.com # Blocked by gwd\n\
+             # === gwd END ===\n\
+             0.0.0.0 # Blocked by gwd\n",
+        )
+        .unwrap();
+
+        let removed = remove_malformed_gwd_lines_at(false, mock_hosts.path()).unwrap();
+
+        assert_eq!(
+            removed,
+            vec![
+                "not-an-ip bad.com # Blocked by gwd".to_string(),
+                "0.0.0.0 # Blocked by gwd".to_string()
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            sealed("127.0.0.1 localhost\n# === gwd START ===\n0.0.0.0 ok.com # Blocked by gwd\n# === gwd END ===\n")
+        );
+        assert!(malformed_gwd_lines_at(mock_hosts.path())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_domain() {
+        assert_eq!(
+            validate_domain("https://Example.com/", false).unwrap(),
+            "example.com"
+        );
+        assert_eq!(
+            validate_domain("www.my-site.co.uk", false).unwrap(),
+            "www.my-site.co.uk"
+        );
+
+        for bad in [
+            "not a domain!!",
+            "localhost",
+            "a..com",
+            "under_score.com",
+            &format!("{}.com", "a".repeat(64)),
+        ] {
+            assert!(
+                matches!(validate_domain(bad, false), Err(AppError::InvalidDomain(_))),
+                "{} should be rejected",
+                bad
+            );
+        }
+
+        assert!(matches!(
+            validate_domain("192.168.1.10", false),
+            Err(AppError::InvalidDomain(_))
+        ));
+        assert_eq!(
+            validate_domain("192.168.1.10", true).unwrap(),
+            "192.168.1.10"
+        );
+    }
+
+    #[test]
+    fn test_block_websites_rejects_invalid_domain_without_writing() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+
+        let result = block_at(&["example.com", "not a domain!!"], mock_hosts.path());
+
+        assert!(matches!(result, Err(AppError::InvalidDomain(_))));
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            "127.0.0.1 localhost\n"
+        );
+    }
+
+    #[test]
+    fn test_block_refuses_protected_hostnames_without_force() {
+        let mock_hosts = create_mock_hosts("127.0.0.1 localhost\n").unwrap();
+        let options = BlockOptions {
+            protected: protected_hostnames(&["router.lan".to_string()]),
+            prefixes: Vec::new(),
+            verbosity: Verbosity::Quiet,
+            ..BlockOptions::default()
+        };
+        assert!(options.protected.contains(&"localhost".to_string()));
+        let block = |domain: &str, options: &BlockOptions| {
+            block_websites_at(&[domain.to_string()], options, mock_hosts.path())
+        };
+
+        for domain in ["localhost", "LOCALHOST.", "router.lan"] {
+            match block(domain, &options) {
+                Err(AppError::InvalidDomain(message)) => {
+                    assert!(message.contains("--force"), "{}", message)
+                }
+                result => panic!("{} was not refused: {:?}", domain, result.map(|_| ())),
+            }
+        }
+        assert_eq!(
+            fs::read_to_string(mock_hosts.path()).unwrap(),
+            "127.0.0.1 localhost\n"
+        );
+
+        let forced = BlockOptions {
+            force: true,
+            ..options.clone()
+        };
+        let report = block("localhost", &forced).unwrap();
+        assert_eq!(report.added, vec!["localhost"]);
+        assert!(is_blocked("localhost", mock_hosts.path()).unwrap());
+        // Anything else still has to look like a domain
+        assert!(block("intranet", &forced).is_err());
+    }
+
+    #[test]
+    fn test_read_domain_file() {
+        let list = create_mock_hosts(
+            "# My blocklist\n\
+             reddit.com\n\
+             \n\
+             https://YouTube.com  # videos\n\
+             http://\n\
+             reddit.com\n",
+        )
+        .unwrap();
+
+        let domain_file = read_domain_file(list.path(), false).unwrap();
+        assert_eq!(domain_file.domains, vec!["reddit.com", "youtube.com"]);
+        assert_eq!(domain_file.invalid, vec![(5, "http://".to_string())]);
+    }
+
+    #[test]
+    fn test_check_max_entries() {
+        assert!(check_max_entries(DEFAULT_MAX_ENTRIES, DEFAULT_MAX_ENTRIES).is_ok());
+        let error = check_max_entries(DEFAULT_MAX_ENTRIES + 1, DEFAULT_MAX_ENTRIES).unwrap_err();
+        assert!(matches!(
+            error,
+            AppError::TooManyEntries {
+                count: 5001,
+                max: 5000
+            }
+        ));
+        assert!(error
+            .to_string()
+            .starts_with("The list has 5001 domains, more than the cap of 5000"));
+        assert_eq!(error.exit_code(), 3);
+        // 0 turns the cap off
+        assert!(check_max_entries(1_000_000, 0).is_ok());
+    }
+
+    #[test]
+    fn test_run_flush_command_reports_missing_binary() {
+        let result = run_flush_command("gwd-no-such-flush-command", &[]);
+        assert!(matches!(result, Err(AppError::DnsFlush(msg)) if msg.contains("not found")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_custom_dns_flush_command_reports_exit_status() {
+        assert!(run_dns_flush_command("true").is_ok());
+        let result = run_dns_flush_command("echo busy >&2; exit 3");
+        assert!(matches!(result, Err(AppError::DnsFlush(msg))
+            if msg.contains("exit status 3") && msg.ends_with(": busy")));
+    }
+
+    #[test]
+    fn test_block_and_unblock_reports() {
+        let mock_hosts = create_mock_hosts("0.0.0.0 example.com # Blocked by gwd\n").unwrap();
+        let options = BlockOptions {
+            verbosity: Verbosity::Quiet,
+            ..Default::default()
+        };
+
+        let report = block_websites_at(
+            &["https://Example.com/".to_string()],
+            &options,
+            mock_hosts.path(),
+        )
+        .unwrap();
+        assert_eq!(
+            report,
+            BlockReport {
+                domains: vec!["example.com".to_string()],
+                added: vec!["www.example.com".to_string()],
+                already_present: vec!["example.com".to_string()],
+                foreign: Vec::new(),
+                entries: vec!["0.0.0.0 www.example.com # Blocked by gwd".to_string()],
+            }
+        );
+
+        let report = unblock_website_at("example.com", &no_challenge(), mock_hosts.path()).unwrap();
+        assert_eq!(report.domains, vec!["example.com"]);
+        assert_eq!(
+            report.removed,
+            vec![
+                "0.0.0.0 example.com # Blocked by gwd",
+                "0.0.0.0 www.example.com # Blocked by gwd"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_block_websites_with_label_and_lock() {
+        let mock_hosts = create_mock_hosts("").unwrap();
+        let options = BlockOptions {
+            label: Some("social".to_string()),
+            lock_until: Some(4_102_444_800),
+            ..Default::default()
+        };
+
+        block_websites_at(&["reddit.com".to_string()], &options, mock_hosts.path()).unwrap();
+
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        assert!(content.contains("0.0.0.0 reddit.com # Blocked by gwd [social] until 4102444800\n"));
+        assert_eq!(
+            list_blocked_entries_at(mock_hosts.path()).unwrap(),
+            vec![BlockedEntry {
+                domain: "reddit.com".to_string(),
+                label: Some("social".to_string()),
+                until: Some(4_102_444_800),
+                until_reboot: false,
+                blocked_at: None,
+                reason: None,
+                soft: false,
+            }]
+        );
+        // The lock is still found with a label in front of it
+        assert!(matches!(
+            unblock_website_at("reddit.com", &no_challenge(), mock_hosts.path()),
+            Err(AppError::LockActive { .. })
+        ));
+    }
+
+    #[test]
+    fn test_blocked_at_is_recorded_once() {
+        let mock_hosts = create_mock_hosts("0.0.0.0 old.com # Blocked by gwd\n").unwrap();
+        let options = BlockOptions {
+            label: Some("social".to_string()),
+            blocked_at: Some(1_705_311_000),
+            ..Default::default()
+        };
+
+        block_websites_at(&["a.com".to_string()], &options, mock_hosts.path()).unwrap();
+        // Blocking again later must not add a second, differently timestamped line
+        let later = BlockOptions {
+            blocked_at: Some(1_705_400_000),
+            ..options
+        };
+        let report = block_websites_at(&["a.com".to_string()], &later, mock_hosts.path()).unwrap();
+
+        assert!(report.added.is_empty());
+        let content = fs::read_to_string(mock_hosts.path()).unwrap();
+        assert!(content.contains("0.0.0.0 a.com # Blocked by gwd [social] @2024-01-15T09:30:00Z\n"));
+        let entries = list_blocked_entries_at(mock_hosts.path()).unwrap();
+        assert_eq!(entries[0].blocked_at, None); // legacy entry without a timestamp
+        assert_eq!(entries[1].blocked_at, Some(1_705_311_000));
+        assert_eq!(entries[1].label.as_deref(), Some("social"));
+    }
+
+    #[test]
+    fn test_unblock_websites_batch() {
+        let mock_hosts = create_mock_hosts(
+            "0.0.0.0 a.com # Blocked by gwd [social]\n\
+             0.0.0.0 www.a.com # Blocked by gwd [social]\n\
+             0.0.0.0 b.com # Blocked by gwd [social]\n\
+             0.0.0.0 c.com # Blocked by gwd\n",
+        )
+        .unwrap();
+
+        unblock_websites_at(
+            &["a.com".to_string(), "b.com".to_string()],
+            &no_challenge(),
+            mock_hosts.path(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            list_blocked_domains_at(mock_hosts.path()).unwrap(),
+            vec!["c.com"]
+        );
+    }
 }
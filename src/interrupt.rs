@@ -0,0 +1,87 @@
+use crate::error::Result;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// Set by the SIGINT handler while an InterruptGuard is installed
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+// Guards currently installed. Only the outermost one starts from a clean slate, so a nested
+// guard (a hosts file write during gwd watch) can't swallow a Ctrl-C the outer one hasn't seen.
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(test)]
+thread_local! {
+    // Lets a test act as if Ctrl-C had been pressed, without sending a signal to the whole
+    // test binary
+    static SIMULATED: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+// Catches Ctrl-C for the span of an operation that must not be cut off halfway, such as
+// replacing the hosts file: instead of ending gwd with a temp file and the lock left behind, the
+// keypress is recorded and the operation checks interrupted() to roll itself back and return
+// AppError::Interrupted. Dropping the guard puts back the previous handling, so anywhere else
+// Ctrl-C ends gwd as usual. (The hidden challenge needs no guard: it reads Ctrl-C as a key.)
+pub struct InterruptGuard {
+    #[cfg(unix)]
+    previous: nix::sys::signal::SigAction,
+}
+
+impl InterruptGuard {
+    #[cfg(unix)]
+    pub fn install() -> Result<InterruptGuard> {
+        use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+
+        extern "C" fn on_interrupt(_: nix::libc::c_int) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        }
+
+        if DEPTH.load(Ordering::SeqCst) == 0 {
+            INTERRUPTED.store(false, Ordering::SeqCst);
+        }
+        // SA_RESTART keeps the file operations in progress from failing with EINTR
+        let action = SigAction::new(
+            SigHandler::Handler(on_interrupt),
+            SaFlags::SA_RESTART,
+            SigSet::empty(),
+        );
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+        let previous = unsafe { sigaction(Signal::SIGINT, &action) }.map_err(|e| {
+            crate::error::AppError::Io(format!("Failed to install the Ctrl-C handler: {}", e))
+        })?;
+        DEPTH.fetch_add(1, Ordering::SeqCst);
+        Ok(InterruptGuard { previous })
+    }
+
+    #[cfg(not(unix))]
+    pub fn install() -> Result<InterruptGuard> {
+        DEPTH.fetch_add(1, Ordering::SeqCst);
+        Ok(InterruptGuard {})
+    }
+
+    // Whether Ctrl-C was pressed since the guard was installed
+    pub fn interrupted(&self) -> bool {
+        #[cfg(test)]
+        if SIMULATED.with(|simulated| simulated.get()) {
+            return true;
+        }
+        INTERRUPTED.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        DEPTH.fetch_sub(1, Ordering::SeqCst);
+        #[cfg(unix)]
+        {
+            use nix::sys::signal::{sigaction, Signal};
+            // SAFETY: restores the handler that was in place before install()
+            if let Err(e) = unsafe { sigaction(Signal::SIGINT, &self.previous) } {
+                eprintln!("Warning: could not restore the Ctrl-C handler: {}", e);
+            }
+        }
+    }
+}
+
+// Makes every InterruptGuard on the calling thread report a Ctrl-C until called with false
+#[cfg(test)]
+pub(crate) fn simulate_interrupt(interrupted: bool) {
+    SIMULATED.with(|simulated| simulated.set(interrupted));
+}
@@ -0,0 +1,62 @@
+// gwd's blocking logic as a library, so other programs (e.g. a GUI) can block and unblock
+// websites the way the `gwd` command does. The binary in main.rs parses the command line and
+// reports the results; what changes the hosts file lives here.
+//
+// The main entry points are re-exported below. Unblocking runs the terminal challenge by
+// default; set `ChallengeOptions::verifier` to supply your own check, or use
+// `ChallengeOptions::skip_challenge()` if you gate unblocking yourself.
+
+pub mod challenge;
+pub mod config;
+pub mod cooldown;
+pub mod diff;
+pub mod doctor;
+mod elevate;
+pub mod error;
+pub mod export;
+pub mod focus;
+pub mod focusmode;
+pub mod history;
+pub mod hooks;
+pub mod hosts;
+mod interrupt;
+mod logging;
+pub mod output;
+pub mod profile;
+mod prompt;
+pub mod remote;
+pub mod schedule;
+mod scheduler;
+pub mod stats;
+mod suggest;
+mod terminal;
+mod timing;
+mod watch;
+pub mod wildcard;
+
+pub use challenge::{ChallengeKind, ChallengeOptions, ChallengeVerifier};
+pub use error::{AppError, Result};
+pub use hosts::{
+    block_website, block_websites, canonicalize_domain, count_blocked, get_hosts_path, is_blocked,
+    list_blocked_domains, unblock_website, unblock_websites, BlockOptions, BlockReport,
+    UnblockOptions, UnblockReport,
+};
+
+// What the gwd binary needs from the internal modules above. It isn't part of the library's API.
+#[doc(hidden)]
+pub use elevate::relaunch_elevated;
+#[doc(hidden)]
+pub use logging::init_logging;
+#[doc(hidden)]
+pub use prompt::require_confirmation;
+#[doc(hidden)]
+pub use scheduler::{install_scheduler, uninstall_scheduler};
+#[doc(hidden)]
+pub use suggest::did_you_mean;
+#[doc(hidden)]
+pub use timing::{
+    boot_time, format_age, format_clock, format_duration, format_rfc3339, now_timestamp,
+    parse_duration, resolve_until,
+};
+#[doc(hidden)]
+pub use watch::watch_file;
@@ -0,0 +1,72 @@
+use crate::error::{AppError, Result};
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing_subscriber::EnvFilter;
+
+// Standard variable for tracing filter directives, e.g. RUST_LOG=gwd=trace
+pub const LOG_ENV_VAR: &str = "RUST_LOG";
+
+// Without RUST_LOG or --log-file only warnings are shown, which gwd rarely has
+const DEFAULT_FILTER: &str = "warn";
+// --log-file without RUST_LOG: everything gwd itself records, warnings from its dependencies
+const LOG_FILE_FILTER: &str = "warn,gwd=debug";
+
+// The filter directives for a run: RUST_LOG when set, otherwise the default for where the log goes
+pub fn log_filter(rust_log: Option<&str>, to_file: bool) -> String {
+    match rust_log
+        .map(str::trim)
+        .filter(|directives| !directives.is_empty())
+    {
+        Some(directives) => directives.to_string(),
+        None if to_file => LOG_FILE_FILTER.to_string(),
+        None => DEFAULT_FILTER.to_string(),
+    }
+}
+
+// Starts the debug log: gwd's tracing events go to `log_file` (appended to) or else to stderr,
+// filtered by log_filter. Stdout, and with it everything gwd normally prints, is never touched.
+pub fn init_logging(log_file: Option<&Path>) -> Result<()> {
+    let rust_log = std::env::var(LOG_ENV_VAR).ok();
+    let directives = log_filter(rust_log.as_deref(), log_file.is_some());
+    let filter = EnvFilter::try_new(&directives).map_err(|e| {
+        AppError::Config(format!("Invalid {} {:?}: {}", LOG_ENV_VAR, directives, e))
+    })?;
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    let installed = match log_file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| {
+                    AppError::Io(format!("Failed to open the log file {:?}: {}", path, e))
+                })?;
+            builder
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+                .try_init()
+        }
+        None => builder
+            .with_ansi(io::stderr().is_terminal())
+            .with_writer(io::stderr)
+            .try_init(),
+    };
+    installed.map_err(|e| AppError::Io(format!("Failed to start the debug log: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_filter() {
+        assert_eq!(log_filter(None, false), "warn");
+        assert_eq!(log_filter(None, true), "warn,gwd=debug");
+        assert_eq!(log_filter(Some(" "), true), "warn,gwd=debug");
+        assert_eq!(log_filter(Some("gwd=trace"), false), "gwd=trace");
+        assert_eq!(log_filter(Some("gwd=trace"), true), "gwd=trace");
+        assert!(EnvFilter::try_new(log_filter(None, true)).is_ok());
+    }
+}
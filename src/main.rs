@@ -1,105 +1,3069 @@
-mod challenge;
-mod error;
-mod hosts;
-
-use clap::Parser;
-use error::{AppError, Result};
-use hosts::{block_website, get_hosts_path, unblock_website}; // Import necessary functions
+use chrono::{Local, TimeZone};
+use clap::{CommandFactory, Parser};
+use gwd::challenge::{
+    force_allowed, load_wordlist, ChallengeKind, ChallengeLengths, ChallengeOptions,
+    CommitmentPhrase, ALLOW_FORCE_ENV_VAR, DEFAULT_CHALLENGE_LENGTH,
+};
+use gwd::config::{config_path, load_config, Config};
+use gwd::cooldown::{cooldown_path, CooldownSettings};
+use gwd::diff::{preview_change, Preview};
+use gwd::doctor::{collect_info, run_diagnostics, EnvironmentInfo};
+use gwd::error::{AppError, Result};
+use gwd::export::{parse_import, render_export, ExportFormat, Import};
+use gwd::focus::{
+    active_sessions_at, end_session_at, ended_sessions_at, focus_path, start_session_at,
+};
+use gwd::focusmode::{focus_mode_domains, FOCUS_MODE_LABEL};
+use gwd::history::{
+    all_records, append_record, build_log, drop_last_record, last_record, HistoryAction,
+    HistoryRecord, LogEntry, DEFAULT_HISTORY_LIMIT,
+};
+use gwd::hooks::{run_hook, HookEvent};
+use gwd::hosts::{
+    block_state_in, block_websites, block_websites_at, check_max_entries, check_permissions,
+    count_blocked, end_focus_session_at, flush_dns, flush_dns_hint, get_hosts_path,
+    gwd_entry_count_at, harden_websites, hosts_path_source, list_blocked_entries_at,
+    list_foreign_blocks_in, list_paused_at, list_section_entries_at, list_section_entries_in,
+    lock_until_in, malformed_gwd_lines_at, move_websites, normalize_hosts, normalize_hosts_at,
+    parse_redirect_ip, pause_blocks_at, protected_hostnames, read_domain_file,
+    remove_expired_entries_at, remove_malformed_gwd_lines_at, reset_cooling, resume_blocks_at,
+    run_dns_flush_command, set_hosts_path_override, set_strict_checksum, sinkhole_ips,
+    sweep_expired, sweep_paused, tidy_temp_files_at, unblock_all_websites, unblock_all_websites_at,
+    unblock_label, unblock_label_at, unblock_section, unblock_section_at, unblock_website,
+    unblock_website_at, unblock_websites, undo_block_at, undo_unblock_at, BatchSummary,
+    BlockOptions, BlockReport, BlockState, BlockedEntry, CommentStyle, DomainFile, HostsPathSource,
+    UnblockOptions, DEFAULT_MAX_ENTRIES, DEFAULT_REDIRECT_IP, HOSTS_PATH_ENV_VAR,
+}; // Import necessary functions
+use gwd::output::{
+    paint, paint_stderr, set_color_choice, ColorChoice, Tone, Verbosity, PROGRESS_THRESHOLD,
+};
+use gwd::profile::{load_profiles, profile_domains, save_profiles, validate_profile_name};
+use gwd::remote::{cached_list, fetch_list};
+use gwd::schedule::{load_schedule, plan_schedule};
+use gwd::stats::{build_stats, Stats};
+use gwd::wildcard::{expand_pattern, is_pattern, load_known_hostnames, pattern_label};
+use gwd::{
+    boot_time, did_you_mean, format_age, format_clock, format_duration, format_rfc3339,
+    init_logging, install_scheduler, now_timestamp, parse_duration, relaunch_elevated,
+    require_confirmation, resolve_until, uninstall_scheduler, watch_file,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 /// Get Work Done (gwd) - A command line tool to block/unblock websites.
-#[derive(Parser, Debug)]
+#[derive(Parser, Clone, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    // Without a subcommand, gwd shows an interactive menu (see run_menu)
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
+
+    /// Print the changes that would be made to the hosts file without writing anything.
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Print a single JSON object with the result (or error) instead of human-readable text.
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Don't flush the DNS cache after changing the hosts file; just print how to do it (also the default with flush_dns = false in config.toml).
+    #[arg(long, global = true)]
+    no_flush: bool,
+
+    /// Only print errors (and the data asked for, e.g. by list).
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Also print the hosts file path, the regexes used and each line examined.
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// When to colour the output. --json output is never coloured.
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// The hosts file to edit. Takes precedence over the GWD_HOSTS_PATH environment variable, which takes precedence over the OS default (/etc/hosts, or C:\Windows\System32\drivers\etc\hosts on Windows). Its directory must already exist.
+    #[arg(long, global = true, value_name = "PATH")]
+    hosts_path: Option<PathBuf>,
+
+    /// If gwd lacks the rights to edit the hosts file, run the command again through sudo (Unix) or a UAC prompt (Windows).
+    #[arg(long, global = true)]
+    elevate: bool,
+
+    /// Don't ask before changes that rewrite many entries at once (unblock --all, normalize, verify --remove). Without it, those commands refuse to run when stdin isn't a terminal.
+    #[arg(short, long, global = true)]
+    yes: bool,
+
+    /// Refuse to change the hosts file when gwd's section was edited outside gwd, instead of only warning. 'gwd normalize' accepts the edit.
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Don't remove blocks whose --for/--until lock has run out before running the command. Without it, every gwd command removes them first, so no background job is needed.
+    #[arg(long, global = true)]
+    no_sweep: bool,
+
+    /// Append a debug log of what gwd does (files read and written, commands run, hooks) to this file. RUST_LOG sets what it records, and without --log-file sends the log to stderr; by default only warnings are shown.
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    // Set on the relaunched process, so it never tries to elevate again
+    #[arg(long, global = true, hide = true)]
+    elevated: bool,
 }
 
-#[derive(Parser, Debug)]
+impl Args {
+    // --json prints its own result, so it silences progress messages like --quiet does
+    fn verbosity(&self) -> Verbosity {
+        if self.quiet || self.json {
+            Verbosity::Quiet
+        } else if self.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
+}
+
+// The unblock challenge flags shared by every command that removes entries
+#[derive(clap::Args, Clone, Debug)]
+struct ChallengeArgs {
+    /// Number of random words required for the unblock challenge (default: challenge_length from config.toml, or 5). Set to 0 to disable.
+    #[arg(long, value_parser = clap::value_parser!(u16).range(0..))]
+    challenge_length: Option<u16>,
+
+    /// Kind of challenge to pass before unblocking. Longer math challenges use harder problems.
+    #[arg(long, value_enum, default_value_t = ChallengeKind::Words)]
+    challenge_type: ChallengeKind,
+
+    /// Take the challenge words from this file (one word or phrase per line) instead of the built-in list.
+    #[arg(long, value_name = "FILE")]
+    wordlist: Option<PathBuf>,
+
+    /// Fail the challenge unless it is completed within this many seconds.
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    challenge_timeout: Option<u64>,
+
+    /// Show the challenge words one at a time, each only briefly, and don't echo what you type, so the answer can't just be copied off the screen.
+    #[arg(long)]
+    challenge_hidden: bool,
+
+    /// How many tries the challenge allows before it fails.
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u16).range(1..))]
+    challenge_attempts: u16,
+
+    /// How long unblocking is refused after all tries fail (e.g. 5m, 1h). Doubles on every repeat, up to a day.
+    #[arg(long, value_name = "DURATION", default_value = "5m")]
+    cooldown: String,
+}
+
+impl ChallengeArgs {
+    // Prompts go to stderr when stdout is reserved for --json or silenced by --quiet
+    fn options(&self, verbosity: Verbosity, config: &Config) -> Result<ChallengeOptions> {
+        let length = self
+            .challenge_length
+            .or(config.challenge_length)
+            .map_or(DEFAULT_CHALLENGE_LENGTH, usize::from);
+        // Per-domain and per-label lengths from config.toml apply unless the flag is given
+        let lengths = if self.challenge_length.is_some() {
+            ChallengeLengths::default()
+        } else {
+            ChallengeLengths::from_config(config)
+        };
+        let words = match &self.wordlist {
+            Some(path) if self.challenge_type == ChallengeKind::Words => Some(load_wordlist(
+                path,
+                lengths
+                    .longest()
+                    .map_or(length, |longest| longest.max(length)),
+            )?),
+            _ => None,
+        };
+        Ok(ChallengeOptions {
+            kind: self.challenge_type,
+            length,
+            prompt_on_stderr: verbosity.is_quiet(),
+            words,
+            timeout: self.challenge_timeout.map(Duration::from_secs),
+            hidden: self.challenge_hidden,
+            attempts: self.challenge_attempts as usize,
+            cooldown: Some(CooldownSettings {
+                path: cooldown_path()?,
+                base: parse_duration(&self.cooldown)?,
+            }),
+            confirmation_phrase: None,
+            commitment_phrase: CommitmentPhrase::from_config(config),
+            lengths,
+            verifier: None,
+        })
+    }
+}
+
+#[derive(Parser, Clone, Debug)]
 enum Commands {
-    /// Blocks a website by adding entries to the hosts file.
+    /// Blocks one or more websites by adding entries to the hosts file.
     Block {
-        /// The domain name to block (e.g., example.com). 'www.' is handled automatically.
-        #[arg(required = true)]
-        domain: String,
+        /// The domain names to block (e.g., example.com reddit.com). 'www.' is handled automatically. A pattern like '*.example.com' is expanded to the subdomains gwd knows about.
+        #[arg(required_unless_present_any = ["from_file", "list_url"], num_args = 1..)]
+        domains: Vec<String>,
+
+        /// Also block every domain listed in this file (one per line, '#' starts a comment).
+        #[arg(long, value_name = "FILE")]
+        from_file: Option<PathBuf>,
+
+        /// Also block every domain in the list downloaded from this URL (same format as --from-file). Needs the remote-lists feature.
+        #[arg(long, value_name = "URL")]
+        list_url: Option<String>,
+
+        /// Tag the entries with this label, so 'unblock --label' can remove them together.
+        #[arg(long, value_name = "LABEL", conflicts_with = "list_url")]
+        label: Option<String>,
+
+        /// IP address blocked domains are redirected to (e.g., 127.0.0.1 to serve a local page). Defaults to redirect_ip from config.toml, or 0.0.0.0.
+        #[arg(long, value_name = "IP")]
+        redirect: Option<String>,
+
+        /// Also write IPv6 sinkhole entries (::) so the sites can't be reached over IPv6.
+        #[arg(long)]
+        ipv6: bool,
+
+        /// Refuse to unblock before this local time (HH:MM, e.g. 17:00).
+        #[arg(long, value_name = "TIME", conflicts_with = "for_duration")]
+        until: Option<String>,
+
+        /// Refuse to unblock until this much time has passed (e.g. 30m, 2h, 1h30m). The block is removed by the first gwd command run after that.
+        #[arg(long = "for", visible_alias = "temporarily", value_name = "DURATION")]
+        for_duration: Option<String>,
+
+        /// Only block until the next reboot: the first gwd command run after the system restarts removes the entries. Where gwd can't tell when the system booted, the entries stay until unblocked.
+        #[arg(long, conflicts_with = "soft")]
+        until_reboot: bool,
+
+        /// Accept IP addresses as well as domain names.
+        #[arg(long)]
+        allow_ip: bool,
+
+        /// Only block the bare domain, without the 'www.' variant (also the default with add_www = false in config.toml).
+        #[arg(long)]
+        no_www: bool,
+
+        /// Block the 'www.' variant too, even with add_www = false in config.toml.
+        #[arg(long, conflicts_with = "no_www")]
+        www: bool,
+
+        /// Also block this subdomain variant (e.g. --also m --also app). Can be repeated.
+        #[arg(long = "also", value_name = "PREFIX")]
+        also: Vec<String>,
+
+        /// Add gwd's own entry even where a line gwd didn't write (e.g. from another blocklist) already blocks the site, and block names the machine relies on, such as localhost or its own hostname.
+        #[arg(long)]
+        force: bool,
+
+        /// Why you're blocking the sites (e.g. "too much doomscrolling"); unblock shows it again before the challenge.
+        #[arg(long, value_name = "TEXT")]
+        reason: Option<String>,
+
+        /// Only record a soft block: the entries are written commented out, so they don't take effect until 'gwd harden'.
+        #[arg(long, conflicts_with_all = ["until", "for_duration"])]
+        soft: bool,
+
+        /// Write the entries under this named sub-section of gwd's part of the hosts file (a '# gwd:<name>' header, added if missing), to keep a large file organized.
+        #[arg(long, visible_alias = "append-to-section", value_name = "NAME")]
+        section: Option<String>,
+
+        /// How the entries' comment records the label, lock, time and reason: 'plain' ('# Blocked by gwd [work] until ...') or 'json' ('# gwd {"label":"work",...}', for other tools to read). Defaults to comment_style in config.toml, else plain.
+        #[arg(long, value_enum, value_name = "STYLE")]
+        comment_style: Option<CommentStyle>,
+
+        /// Refuse to block more than this many domains at once (from --from-file, --list-url and the command line together), so a broken or hostile list can't bloat the hosts file. Defaults to max_entries in config.toml, else 5000; 0 means no cap.
+        #[arg(long, value_name = "N")]
+        max_entries: Option<usize>,
+    },
+    /// Turns soft blocks (block --soft) of the websites into real blocks, keeping their label and reason.
+    Harden {
+        /// The domain names whose soft blocks to harden (e.g., example.com). Their 'www.' and other variants are included.
+        #[arg(required = true, num_args = 1..)]
+        domains: Vec<String>,
+    },
+    /// Gives already blocked websites a new label, without unblocking them (so without a challenge). Their locks and reasons stay as they are.
+    Move {
+        /// The blocked domain names to relabel (e.g., example.com). Their 'www.' and other variants are included.
+        #[arg(required = true, num_args = 1..)]
+        domains: Vec<String>,
+
+        /// The label to give them.
+        #[arg(long, value_name = "LABEL")]
+        to: String,
     },
     /// Unblocks a website after a typing challenge.
     Unblock {
-        /// The domain name to unblock (e.g., example.com). 'www.' is handled automatically.
+        /// The domain name to unblock (e.g., example.com). 'www.' is handled automatically. A pattern like '*.example.com' unblocks everything it was expanded to.
+        #[arg(
+            required_unless_present_any = ["all", "from_file", "list_url", "label", "section"],
+            conflicts_with_all = ["all", "label"]
+        )]
+        domain: Option<String>,
+
+        /// Unblock every website blocked by gwd, with a single challenge.
+        #[arg(long)]
+        all: bool,
+
+        /// Unblock every domain listed in this file (one per line, '#' starts a comment), with a single challenge.
+        #[arg(long, value_name = "FILE", conflicts_with = "all")]
+        from_file: Option<PathBuf>,
+
+        /// Unblock every domain of a list blocked with block --list-url, using the copy saved when it was blocked.
+        #[arg(long, value_name = "URL", conflicts_with_all = ["all", "from_file"])]
+        list_url: Option<String>,
+
+        /// Unblock everything blocked with this label (block --label, or a profile), with a single challenge.
+        #[arg(long, value_name = "LABEL", conflicts_with_all = ["all", "from_file", "list_url"])]
+        label: Option<String>,
+
+        /// Only remove entries from this sub-section (block --section). Without a domain, unblocks everything in it, with a single challenge.
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["all", "label"])]
+        section: Option<String>,
+
+        /// Also remove 0.0.0.0/:: sinkhole lines gwd didn't write (added by hand or from another blocklist) for the domains, anywhere in the hosts file. Without it, only gwd's own entries are removed.
+        #[arg(long, conflicts_with_all = ["all", "label", "section"])]
+        include_foreign: bool,
+
+        /// For when a site is needed urgently: a much longer challenge that --challenge-length can't shorten, followed by a typed confirmation sentence. Also the only way to unblock during a no_unblock period from config.toml.
+        #[arg(long)]
+        emergency: bool,
+
+        /// Skip the challenge whatever its length, for scripts. Only honored when stdin isn't a terminal or GWD_ALLOW_FORCE=1 is set. Timed locks still apply.
+        #[arg(long, conflicts_with = "emergency")]
+        force: bool,
+
+        #[command(flatten)]
+        challenge: ChallengeArgs,
+    },
+    /// Turns every gwd block off for a while (e.g. --for 10m) after the unblock challenge. The entries are commented out rather than removed, and come back when the time is up (with the next gwd command) or with 'gwd resume'. Timed locks still apply.
+    Pause {
+        /// How long to pause for (e.g. 10m, 1h).
+        #[arg(long = "for", value_name = "DURATION")]
+        for_duration: String,
+
+        #[command(flatten)]
+        challenge: ChallengeArgs,
+    },
+    /// Turns blocks paused with 'gwd pause' back on now. Needs no challenge.
+    Resume,
+    /// Reports whether gwd currently blocks a website. Exits with 0 if blocked, 1 if not.
+    Status {
+        /// The domain name to check (e.g., example.com). 'www.' is handled automatically.
         #[arg(required = true)]
         domain: String,
+    },
+    /// Lists all websites currently blocked by gwd.
+    List {
+        /// Only print the number of blocked websites.
+        #[arg(long)]
+        count: bool,
+
+        /// Group the blocked websites by the profile they were blocked with.
+        #[arg(long, conflicts_with = "count")]
+        by_profile: bool,
+
+        /// Only list websites blocked with this label (block --label, or a profile).
+        #[arg(long, value_name = "LABEL", conflicts_with = "by_profile")]
+        label: Option<String>,
+
+        /// Only list websites in this sub-section (block --section).
+        #[arg(long, value_name = "NAME")]
+        section: Option<String>,
+
+        /// Also show why each website was blocked (block --reason).
+        #[arg(long, conflicts_with = "count")]
+        reasons: bool,
 
-        /// Number of random words required for the unblock challenge. Set to 0 to disable.
-        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u16).range(0..))]
-        // Allow 0
-        challenge_length: u16,
+        /// Also list websites blocked by hosts lines gwd didn't write: ones sending them to 0.0.0.0, :: or an address in sinkhole_ips from config.toml.
+        #[arg(long, conflicts_with_all = ["count", "by_profile", "label", "section"])]
+        foreign: bool,
+    },
+    /// Writes every website gwd blocks, with its label and lock, so import can recreate the blocks elsewhere. Changes nothing.
+    Export {
+        /// toml keeps labels and --until/--for locks; list is one domain per line, like a --from-file blocklist. hosts, dnsmasq and unbound write configuration for other blockers (with the www. variants and labels as comments), which import can't read back.
+        #[arg(long, value_enum, default_value_t = ExportFormat::Toml)]
+        format: ExportFormat,
+
+        /// Write to this file instead of stdout.
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Blocks every website in a file written by export (either format), skipping those already blocked.
+    Import {
+        /// The file to read.
+        file: PathBuf,
+
+        /// Refuse to import more than this many domains. Defaults to max_entries in config.toml, else 5000; 0 means no cap.
+        #[arg(long, value_name = "N")]
+        max_entries: Option<usize>,
+    },
+    /// Shows what a block, unblock, normalize or import would change in the hosts file, as a unified diff, without writing anything (or asking for the challenge).
+    Diff {
+        #[command(subcommand)]
+        change: DiffChange,
+    },
+    /// Forgets a website's recent unblocks, which make each further unblock's challenge longer.
+    ResetCooling {
+        /// The domain name (e.g., example.com).
+        domain: String,
+    },
+    /// Saves a named list of websites (a profile) for use with block-profile.
+    SaveProfile {
+        /// The profile name (letters, digits, '-' and '_').
+        name: String,
+
+        /// The domain names in the profile. Replaces any existing list with the same name.
+        #[arg(required = true, num_args = 1..)]
+        domains: Vec<String>,
+    },
+    /// Blocks every website in a saved profile.
+    BlockProfile {
+        /// The profile name (e.g., social).
+        name: String,
+
+        /// IP address blocked domains are redirected to (e.g., 127.0.0.1 to serve a local page). Defaults to redirect_ip from config.toml, or 0.0.0.0.
+        #[arg(long, value_name = "IP")]
+        redirect: Option<String>,
+
+        /// Also write IPv6 sinkhole entries (::) so the sites can't be reached over IPv6.
+        #[arg(long)]
+        ipv6: bool,
+
+        /// Refuse to unblock before this local time (HH:MM, e.g. 17:00).
+        #[arg(long, value_name = "TIME", conflicts_with = "for_duration")]
+        until: Option<String>,
+
+        /// Refuse to unblock until this much time has passed (e.g. 30m, 2h, 1h30m).
+        #[arg(long = "for", value_name = "DURATION")]
+        for_duration: Option<String>,
+    },
+    /// Unblocks every website in a saved profile after a single challenge.
+    UnblockProfile {
+        /// The profile name (e.g., social).
+        name: String,
+
+        #[command(flatten)]
+        challenge: ChallengeArgs,
+    },
+    /// Blocks a profile for a focus session (e.g. --for 25m) and unblocks it without a challenge when the session ends. Stopping early (Ctrl-C) leaves the sites blocked.
+    Focus {
+        /// The profile to block during the session (e.g., social).
+        #[arg(long)]
+        profile: String,
+
+        /// How long the session lasts (e.g. 25m, 1h).
+        #[arg(long = "for", value_name = "DURATION")]
+        for_duration: String,
+
+        /// Return right away and let apply-schedule (see schedule install) unblock the profile when the session ends.
+        #[arg(long)]
+        detach: bool,
+    },
+    /// Blocks a built-in list of distracting sites (social media, video, news, shopping, games) except the ones you allow. Like every gwd block this only changes how names resolve through the hosts file; it is not a firewall, and IP addresses, other domains or apps using their own DNS (e.g. DNS over HTTPS) still get through.
+    FocusMode {
+        #[command(subcommand)]
+        action: FocusModeAction,
+    },
+    /// Keeps a profile blocked: blocks it, then watches the hosts file and puts back any of its entries that get removed, until Ctrl-C. The sites stay blocked afterwards.
+    Watch {
+        /// The profile to keep blocked (e.g., social).
+        #[arg(long)]
+        profile: String,
+    },
+    /// Blocks or unblocks profiles to match schedule.toml. Meant to be run every minute by cron or a timer.
+    ApplySchedule,
+    /// Installs or removes the recurring job that runs apply-schedule every minute.
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleAction,
+    },
+    /// Reverts the last block or unblock. Undoing a block asks for the unblock challenge.
+    Undo {
+        #[command(flatten)]
+        challenge: ChallengeArgs,
+    },
+    /// Lists past blocks and unblocks, newest first, with how long each unblocked site had been blocked.
+    #[command(alias = "history")]
+    Log {
+        /// Only show actions from this far back (e.g. 7d, 12h).
+        #[arg(long, value_name = "DURATION")]
+        since: Option<String>,
+    },
+    /// Summarizes your blocking habits from the journal: how long each website was blocked, how often it was unblocked, the average block and the most unblocked websites. Never changes anything.
+    Stats {
+        /// Only count the time from this far back (e.g. 7d, 12h).
+        #[arg(long, value_name = "DURATION")]
+        since: Option<String>,
+    },
+    /// Checks for common setup problems and suggests fixes. Exits with 0 only if all checks pass.
+    Doctor,
+    /// Prints facts for bug reports: gwd's version, the OS, the hosts file in use and where that choice came from, the user and the number of gwd entries. Never changes anything.
+    Info,
+    /// Lists gwd-tagged lines that aren't valid entries. Exits with 1 if any are left.
+    Verify {
+        /// Delete the malformed lines from the hosts file.
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Removes blocks whose --until/--for lock has run out, and any temp file left by an interrupted write, next to the hosts file or in the system temp directory.
+    Tidy,
+    /// Removes duplicate gwd entries and rewrites the rest in a consistent format.
+    Normalize,
+    /// Shows where gwd reads its settings from.
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Prints a shell completion script (e.g. gwd completions zsh > ~/.zfunc/_gwd).
+    Completions {
+        /// The shell to generate completions for.
+        shell: clap_complete::Shell,
     },
 }
 
-fn check_permissions() -> Result<()> {
-    let hosts_path = get_hosts_path()?; // Get the path once
-    #[cfg(windows)]
-    {
-        // Basic check: Can we open the hosts file for writing?
-        // A more robust check involves checking the user's token, but this is simpler.
-        match std::fs::OpenOptions::new()
-            .write(true)
-            .open(&hosts_path) // Use the fetched path
-        {
-            Ok(_) => Ok(()),
-            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
-                Err(AppError::PermissionDenied(hosts_path)) // Pass the path
+#[derive(clap::Subcommand, Clone, Debug)]
+enum ScheduleAction {
+    /// Writes and enables a systemd timer (Linux), launchd job (macOS) or scheduled task (Windows).
+    Install,
+    /// Disables and removes the job written by install.
+    Uninstall,
+}
+
+#[derive(clap::Subcommand, Clone, Debug)]
+enum FocusModeAction {
+    /// Blocks the built-in distraction list, tagged [focusmode].
+    On {
+        /// Keep this domain (and its subdomains) reachable. Can be repeated.
+        #[arg(long, value_name = "DOMAIN")]
+        allow: Vec<String>,
+    },
+    /// Removes everything focus-mode on blocked, after the usual challenge.
+    Off {
+        #[command(flatten)]
+        challenge: ChallengeArgs,
+    },
+}
+
+// The changes gwd diff can preview. Their flags mirror those of the commands themselves.
+#[derive(clap::Subcommand, Clone, Debug)]
+enum DiffChange {
+    /// Preview blocking websites.
+    Block {
+        /// The domain names to block (e.g., example.com).
+        #[arg(required_unless_present = "from_file", num_args = 1..)]
+        domains: Vec<String>,
+
+        /// Also block every domain listed in this file (one per line, '#' starts a comment).
+        #[arg(long, value_name = "FILE")]
+        from_file: Option<PathBuf>,
+
+        /// Tag the entries with this label.
+        #[arg(long, value_name = "LABEL")]
+        label: Option<String>,
+
+        /// IP address blocked domains are redirected to. Defaults to redirect_ip from config.toml, or 0.0.0.0.
+        #[arg(long, value_name = "IP")]
+        redirect: Option<String>,
+
+        /// Also write IPv6 sinkhole entries (::).
+        #[arg(long)]
+        ipv6: bool,
+
+        /// Refuse to unblock before this local time (HH:MM, e.g. 17:00).
+        #[arg(long, value_name = "TIME", conflicts_with = "for_duration")]
+        until: Option<String>,
+
+        /// Refuse to unblock until this much time has passed (e.g. 30m, 2h, 1h30m).
+        #[arg(long = "for", value_name = "DURATION")]
+        for_duration: Option<String>,
+
+        /// Only block the bare domain, without the 'www.' variant.
+        #[arg(long)]
+        no_www: bool,
+
+        /// Block the 'www.' variant too, even with add_www = false in config.toml.
+        #[arg(long, conflicts_with = "no_www")]
+        www: bool,
+
+        /// Also block this subdomain variant (e.g. --also m). Can be repeated.
+        #[arg(long = "also", value_name = "PREFIX")]
+        also: Vec<String>,
+
+        /// Add gwd's own entry even where a line gwd didn't write already blocks the site, and block names the machine relies on.
+        #[arg(long)]
+        force: bool,
+
+        /// Only record a soft block, written commented out.
+        #[arg(long, conflicts_with_all = ["until", "for_duration"])]
+        soft: bool,
+
+        /// Write the entries under this named sub-section of gwd's part of the hosts file.
+        #[arg(long, value_name = "NAME")]
+        section: Option<String>,
+
+        /// How the entries' comment records the label, lock, time and reason.
+        #[arg(long, value_enum, value_name = "STYLE")]
+        comment_style: Option<CommentStyle>,
+
+        /// Refuse to block more than this many domains at once (0 means no cap).
+        #[arg(long, value_name = "N")]
+        max_entries: Option<usize>,
+    },
+    /// Preview unblocking websites. Timed locks still apply.
+    Unblock {
+        /// The domain name to unblock (e.g., example.com).
+        #[arg(
+            required_unless_present_any = ["all", "label", "section"],
+            conflicts_with_all = ["all", "label"]
+        )]
+        domain: Option<String>,
+
+        /// Unblock every website blocked by gwd.
+        #[arg(long)]
+        all: bool,
+
+        /// Unblock everything blocked with this label.
+        #[arg(long, value_name = "LABEL", conflicts_with = "all")]
+        label: Option<String>,
+
+        /// Only remove entries from this sub-section. Without a domain, unblocks everything in it.
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["all", "label"])]
+        section: Option<String>,
+    },
+    /// Preview normalize.
+    Normalize,
+    /// Preview importing a file written by export.
+    Import {
+        /// The file to read.
+        file: PathBuf,
+
+        /// Refuse to import more than this many domains (0 means no cap).
+        #[arg(long, value_name = "N")]
+        max_entries: Option<usize>,
+    },
+}
+
+#[derive(clap::Subcommand, Clone, Debug)]
+enum ConfigAction {
+    /// Prints the path of config.toml (it may not exist yet).
+    Path,
+}
+
+// Resolves --redirect/--until/--for into block options shared by block and block-profile,
+// falling back to config.toml for what the command line leaves out
+fn block_options(
+    redirect: Option<String>,
+    ipv6: bool,
+    until: Option<String>,
+    for_duration: Option<String>,
+    label: Option<String>,
+    dry_run: bool,
+    config: &Config,
+) -> Result<BlockOptions> {
+    let redirect_ip = match redirect.or_else(|| config.redirect_ip.clone()) {
+        Some(ip) => parse_redirect_ip(&ip)?,
+        None => DEFAULT_REDIRECT_IP,
+    };
+    let lock_until = match (until, for_duration) {
+        (Some(time), _) => Some(resolve_until(&time, Local::now())?.timestamp()),
+        (None, Some(duration)) => {
+            Some(now_timestamp() + parse_duration(&duration)?.as_secs() as i64)
+        }
+        (None, None) => None,
+    };
+    let mut options = BlockOptions {
+        redirect_ip,
+        ipv6,
+        lock_until,
+        label,
+        blocked_at: Some(now_timestamp()),
+        comment_style: config.comment_style.unwrap_or_default(),
+        protected: protected_hostnames(&config.protected_hostnames),
+        sinkhole_ips: sinkhole_ips(&config.sinkhole_ips),
+        dry_run,
+        ..Default::default()
+    };
+    if let Some(add_www) = config.add_www {
+        set_www(&mut options.prefixes, add_www);
+    }
+    Ok(options)
+}
+
+// Blocks whatever of a profile's domains isn't blocked already, under the profile's label, and
+// records what that added in the history. Shared by apply-schedule and watch, which both bring
+// the hosts file back in line with what should be blocked.
+fn reapply_profile(
+    name: &str,
+    domains: &[String],
+    dry_run: bool,
+    config: &Config,
+    verbosity: Verbosity,
+) -> Result<BlockReport> {
+    let mut options = block_options(
+        None,
+        false,
+        None,
+        None,
+        Some(name.to_string()),
+        dry_run,
+        config,
+    )?;
+    options.verbosity = verbosity;
+    let report = block_websites(domains, &options)?;
+    if !dry_run && !report.added.is_empty() {
+        record_history(
+            config,
+            HistoryAction::Block,
+            &report.domains,
+            &report.entries,
+        );
+    }
+    Ok(report)
+}
+
+// Adds or drops the 'www.' variant from the subdomain variants a block writes
+fn set_www(prefixes: &mut Vec<String>, add_www: bool) {
+    let has_www = prefixes.iter().any(|prefix| prefix == "www");
+    if add_www && !has_www {
+        prefixes.insert(0, "www".to_string());
+    } else if !add_www {
+        prefixes.retain(|prefix| prefix != "www");
+    }
+}
+
+// Prints one JSON object for --json: the fields of `result` plus `extra`,
+// tagged with the action that produced it
+fn print_json(action: &str, result: impl Serialize, extra: serde_json::Value) -> Result<()> {
+    let mut value = serde_json::to_value(result)
+        .map_err(|e| AppError::Io(format!("Failed to serialize JSON output: {}", e)))?;
+    if let Some(object) = value.as_object_mut() {
+        object.insert("action".to_string(), json!(action));
+        if let serde_json::Value::Object(extra) = extra {
+            object.extend(extra);
+        }
+    }
+    println!("{}", value);
+    Ok(())
+}
+
+// Makes a hosts file change take effect: flushes the DNS cache (a failure is only a warning,
+// since the hosts file is already updated) or, with --no-flush, says how to do it by hand.
+// `flush_command` is dns_flush_command from config.toml, which replaces the built-in commands.
+fn refresh_dns(no_flush: bool, flush_command: Option<&str>, verbosity: Verbosity) {
+    match (no_flush, flush_command) {
+        (true, Some(command)) => verbosity.info(format_args!(
+            "Run '{}' if the change doesn't take effect immediately.",
+            command
+        )),
+        (true, None) => verbosity.info(flush_dns_hint()),
+        (false, Some(command)) => match run_dns_flush_command(command) {
+            Ok(()) => verbosity.info("Flushed the DNS cache."),
+            Err(e) => eprintln!("Warning: {}", e),
+        },
+        (false, None) => refresh_with_builtin_flush(verbosity),
+    }
+}
+
+fn refresh_with_builtin_flush(verbosity: Verbosity) {
+    match flush_dns() {
+        Ok(true) => verbosity.info("Flushed the DNS cache."),
+        Ok(false) => {}
+        Err(e) => eprintln!("Warning: {}", e),
+    }
+}
+
+// Whether a command may change the hosts file, and so needs the rights to. Commands that only read
+// it (listing, verifying, exporting) work without sudo, and saving a profile or printing
+// completions doesn't touch it at all.
+fn needs_write_access(command: &Commands) -> bool {
+    !matches!(
+        command,
+        Commands::SaveProfile { .. }
+            | Commands::ResetCooling { .. }
+            | Commands::Config { .. }
+            | Commands::Completions { .. }
+            | Commands::Doctor
+            | Commands::Info
+            | Commands::Schedule { .. }
+            | Commands::Verify { remove: false }
+            | Commands::Export { .. }
+            | Commands::Log { .. }
+            | Commands::Stats { .. }
+            | Commands::Diff { .. }
+            | Commands::List { .. }
+            | Commands::Status { .. }
+    )
+}
+
+// Removes expired blocks before a command runs. It's best effort: without the rights to edit the
+// hosts file (e.g. `gwd list` without sudo) they stay until a command that has them. The removal
+// isn't recorded in the history, so `gwd undo` still undoes the user's own last change.
+fn sweep_expired_blocks(no_flush: bool, flush_command: Option<&str>, verbosity: Verbosity) {
+    // A pause that is over ends first, so a block that expired meanwhile goes too
+    let resumed = match get_hosts_path().and_then(|path| sweep_paused(&path)) {
+        Ok(resumed) => resumed,
+        Err(e) => {
+            verbosity.debug(format_args!("Could not resume paused blocks: {}", e));
+            Vec::new()
+        }
+    };
+    if !resumed.is_empty() {
+        verbosity.info(paint(
+            format_args!("The pause is over; resumed {} entries.", resumed.len()),
+            Tone::Added,
+        ));
+        refresh_dns(no_flush, flush_command, verbosity);
+    }
+    let removed = match get_hosts_path().and_then(|path| sweep_expired(&path)) {
+        Ok(removed) => removed,
+        Err(e) => {
+            verbosity.debug(format_args!("Could not remove expired blocks: {}", e));
+            return;
+        }
+    };
+    for line in &removed {
+        verbosity.info(paint(
+            format_args!("Removed expired block: {}", line),
+            Tone::Removed,
+        ));
+    }
+    if !removed.is_empty() {
+        refresh_dns(no_flush, flush_command, verbosity);
+    }
+}
+
+// Runs the change gwd diff asks about against a scratch copy of the hosts file (see
+// preview_change), quietly and without a challenge, history, hooks or DNS flush
+fn preview_diff(change: DiffChange, hosts_path: &Path, config: &Config) -> Result<Preview> {
+    let quiet = Verbosity::Quiet;
+    match change {
+        DiffChange::Block {
+            domains,
+            from_file,
+            label,
+            redirect,
+            ipv6,
+            until,
+            for_duration,
+            no_www,
+            www,
+            also,
+            force,
+            soft,
+            section,
+            comment_style,
+            max_entries,
+        } => {
+            let DomainFile { domains, .. } = collect_domains(domains, from_file, false)?;
+            check_max_entries(domains.len(), entry_cap(max_entries, config))?;
+            if let Some(label) = &label {
+                validate_profile_name(label)?;
+            }
+            let mut options =
+                block_options(redirect, ipv6, until, for_duration, label, false, config)?;
+            options.verbosity = quiet;
+            if no_www || www {
+                set_www(&mut options.prefixes, www);
+            }
+            options.prefixes.extend(also);
+            options.force = force;
+            options.soft = soft;
+            options.section = section;
+            if let Some(style) = comment_style {
+                options.comment_style = style;
             }
-            Err(e) => Err(AppError::Io(format!("Error checking permissions on {:?}: {}", hosts_path, e))), // Convert error to string
+            preview_change(hosts_path, |copy| {
+                block_websites_at(&domains, &options, copy)
+            })
+        }
+        DiffChange::Unblock {
+            domain,
+            all,
+            label,
+            section,
+        } => {
+            let options = UnblockOptions {
+                challenge: ChallengeOptions::skip_challenge(),
+                verbosity: quiet,
+                section: section.clone(),
+                ..Default::default()
+            };
+            preview_change(hosts_path, |copy| match (domain, label, section) {
+                (Some(domain), _, _) => unblock_website_at(&domain, &options, copy),
+                (None, Some(label), _) => unblock_label_at(&label, false, quiet, copy),
+                (None, None, Some(section)) => unblock_section_at(&section, &options, copy),
+                (None, None, None) => {
+                    debug_assert!(all);
+                    unblock_all_websites_at(&options, copy)
+                }
+            })
+        }
+        DiffChange::Normalize => {
+            preview_change(hosts_path, |copy| normalize_hosts_at(false, quiet, copy))
+        }
+        DiffChange::Import { file, max_entries } => {
+            let groups = import_groups(&read_import(&file)?)?;
+            check_max_entries(
+                groups.values().map(Vec::len).sum(),
+                entry_cap(max_entries, config),
+            )?;
+            preview_change(hosts_path, |copy| {
+                for (key, group) in groups {
+                    let mut options = import_options(key, false, config)?;
+                    options.verbosity = quiet;
+                    block_websites_at(&group, &options, copy)?;
+                }
+                Ok(())
+            })
         }
     }
-    #[cfg(unix)] // Add cfg attribute for Unix block
-    {
-        // Check if the effective user ID is root (0)
-        if !nix::unistd::Uid::effective().is_root() {
-            Err(AppError::PermissionDenied(hosts_path)) // Pass the path
+}
+
+// Blocks the plain domains in one pass over `hosts_path`, then each pattern's expansion. `before`
+// gets every domain first (and runs the pre-block hook); for more than `cap` of them, neither it
+// nor any write happens.
+fn block_all_at(
+    domains: &[String],
+    expansions: Vec<(String, Vec<String>)>,
+    cap: usize,
+    options: &BlockOptions,
+    hosts_path: &Path,
+    before: impl FnOnce(&[String]) -> Result<()>,
+) -> Result<BlockReport> {
+    let all_domains: Vec<String> = domains
+        .iter()
+        .chain(expansions.iter().flat_map(|(_, expanded)| expanded))
+        .cloned()
+        .collect();
+    check_max_entries(all_domains.len(), cap)?;
+    before(&all_domains)?;
+    let mut report = if domains.is_empty() {
+        BlockReport::default()
+    } else {
+        block_websites_at(domains, options, hosts_path)?
+    };
+    // Each pattern's hostnames share a label (--label wins) and get no www. variants,
+    // since the expansion already lists the subdomains to block
+    for (pattern_label, expanded) in expansions {
+        let mut pattern_options = options.clone();
+        pattern_options.label = options.label.clone().or(Some(pattern_label));
+        pattern_options.prefixes.clear();
+        report.merge(block_websites_at(&expanded, &pattern_options, hosts_path)?);
+    }
+    Ok(report)
+}
+
+// Blocks an import's groups (see import_groups) in `hosts_path`, each with the options
+// `options_for` makes from its key, and hands each group's report to `after_group`. `before` gets
+// every domain first (and runs the pre-block hook); for more than `cap` of them, neither it nor
+// any write happens.
+fn import_at(
+    groups: ImportGroups,
+    cap: usize,
+    options_for: impl Fn(ImportKey) -> Result<BlockOptions>,
+    hosts_path: &Path,
+    before: impl FnOnce(&[String]) -> Result<()>,
+    mut after_group: impl FnMut(&BlockReport),
+) -> Result<BlockReport> {
+    let domains: Vec<String> = groups.values().flatten().cloned().collect();
+    check_max_entries(domains.len(), cap)?;
+    before(&domains)?;
+    let mut report = BlockReport::default();
+    for (key, group) in groups {
+        let group_report = block_websites_at(&group, &options_for(key)?, hosts_path)?;
+        after_group(&group_report);
+        report.merge(group_report);
+    }
+    Ok(report)
+}
+
+// Reads an import file, warning about the lines of a list that aren't domains
+fn read_import(file: &Path) -> Result<Import> {
+    let content = fs::read_to_string(file)
+        .map_err(|e| AppError::Io(format!("Failed to read import file {:?}: {}", file, e)))?;
+    let import = parse_import(&content)?;
+    warn_invalid_lines(&file.display(), &import.invalid);
+    Ok(import)
+}
+
+// An import's blocks, grouped by what goes through block_websites together: blocks sharing a
+// label, lock, softness, reason and time they were blocked at. A lock that has run out in the
+// meantime isn't recreated.
+fn import_groups(import: &Import) -> Result<ImportGroups> {
+    let now = now_timestamp();
+    let mut groups = ImportGroups::new();
+    for block in &import.blocks {
+        if let Some(label) = &block.label {
+            if label != FOCUS_MODE_LABEL {
+                validate_profile_name(label)?;
+            }
+        }
+        let until = block.until.filter(|until| *until > now);
+        groups
+            .entry((
+                block.label.clone(),
+                until,
+                block.soft,
+                block.reason.clone(),
+                block.blocked_at,
+            ))
+            .or_default()
+            .push(block.domain.clone());
+    }
+    Ok(groups)
+}
+
+// The most domains one block or import may add: --max-entries, else max_entries in config.toml
+fn entry_cap(max_entries: Option<usize>, config: &Config) -> usize {
+    max_entries
+        .or(config.max_entries)
+        .unwrap_or(DEFAULT_MAX_ENTRIES)
+}
+
+// (label, lock, soft, reason, blocked at) -> domains
+type ImportKey = (
+    Option<String>,
+    Option<i64>,
+    bool,
+    Option<String>,
+    Option<i64>,
+);
+type ImportGroups = BTreeMap<ImportKey, Vec<String>>;
+
+// The options one group of an import (see import_groups) is blocked with
+fn import_options(key: ImportKey, dry_run: bool, config: &Config) -> Result<BlockOptions> {
+    let (label, until, soft, reason, blocked_at) = key;
+    let mut options = block_options(None, false, None, None, label, dry_run, config)?;
+    options.lock_until = until;
+    options.soft = soft;
+    options.reason = reason;
+    // Blocks exported without the time they were blocked at count as blocked now
+    options.blocked_at = blocked_at.or(options.blocked_at);
+    Ok(options)
+}
+
+// Adds the domains listed in --from-file to those given on the command line.
+// Invalid lines are reported with their line number and skipped; they're also returned for --json.
+fn collect_domains(
+    domains: Vec<String>,
+    from_file: Option<PathBuf>,
+    allow_ip: bool,
+) -> Result<DomainFile> {
+    let Some(path) = from_file else {
+        return Ok(DomainFile {
+            domains,
+            invalid: Vec::new(),
+        });
+    };
+    let mut domain_file = read_domain_file(&path, allow_ip)?;
+    warn_invalid_lines(&path.display(), &domain_file.invalid);
+    domain_file.domains.splice(0..0, domains);
+    if domain_file.domains.is_empty() {
+        return Err(AppError::InvalidDomain(format!(
+            "no valid domains found in {}",
+            path.display()
+        )));
+    }
+    Ok(domain_file)
+}
+
+// The closing line of a block, e.g. "Blocked 7, skipped 3 already present, 1 invalid."
+// The per-entry detail is only printed with --verbose.
+fn print_block_summary(summary: &BatchSummary, dry_run: bool, verbosity: Verbosity) {
+    let action = if dry_run { "Would block" } else { "Blocked" };
+    verbosity.info(summary.describe(action, "already present"));
+}
+
+// The closing line of an unblock, e.g. "Unblocked 4, skipped 1 not blocked."
+fn print_unblock_summary(summary: &BatchSummary, dry_run: bool, verbosity: Verbosity) {
+    let action = if dry_run {
+        "Would unblock"
+    } else {
+        "Unblocked"
+    };
+    verbosity.info(summary.describe(action, "not blocked"));
+}
+
+// Points out likely typos among the domains an unblock found nothing for, e.g. "Did you mean
+// 'example.com'?" for "exmaple.com". Only for people reading the output, so not with
+// --quiet or --json.
+fn suggest_blocked_domains(not_blocked: &[&str], verbosity: Verbosity) -> Result<()> {
+    if not_blocked.is_empty() || verbosity.is_quiet() {
+        return Ok(());
+    }
+    let blocked: Vec<String> = list_blocked_entries_at(&get_hosts_path()?)?
+        .into_iter()
+        .map(|entry| entry.domain)
+        .collect();
+    for domain in not_blocked {
+        if let Some(suggestion) = did_you_mean(domain, &blocked) {
+            verbosity.info(paint(
+                format_args!(
+                    "'{}' is not blocked. Did you mean '{}'?",
+                    domain, suggestion
+                ),
+                Tone::Note,
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Reports the lines of a domain file (or downloaded list) that were skipped
+fn warn_invalid_lines(source: &dyn std::fmt::Display, invalid: &[(usize, String)]) {
+    for (line_number, line) in invalid {
+        eprintln!(
+            "Warning: {}:{}: invalid domain '{}', skipping.",
+            source, line_number, line
+        );
+    }
+}
+
+// How status describes a gwd block: plain "blocked", or with the time its lock has left
+fn describe_block(until: Option<i64>, now: i64) -> String {
+    match until {
+        None => "blocked".to_string(),
+        Some(until) if until > now => format!(
+            "blocked, {} left",
+            format_duration(&Duration::from_secs((until - now) as u64))
+        ),
+        Some(_) => "block expired (run gwd tidy)".to_string(),
+    }
+}
+
+// One line of `list`: the domain, its label (with `with_label`), when it was blocked, its
+// lock's state and its --reason (with `with_reason`), as far as the entry records them
+fn describe_listed_entry(
+    entry: &BlockedEntry,
+    now: i64,
+    with_label: bool,
+    with_reason: bool,
+) -> String {
+    let name = match &entry.label {
+        Some(label) if with_label => format!("{} [{}]", entry.domain, label),
+        _ => entry.domain.clone(),
+    };
+    let mut details = Vec::new();
+    let verb = if entry.soft {
+        "soft-blocked"
+    } else {
+        "blocked"
+    };
+    match entry.blocked_at {
+        Some(blocked_at) => details.push(format!("{} {}", verb, format_age(now - blocked_at))),
+        None if entry.soft => details.push(verb.to_string()),
+        None => {}
+    }
+    if entry.until_reboot {
+        details.push("until reboot".to_string());
+    }
+    match entry.until {
+        None => {}
+        Some(until) if until > now => details.push(format!(
+            "{} left",
+            format_duration(&Duration::from_secs((until - now) as u64))
+        )),
+        Some(_) => details.push("block expired, run gwd tidy".to_string()),
+    }
+    let mut line = if details.is_empty() {
+        name
+    } else {
+        format!("{} ({})", name, details.join(", "))
+    };
+    if let Some(reason) = entry.reason.as_ref().filter(|_| with_reason) {
+        line.push_str(&format!(": {}", reason));
+    }
+    line
+}
+
+// Waits out a foreground focus session. On a terminal the time left is redrawn every second;
+// otherwise it just sleeps.
+fn wait_for_session(duration: Duration, verbosity: Verbosity) {
+    let end = Instant::now() + duration;
+    let live = !verbosity.is_quiet() && std::io::stdout().is_terminal();
+    loop {
+        let remaining = end.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        if live {
+            // Round up, so the clock starts at the full duration and never shows 00:00 early
+            let shown = Duration::from_secs(remaining.as_secs() + 1).min(duration);
+            print!("\rFocus: {} left ", format_clock(&shown));
+            let _ = std::io::stdout().flush();
+        }
+        std::thread::sleep(remaining.min(Duration::from_secs(1)));
+    }
+    if live {
+        println!();
+    }
+}
+
+// The lines of `gwd info`, e.g. "Hosts file:  /etc/hosts (OS default)"
+fn describe_info(info: &EnvironmentInfo) -> Vec<String> {
+    let source = match info.hosts_path_source {
+        HostsPathSource::Flag => "from --hosts-path".to_string(),
+        HostsPathSource::Env => format!("from {}", HOSTS_PATH_ENV_VAR),
+        HostsPathSource::Default => "OS default".to_string(),
+    };
+    let hosts_path = match &info.hosts_path {
+        Some(path) => format!("{} ({})", path.display(), source),
+        None => format!("unresolved ({})", source),
+    };
+    let access = match (info.hosts_file_exists, info.writable) {
+        (false, true) => "missing (block would create it)",
+        (false, false) => "missing, and can't be created",
+        (true, true) => "exists, writable",
+        (true, false) => "exists, not writable",
+    };
+    let user = format!(
+        "{}{}",
+        info.user.as_deref().unwrap_or("unknown"),
+        if info.elevated {
+            " (root/administrator)"
         } else {
-            Ok(())
+            ""
         }
+    );
+    let entries = match info.gwd_entries {
+        Some(count) => count.to_string(),
+        None => "unknown".to_string(),
+    };
+    let mut lines = vec![
+        format!("gwd version: {}", info.version),
+        format!("OS:          {}", info.os),
+        format!("Hosts file:  {}", hosts_path),
+        format!("             {}", access),
+        format!("User:        {}", user),
+        format!("gwd entries: {}", entries),
+    ];
+    if let Some(problem) = &info.problem {
+        lines.push(format!("Problem:     {}", problem));
     }
-    #[cfg(not(any(unix, windows)))] // Handle other OSes
-    {
-        // Assume permissions are okay on unknown platforms for now
-        println!("Warning: Unknown platform, cannot reliably check permissions.");
-        Ok(())
+    lines
+}
+
+// One line of `gwd log`, e.g. "2024-01-15 17:02  unblock  reddit.com (blocked for 2h5m)"
+fn describe_log_entry(entry: &LogEntry) -> String {
+    let time = Local
+        .timestamp_opt(entry.timestamp, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| entry.timestamp.to_string());
+    let action = match entry.action {
+        HistoryAction::Block => "block",
+        HistoryAction::Unblock => "unblock",
+    };
+    let domains = entry
+        .domains
+        .iter()
+        .map(|domain| match entry.blocked_for.get(domain) {
+            Some(secs) => format!(
+                "{} (blocked for {})",
+                domain,
+                format_duration(&Duration::from_secs((*secs).max(0) as u64))
+            ),
+            None => domain.clone(),
+        })
+        .collect::<Vec<_>>();
+    format!("{}  {:<7}  {}", time, action, domains.join(", "))
+}
+
+// Prints `gwd stats` as a table, longest blocked first, followed by the totals
+fn print_stats(stats: &Stats) {
+    let duration = |secs: i64| format_duration(&Duration::from_secs(secs.max(0) as u64));
+    if let Some(since) = stats.since {
+        let time = Local
+            .timestamp_opt(since, 0)
+            .single()
+            .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| since.to_string());
+        println!("Since {}:", time);
+    }
+    let width = stats
+        .domains
+        .iter()
+        .map(|domain| domain.domain.len())
+        .chain(["Website".len()])
+        .max()
+        .unwrap_or_default();
+    println!(
+        "{:<width$}  {:>8}  {:>6}  {:>8}  {:>8}",
+        "Website", "Blocked", "Blocks", "Unblocks", "Average"
+    );
+    for domain in &stats.domains {
+        let average = domain
+            .average_block_seconds
+            .map_or("-".to_string(), duration);
+        let line = format!(
+            "{:<width$}  {:>8}  {:>6}  {:>8}  {:>8}",
+            domain.domain,
+            duration(domain.blocked_seconds),
+            domain.blocks,
+            domain.unblocks,
+            average
+        );
+        if domain.active {
+            println!("{}  {}", line, paint("(still blocked)", Tone::Note));
+        } else {
+            println!("{}", line);
+        }
+    }
+    let average = match stats.average_block_seconds {
+        Some(secs) => format!(", average block {}", duration(secs)),
+        None => String::new(),
+    };
+    println!(
+        "Total: {} block(s), {} unblock(s){}.",
+        stats.blocks, stats.unblocks, average
+    );
+    if !stats.most_unblocked.is_empty() {
+        let tempting: Vec<String> = stats
+            .most_unblocked
+            .iter()
+            .filter_map(|name| stats.domains.iter().find(|domain| &domain.domain == name))
+            .map(|domain| format!("{} ({})", domain.domain, domain.unblocks))
+            .collect();
+        println!("Most unblocked: {}", tempting.join(", "));
     }
 }
 
-fn run() -> Result<()> {
-    // Check permissions *before* parsing args or reading files
-    check_permissions()?;
+// Journals a block/unblock for `gwd undo`. The hosts file has already changed by now,
+// so a journal failure is only a warning.
+fn record_history(config: &Config, action: HistoryAction, domains: &[String], entries: &[String]) {
+    let record = HistoryRecord {
+        action,
+        timestamp: now_timestamp(),
+        domains: domains.to_vec(),
+        entries: entries.to_vec(),
+    };
+    let limit = config.history_limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    if let Err(e) = append_record(&record, limit) {
+        eprintln!("Warning: could not record this action for undo: {}", e);
+    }
+}
 
-    let args = Args::parse();
+// The hosts files list and status read: the one gwd writes, then the config's
+// extra_hosts_files, which are only ever read
+fn hosts_files_to_read(config: &Config) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![get_hosts_path()?];
+    for path in &config.extra_hosts_files {
+        if !paths.contains(path) {
+            paths.push(path.clone());
+        }
+    }
+    Ok(paths)
+}
 
-    match args.command {
-        Commands::Block { domain } => {
-            println!("Attempting to block '{}'...", domain);
-            // Call the combined block_website function from hosts module
-            block_website(&domain)?;
-            // Success messages are now handled within block_website
+fn run(args: Args, command: Commands) -> Result<()> {
+    let verbosity = args.verbosity();
+    if let Some(path) = &args.hosts_path {
+        set_hosts_path_override(path.clone())?;
+    }
+    set_strict_checksum(args.strict);
+    if let Ok(hosts_path) = get_hosts_path() {
+        tracing::debug!(path = ?hosts_path, source = ?hosts_path_source(), "using the hosts file");
+    }
+    // Diagnosing problems, printing completions or the config path must work even with a
+    // broken config file
+    let config = match command {
+        Commands::Config { .. }
+        | Commands::Completions { .. }
+        | Commands::Doctor
+        | Commands::Info => Config::default(),
+        _ => load_config()?,
+    };
+    let no_flush = args.no_flush || config.flush_dns == Some(false);
+    // An empty dns_flush_command means the same as leaving it out
+    let flush_command = config
+        .dns_flush_command
+        .as_deref()
+        .map(str::trim)
+        .filter(|command| !command.is_empty());
+    // A dry run changes nothing, so there is nothing to announce to hooks
+    let hook = |event: HookEvent, domains: &[String]| {
+        if args.dry_run {
+            Ok(())
+        } else {
+            run_hook(&config.hooks, event, domains, verbosity)
+        }
+    };
+
+    // Check permissions *before* reading files; a dry run never writes, so it doesn't need them
+    if !args.dry_run && needs_write_access(&command) {
+        if let Err(e) = check_permissions(&get_hosts_path()?) {
+            if args.elevate && !args.elevated && matches!(e, AppError::PermissionDenied(_)) {
+                match relaunch_elevated(verbosity) {
+                    Ok(code) => std::process::exit(code),
+                    Err(elevate_error) => {
+                        eprintln!("Warning: could not elevate: {}", elevate_error)
+                    }
+                }
+            }
+            return Err(e);
+        }
+    }
+
+    // Blocks whose lock has run out are removed by whichever gwd command runs next. Tidy reports
+    // them itself, and commands that never touch the hosts file leave them alone.
+    if !args.no_sweep
+        && !args.dry_run
+        && !matches!(
+            command,
+            Commands::Tidy
+                | Commands::Diff { .. }
+                | Commands::SaveProfile { .. }
+                | Commands::ResetCooling { .. }
+                | Commands::Config { .. }
+                | Commands::Completions { .. }
+                | Commands::Doctor
+                | Commands::Info
+                | Commands::Schedule { .. }
+        )
+    {
+        sweep_expired_blocks(no_flush, flush_command, verbosity);
+    }
+
+    match command {
+        Commands::Block {
+            domains,
+            from_file,
+            list_url,
+            label,
+            redirect,
+            ipv6,
+            until,
+            for_duration,
+            until_reboot,
+            allow_ip,
+            no_www,
+            www,
+            also,
+            force,
+            reason,
+            soft,
+            section,
+            comment_style,
+            max_entries,
+        } => {
+            // Patterns like "*.example.com" are expanded separately, into their own label
+            let (patterns, domains): (Vec<String>, Vec<String>) =
+                domains.into_iter().partition(|domain| is_pattern(domain));
+            let DomainFile {
+                mut domains,
+                invalid: invalid_lines,
+            } = collect_domains(domains, from_file, allow_ip)?;
+            if let Some(label) = &label {
+                validate_profile_name(label)?;
+            }
+            // Entries from a list are tagged with its label, so `gwd list` shows where they came from
+            let mut label = label;
+            if let Some(url) = &list_url {
+                verbosity.info(format_args!("Downloading {}...", url));
+                let list = fetch_list(url, allow_ip)?;
+                warn_invalid_lines(url, &list.domains.invalid);
+                for domain in list.domains.domains {
+                    if !domains.contains(&domain) {
+                        domains.push(domain);
+                    }
+                }
+                label = Some(list.label);
+            }
+            let mut options = block_options(
+                redirect,
+                ipv6,
+                until,
+                for_duration,
+                label,
+                args.dry_run,
+                &config,
+            )?;
+            options.verbosity = verbosity;
+            options.allow_ip = allow_ip;
+            // --www/--no-www win over add_www from config.toml
+            if no_www || www {
+                set_www(&mut options.prefixes, www);
+            }
+            options.prefixes.extend(also);
+            options.force = force;
+            options.reason = reason;
+            options.soft = soft;
+            options.section = section;
+            if let Some(style) = comment_style {
+                options.comment_style = style;
+            }
+            options.until_reboot = until_reboot;
+            if until_reboot && boot_time().is_none() {
+                eprintln!(
+                    "Warning: gwd can't tell when this system booted, so these blocks won't be \
+                     removed on reboot; unblock them as usual."
+                );
+            }
+            let known = if patterns.is_empty() {
+                Vec::new()
+            } else {
+                load_known_hostnames()?
+            };
+            let mut expansions = Vec::new();
+            for pattern in &patterns {
+                let expanded = expand_pattern(pattern, &known)?;
+                verbosity.info(format_args!(
+                    "Expanding '{}' to {} known hostname(s); the hosts file can't block wildcards.",
+                    pattern,
+                    expanded.len()
+                ));
+                expansions.push((pattern_label(pattern), expanded));
+            }
+            // A large list would flood the terminal, so it gets a count and a progress bar
+            let mut large = false;
+            let report = block_all_at(
+                &domains,
+                expansions,
+                entry_cap(max_entries, &config),
+                &options,
+                &get_hosts_path()?,
+                |all_domains| {
+                    large = all_domains.len() >= PROGRESS_THRESHOLD;
+                    if large {
+                        verbosity.info(format_args!(
+                            "Attempting to block {} domains...",
+                            all_domains.len()
+                        ));
+                    } else {
+                        verbosity.info(format_args!(
+                            "Attempting to block '{}'...",
+                            all_domains.join("', '")
+                        ));
+                    }
+                    hook(HookEvent::PreBlock, all_domains)
+                },
+            )?;
+            let summary = report.summary().with_invalid(&invalid_lines, allow_ip);
+            print_block_summary(&summary, args.dry_run, verbosity);
+            if large && !args.dry_run && !report.foreign.is_empty() {
+                verbosity.info(paint(
+                    format_args!(
+                        "{} domain(s) were already blocked by non-gwd entries.",
+                        report.foreign.len()
+                    ),
+                    Tone::Note,
+                ));
+            }
+            if soft && !report.added.is_empty() {
+                verbosity.info(paint(
+                    "Soft blocks don't take effect; 'gwd harden <domain>' turns them into real blocks.",
+                    Tone::Note,
+                ));
+            }
+            if !args.dry_run && !report.added.is_empty() {
+                record_history(
+                    &config,
+                    HistoryAction::Block,
+                    &report.domains,
+                    &report.entries,
+                );
+                // A soft block changes no lookups, so there is nothing to flush
+                if !soft {
+                    refresh_dns(no_flush, flush_command, verbosity);
+                }
+                hook(HookEvent::PostBlock, &report.domains)?;
+            }
+            if args.json {
+                print_json(
+                    "block",
+                    &report,
+                    json!({
+                        "dry_run": args.dry_run,
+                        "invalid_lines": invalid_lines,
+                        "summary": summary,
+                    }),
+                )?;
+            }
         }
         Commands::Unblock {
             domain,
-            challenge_length,
+            all,
+            from_file,
+            list_url,
+            label,
+            section,
+            include_foreign,
+            emergency,
+            force,
+            challenge,
+        } => {
+            let mut challenge_options = challenge.options(verbosity, &config)?;
+            if emergency {
+                challenge_options = challenge_options.emergency();
+            }
+            if force && !force_allowed() {
+                eprintln!(
+                    "Warning: --force is ignored on a terminal unless {}=1 is set.",
+                    ALLOW_FORCE_ENV_VAR
+                );
+            }
+            let options = UnblockOptions {
+                challenge: challenge_options,
+                dry_run: args.dry_run,
+                verbosity,
+                force: force && force_allowed(),
+                // --emergency is the way out of the no_unblock periods
+                no_unblock: if emergency {
+                    Vec::new()
+                } else {
+                    config.no_unblock.clone()
+                },
+                section: section.clone(),
+                include_foreign,
+            };
+            let mut invalid_lines = Vec::new();
+            // A pattern unblocks the label its expansion was blocked under
+            let label = label.or_else(|| {
+                domain
+                    .as_deref()
+                    .filter(|domain| is_pattern(domain))
+                    .map(pattern_label)
+            });
+            // The domains handed to the unblock hooks
+            let targets: Vec<String>;
+            let report = if all {
+                verbosity.info("Attempting to unblock all websites blocked by gwd...");
+                let blocked: BTreeSet<String> = list_blocked_entries_at(&get_hosts_path()?)?
+                    .into_iter()
+                    .map(|entry| entry.domain)
+                    .collect();
+                targets = blocked.into_iter().collect();
+                let entry_count = gwd_entry_count_at(&get_hosts_path()?)?;
+                if entry_count > 0 && !args.dry_run {
+                    require_confirmation(
+                        &format!("This will remove {} entries.", entry_count),
+                        args.yes,
+                    )?;
+                }
+                hook(HookEvent::PreUnblock, &targets)?;
+                unblock_all_websites(&options)?
+            } else if let Some(label) = label {
+                verbosity.info(format_args!(
+                    "Attempting to unblock everything blocked as '{}'...",
+                    label
+                ));
+                let hosts_path = get_hosts_path()?;
+                targets = list_blocked_entries_at(&hosts_path)?
+                    .into_iter()
+                    .filter(|entry| entry.label.as_deref() == Some(label.as_str()))
+                    .map(|entry| entry.domain)
+                    .collect();
+                hook(HookEvent::PreUnblock, &targets)?;
+                unblock_label(&label, &options)?
+            } else if let (Some(section), None, None, None) =
+                (&section, &domain, &from_file, &list_url)
+            {
+                verbosity.info(format_args!(
+                    "Attempting to unblock everything in section '{}'...",
+                    section
+                ));
+                targets = list_section_entries_at(Some(section), &get_hosts_path()?)?
+                    .into_iter()
+                    .map(|entry| entry.domain)
+                    .collect();
+                hook(HookEvent::PreUnblock, &targets)?;
+                unblock_section(section, &options)?
+            } else if let Some(url) = list_url {
+                let list = cached_list(&url, true)?;
+                targets = domain.into_iter().chain(list.domains.domains).collect();
+                verbosity.info(format_args!(
+                    "Attempting to unblock the {} domain(s) of {}...",
+                    targets.len(),
+                    url
+                ));
+                hook(HookEvent::PreUnblock, &targets)?;
+                unblock_websites(&targets, &options)?
+            } else if from_file.is_some() {
+                let domain_file = collect_domains(domain.into_iter().collect(), from_file, true)?;
+                targets = domain_file.domains;
+                invalid_lines = domain_file.invalid;
+                verbosity.info(format_args!(
+                    "Attempting to unblock '{}'...",
+                    targets.join("', '")
+                ));
+                hook(HookEvent::PreUnblock, &targets)?;
+                unblock_websites(&targets, &options)?
+            } else if let Some(domain) = domain {
+                verbosity.info(format_args!("Attempting to unblock '{}'...", domain));
+                targets = vec![domain];
+                hook(HookEvent::PreUnblock, &targets)?;
+                // Call the combined unblock_website function from hosts module
+                // It handles the challenge internally now based on the count
+                unblock_website(&targets[0], &options)?
+            } else {
+                unreachable!("clap requires a domain unless --all is given")
+            };
+            let summary = report.summary().with_invalid(&invalid_lines, true);
+            print_unblock_summary(&summary, args.dry_run, verbosity);
+            suggest_blocked_domains(&report.not_blocked(), verbosity)?;
+            if !args.dry_run && !report.removed.is_empty() {
+                record_history(
+                    &config,
+                    HistoryAction::Unblock,
+                    &report.domains,
+                    &report.removed,
+                );
+                refresh_dns(no_flush, flush_command, verbosity);
+                hook(HookEvent::PostUnblock, &targets)?;
+            }
+            if args.json {
+                print_json(
+                    "unblock",
+                    &report,
+                    json!({
+                        "dry_run": args.dry_run,
+                        "invalid_lines": invalid_lines,
+                        "summary": summary,
+                    }),
+                )?;
+            }
+        }
+        Commands::Harden { domains } => {
+            verbosity.info(format_args!(
+                "Hardening the soft blocks of '{}'...",
+                domains.join("', '")
+            ));
+            hook(HookEvent::PreBlock, &domains)?;
+            let report = harden_websites(&domains, args.dry_run, verbosity)?;
+            let verb = if args.dry_run {
+                "Would harden"
+            } else {
+                "Hardened"
+            };
+            verbosity.info(format_args!("{} {}.", verb, report.added.len()));
+            if !args.dry_run && !report.added.is_empty() {
+                record_history(
+                    &config,
+                    HistoryAction::Block,
+                    &report.domains,
+                    &report.entries,
+                );
+                refresh_dns(no_flush, flush_command, verbosity);
+                hook(HookEvent::PostBlock, &report.domains)?;
+            }
+            if args.json {
+                print_json("harden", &report, json!({ "dry_run": args.dry_run }))?;
+            }
+        }
+        Commands::Pause {
+            for_duration,
+            challenge,
         } => {
-            println!("Attempting to unblock '{}'...", domain);
-            // Call the combined unblock_website function from hosts module
-            // It handles the challenge internally now based on the count
-            unblock_website(&domain, challenge_length as usize)?;
-            // Success messages are now handled within unblock_website
+            let duration = parse_duration(&for_duration)?;
+            let resume_at = now_timestamp() + duration.as_secs() as i64;
+            let options = UnblockOptions {
+                challenge: challenge.options(verbosity, &config)?,
+                dry_run: args.dry_run,
+                verbosity,
+                no_unblock: config.no_unblock.clone(),
+                ..Default::default()
+            };
+            let report = pause_blocks_at(resume_at, &options, &get_hosts_path()?)?;
+            if !report.paused.is_empty() {
+                verbosity.info(format_args!(
+                    "{} {} entries for {}; 'gwd resume' turns them back on sooner.",
+                    if args.dry_run {
+                        "Would pause"
+                    } else {
+                        "Paused"
+                    },
+                    report.paused.len(),
+                    format_duration(&duration)
+                ));
+                if !args.dry_run {
+                    refresh_dns(no_flush, flush_command, verbosity);
+                }
+            }
+            if args.json {
+                print_json("pause", &report, json!({ "dry_run": args.dry_run }))?;
+            }
+        }
+        Commands::Resume => {
+            let resumed = resume_blocks_at(args.dry_run, verbosity, &get_hosts_path()?)?;
+            if resumed.is_empty() {
+                verbosity.info("No paused entries found.");
+            } else {
+                verbosity.info(format_args!(
+                    "{} {} entries.",
+                    if args.dry_run {
+                        "Would resume"
+                    } else {
+                        "Resumed"
+                    },
+                    resumed.len()
+                ));
+                if !args.dry_run {
+                    refresh_dns(no_flush, flush_command, verbosity);
+                }
+            }
+            if args.json {
+                print_json(
+                    "resume",
+                    json!({ "resumed": resumed }),
+                    json!({ "dry_run": args.dry_run }),
+                )?;
+            }
+        }
+        Commands::Move { domains, to } => {
+            validate_profile_name(&to)?;
+            let report = move_websites(&domains, &to, args.dry_run, verbosity)?;
+            let verb = if args.dry_run { "Would move" } else { "Moved" };
+            verbosity.info(format_args!(
+                "{} {} to '{}'{}.",
+                verb,
+                report.added.len(),
+                to,
+                match report.already_present.len() {
+                    0 => String::new(),
+                    already => format!(", {} already there", already),
+                }
+            ));
+            if args.json {
+                print_json(
+                    "move",
+                    &report,
+                    json!({ "dry_run": args.dry_run, "label": to }),
+                )?;
+            }
+        }
+        Commands::Status { domain } => {
+            let hosts_paths = hosts_files_to_read(&config)?;
+            let sinkholes = sinkhole_ips(&config.sinkhole_ips);
+            let state = block_state_in(&domain, &hosts_paths, &sinkholes)?;
+            let blocked = state == BlockState::Gwd;
+            let until = lock_until_in(&domain, &hosts_paths)?;
+            if args.json {
+                print_json(
+                    "status",
+                    json!({ "domain": domain, "blocked": blocked, "state": state, "until": until }),
+                    json!({}),
+                )?;
+            } else if verbosity.is_quiet() {
+                // The exit code alone answers the question
+            } else if blocked {
+                println!("{}", describe_block(until, now_timestamp()));
+            } else if state == BlockState::Foreign {
+                // Say why a blocked-looking site isn't ours, so the exit code isn't a surprise
+                println!("not blocked (a non-gwd hosts entry blocks it)");
+            } else if state == BlockState::Paused {
+                println!("paused (blocked again when the pause ends, or with 'gwd resume')");
+            } else if state == BlockState::Soft {
+                println!("soft-blocked (not in effect; 'gwd harden' makes it a real block)");
+            } else {
+                println!("not blocked");
+            }
+            if !blocked {
+                std::process::exit(1);
+            }
+        }
+        Commands::List {
+            count,
+            by_profile,
+            label,
+            section,
+            reasons,
+            foreign,
+        } => {
+            let hosts_paths = hosts_files_to_read(&config)?;
+            // Status bars poll the bare count, so it skips building the list
+            if count && !args.json && label.is_none() && section.is_none() && hosts_paths.len() == 1
+            {
+                println!("{}", count_blocked(&hosts_paths[0])?);
+                return Ok(());
+            }
+            let mut entries = list_section_entries_in(section.as_deref(), &hosts_paths)?;
+            if let Some(label) = &label {
+                entries.retain(|entry| entry.label.as_ref() == Some(label));
+            }
+            let foreign_blocks = if foreign {
+                list_foreign_blocks_in(
+                    &hosts_paths,
+                    &sinkhole_ips(&config.sinkhole_ips),
+                    &protected_hostnames(&config.protected_hostnames),
+                )?
+            } else {
+                Vec::new()
+            };
+            let mut paused = Vec::new();
+            for hosts_path in &hosts_paths {
+                paused.extend(list_paused_at(hosts_path)?);
+            }
+            let now = now_timestamp();
+            if args.json {
+                let mut extra = if foreign {
+                    json!({ "foreign": foreign_blocks })
+                } else {
+                    json!({})
+                };
+                if let Some(resume_at) = paused.iter().map(|(resume_at, _)| *resume_at).max() {
+                    extra["paused"] = json!({ "count": paused.len(), "resume_at": resume_at });
+                }
+                print_json(
+                    "list",
+                    json!({ "count": entries.len(), "entries": entries }),
+                    extra,
+                )?;
+            } else if by_profile {
+                // Group entries by label, listing unlabelled blocks last
+                let mut groups: Vec<(Option<String>, Vec<BlockedEntry>)> = Vec::new();
+                for entry in entries {
+                    match groups.iter_mut().find(|(label, _)| *label == entry.label) {
+                        Some((_, group)) => group.push(entry),
+                        None => groups.push((entry.label.clone(), vec![entry])),
+                    }
+                }
+                groups.sort_by_key(|(label, _)| label.is_none());
+                if groups.is_empty() {
+                    println!("No websites are currently blocked by gwd.");
+                }
+                for (label, group) in groups {
+                    match label {
+                        Some(label) => println!("[{}]", label),
+                        None => println!("(no profile)"),
+                    }
+                    for entry in group {
+                        println!("  {}", describe_listed_entry(&entry, now, false, reasons));
+                    }
+                }
+            } else if count {
+                println!("{}", entries.len());
+            } else if entries.is_empty() {
+                match (label, section) {
+                    (Some(label), _) => {
+                        println!("No websites are currently blocked as '{}'.", label)
+                    }
+                    (None, Some(section)) => {
+                        println!(
+                            "No websites are currently blocked in section '{}'.",
+                            section
+                        )
+                    }
+                    (None, None) => println!("No websites are currently blocked by gwd."),
+                }
+            } else {
+                for entry in &entries {
+                    // Filtering by label already says which label they all have
+                    println!(
+                        "{}",
+                        describe_listed_entry(entry, now, label.is_none(), reasons)
+                    );
+                }
+            }
+            if !args.json {
+                for block in &foreign_blocks {
+                    println!("{} (not gwd: {})", block.hostname, block.ip);
+                }
+                if let Some(resume_at) = paused.iter().map(|(resume_at, _)| *resume_at).max() {
+                    println!(
+                        "{} entries are paused for another {} ('gwd resume' turns them back on now).",
+                        paused.len(),
+                        format_duration(&Duration::from_secs((resume_at - now).max(0) as u64))
+                    );
+                }
+            }
+        }
+        Commands::Export { format, output } => {
+            let entries = list_blocked_entries_at(&get_hosts_path()?)?;
+            let content = render_export(&entries, format)?;
+            match &output {
+                Some(path) => {
+                    fs::write(path, &content).map_err(|e| {
+                        AppError::Io(format!("Failed to write export file {:?}: {}", path, e))
+                    })?;
+                    verbosity.info(format_args!(
+                        "Exported {} blocked website(s) to {}.",
+                        entries.len(),
+                        path.display()
+                    ));
+                }
+                None => print!("{}", content),
+            }
+        }
+        Commands::Import { file, max_entries } => {
+            let import = read_import(&file)?;
+            let report = import_at(
+                import_groups(&import)?,
+                entry_cap(max_entries, &config),
+                |key| {
+                    let mut options = import_options(key, args.dry_run, &config)?;
+                    options.verbosity = verbosity;
+                    Ok(options)
+                },
+                &get_hosts_path()?,
+                |domains| {
+                    verbosity.info(format_args!(
+                        "Importing {} website(s) from {}...",
+                        domains.len(),
+                        file.display()
+                    ));
+                    hook(HookEvent::PreBlock, domains)
+                },
+                |group_report| {
+                    if !args.dry_run && !group_report.added.is_empty() {
+                        record_history(
+                            &config,
+                            HistoryAction::Block,
+                            &group_report.domains,
+                            &group_report.entries,
+                        );
+                    }
+                },
+            )?;
+            let summary = report.summary().with_invalid(&import.invalid, false);
+            print_block_summary(&summary, args.dry_run, verbosity);
+            if !args.dry_run && !report.added.is_empty() {
+                refresh_dns(no_flush, flush_command, verbosity);
+                hook(HookEvent::PostBlock, &report.domains)?;
+            }
+            if args.json {
+                print_json(
+                    "import",
+                    &report,
+                    json!({ "dry_run": args.dry_run, "summary": summary }),
+                )?;
+            }
+        }
+        Commands::Diff { change } => {
+            let hosts_path = get_hosts_path()?;
+            let preview = preview_diff(change, &hosts_path, &config)?;
+            let diff = preview.unified_diff(&hosts_path);
+            if args.json {
+                print_json(
+                    "diff",
+                    json!({ "changed": preview.changed(), "diff": diff }),
+                    json!({}),
+                )?;
+            } else if diff.is_empty() {
+                verbosity.info(format_args!("No changes to {:?}.", hosts_path));
+            } else {
+                for line in diff.lines() {
+                    let tone = if line.starts_with("+++") || line.starts_with("---") {
+                        None
+                    } else if line.starts_with('+') {
+                        Some(Tone::Added)
+                    } else if line.starts_with('-') {
+                        Some(Tone::Removed)
+                    } else if line.starts_with("@@") {
+                        Some(Tone::Note)
+                    } else {
+                        None
+                    };
+                    match tone {
+                        Some(tone) => println!("{}", paint(line, tone)),
+                        None => println!("{}", line),
+                    }
+                }
+            }
+        }
+        Commands::ResetCooling { domain } => {
+            if args.dry_run {
+                verbosity.info(format_args!("Dry run: cooling of {} not reset.", domain));
+                return Ok(());
+            }
+            let reset = reset_cooling(&domain, &cooldown_path()?)?;
+            if args.json {
+                print_json(
+                    "reset-cooling",
+                    json!({ "domain": domain, "reset": reset }),
+                    json!({}),
+                )?;
+            } else if reset {
+                verbosity.info(format_args!(
+                    "Reset cooling for {}; its next challenge has the usual length.",
+                    domain
+                ));
+            } else {
+                verbosity.info(format_args!("{} has no recent unblocks to forget.", domain));
+            }
+        }
+        Commands::SaveProfile { name, domains } => {
+            validate_profile_name(&name)?;
+            let mut profiles = load_profiles()?;
+            let count = domains.len();
+            profiles.insert(name.clone(), domains.clone());
+            if !args.dry_run {
+                save_profiles(&profiles)?;
+            }
+            if args.json {
+                print_json(
+                    "save-profile",
+                    json!({ "dry_run": args.dry_run, "name": name, "domains": domains }),
+                    json!({}),
+                )?;
+            } else if args.dry_run {
+                verbosity.info(format_args!("Dry run: profile '{}' not saved.", name));
+            } else {
+                verbosity.info(format_args!(
+                    "Saved profile '{}' with {} domain(s).",
+                    name, count
+                ));
+            }
+        }
+        Commands::BlockProfile {
+            name,
+            redirect,
+            ipv6,
+            until,
+            for_duration,
+        } => {
+            let domains = profile_domains(&load_profiles()?, &name)?;
+            let mut options = block_options(
+                redirect,
+                ipv6,
+                until,
+                for_duration,
+                Some(name.clone()),
+                args.dry_run,
+                &config,
+            )?;
+            options.verbosity = verbosity;
+            verbosity.info(format_args!("Attempting to block profile '{}'...", name));
+            hook(HookEvent::PreBlock, &domains)?;
+            let report = block_websites(&domains, &options)?;
+            print_block_summary(&report.summary(), args.dry_run, verbosity);
+            if !args.dry_run && !report.added.is_empty() {
+                record_history(
+                    &config,
+                    HistoryAction::Block,
+                    &report.domains,
+                    &report.entries,
+                );
+                refresh_dns(no_flush, flush_command, verbosity);
+                hook(HookEvent::PostBlock, &report.domains)?;
+            }
+            if args.json {
+                print_json(
+                    "block-profile",
+                    &report,
+                    json!({
+                        "dry_run": args.dry_run,
+                        "profile": name,
+                        "summary": report.summary(),
+                    }),
+                )?;
+            }
+        }
+        Commands::UnblockProfile { name, challenge } => {
+            let domains = profile_domains(&load_profiles()?, &name)?;
+            let options = UnblockOptions {
+                challenge: challenge.options(verbosity, &config)?,
+                dry_run: args.dry_run,
+                verbosity,
+                force: false,
+                no_unblock: config.no_unblock.clone(),
+                section: None,
+                include_foreign: false,
+            };
+            verbosity.info(format_args!("Attempting to unblock profile '{}'...", name));
+            hook(HookEvent::PreUnblock, &domains)?;
+            let report = unblock_websites(&domains, &options)?;
+            print_unblock_summary(&report.summary(), args.dry_run, verbosity);
+            if !args.dry_run && !report.removed.is_empty() {
+                record_history(
+                    &config,
+                    HistoryAction::Unblock,
+                    &report.domains,
+                    &report.removed,
+                );
+                refresh_dns(no_flush, flush_command, verbosity);
+                hook(HookEvent::PostUnblock, &report.domains)?;
+            }
+            if args.json {
+                print_json(
+                    "unblock-profile",
+                    &report,
+                    json!({
+                        "dry_run": args.dry_run,
+                        "profile": name,
+                        "summary": report.summary(),
+                    }),
+                )?;
+            }
+        }
+        Commands::Focus {
+            profile,
+            for_duration,
+            detach,
+        } => {
+            let domains = profile_domains(&load_profiles()?, &profile)?;
+            let duration = parse_duration(&for_duration)?;
+            let mut options = block_options(
+                None,
+                false,
+                None,
+                None,
+                Some(profile.clone()),
+                args.dry_run,
+                &config,
+            )?;
+            options.verbosity = verbosity;
+            verbosity.info(format_args!(
+                "Starting a {} focus session on profile '{}'...",
+                format_duration(&duration),
+                profile
+            ));
+            hook(HookEvent::PreBlock, &domains)?;
+            let blocked = block_websites(&domains, &options)?;
+            print_block_summary(&blocked.summary(), args.dry_run, verbosity);
+            if !args.dry_run && !blocked.added.is_empty() {
+                record_history(
+                    &config,
+                    HistoryAction::Block,
+                    &blocked.domains,
+                    &blocked.entries,
+                );
+                refresh_dns(no_flush, flush_command, verbosity);
+                hook(HookEvent::PostBlock, &blocked.domains)?;
+            }
+
+            let ends_at = now_timestamp() + duration.as_secs() as i64;
+            let mut removed = Vec::new();
+            if args.dry_run {
+                verbosity.info("Dry run: no session started.");
+            } else if detach {
+                start_session_at(&focus_path()?, &profile, ends_at, &blocked.entries)?;
+                verbosity.info(format_args!(
+                    "The session ends at {}. apply-schedule then removes what it blocked under '{}' (see gwd schedule install).",
+                    Local::now()
+                        .checked_add_signed(chrono::Duration::seconds(duration.as_secs() as i64))
+                        .map_or_else(String::new, |end| end.format("%H:%M").to_string()),
+                    profile
+                ));
+            } else {
+                verbosity.info(format_args!(
+                    "Press Ctrl-C to stop early; the sites then stay blocked until 'gwd unblock --label {}'.",
+                    profile
+                ));
+                wait_for_session(duration, verbosity);
+                verbosity.info("Focus session complete.");
+                // The session ran its course, so removing what it added needs no challenge
+                let report =
+                    end_focus_session_at(&blocked.entries, false, verbosity, &get_hosts_path()?)?;
+                print_unblock_summary(&report.summary(), false, verbosity);
+                if !report.removed.is_empty() {
+                    record_history(
+                        &config,
+                        HistoryAction::Unblock,
+                        &report.domains,
+                        &report.removed,
+                    );
+                    refresh_dns(no_flush, flush_command, verbosity);
+                    hook(HookEvent::PostUnblock, &report.domains)?;
+                }
+                removed = report.removed;
+            }
+            if args.json {
+                print_json(
+                    "focus",
+                    json!({
+                        "profile": profile,
+                        "added": blocked.added,
+                        "removed": removed,
+                        "ends_at": format_rfc3339(ends_at),
+                        "detached": detach,
+                    }),
+                    json!({ "dry_run": args.dry_run }),
+                )?;
+            }
+        }
+        Commands::FocusMode {
+            action: FocusModeAction::On { allow },
+        } => {
+            let domains = focus_mode_domains(&allow)?;
+            let mut options = block_options(
+                None,
+                false,
+                None,
+                None,
+                Some(FOCUS_MODE_LABEL.to_string()),
+                args.dry_run,
+                &config,
+            )?;
+            options.verbosity = verbosity;
+            verbosity.info(format_args!(
+                "Turning focus mode on ({} distracting sites)...",
+                domains.len()
+            ));
+            hook(HookEvent::PreBlock, &domains)?;
+            let report = block_websites(&domains, &options)?;
+            print_block_summary(&report.summary(), args.dry_run, verbosity);
+            if !args.dry_run && !report.added.is_empty() {
+                record_history(
+                    &config,
+                    HistoryAction::Block,
+                    &report.domains,
+                    &report.entries,
+                );
+                refresh_dns(no_flush, flush_command, verbosity);
+                hook(HookEvent::PostBlock, &report.domains)?;
+            }
+            if args.json {
+                print_json(
+                    "focus-mode-on",
+                    &report,
+                    json!({ "dry_run": args.dry_run, "allow": allow }),
+                )?;
+            }
+        }
+        Commands::FocusMode {
+            action: FocusModeAction::Off { challenge },
+        } => {
+            let options = UnblockOptions {
+                challenge: challenge.options(verbosity, &config)?,
+                dry_run: args.dry_run,
+                verbosity,
+                force: false,
+                no_unblock: config.no_unblock.clone(),
+                section: None,
+                include_foreign: false,
+            };
+            verbosity.info("Turning focus mode off...");
+            let hosts_path = get_hosts_path()?;
+            let targets: Vec<String> = list_blocked_entries_at(&hosts_path)?
+                .into_iter()
+                .filter(|entry| entry.label.as_deref() == Some(FOCUS_MODE_LABEL))
+                .map(|entry| entry.domain)
+                .collect();
+            if !targets.is_empty() {
+                hook(HookEvent::PreUnblock, &targets)?;
+            }
+            let report = unblock_label(FOCUS_MODE_LABEL, &options)?;
+            print_unblock_summary(&report.summary(), args.dry_run, verbosity);
+            if !args.dry_run && !report.removed.is_empty() {
+                record_history(
+                    &config,
+                    HistoryAction::Unblock,
+                    &report.domains,
+                    &report.removed,
+                );
+                refresh_dns(no_flush, flush_command, verbosity);
+                hook(HookEvent::PostUnblock, &targets)?;
+            }
+            if args.json {
+                print_json(
+                    "focus-mode-off",
+                    &report,
+                    json!({ "dry_run": args.dry_run }),
+                )?;
+            }
+        }
+        Commands::Watch { profile } => {
+            let domains = profile_domains(&load_profiles()?, &profile)?;
+            hook(HookEvent::PreBlock, &domains)?;
+            let report = reapply_profile(&profile, &domains, args.dry_run, &config, verbosity)?;
+            print_block_summary(&report.summary(), args.dry_run, verbosity);
+            if args.dry_run {
+                verbosity.info("Dry run: nothing is watched.");
+                return Ok(());
+            }
+            if !report.added.is_empty() {
+                refresh_dns(no_flush, flush_command, verbosity);
+                hook(HookEvent::PostBlock, &report.domains)?;
+            }
+
+            let hosts_path = get_hosts_path()?;
+            verbosity.info(format_args!(
+                "Watching {:?} for removed '{}' entries. Press Ctrl-C to stop; the sites then stay blocked until 'gwd unblock --label {}'.",
+                hosts_path, profile, profile
+            ));
+            watch_file(&hosts_path, || {
+                // Our own write fires the watch again, and then finds nothing to do
+                let report =
+                    match reapply_profile(&profile, &domains, false, &config, Verbosity::Quiet) {
+                        Ok(report) => report,
+                        Err(AppError::Interrupted) => return Err(AppError::Interrupted),
+                        // e.g. --strict refusing an edited section; keep watching
+                        Err(e) => {
+                            eprintln!("Warning: could not re-apply '{}': {}", profile, e);
+                            return Ok(());
+                        }
+                    };
+                if report.added.is_empty() {
+                    return Ok(());
+                }
+                verbosity.info(paint(
+                    format_args!(
+                        "[{}] Re-applied {} removed entr{}: {}",
+                        Local::now().format("%H:%M:%S"),
+                        report.entries.len(),
+                        if report.entries.len() == 1 {
+                            "y"
+                        } else {
+                            "ies"
+                        },
+                        report.added.join(", ")
+                    ),
+                    Tone::Added,
+                ));
+                refresh_dns(no_flush, flush_command, verbosity);
+                hook(HookEvent::PostBlock, &report.domains)
+            })?;
+            verbosity.info(format_args!(
+                "Stopped watching; '{}' stays blocked.",
+                profile
+            ));
+        }
+        Commands::ApplySchedule => {
+            let hosts_path = get_hosts_path()?;
+            let blocked_labels: BTreeSet<String> = list_blocked_entries_at(&hosts_path)?
+                .into_iter()
+                .filter_map(|entry| entry.label)
+                .collect();
+            let mut plan = plan_schedule(
+                &load_schedule()?,
+                Local::now().naive_local(),
+                &blocked_labels,
+            );
+            let profiles = load_profiles()?;
+            // A detached focus session keeps its profile blocked even outside the schedule, and
+            // unblocks it once it's over
+            let focus_file = focus_path()?;
+            let now = now_timestamp();
+            let focusing = active_sessions_at(&focus_file, now)?;
+            plan.unblock.retain(|name| !focusing.contains(name));
+            let finished = ended_sessions_at(&focus_file, now)?;
+
+            // Blocks are re-applied on every run, so only the post hooks (which only run when
+            // something actually changed) make sense here
+            let mut added = Vec::new();
+            for name in &plan.block {
+                let domains = profile_domains(&profiles, name)?;
+                let report = reapply_profile(name, &domains, args.dry_run, &config, verbosity)?;
+                if !args.dry_run && !report.added.is_empty() {
+                    hook(HookEvent::PostBlock, &report.domains)?;
+                }
+                added.extend(report.entries);
+            }
+            let mut removed = Vec::new();
+            for name in &plan.unblock {
+                verbosity.info(format_args!("Schedule window for '{}' is over.", name));
+                match unblock_label_at(name, args.dry_run, verbosity, &hosts_path) {
+                    Ok(report) => {
+                        if !args.dry_run && !report.removed.is_empty() {
+                            record_history(&config, HistoryAction::Unblock, &[], &report.removed);
+                            // The profile may have been deleted since it was blocked
+                            let domains = profile_domains(&profiles, name).unwrap_or_default();
+                            hook(HookEvent::PostUnblock, &domains)?;
+                        }
+                        removed.extend(report.removed);
+                    }
+                    // A manual --until/--for lock outlasts the schedule; try again next run
+                    Err(e @ AppError::LockActive { .. }) => eprintln!("Warning: {}", e),
+                    Err(e) => return Err(e),
+                }
+            }
+            // A finished session takes back only the lines it added, not other blocks under
+            // the profile's label, which still need the challenge
+            for (name, session) in &finished {
+                verbosity.info(format_args!("Focus session on '{}' is over.", name));
+                let report =
+                    end_focus_session_at(&session.entries, args.dry_run, verbosity, &hosts_path)?;
+                if !args.dry_run {
+                    end_session_at(&focus_file, name)?;
+                    if !report.removed.is_empty() {
+                        record_history(&config, HistoryAction::Unblock, &[], &report.removed);
+                        hook(HookEvent::PostUnblock, &report.domains)?;
+                    }
+                }
+                removed.extend(report.removed);
+            }
+
+            if !args.dry_run && (!added.is_empty() || !removed.is_empty()) {
+                refresh_dns(no_flush, flush_command, verbosity);
+            }
+            if args.json {
+                print_json(
+                    "apply-schedule",
+                    json!({
+                        "block": plan.block,
+                        "unblock": plan.unblock,
+                        "focus_ended": finished.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+                        "added": added,
+                        "removed": removed,
+                    }),
+                    json!({ "dry_run": args.dry_run }),
+                )?;
+            }
+        }
+        Commands::Schedule { action } => {
+            let (name, report) = match action {
+                ScheduleAction::Install => (
+                    "schedule-install",
+                    install_scheduler(args.dry_run, verbosity)?,
+                ),
+                ScheduleAction::Uninstall => (
+                    "schedule-uninstall",
+                    uninstall_scheduler(args.dry_run, verbosity)?,
+                ),
+            };
+            if args.json {
+                print_json(name, &report, json!({ "dry_run": args.dry_run }))?;
+            } else if report.unchanged {
+                verbosity.info(match action {
+                    ScheduleAction::Install => "The scheduled job is already installed.",
+                    ScheduleAction::Uninstall => "No scheduled job is installed.",
+                });
+            } else if args.dry_run {
+                verbosity.info("Dry run: nothing was changed.");
+            }
+        }
+        Commands::Undo { challenge } => {
+            let Some(record) = last_record()? else {
+                verbosity.info("Nothing to undo.");
+                if args.json {
+                    print_json("undo", json!({ "undone": null }), json!({}))?;
+                }
+                return Ok(());
+            };
+            let hosts_path = get_hosts_path()?;
+            let domain_list = if record.domains.is_empty() {
+                "all websites".to_string()
+            } else {
+                format!("'{}'", record.domains.join("', '"))
+            };
+            let changed = match record.action {
+                HistoryAction::Block => {
+                    verbosity.info(format_args!("Undoing block of {}...", domain_list));
+                    let options = UnblockOptions {
+                        challenge: challenge.options(verbosity, &config)?,
+                        dry_run: args.dry_run,
+                        verbosity,
+                        force: false,
+                        no_unblock: config.no_unblock.clone(),
+                        section: None,
+                        include_foreign: false,
+                    };
+                    undo_block_at(&record.entries, &options, &hosts_path)?.removed
+                }
+                HistoryAction::Unblock => {
+                    verbosity.info(format_args!("Undoing unblock of {}...", domain_list));
+                    undo_unblock_at(&record.entries, args.dry_run, verbosity, &hosts_path)?
+                }
+            };
+            if args.dry_run {
+                verbosity.info(format_args!(
+                    "Dry run: no changes written to {:?}.",
+                    hosts_path
+                ));
+            } else {
+                drop_last_record()?;
+                if changed.is_empty() {
+                    verbosity.info("The hosts file already matches; nothing to change.");
+                } else {
+                    refresh_dns(no_flush, flush_command, verbosity);
+                }
+            }
+            if args.json {
+                print_json(
+                    "undo",
+                    json!({ "undone": record, "changed": changed }),
+                    json!({ "dry_run": args.dry_run }),
+                )?;
+            }
+        }
+        Commands::Log { since } => {
+            let since = match since {
+                Some(since) => Some(now_timestamp() - parse_duration(&since)?.as_secs() as i64),
+                None => None,
+            };
+            let log = build_log(&all_records()?, since);
+            if args.json {
+                print_json("log", json!({ "entries": log }), json!({}))?;
+            } else if log.is_empty() {
+                verbosity.info("No blocks or unblocks recorded.");
+            } else {
+                for entry in &log {
+                    println!("{}", describe_log_entry(entry));
+                }
+            }
+        }
+        Commands::Stats { since } => {
+            let now = now_timestamp();
+            let since = match since {
+                Some(since) => Some(now - parse_duration(&since)?.as_secs() as i64),
+                None => None,
+            };
+            let stats = build_stats(&all_records()?, since, now);
+            if args.json {
+                print_json("stats", &stats, json!({}))?;
+            } else if stats.domains.is_empty() {
+                verbosity.info("No blocks or unblocks recorded.");
+            } else {
+                print_stats(&stats);
+            }
+        }
+        Commands::Doctor => {
+            let checks = run_diagnostics();
+            let healthy = checks.iter().all(|check| check.passed);
+            if args.json {
+                print_json(
+                    "doctor",
+                    json!({ "healthy": healthy, "checks": checks }),
+                    json!({}),
+                )?;
+            } else {
+                for check in &checks {
+                    println!(
+                        "[{}] {}",
+                        if check.passed { "ok" } else { "!!" },
+                        check.label
+                    );
+                    if let Some(advice) = &check.advice {
+                        println!("     fix: {}", advice);
+                    }
+                }
+                if healthy {
+                    verbosity.info("Everything looks good.");
+                }
+            }
+            if !healthy {
+                std::process::exit(1);
+            }
+        }
+        Commands::Info => {
+            let info = collect_info();
+            if args.json {
+                print_json("info", &info, json!({}))?;
+            } else {
+                for line in describe_info(&info) {
+                    println!("{}", line);
+                }
+            }
+        }
+        Commands::Verify { remove } => {
+            let hosts_path = get_hosts_path()?;
+            let problems = malformed_gwd_lines_at(&hosts_path)?;
+            let removed = if remove {
+                if !problems.is_empty() && !args.dry_run {
+                    require_confirmation(
+                        &format!("This will remove {} malformed lines.", problems.len()),
+                        args.yes,
+                    )?;
+                }
+                remove_malformed_gwd_lines_at(args.dry_run, &hosts_path)?
+            } else {
+                Vec::new()
+            };
+            if !removed.is_empty() && !args.dry_run {
+                record_history(&config, HistoryAction::Unblock, &[], &removed);
+            }
+            if args.json {
+                let problems: Vec<_> = problems
+                    .iter()
+                    .map(|(line, problem)| json!({ "line": line, "problem": problem }))
+                    .collect();
+                print_json(
+                    "verify",
+                    json!({ "problems": problems, "removed": removed }),
+                    json!({ "dry_run": args.dry_run }),
+                )?;
+            } else {
+                for (line, problem) in &problems {
+                    println!("line {}: {}", line, problem);
+                }
+                for line in &removed {
+                    if args.dry_run {
+                        verbosity.info(paint(format_args!("- {}", line), Tone::Removed));
+                    } else {
+                        verbosity
+                            .info(paint(format_args!("Removed line: {}", line), Tone::Removed));
+                    }
+                }
+                if problems.is_empty() {
+                    verbosity.info("All gwd entries are well-formed.");
+                } else if !remove {
+                    verbosity.info("Run 'gwd verify --remove' to delete the malformed entries.");
+                }
+            }
+            // Section marker problems survive --remove if no line had to be deleted
+            let fixed = remove && !args.dry_run && malformed_gwd_lines_at(&hosts_path)?.is_empty();
+            if !problems.is_empty() && !fixed {
+                std::process::exit(1);
+            }
+        }
+        Commands::Tidy => {
+            let hosts_path = get_hosts_path()?;
+            let removed = tidy_temp_files_at(args.dry_run, &hosts_path)?;
+            let expired = remove_expired_entries_at(args.dry_run, &hosts_path)?;
+            for line in &expired {
+                if args.dry_run {
+                    verbosity.info(paint(format_args!("- {}", line), Tone::Removed));
+                } else {
+                    verbosity.info(paint(
+                        format_args!("Removed expired block: {}", line),
+                        Tone::Removed,
+                    ));
+                }
+            }
+            if !args.dry_run && !expired.is_empty() {
+                record_history(&config, HistoryAction::Unblock, &[], &expired);
+                refresh_dns(no_flush, flush_command, verbosity);
+            }
+            if args.json {
+                print_json(
+                    "tidy",
+                    json!({
+                        "removed": !removed.is_empty(),
+                        "paths": removed,
+                        "expired": expired,
+                    }),
+                    json!({ "dry_run": args.dry_run }),
+                )?;
+            } else if removed.is_empty() {
+                verbosity.info("No stale temp files found.");
+            } else {
+                for path in &removed {
+                    if args.dry_run {
+                        verbosity.info(format_args!("Would remove stale temp file {:?}.", path));
+                    } else {
+                        verbosity.info(format_args!("Removed stale temp file {:?}.", path));
+                    }
+                }
+            }
+        }
+        Commands::Normalize => {
+            if !args.dry_run {
+                // A silent dry run tells how much would change
+                let preview = normalize_hosts(true, Verbosity::Quiet)?;
+                if preview.duplicates_removed > 0 || preview.reformatted > 0 {
+                    require_confirmation(
+                        &format!(
+                            "This will remove {} duplicate entries and rewrite {} entries.",
+                            preview.duplicates_removed, preview.reformatted
+                        ),
+                        args.yes,
+                    )?;
+                }
+            }
+            let report = normalize_hosts(args.dry_run, verbosity)?;
+            if args.json {
+                print_json("normalize", &report, json!({ "dry_run": args.dry_run }))?;
+            } else {
+                let outcome = if args.dry_run {
+                    "would be removed (dry run)"
+                } else {
+                    "removed"
+                };
+                verbosity.info(format_args!(
+                    "Duplicate entries {}: {}. Entries reformatted: {}.",
+                    outcome, report.duplicates_removed, report.reformatted
+                ));
+                if report.checksum_reset {
+                    verbosity.info(
+                        "gwd's section had been edited outside gwd; its checksum now matches again.",
+                    );
+                }
+            }
+        }
+        Commands::Config {
+            action: ConfigAction::Path,
+        } => {
+            let path = config_path()?;
+            if args.json {
+                print_json("config-path", json!({ "path": path }), json!({}))?;
+            } else {
+                println!("{}", path.display());
+            }
+        }
+        Commands::Completions { shell } => {
+            clap_complete::generate(shell, &mut Args::command(), "gwd", &mut std::io::stdout());
         }
     }
 
     Ok(())
 }
 
+// The menu shown by `gwd` without a subcommand, for people who'd rather not learn the commands
+const MENU: &str = "\
+What would you like to do?
+  1) List blocked websites
+  2) Block a website
+  3) Unblock a website
+  4) Quit
+";
+
+// Runs the interactive menu on the terminal until the user quits (or stdin ends)
+fn run_menu(args: &Args) -> Result<()> {
+    run_menu_with(args, &mut io::stdin().lock(), &mut io::stdout(), run)
+}
+
+// Each choice runs the matching subcommand with the global flags gwd was started with (e.g.
+// --hosts-path), so hooks, history, DNS flushing and the unblock challenge all work as usual.
+// A failed action prints its error and the menu comes back.
+fn run_menu_with(
+    args: &Args,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+    mut dispatch: impl FnMut(Args, Commands) -> Result<()>,
+) -> Result<()> {
+    loop {
+        write!(out, "\n{}", MENU)?;
+        let Some(choice) = ask("Choose 1-4: ", input, out)? else {
+            return Ok(());
+        };
+        let argv: Vec<String> = match choice.to_lowercase().as_str() {
+            "1" | "list" => vec!["list".to_string()],
+            "2" | "block" => {
+                let Some(sites) = ask("Website(s) to block (e.g. reddit.com): ", input, out)?
+                else {
+                    return Ok(());
+                };
+                std::iter::once("block")
+                    .chain(sites.split_whitespace())
+                    .map(String::from)
+                    .collect()
+            }
+            "3" | "unblock" => {
+                let Some(site) = ask("Website to unblock: ", input, out)? else {
+                    return Ok(());
+                };
+                std::iter::once("unblock")
+                    .chain(site.split_whitespace().take(1))
+                    .map(String::from)
+                    .collect()
+            }
+            "4" | "q" | "quit" | "exit" => return Ok(()),
+            _ => {
+                writeln!(out, "Please enter a number from 1 to 4.")?;
+                continue;
+            }
+        };
+        // An empty answer goes back to the menu
+        if argv.len() < 2 && argv[0] != "list" {
+            continue;
+        }
+        match Args::try_parse_from(std::iter::once("gwd".to_string()).chain(argv)) {
+            Ok(Args {
+                command: Some(command),
+                ..
+            }) => {
+                if let Err(e) = dispatch(args.clone(), command) {
+                    report_error(&e, args.json);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => writeln!(out, "{}", e.to_string().trim_end())?,
+        }
+    }
+}
+
+// Prints `prompt` and reads one answer, trimmed; None once the input has ended
+fn ask(prompt: &str, input: &mut dyn BufRead, out: &mut dyn Write) -> Result<Option<String>> {
+    write!(out, "{}", prompt)?;
+    out.flush()?;
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        writeln!(out)?;
+        return Ok(None);
+    }
+    Ok(Some(answer.trim().to_string()))
+}
+
+fn report_error(e: &AppError, json_output: bool) {
+    if json_output {
+        // Errors go to stdout too, so a JSON consumer only has one stream to parse
+        println!(
+            "{}",
+            json!({ "error": e.to_string(), "code": e.exit_code() })
+        );
+    } else {
+        eprintln!(
+            "{}",
+            paint_stderr(format_args!("Error: {}", e), Tone::Removed)
+        );
+    }
+}
+
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+    let mut args = Args::parse();
+    let json_output = args.json;
+    set_color_choice(if json_output {
+        ColorChoice::Never
+    } else {
+        args.color
+    });
+    if let Err(e) = init_logging(args.log_file.as_deref()) {
+        report_error(&e, json_output);
+        std::process::exit(e.exit_code());
+    }
+    tracing::debug!(
+        version = env!("CARGO_PKG_VERSION"),
+        args = ?std::env::args_os().skip(1).collect::<Vec<_>>(),
+        "gwd started"
+    );
+    let result = match args.command.take() {
+        Some(command) => run(args, command),
+        None if io::stdin().is_terminal() => run_menu(&args),
+        None => Args::command()
+            .error(
+                clap::error::ErrorKind::MissingSubcommand,
+                "a subcommand is required when stdin isn't a terminal (the menu needs one)",
+            )
+            .exit(),
+    };
+    if let Err(e) = result {
+        report_error(&e, json_output);
+        std::process::exit(e.exit_code());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completions_include_subcommands_and_flags() {
+        let mut script = Vec::new();
+        clap_complete::generate(
+            clap_complete::Shell::Bash,
+            &mut Args::command(),
+            "gwd",
+            &mut script,
+        );
+        let script = String::from_utf8(script).unwrap();
+        for expected in ["block", "unblock", "status", "list", "--challenge-length"] {
+            assert!(script.contains(expected), "missing '{}'", expected);
+        }
+    }
+
+    #[test]
+    fn test_www_flags_override_config() {
+        let no_www = Config {
+            add_www: Some(false),
+            ..Default::default()
+        };
+        let options = block_options(None, false, None, None, None, true, &no_www).unwrap();
+        assert!(options.prefixes.is_empty());
+
+        let mut prefixes = options.prefixes;
+        set_www(&mut prefixes, true);
+        set_www(&mut prefixes, true);
+        assert_eq!(prefixes, vec!["www"]);
+        prefixes.push("m".to_string());
+        set_www(&mut prefixes, false);
+        assert_eq!(prefixes, vec!["m"]);
+
+        assert!(Args::try_parse_from(["gwd", "block", "x.com", "--www", "--no-www"]).is_err());
+    }
+
+    #[test]
+    fn test_menu_dispatches_subcommands_and_survives_errors() {
+        let args = Args::try_parse_from(["gwd", "--hosts-path", "scratch/hosts"]).unwrap();
+        let mut input =
+            "1\n2\nreddit.com x.com\nbogus\n3\n\n3\nreddit.com\n2\nnot a domain!\nq\n1\n"
+                .as_bytes();
+        let mut out = Vec::new();
+        let mut dispatched = Vec::new();
+        run_menu_with(&args, &mut input, &mut out, |args, command| {
+            assert_eq!(args.hosts_path, Some(PathBuf::from("scratch/hosts")));
+            dispatched.push(format!("{:?}", command));
+            Err(AppError::InvalidDomain("x".to_string()))
+        })
+        .unwrap();
+
+        // "q" stops the menu before the last "1"
+        assert_eq!(dispatched.len(), 4);
+        assert!(dispatched[0].starts_with("List"));
+        assert!(dispatched[1].contains(r#"domains: ["reddit.com", "x.com"]"#));
+        assert!(dispatched[2].contains(r#"domain: Some("reddit.com")"#));
+        assert!(dispatched[3].contains(r#"domains: ["not", "a", "domain!"]"#));
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Please enter a number from 1 to 4."));
+        assert_eq!(out.matches("What would you like to do?").count(), 7);
+    }
+
+    #[test]
+    fn test_menu_ends_with_the_input() {
+        let args = Args::try_parse_from(["gwd"]).unwrap();
+        let mut out = Vec::new();
+        run_menu_with(&args, &mut "2\n".as_bytes(), &mut out, |_, _| {
+            panic!("nothing to run")
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_reading_commands_need_no_write_access() {
+        let needs = |command: &[&str]| {
+            let args = Args::try_parse_from(["gwd"].iter().chain(command)).unwrap();
+            needs_write_access(&args.command.unwrap())
+        };
+        assert!(!needs(&["list"]));
+        assert!(!needs(&["list", "--count"]));
+        assert!(!needs(&["status", "a.com"]));
+        assert!(needs(&["block", "a.com"]));
+        assert!(needs(&["unblock", "a.com"]));
+    }
+
+    #[test]
+    fn test_block_and_import_over_the_cap_change_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let hosts_path = dir.path().join("hosts");
+        fs::write(&hosts_path, "127.0.0.1 localhost\n").unwrap();
+        let domains: Vec<String> = ["a.com", "b.com", "c.com"].map(String::from).to_vec();
+        let options = BlockOptions::default();
+        let no_hook = |_: &[String]| -> Result<()> { panic!("the pre-block hook ran") };
+
+        for (plain, expansions) in [
+            (&domains[..], Vec::new()),
+            // Expanded patterns count against the cap too
+            (
+                &domains[..1],
+                vec![("wildcard-x-com".to_string(), domains[1..].to_vec())],
+            ),
+        ] {
+            assert!(matches!(
+                block_all_at(plain, expansions, 2, &options, &hosts_path, no_hook),
+                Err(AppError::TooManyEntries { count: 3, max: 2 })
+            ));
+        }
+        let import = parse_import("a.com\nb.com\nc.com\n").unwrap();
+        assert!(matches!(
+            import_at(
+                import_groups(&import).unwrap(),
+                2,
+                |_| Ok(BlockOptions::default()),
+                &hosts_path,
+                no_hook,
+                |_| panic!("a group was blocked"),
+            ),
+            Err(AppError::TooManyEntries { count: 3, max: 2 })
+        ));
+        assert_eq!(
+            fs::read_to_string(&hosts_path).unwrap(),
+            "127.0.0.1 localhost\n"
+        );
+
+        // Within the cap, the same list goes through
+        let report =
+            block_all_at(&domains, Vec::new(), 3, &options, &hosts_path, |_| Ok(())).unwrap();
+        assert_eq!(report.domains.len(), 3);
+    }
+
+    #[test]
+    fn test_hosts_path_is_accepted_by_every_command() {
+        for command in [&["list"][..], &["block", "a.com"], &["unblock", "--all"]] {
+            let args = Args::try_parse_from(
+                ["gwd"]
+                    .iter()
+                    .chain(command)
+                    .chain(&["--hosts-path", "scratch/hosts"]),
+            )
+            .unwrap();
+            assert_eq!(args.hosts_path, Some(PathBuf::from("scratch/hosts")));
+        }
     }
 }
@@ -0,0 +1,268 @@
+use clap::ValueEnum;
+use owo_colors::OwoColorize;
+use std::fmt::Display;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// How much gwd prints while it works. Errors always go to stderr regardless.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    // Nothing but errors (--quiet, and --json where the result is printed separately)
+    Quiet,
+    // Progress messages such as "Adding entry for: ..."
+    #[default]
+    Normal,
+    // Also the hosts path, the regexes used and each line examined (--verbose)
+    Verbose,
+}
+
+impl Verbosity {
+    // Prints a normal progress message
+    pub fn info(self, message: impl Display) {
+        if self >= Verbosity::Normal {
+            println!("{}", message);
+        }
+    }
+
+    // Prints a diagnostic message that only --verbose shows. It also goes to the debug log
+    // (--log-file, RUST_LOG), without colours, whatever the verbosity.
+    pub fn debug(self, message: impl Display) {
+        let print = self >= Verbosity::Verbose;
+        if print || tracing::enabled!(target: VERBOSE_LOG_TARGET, tracing::Level::DEBUG) {
+            let message = message.to_string();
+            tracing::debug!(target: VERBOSE_LOG_TARGET, "{}", strip_styles(&message));
+            if print {
+                println!("{}", message);
+            }
+        }
+    }
+
+    pub fn is_quiet(self) -> bool {
+        self == Verbosity::Quiet
+    }
+}
+
+// Target of the --verbose messages in the debug log
+const VERBOSE_LOG_TARGET: &str = "gwd::verbose";
+
+// When to colour output (--color)
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ColorChoice {
+    /// Colour output going to a terminal, unless NO_COLOR is set.
+    #[default]
+    Auto,
+    /// Always colour output.
+    Always,
+    /// Never colour output.
+    Never,
+}
+
+// What a coloured message is about
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tone {
+    // Entries added or restored (green)
+    Added,
+    // Entries removed, and errors (red)
+    Removed,
+    // Side notes such as "already exists" (dim)
+    Note,
+}
+
+// Batches of at least this many domains report their progress instead of a line per domain
+pub const PROGRESS_THRESHOLD: usize = 200;
+// Width of the progress bar itself, between the brackets
+const PROGRESS_BAR_WIDTH: usize = 30;
+
+// Progress through a long batch, e.g. blocking a 10,000-line list. When stdout is a terminal it is
+// a bar redrawn in place; otherwise the batch gets one line when it starts and one when it ends.
+// Nothing is shown with --quiet (or --json). Dropping it without finish(), e.g. on an error,
+// clears the bar so the error starts on a clean line.
+pub struct Progress {
+    label: String,
+    total: usize,
+    done: usize,
+    // Percentage last drawn, so the bar is only redrawn when it visibly changes
+    drawn: Option<usize>,
+    bar: bool,
+    verbosity: Verbosity,
+}
+
+impl Progress {
+    pub fn start(label: &str, total: usize, verbosity: Verbosity) -> Progress {
+        let progress = Progress {
+            label: label.to_string(),
+            total,
+            done: 0,
+            drawn: None,
+            bar: verbosity == Verbosity::Normal && io::stdout().is_terminal(),
+            verbosity,
+        };
+        if !progress.bar {
+            verbosity.info(format_args!("{} {} entries...", label, total));
+        }
+        progress
+    }
+
+    pub fn advance(&mut self, count: usize) {
+        self.done = (self.done + count).min(self.total);
+        let percent = self.done * 100 / self.total.max(1);
+        if self.bar && self.drawn != Some(percent) {
+            self.drawn = Some(percent);
+            print!("\r{}", render_progress(&self.label, self.done, self.total));
+            let _ = io::stdout().flush();
+        }
+    }
+
+    pub fn finish(mut self) {
+        self.clear();
+        if !self.bar {
+            self.verbosity.info(format_args!(
+                "{} done: {} of {} entries.",
+                self.label, self.done, self.total
+            ));
+        }
+    }
+
+    fn clear(&mut self) {
+        if self.drawn.take().is_some() {
+            print!("\r\x1b[2K");
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+// One frame of the bar, e.g. "Blocking [=========>          ] 312/1000"
+fn render_progress(label: &str, done: usize, total: usize) -> String {
+    let filled = done * PROGRESS_BAR_WIDTH / total.max(1);
+    let head = if filled < PROGRESS_BAR_WIDTH { ">" } else { "" };
+    format!(
+        "{} [{:<width$}] {}/{}",
+        label,
+        format!("{}{}", "=".repeat(filled), head),
+        done,
+        total,
+        width = PROGRESS_BAR_WIDTH
+    )
+}
+
+// Set once from main before anything is printed
+static COLOR_CHOICE: AtomicU8 = AtomicU8::new(ColorChoice::Auto as u8);
+
+pub fn set_color_choice(choice: ColorChoice) {
+    COLOR_CHOICE.store(choice as u8, Ordering::Relaxed);
+}
+
+fn color_choice() -> ColorChoice {
+    match COLOR_CHOICE.load(Ordering::Relaxed) {
+        1 => ColorChoice::Always,
+        2 => ColorChoice::Never,
+        _ => ColorChoice::Auto,
+    }
+}
+
+// Whether to emit escape codes on a stream; see https://no-color.org for NO_COLOR
+fn should_color(choice: ColorChoice, is_terminal: bool, no_color: bool) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => is_terminal && !no_color,
+    }
+}
+
+fn no_color_env() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+fn style(text: impl Display, tone: Tone, enabled: bool) -> String {
+    match (enabled, tone) {
+        (false, _) => text.to_string(),
+        (true, Tone::Added) => text.green().to_string(),
+        (true, Tone::Removed) => text.red().to_string(),
+        (true, Tone::Note) => text.dimmed().to_string(),
+    }
+}
+
+// `text` without the ANSI colour codes paint adds
+fn strip_styles(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // An escape runs up to the letter that ends it, e.g. "\x1b[32m"
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+// Colours a message bound for stdout
+pub fn paint(text: impl Display, tone: Tone) -> String {
+    let enabled = should_color(color_choice(), io::stdout().is_terminal(), no_color_env());
+    style(text, tone, enabled)
+}
+
+// Colours a message bound for stderr
+pub fn paint_stderr(text: impl Display, tone: Tone) -> String {
+    let enabled = should_color(color_choice(), io::stderr().is_terminal(), no_color_env());
+    style(text, tone, enabled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_color() {
+        assert!(should_color(ColorChoice::Auto, true, false));
+        assert!(!should_color(ColorChoice::Auto, true, true));
+        assert!(!should_color(ColorChoice::Auto, false, false));
+        assert!(should_color(ColorChoice::Always, false, true));
+        assert!(!should_color(ColorChoice::Never, true, false));
+    }
+
+    #[test]
+    fn test_strip_styles() {
+        let painted = style("Adding: 0.0.0.0 example.com", Tone::Added, true);
+        assert_ne!(painted, "Adding: 0.0.0.0 example.com");
+        assert_eq!(strip_styles(&painted), "Adding: 0.0.0.0 example.com");
+        assert_eq!(strip_styles("no colours [here]"), "no colours [here]");
+    }
+
+    #[test]
+    fn test_render_progress() {
+        assert_eq!(
+            render_progress("Blocking", 0, 10),
+            format!("Blocking [>{}] 0/10", " ".repeat(29))
+        );
+        assert_eq!(
+            render_progress("Blocking", 5, 10),
+            format!("Blocking [{}>{}] 5/10", "=".repeat(15), " ".repeat(14))
+        );
+        assert_eq!(
+            render_progress("Blocking", 10, 10),
+            format!("Blocking [{}] 10/10", "=".repeat(30))
+        );
+    }
+
+    #[test]
+    fn test_style() {
+        assert_eq!(style("x.com", Tone::Added, false), "x.com");
+        assert_eq!(
+            style("x.com", Tone::Added, true),
+            "\u{1b}[32mx.com\u{1b}[39m"
+        );
+    }
+}
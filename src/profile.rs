@@ -0,0 +1,130 @@
+use crate::error::{AppError, Result};
+use crate::focusmode::FOCUS_MODE_LABEL;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+const PROFILES_FILE_NAME: &str = "profiles.toml";
+// Environment variable that overrides the directory gwd reads its config files from
+pub const CONFIG_DIR_ENV_VAR: &str = "GWD_CONFIG_DIR";
+
+// Named lists of domains, stored as top-level keys in profiles.toml, e.g.
+//   social = ["reddit.com", "twitter.com"]
+pub type Profiles = BTreeMap<String, Vec<String>>;
+
+// Directory holding gwd's config files (e.g. ~/.config/gwd)
+pub fn config_dir() -> Result<PathBuf> {
+    match std::env::var_os(CONFIG_DIR_ENV_VAR) {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => dirs::config_dir()
+            .map(|dir| dir.join("gwd"))
+            .ok_or_else(|| {
+                AppError::Config("Could not determine the config directory".to_string())
+            }),
+    }
+}
+
+// Location of the profiles file
+pub fn profiles_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join(PROFILES_FILE_NAME))
+}
+
+// Loads all profiles; a missing file simply means there are none yet
+pub fn load_profiles() -> Result<Profiles> {
+    let path = profiles_path()?;
+    match fs::read_to_string(&path) {
+        Ok(content) => parse_profiles(&content)
+            .map_err(|e| AppError::Config(format!("Invalid profiles file {:?}: {}", path, e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Profiles::new()),
+        Err(e) => Err(AppError::Io(format!(
+            "Failed to read profiles file {:?}: {}",
+            path, e
+        ))),
+    }
+}
+
+// Writes all profiles back, creating the config directory if needed
+pub fn save_profiles(profiles: &Profiles) -> Result<()> {
+    let path = profiles_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content = toml::to_string(profiles)
+        .map_err(|e| AppError::Config(format!("Failed to serialize profiles: {}", e)))?;
+    fs::write(&path, content)
+        .map_err(|e| AppError::Io(format!("Failed to write profiles file {:?}: {}", path, e)))
+}
+
+// Looks up the domains of one profile
+pub fn profile_domains(profiles: &Profiles, name: &str) -> Result<Vec<String>> {
+    match profiles.get(name) {
+        Some(domains) if !domains.is_empty() => Ok(domains.clone()),
+        Some(_) => Err(AppError::Config(format!(
+            "Profile '{}' has no domains",
+            name
+        ))),
+        None => Err(AppError::UnknownProfile(name.to_string())),
+    }
+}
+
+// Profile names end up inside "[...]" in the hosts file comment, so keep them simple
+pub fn validate_profile_name(name: &str) -> Result<()> {
+    if name == FOCUS_MODE_LABEL {
+        return Err(AppError::Config(format!(
+            "'{}' is reserved for gwd focus-mode",
+            name
+        )));
+    }
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::Config(format!(
+            "Invalid profile name '{}': use letters, digits, '-' and '_' only",
+            name
+        )))
+    }
+}
+
+fn parse_profiles(content: &str) -> std::result::Result<Profiles, toml::de::Error> {
+    toml::from_str(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profiles() {
+        let profiles =
+            parse_profiles("social = [\"reddit.com\", \"x.com\"]\nnews = [\"cnn.com\"]\n").unwrap();
+        assert_eq!(
+            profile_domains(&profiles, "social").unwrap(),
+            vec!["reddit.com", "x.com"]
+        );
+        assert!(matches!(
+            profile_domains(&profiles, "games"),
+            Err(AppError::UnknownProfile(_))
+        ));
+    }
+
+    #[test]
+    fn test_profiles_round_trip() {
+        let mut profiles = Profiles::new();
+        profiles.insert("social".to_string(), vec!["reddit.com".to_string()]);
+        let content = toml::to_string(&profiles).unwrap();
+        assert_eq!(parse_profiles(&content).unwrap(), profiles);
+    }
+
+    #[test]
+    fn test_validate_profile_name() {
+        assert!(validate_profile_name("deep_work-2").is_ok());
+        assert!(validate_profile_name("").is_err());
+        assert!(validate_profile_name("has space").is_err());
+        assert!(validate_profile_name("a]b").is_err());
+        assert!(validate_profile_name(FOCUS_MODE_LABEL).is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use crate::error::{AppError, Result};
+use std::io::{self, BufRead, IsTerminal, Write};
+
+// Asks a yes/no question on stderr before a change that rewrites many entries at once, e.g.
+// "This will remove 42 entries. Continue? [y/N]". `assume_yes` (--yes) answers for the user.
+// Without a terminal to ask on, the answer is no, so a script never edits more than it meant to.
+pub fn confirm(prompt: &str, assume_yes: bool) -> Result<bool> {
+    if assume_yes {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        eprintln!("{} Not confirmed: stdin is not a terminal.", prompt);
+        return Ok(false);
+    }
+    confirm_from(prompt, &mut io::stdin().lock(), &mut io::stderr())
+}
+
+// Like confirm, but fails with AppError::Cancelled when the answer is no
+pub fn require_confirmation(prompt: &str, assume_yes: bool) -> Result<()> {
+    if confirm(prompt, assume_yes)? {
+        Ok(())
+    } else {
+        Err(AppError::Cancelled)
+    }
+}
+
+fn confirm_from(prompt: &str, input: &mut dyn BufRead, out: &mut dyn Write) -> Result<bool> {
+    write!(out, "{} Continue? [y/N] ", prompt)?;
+    out.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirm_from_defaults_to_no() {
+        let mut out = Vec::new();
+        for (answer, expected) in [
+            ("y\n", true),
+            ("YES\n", true),
+            ("n\n", false),
+            ("\n", false),
+            ("", false),
+            ("sure\n", false),
+        ] {
+            let mut input = answer.as_bytes();
+            assert_eq!(
+                confirm_from("Remove?", &mut input, &mut out).unwrap(),
+                expected,
+                "{:?}",
+                answer
+            );
+        }
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .starts_with("Remove? Continue? [y/N] "));
+    }
+
+    #[test]
+    fn test_assume_yes_skips_the_question() {
+        assert!(confirm("Remove?", true).unwrap());
+    }
+}
@@ -0,0 +1,172 @@
+use crate::error::{AppError, Result};
+use crate::history::state_dir;
+use crate::hosts::{parse_domain_list, DomainFile};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Downloaded lists are kept here (inside the state directory), one file per URL
+const LIST_CACHE_DIR_NAME: &str = "lists";
+// Anything bigger is not a blocklist gwd should be writing into the hosts file
+#[cfg_attr(not(feature = "remote-lists"), allow(dead_code))]
+const MAX_LIST_BYTES: u64 = 10 * 1024 * 1024;
+
+// A blocklist fetched with --list-url
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteList {
+    // Label its entries are tagged with in the hosts file, derived from the URL
+    pub label: String,
+    pub domains: DomainFile,
+}
+
+// The hosts file label for a list URL. Labels end up inside "[...]", so the URL itself can't be
+// used; a hash keeps it short and the same on every run.
+pub fn list_label(url: &str) -> String {
+    format!("list-{:08x}", fnv1a(url.trim().as_bytes()) as u32)
+}
+
+// Downloads the list at `url`, validates it and keeps a copy for a later unblock
+pub fn fetch_list(url: &str, allow_ip: bool) -> Result<RemoteList> {
+    let content = download(url)?;
+    let list = parse_list(url, &content, allow_ip)?;
+    cache_list_at(&list_cache_dir()?, url, &content)?;
+    Ok(list)
+}
+
+// The list as it was when it was last blocked, so unblocking removes exactly that set even if
+// the list has changed upstream or the network is down. Downloads it if it was never cached.
+pub fn cached_list(url: &str, allow_ip: bool) -> Result<RemoteList> {
+    match fs::read_to_string(cache_path_at(&list_cache_dir()?, url)) {
+        Ok(content) => parse_list(url, &content, allow_ip),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => fetch_list(url, allow_ip),
+        Err(e) => Err(AppError::Io(format!(
+            "Failed to read the cached copy of {}: {}",
+            url, e
+        ))),
+    }
+}
+
+fn list_cache_dir() -> Result<PathBuf> {
+    Ok(state_dir()?.join(LIST_CACHE_DIR_NAME))
+}
+
+fn cache_path_at(dir: &Path, url: &str) -> PathBuf {
+    dir.join(format!("{}.txt", list_label(url)))
+}
+
+fn cache_list_at(dir: &Path, url: &str, content: &str) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = cache_path_at(dir, url);
+    // The URL goes first as a comment, so the cached file says where it came from
+    fs::write(&path, format!("# {}\n{}", url.trim(), content))
+        .map_err(|e| AppError::Io(format!("Failed to cache blocklist {:?}: {}", path, e)))
+}
+
+// A response that parses to no domains at all (e.g. an error page served with status 200) is
+// rejected rather than treated as an empty list
+fn parse_list(url: &str, content: &str, allow_ip: bool) -> Result<RemoteList> {
+    if content.trim_start().starts_with('<') {
+        return Err(AppError::MalformedList(format!(
+            "{} returned HTML, not a list of domains",
+            url
+        )));
+    }
+    let domains = parse_domain_list(content, allow_ip);
+    if domains.domains.is_empty() {
+        return Err(AppError::MalformedList(format!(
+            "{} contains no valid domains",
+            url
+        )));
+    }
+    Ok(RemoteList {
+        label: list_label(url),
+        domains,
+    })
+}
+
+#[cfg(feature = "remote-lists")]
+fn download(url: &str) -> Result<String> {
+    use std::io::Read;
+    use std::time::Duration;
+
+    let response = ureq::get(url.trim())
+        .timeout(Duration::from_secs(30))
+        .call()
+        .map_err(|e| match e {
+            ureq::Error::Status(code, _) => {
+                AppError::Http(format!("{} returned HTTP {}", url, code))
+            }
+            ureq::Error::Transport(transport) => AppError::Http(format!("{}: {}", url, transport)),
+        })?;
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_LIST_BYTES + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| AppError::Http(format!("{}: {}", url, e)))?;
+    if bytes.len() as u64 > MAX_LIST_BYTES {
+        return Err(AppError::MalformedList(format!(
+            "{} is larger than {} MiB",
+            url,
+            MAX_LIST_BYTES / (1024 * 1024)
+        )));
+    }
+    String::from_utf8(bytes)
+        .map_err(|_| AppError::MalformedList(format!("{} is not UTF-8 text", url)))
+}
+
+#[cfg(not(feature = "remote-lists"))]
+fn download(url: &str) -> Result<String> {
+    Err(AppError::Config(format!(
+        "Can't download {}: this gwd was built without the 'remote-lists' feature",
+        url
+    )))
+}
+
+// 64-bit FNV-1a; unlike std's hashers its output is fixed, so cache names survive upgrades
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://example.com/social.txt";
+
+    #[test]
+    fn test_list_label_is_stable_and_valid() {
+        let label = list_label(URL);
+        assert_eq!(label, list_label(&format!(" {} ", URL)));
+        assert_ne!(label, list_label("https://example.com/news.txt"));
+        assert!(crate::profile::validate_profile_name(&label).is_ok());
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let list = parse_list(URL, "# social\nreddit.com\nnot a domain\n", false).unwrap();
+        assert_eq!(list.domains.domains, vec!["reddit.com"]);
+        assert_eq!(list.domains.invalid, vec![(3, "not a domain".to_string())]);
+        assert_eq!(list.label, list_label(URL));
+
+        assert!(matches!(
+            parse_list(URL, "<!DOCTYPE html><html></html>", false),
+            Err(AppError::MalformedList(_))
+        ));
+        assert!(matches!(
+            parse_list(URL, "# nothing here\n", false),
+            Err(AppError::MalformedList(_))
+        ));
+    }
+
+    #[test]
+    fn test_cached_copy_parses_back() {
+        let dir = tempfile::tempdir().unwrap();
+        cache_list_at(dir.path(), URL, "reddit.com\ntwitter.com\n").unwrap();
+
+        let content = fs::read_to_string(cache_path_at(dir.path(), URL)).unwrap();
+        let list = parse_list(URL, &content, false).unwrap();
+        assert_eq!(list.domains.domains, vec!["reddit.com", "twitter.com"]);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::error::{AppError, Result};
+use crate::profile::config_dir;
+use chrono::{Datelike, Days, NaiveDateTime, NaiveTime, Weekday};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const SCHEDULE_FILE_NAME: &str = "schedule.toml";
+// Back-to-back no-unblock periods are followed this many times when working out how long the
+// current one lasts, so a period that never ends can't loop forever
+const MAX_CHAINED_PERIODS: usize = 14;
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+// schedule.toml as written by the user, e.g.
+//   [[window]]
+//   profile = "social"
+//   days = "mon-fri"
+//   start = "09:00"
+//   end = "17:00"
+#[derive(Debug, Default, Deserialize)]
+struct ScheduleFile {
+    #[serde(default)]
+    window: Vec<RawWindow>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawWindow {
+    profile: String,
+    // e.g. "mon-fri", "sat,sun" or "daily"; defaults to every day
+    #[serde(default = "every_day")]
+    days: String,
+    start: String,
+    end: String,
+}
+
+fn every_day() -> String {
+    "daily".to_string()
+}
+
+// A time range on some days of the week. A period whose end is at or before its start runs
+// past midnight (e.g. 22:00-06:00) and belongs to the day it starts on. In config.toml, e.g.
+//   [[no_unblock]]
+//   days = "mon-fri"
+//   start = "00:00"
+//   end = "18:00"
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawPeriod")]
+pub struct Period {
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPeriod {
+    #[serde(default = "every_day")]
+    days: String,
+    start: String,
+    end: String,
+}
+
+impl TryFrom<RawPeriod> for Period {
+    type Error = String;
+
+    fn try_from(raw: RawPeriod) -> std::result::Result<Period, String> {
+        Ok(Period {
+            days: parse_days(&raw.days)?,
+            start: parse_time(&raw.start)?,
+            end: parse_time(&raw.end)?,
+        })
+    }
+}
+
+impl Period {
+    // Whether `now` (local time) falls inside this period
+    pub fn contains(&self, now: NaiveDateTime) -> bool {
+        let time = now.time();
+        let today = now.weekday();
+        if self.start < self.end {
+            self.days.contains(&today) && time >= self.start && time < self.end
+        } else {
+            (self.days.contains(&today) && time >= self.start)
+                || (self.days.contains(&today.pred()) && time < self.end)
+        }
+    }
+
+    // When the occurrence of this period that contains `now` ends
+    fn end_after(&self, now: NaiveDateTime) -> NaiveDateTime {
+        let date = now.date();
+        if self.start >= self.end && now.time() >= self.start {
+            (date + Days::new(1)).and_time(self.end)
+        } else {
+            date.and_time(self.end)
+        }
+    }
+}
+
+// How long unblocking stays forbidden when `now` falls inside one of the no-unblock periods
+// from config.toml, following on into any period that starts as the current one ends
+pub fn no_unblock_remaining(periods: &[Period], now: NaiveDateTime) -> Option<Duration> {
+    let mut until = now;
+    for _ in 0..MAX_CHAINED_PERIODS {
+        match periods.iter().find(|period| period.contains(until)) {
+            Some(period) => until = period.end_after(until),
+            None => break,
+        }
+    }
+    (until > now).then(|| (until - now).to_std().unwrap_or_default())
+}
+
+// A time range during which a profile should be blocked
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Window {
+    pub profile: String,
+    pub period: Period,
+}
+
+impl Window {
+    // Whether `now` (local time) falls inside this window
+    pub fn contains(&self, now: NaiveDateTime) -> bool {
+        self.period.contains(now)
+    }
+}
+
+// What apply-schedule has to do to match the schedule
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SchedulePlan {
+    // Profiles inside one of their windows. Blocking is idempotent, so these are always
+    // (re)applied, which also picks up domains added to a profile since it was blocked.
+    pub block: Vec<String>,
+    // Scheduled profiles outside all their windows that still have entries in the hosts file
+    pub unblock: Vec<String>,
+}
+
+// Location of the schedule file, next to profiles.toml
+pub fn schedule_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join(SCHEDULE_FILE_NAME))
+}
+
+// Loads the schedule; a missing file means nothing is scheduled
+pub fn load_schedule() -> Result<Vec<Window>> {
+    let path = schedule_path()?;
+    match fs::read_to_string(&path) {
+        Ok(content) => parse_schedule(&content)
+            .map_err(|e| AppError::Config(format!("Invalid schedule file {:?}: {}", path, e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(AppError::Io(format!(
+            "Failed to read schedule file {:?}: {}",
+            path, e
+        ))),
+    }
+}
+
+// Diffs the schedule against the labels currently blocked in the hosts file
+pub fn plan_schedule(
+    windows: &[Window],
+    now: NaiveDateTime,
+    blocked_labels: &BTreeSet<String>,
+) -> SchedulePlan {
+    let mut plan = SchedulePlan::default();
+    let scheduled: BTreeSet<&str> = windows.iter().map(|w| w.profile.as_str()).collect();
+    for profile in scheduled {
+        let active = windows
+            .iter()
+            .any(|w| w.profile == profile && w.contains(now));
+        if active {
+            plan.block.push(profile.to_string());
+        } else if blocked_labels.contains(profile) {
+            plan.unblock.push(profile.to_string());
+        }
+    }
+    plan
+}
+
+fn parse_schedule(content: &str) -> std::result::Result<Vec<Window>, String> {
+    let file: ScheduleFile = toml::from_str(content).map_err(|e| e.to_string())?;
+    file.window
+        .into_iter()
+        .map(|raw| {
+            Ok(Window {
+                period: Period {
+                    days: parse_days(&raw.days)?,
+                    start: parse_time(&raw.start)?,
+                    end: parse_time(&raw.end)?,
+                },
+                profile: raw.profile,
+            })
+        })
+        .collect()
+}
+
+fn parse_time(input: &str) -> std::result::Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(input.trim(), "%H:%M")
+        .map_err(|_| format!("invalid time '{}' (expected HH:MM)", input))
+}
+
+// Parses "daily", a single day, a range ("mon-fri", "fri-mon") or a comma-separated list of those
+fn parse_days(input: &str) -> std::result::Result<Vec<Weekday>, String> {
+    let input = input.trim().to_lowercase();
+    if input == "daily" || input == "*" {
+        return Ok(WEEKDAYS.to_vec());
+    }
+    let mut days = Vec::new();
+    for part in input.split(',') {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (parse_weekday(first)?, parse_weekday(last)?),
+            None => (parse_weekday(part)?, parse_weekday(part)?),
+        };
+        let mut day = first;
+        loop {
+            if !days.contains(&day) {
+                days.push(day);
+            }
+            if day == last {
+                break;
+            }
+            day = day.succ();
+        }
+    }
+    Ok(days)
+}
+
+fn parse_weekday(input: &str) -> std::result::Result<Weekday, String> {
+    input
+        .trim()
+        .parse::<Weekday>()
+        .map_err(|_| format!("invalid day '{}' (expected e.g. mon or mon-fri)", input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::collections::BTreeMap;
+
+    fn at(day: u32, time: &str) -> NaiveDateTime {
+        // 2024-01-01 was a Monday
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_time(parse_time(time).unwrap())
+    }
+
+    #[test]
+    fn test_parse_days() {
+        assert_eq!(parse_days("mon-fri").unwrap().len(), 5);
+        assert_eq!(
+            parse_days("Sat, sun").unwrap(),
+            vec![Weekday::Sat, Weekday::Sun]
+        );
+        assert_eq!(
+            parse_days("fri-mon").unwrap(),
+            vec![Weekday::Fri, Weekday::Sat, Weekday::Sun, Weekday::Mon]
+        );
+        assert_eq!(parse_days("daily").unwrap().len(), 7);
+        assert!(parse_days("someday").is_err());
+    }
+
+    #[test]
+    fn test_window_contains() {
+        let work = parse_schedule(
+            "[[window]]\nprofile = \"social\"\ndays = \"mon-fri\"\nstart = \"09:00\"\nend = \"17:00\"\n",
+        )
+        .unwrap()
+        .remove(0);
+        assert!(work.contains(at(1, "09:00")));
+        assert!(!work.contains(at(1, "17:00")));
+        assert!(!work.contains(at(6, "10:00"))); // Saturday
+
+        let night = parse_schedule(
+            "[[window]]\nprofile = \"games\"\ndays = \"fri\"\nstart = \"22:00\"\nend = \"06:00\"\n",
+        )
+        .unwrap()
+        .remove(0);
+        assert!(night.contains(at(5, "23:00"))); // Friday night
+        assert!(night.contains(at(6, "05:59"))); // early Saturday
+        assert!(!night.contains(at(5, "05:00"))); // early Friday belongs to Thursday's window
+    }
+
+    #[test]
+    fn test_no_unblock_remaining() {
+        let periods: Vec<Period> = toml::from_str::<BTreeMap<String, Vec<Period>>>(
+            "[[p]]\ndays = \"mon-fri\"\nstart = \"00:00\"\nend = \"18:00\"\n\
+             [[p]]\ndays = \"fri\"\nstart = \"22:00\"\nend = \"02:00\"\n",
+        )
+        .unwrap()
+        .remove("p")
+        .unwrap();
+        let hours = |h: u64| Some(Duration::from_secs(h * 3_600));
+
+        assert_eq!(no_unblock_remaining(&periods, at(1, "10:00")), hours(8));
+        assert_eq!(no_unblock_remaining(&periods, at(1, "18:00")), None);
+        assert_eq!(no_unblock_remaining(&periods, at(6, "10:00")), None); // Saturday
+        assert_eq!(no_unblock_remaining(&periods, at(5, "23:00")), hours(3));
+
+        // Back-to-back periods count as one
+        let chained = [
+            periods[0].clone(),
+            Period {
+                days: vec![Weekday::Mon],
+                start: parse_time("18:00").unwrap(),
+                end: parse_time("20:00").unwrap(),
+            },
+        ];
+        assert_eq!(no_unblock_remaining(&chained, at(1, "17:00")), hours(3));
+
+        assert!(toml::from_str::<Period>("start = \"9am\"\nend = \"17:00\"\n").is_err());
+    }
+
+    #[test]
+    fn test_plan_schedule() {
+        let windows = parse_schedule(
+            "[[window]]\nprofile = \"social\"\ndays = \"mon-fri\"\nstart = \"09:00\"\nend = \"17:00\"\n\
+             [[window]]\nprofile = \"news\"\nstart = \"06:00\"\nend = \"08:00\"\n",
+        )
+        .unwrap();
+        let blocked: BTreeSet<String> = ["news".to_string()].into();
+
+        let plan = plan_schedule(&windows, at(1, "10:00"), &blocked);
+        assert_eq!(plan.block, vec!["social"]);
+        assert_eq!(plan.unblock, vec!["news"]);
+
+        let plan = plan_schedule(&windows, at(1, "07:00"), &blocked);
+        assert_eq!(plan.block, vec!["news"]);
+        assert!(plan.unblock.is_empty());
+    }
+}
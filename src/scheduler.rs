@@ -0,0 +1,478 @@
+use crate::error::{AppError, Result};
+use crate::history::{state_dir, STATE_DIR_ENV_VAR};
+use crate::hosts::{hosts_path_override, HOSTS_PATH_ENV_VAR};
+use crate::output::Verbosity;
+use crate::profile::{config_dir, CONFIG_DIR_ENV_VAR};
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// What the job is called on each platform
+#[cfg(target_os = "linux")]
+const JOB_NAME: &str = "gwd-schedule";
+#[cfg(target_os = "macos")]
+const JOB_LABEL: &str = "com.gwd.apply-schedule";
+#[cfg(windows)]
+const TASK_NAME: &str = "gwd-apply-schedule";
+
+// What `gwd schedule install/uninstall` did, so it can say exactly what changed
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SchedulerReport {
+    // Files written (install) or deleted (uninstall)
+    pub files: Vec<PathBuf>,
+    // Commands run to (de)register the job, as they would be typed in a shell
+    pub commands: Vec<String>,
+    // Install found the job already set up exactly like this; uninstall found nothing to remove
+    pub unchanged: bool,
+}
+
+// The recurring `gwd apply-schedule` run. The job runs as root/SYSTEM (it edits the hosts file),
+// whose config and state directories differ from the installing user's, so the directories this
+// gwd resolves to (and a --hosts-path or GWD_HOSTS_PATH choice) are passed along explicitly.
+struct Job {
+    exe: PathBuf,
+    env: Vec<(&'static str, String)>,
+}
+
+impl Job {
+    fn current() -> Result<Job> {
+        let exe = std::env::current_exe()
+            .map_err(|e| AppError::Io(format!("Could not locate the gwd executable: {}", e)))?;
+        let mut env = vec![
+            (CONFIG_DIR_ENV_VAR, config_dir()?.display().to_string()),
+            (STATE_DIR_ENV_VAR, state_dir()?.display().to_string()),
+        ];
+        if let Some(hosts_path) = hosts_path_override() {
+            env.push((HOSTS_PATH_ENV_VAR, hosts_path.display().to_string()));
+        }
+        Ok(Job { exe, env })
+    }
+}
+
+// Registers a job running `gwd apply-schedule` every minute. Running it again with nothing
+// changed leaves everything as it is.
+pub fn install_scheduler(dry_run: bool, verbosity: Verbosity) -> Result<SchedulerReport> {
+    let job = Job::current()?;
+    platform::install(&job, dry_run, verbosity)
+}
+
+// Removes the job registered by install_scheduler
+pub fn uninstall_scheduler(dry_run: bool, verbosity: Verbosity) -> Result<SchedulerReport> {
+    platform::uninstall(dry_run, verbosity)
+}
+
+// Writes `content` to `path` unless it already holds exactly that. Returns whether it wrote.
+fn write_if_changed(path: &Path, content: &str, dry_run: bool) -> Result<bool> {
+    if fs::read_to_string(path).is_ok_and(|existing| existing == content) {
+        return Ok(false);
+    }
+    if !dry_run {
+        fs::write(path, content).map_err(|e| file_error(e, path))?;
+    }
+    Ok(true)
+}
+
+// Deletes `path` if it exists. Returns whether there was one.
+fn remove_if_present(path: &Path, dry_run: bool) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    if !dry_run {
+        fs::remove_file(path).map_err(|e| file_error(e, path))?;
+    }
+    Ok(true)
+}
+
+// Says which file was written or removed ("Would write ..." in a dry run)
+fn report_file(done: &str, planned: &str, path: &Path, dry_run: bool, verbosity: Verbosity) {
+    let verb = if dry_run { planned } else { done };
+    verbosity.info(format_args!("{} {:?}", verb, path));
+}
+
+fn file_error(e: io::Error, path: &Path) -> AppError {
+    match e.kind() {
+        io::ErrorKind::PermissionDenied => AppError::PermissionDenied(path.to_path_buf()),
+        _ => AppError::Io(format!("Failed to write {:?}: {}", path, e)),
+    }
+}
+
+// Runs a registration command (unless dry_run) and records it in the report
+fn run_command(
+    program: &str,
+    args: &[&str],
+    dry_run: bool,
+    verbosity: Verbosity,
+    report: &mut SchedulerReport,
+) -> Result<()> {
+    let command_line = format!("{} {}", program, args.join(" "));
+    if dry_run {
+        verbosity.info(format_args!("Would run: {}", command_line));
+    } else {
+        verbosity.info(format_args!("Running: {}", command_line));
+        tracing::debug!(program, ?args, "registering with the scheduler");
+        let output = std::process::Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| AppError::Scheduler(format!("failed to run '{}': {}", program, e)))?;
+        if !output.status.success() {
+            return Err(AppError::Scheduler(format!(
+                "'{}' failed: {}",
+                command_line,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+    }
+    report.commands.push(command_line);
+    Ok(())
+}
+
+// Fails early with PermissionDenied instead of half-installing the job
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn require_root(path: &Path) -> Result<()> {
+    if nix::unistd::Uid::effective().is_root() {
+        Ok(())
+    } else {
+        Err(AppError::PermissionDenied(path.to_path_buf()))
+    }
+}
+
+// A systemd service plus a timer firing it every minute, installed system-wide
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+
+    const UNIT_DIR: &str = "/etc/systemd/system";
+
+    pub fn install(job: &Job, dry_run: bool, verbosity: Verbosity) -> Result<SchedulerReport> {
+        if !dry_run {
+            require_root(Path::new(UNIT_DIR))?;
+        }
+        let (service_path, timer_path) = unit_paths();
+        let mut report = SchedulerReport::default();
+        for (path, content) in [
+            (service_path, service_unit(job)),
+            (timer_path, timer_unit()),
+        ] {
+            if write_if_changed(&path, &content, dry_run)? {
+                report_file("Wrote", "Would write", &path, dry_run, verbosity);
+                report.files.push(path);
+            }
+        }
+        report.unchanged = report.files.is_empty();
+        let timer = format!("{}.timer", JOB_NAME);
+        run_command(
+            "systemctl",
+            &["daemon-reload"],
+            dry_run,
+            verbosity,
+            &mut report,
+        )?;
+        run_command(
+            "systemctl",
+            &["enable", "--now", &timer],
+            dry_run,
+            verbosity,
+            &mut report,
+        )?;
+        Ok(report)
+    }
+
+    pub fn uninstall(dry_run: bool, verbosity: Verbosity) -> Result<SchedulerReport> {
+        let (service_path, timer_path) = unit_paths();
+        let mut report = SchedulerReport::default();
+        if !timer_path.exists() && !service_path.exists() {
+            report.unchanged = true;
+            return Ok(report);
+        }
+        if !dry_run {
+            require_root(Path::new(UNIT_DIR))?;
+        }
+        let timer = format!("{}.timer", JOB_NAME);
+        run_command(
+            "systemctl",
+            &["disable", "--now", &timer],
+            dry_run,
+            verbosity,
+            &mut report,
+        )?;
+        for path in [timer_path, service_path] {
+            if remove_if_present(&path, dry_run)? {
+                report_file("Removed", "Would remove", &path, dry_run, verbosity);
+                report.files.push(path);
+            }
+        }
+        run_command(
+            "systemctl",
+            &["daemon-reload"],
+            dry_run,
+            verbosity,
+            &mut report,
+        )?;
+        Ok(report)
+    }
+
+    fn unit_paths() -> (PathBuf, PathBuf) {
+        let dir = Path::new(UNIT_DIR);
+        (
+            dir.join(format!("{}.service", JOB_NAME)),
+            dir.join(format!("{}.timer", JOB_NAME)),
+        )
+    }
+
+    pub(super) fn service_unit(job: &Job) -> String {
+        let mut unit = String::from(
+            "[Unit]\nDescription=Apply the gwd blocking schedule\n\n[Service]\nType=oneshot\n",
+        );
+        for (key, value) in &job.env {
+            unit.push_str(&format!("Environment=\"{}={}\"\n", key, value));
+        }
+        unit.push_str(&format!(
+            "ExecStart=\"{}\" apply-schedule --quiet\n",
+            job.exe.display()
+        ));
+        unit
+    }
+
+    pub(super) fn timer_unit() -> String {
+        format!(
+            "[Unit]\nDescription=Run gwd apply-schedule every minute\n\n\
+             [Timer]\nOnCalendar=*-*-* *:*:00\nUnit={}.service\n\n\
+             [Install]\nWantedBy=timers.target\n",
+            JOB_NAME
+        )
+    }
+}
+
+// A launchd daemon with a 60 second StartInterval
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+
+    const DAEMON_DIR: &str = "/Library/LaunchDaemons";
+
+    pub fn install(job: &Job, dry_run: bool, verbosity: Verbosity) -> Result<SchedulerReport> {
+        if !dry_run {
+            require_root(Path::new(DAEMON_DIR))?;
+        }
+        let path = plist_path();
+        let path_arg = path.display().to_string();
+        let mut report = SchedulerReport::default();
+        if write_if_changed(&path, &launchd_plist(job), dry_run)? {
+            report_file("Wrote", "Would write", &path, dry_run, verbosity);
+            report.files.push(path);
+            // Reload so launchd picks up the new definition; unloading a job that isn't
+            // loaded yet fails harmlessly
+            if !dry_run {
+                let _ = std::process::Command::new("launchctl")
+                    .args(["unload", &path_arg])
+                    .output();
+            }
+            run_command(
+                "launchctl",
+                &["load", "-w", &path_arg],
+                dry_run,
+                verbosity,
+                &mut report,
+            )?;
+        } else {
+            report.unchanged = true;
+        }
+        Ok(report)
+    }
+
+    pub fn uninstall(dry_run: bool, verbosity: Verbosity) -> Result<SchedulerReport> {
+        let path = plist_path();
+        let mut report = SchedulerReport::default();
+        if !path.exists() {
+            report.unchanged = true;
+            return Ok(report);
+        }
+        if !dry_run {
+            require_root(Path::new(DAEMON_DIR))?;
+        }
+        let path_arg = path.display().to_string();
+        run_command(
+            "launchctl",
+            &["unload", "-w", &path_arg],
+            dry_run,
+            verbosity,
+            &mut report,
+        )?;
+        if remove_if_present(&path, dry_run)? {
+            report_file("Removed", "Would remove", &path, dry_run, verbosity);
+            report.files.push(path);
+        }
+        Ok(report)
+    }
+
+    fn plist_path() -> PathBuf {
+        Path::new(DAEMON_DIR).join(format!("{}.plist", JOB_LABEL))
+    }
+
+    pub(super) fn launchd_plist(job: &Job) -> String {
+        let mut env = String::new();
+        for (key, value) in &job.env {
+            env.push_str(&format!(
+                "        <key>{}</key>\n        <string>{}</string>\n",
+                key,
+                xml_escape(value)
+            ));
+        }
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+             <plist version=\"1.0\">\n\
+             <dict>\n\
+             \x20   <key>Label</key>\n\
+             \x20   <string>{}</string>\n\
+             \x20   <key>ProgramArguments</key>\n\
+             \x20   <array>\n\
+             \x20       <string>{}</string>\n\
+             \x20       <string>apply-schedule</string>\n\
+             \x20       <string>--quiet</string>\n\
+             \x20   </array>\n\
+             \x20   <key>EnvironmentVariables</key>\n\
+             \x20   <dict>\n\
+             {}\
+             \x20   </dict>\n\
+             \x20   <key>StartInterval</key>\n\
+             \x20   <integer>60</integer>\n\
+             </dict>\n\
+             </plist>\n",
+            JOB_LABEL,
+            xml_escape(&job.exe.display().to_string()),
+            env
+        )
+    }
+
+    fn xml_escape(value: &str) -> String {
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+}
+
+// A Task Scheduler task running as SYSTEM every minute. schtasks /Create /F replaces an existing
+// task, so installing twice is harmless.
+#[cfg(windows)]
+mod platform {
+    use super::*;
+
+    pub fn install(job: &Job, dry_run: bool, verbosity: Verbosity) -> Result<SchedulerReport> {
+        let mut report = SchedulerReport::default();
+        // schtasks has no way to set environment variables, so they go on a cmd line
+        let mut command = String::new();
+        for (key, value) in &job.env {
+            command.push_str(&format!("set \"{}={}\" && ", key, value));
+        }
+        command.push_str(&format!("\"{}\" apply-schedule --quiet", job.exe.display()));
+        let task_command = format!("cmd /c {}", command);
+        run_schtasks(
+            &[
+                "/Create",
+                "/F",
+                "/TN",
+                TASK_NAME,
+                "/SC",
+                "MINUTE",
+                "/MO",
+                "1",
+                "/RU",
+                "SYSTEM",
+                "/TR",
+                &task_command,
+            ],
+            dry_run,
+            verbosity,
+            &mut report,
+        )?;
+        Ok(report)
+    }
+
+    pub fn uninstall(dry_run: bool, verbosity: Verbosity) -> Result<SchedulerReport> {
+        let mut report = SchedulerReport::default();
+        let exists = std::process::Command::new("schtasks")
+            .args(["/Query", "/TN", TASK_NAME])
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if !exists {
+            report.unchanged = true;
+            return Ok(report);
+        }
+        run_schtasks(
+            &["/Delete", "/F", "/TN", TASK_NAME],
+            dry_run,
+            verbosity,
+            &mut report,
+        )?;
+        Ok(report)
+    }
+
+    // schtasks reports missing admin rights as "Access is denied"
+    fn run_schtasks(
+        args: &[&str],
+        dry_run: bool,
+        verbosity: Verbosity,
+        report: &mut SchedulerReport,
+    ) -> Result<()> {
+        match run_command("schtasks", args, dry_run, verbosity, report) {
+            Err(AppError::Scheduler(message)) if message.contains("Access is denied") => {
+                Err(AppError::PermissionDenied(PathBuf::from(format!(
+                    "Task Scheduler ({})",
+                    TASK_NAME
+                ))))
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod platform {
+    use super::*;
+
+    pub fn install(_job: &Job, _dry_run: bool, _verbosity: Verbosity) -> Result<SchedulerReport> {
+        Err(AppError::UnsupportedOS(std::env::consts::OS.to_string()))
+    }
+
+    pub fn uninstall(_dry_run: bool, _verbosity: Verbosity) -> Result<SchedulerReport> {
+        Err(AppError::UnsupportedOS(std::env::consts::OS.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_systemd_units() {
+        let job = Job {
+            exe: PathBuf::from("/usr/local/bin/gwd"),
+            env: vec![(CONFIG_DIR_ENV_VAR, "/home/me/.config/gwd".to_string())],
+        };
+
+        let service = platform::service_unit(&job);
+        assert!(service.contains("Environment=\"GWD_CONFIG_DIR=/home/me/.config/gwd\"\n"));
+        assert!(service.contains("ExecStart=\"/usr/local/bin/gwd\" apply-schedule --quiet\n"));
+        assert!(platform::timer_unit().contains("OnCalendar=*-*-* *:*:00\n"));
+    }
+
+    #[test]
+    fn test_write_if_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("job");
+
+        assert!(write_if_changed(&path, "a", true).unwrap());
+        assert!(!path.exists());
+        assert!(write_if_changed(&path, "a", false).unwrap());
+        assert!(!write_if_changed(&path, "a", false).unwrap());
+        assert!(write_if_changed(&path, "b", false).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "b");
+
+        assert!(remove_if_present(&path, false).unwrap());
+        assert!(!remove_if_present(&path, false).unwrap());
+    }
+}
@@ -0,0 +1,390 @@
+use crate::history::{affected_domains, HistoryAction, HistoryRecord};
+use crate::hosts::HostsLine;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// Sites listed as the most unblocked (the most tempting ones) by gwd stats
+pub const MOST_UNBLOCKED_COUNT: usize = 5;
+
+// One website's line of `gwd stats`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DomainStats {
+    pub domain: String,
+    // Seconds it was blocked during the period
+    pub blocked_seconds: i64,
+    // Blocks and unblocks recorded during the period
+    pub blocks: usize,
+    pub unblocks: usize,
+    // Mean length in seconds of its blocks that ended during the period, if any did
+    pub average_block_seconds: Option<i64>,
+    // Still blocked as far as the journal knows: blocked with no unblock (or run-out lock) since
+    pub active: bool,
+}
+
+// What `gwd stats` reports for the period from `since` (the start of the journal if None) to
+// `until` (now)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Stats {
+    pub since: Option<i64>,
+    pub until: i64,
+    // Longest blocked first
+    pub domains: Vec<DomainStats>,
+    pub blocks: usize,
+    pub unblocks: usize,
+    pub average_block_seconds: Option<i64>,
+    // Up to MOST_UNBLOCKED_COUNT domains, most unblocks first
+    pub most_unblocked: Vec<String>,
+}
+
+// A domain's block as the journal is replayed
+#[derive(Default)]
+struct Tally {
+    stats: DomainStats,
+    // (start, end of its --for/--until lock) of the block in effect, if any
+    open: Option<(i64, Option<i64>)>,
+    // Lengths of the blocks that ended during the period
+    ended: Vec<i64>,
+}
+
+impl Tally {
+    // Ends the open block at `end`, counting the part of it after `start`
+    fn close(&mut self, end: i64, start: i64) {
+        if let Some((begin, _)) = self.open.take() {
+            self.stats.blocked_seconds += (end - begin.max(start)).max(0);
+            if end >= start {
+                self.ended.push(end - begin);
+            }
+        }
+    }
+
+    // Ends the open block where its lock ran out, if that was by `time`: gwd removes such blocks
+    // on its next run without journaling an unblock
+    fn close_if_expired(&mut self, time: i64, start: i64) {
+        if let Some((_, Some(lock))) = self.open {
+            if lock <= time {
+                self.close(lock, start);
+            }
+        }
+    }
+}
+
+// Replays the journal (oldest first, as all_records returns it) into per-domain statistics for
+// the period from `since` to `now`. The journal may be incomplete: an unblock whose block was
+// rotated out (or happened before gwd kept a journal) still counts as an unblock, and a block
+// without a later unblock counts as still in effect, up to `now` or the end of its lock.
+pub fn build_stats(records: &[HistoryRecord], since: Option<i64>, now: i64) -> Stats {
+    let start = since.unwrap_or(i64::MIN);
+    let mut tallies: BTreeMap<String, Tally> = BTreeMap::new();
+    for record in records {
+        for tally in tallies.values_mut() {
+            tally.close_if_expired(record.timestamp, start);
+        }
+        let in_period = record.timestamp >= start;
+        for domain in affected_domains(record) {
+            let tally = tallies.entry(domain).or_default();
+            match record.action {
+                HistoryAction::Block => {
+                    if in_period {
+                        tally.stats.blocks += 1;
+                    }
+                    let lock = block_lock(record);
+                    match &mut tally.open {
+                        // Blocking it again (e.g. with a longer lock) continues the same block,
+                        // until the later of the two locks; without a lock on either it lasts
+                        // until it is unblocked
+                        Some((_, open_lock)) => *open_lock = later_lock(*open_lock, lock),
+                        None => tally.open = Some((record.timestamp, lock)),
+                    }
+                }
+                HistoryAction::Unblock => {
+                    if in_period {
+                        tally.stats.unblocks += 1;
+                    }
+                    tally.close(record.timestamp, start);
+                }
+            }
+        }
+    }
+
+    let mut stats = Stats {
+        since,
+        until: now,
+        ..Default::default()
+    };
+    let mut ended = Vec::new();
+    for (domain, mut tally) in tallies {
+        tally.close_if_expired(now, start);
+        if let Some((begin, _)) = tally.open.take() {
+            // Still in effect: it counts up to now, but isn't a finished block to average
+            tally.stats.active = true;
+            tally.stats.blocked_seconds += (now - begin.max(start)).max(0);
+        }
+        tally.stats.domain = domain;
+        tally.stats.average_block_seconds = average(&tally.ended);
+        ended.extend(tally.ended);
+        let domain_stats = tally.stats;
+        if domain_stats.blocked_seconds > 0 || domain_stats.blocks > 0 || domain_stats.unblocks > 0
+        {
+            stats.blocks += domain_stats.blocks;
+            stats.unblocks += domain_stats.unblocks;
+            stats.domains.push(domain_stats);
+        }
+    }
+    stats.average_block_seconds = average(&ended);
+
+    let mut tempting: Vec<&DomainStats> = stats
+        .domains
+        .iter()
+        .filter(|domain| domain.unblocks > 0)
+        .collect();
+    tempting.sort_by(|a, b| b.unblocks.cmp(&a.unblocks).then(a.domain.cmp(&b.domain)));
+    stats.most_unblocked = tempting
+        .iter()
+        .take(MOST_UNBLOCKED_COUNT)
+        .map(|domain| domain.domain.clone())
+        .collect();
+    stats.domains.sort_by(|a, b| {
+        b.blocked_seconds
+            .cmp(&a.blocked_seconds)
+            .then(a.domain.cmp(&b.domain))
+    });
+    stats
+}
+
+// The latest --for/--until lock among the lines a block added, if they all have one; a line
+// without a lock keeps the domain blocked until it is unblocked
+fn block_lock(record: &HistoryRecord) -> Option<i64> {
+    let mut latest: Option<i64> = None;
+    for entry in &record.entries {
+        // A line without a lock (or one that can't be read) makes the whole block indefinite
+        let until = HostsLine::parse(entry).gwd_entries().first()?.until?;
+        latest = Some(latest.map_or(until, |latest| latest.max(until)));
+    }
+    latest
+}
+
+// The lock a block keeps when it is renewed: the later of the two, where None (no lock, so
+// until it is unblocked) outlasts any time. Option's own max would rank None lowest.
+fn later_lock(a: Option<i64>, b: Option<i64>) -> Option<i64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        _ => None,
+    }
+}
+
+fn average(durations: &[i64]) -> Option<i64> {
+    if durations.is_empty() {
+        None
+    } else {
+        Some(durations.iter().sum::<i64>() / durations.len() as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(
+        action: HistoryAction,
+        timestamp: i64,
+        domains: &[&str],
+        entries: &[&str],
+    ) -> HistoryRecord {
+        HistoryRecord {
+            action,
+            timestamp,
+            domains: domains.iter().map(|d| d.to_string()).collect(),
+            entries: entries.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    fn domain<'a>(stats: &'a Stats, name: &str) -> &'a DomainStats {
+        stats
+            .domains
+            .iter()
+            .find(|domain| domain.domain == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_stats_pair_blocks_with_unblocks() {
+        let records = vec![
+            at(
+                HistoryAction::Block,
+                100,
+                &["a.com", "b.com"],
+                &[
+                    "0.0.0.0 a.com # Blocked by gwd",
+                    "0.0.0.0 www.a.com # Blocked by gwd",
+                    "0.0.0.0 b.com # Blocked by gwd",
+                ],
+            ),
+            at(
+                HistoryAction::Unblock,
+                400,
+                &["a.com"],
+                &["0.0.0.0 a.com # Blocked by gwd"],
+            ),
+            at(
+                HistoryAction::Block,
+                500,
+                &["a.com"],
+                &["0.0.0.0 a.com # Blocked by gwd"],
+            ),
+            // unblock --all names no domains; they come from the removed lines
+            at(
+                HistoryAction::Unblock,
+                600,
+                &[],
+                &[
+                    "0.0.0.0 a.com # Blocked by gwd",
+                    "0.0.0.0 b.com # Blocked by gwd",
+                ],
+            ),
+            // A block with a lock that ran out at 800, removed without an unblock on record
+            at(
+                HistoryAction::Block,
+                700,
+                &["c.com"],
+                &["0.0.0.0 c.com # Blocked by gwd until 800"],
+            ),
+        ];
+
+        let stats = build_stats(&records, None, 1_000);
+        assert_eq!(
+            stats
+                .domains
+                .iter()
+                .map(|d| d.domain.as_str())
+                .collect::<Vec<_>>(),
+            vec!["b.com", "a.com", "c.com"]
+        );
+        let a = domain(&stats, "a.com");
+        assert_eq!((a.blocks, a.unblocks, a.blocked_seconds), (2, 2, 400));
+        assert_eq!(a.average_block_seconds, Some(200));
+        assert!(!a.active);
+        assert_eq!(domain(&stats, "b.com").blocked_seconds, 500);
+        let c = domain(&stats, "c.com");
+        assert_eq!((c.blocked_seconds, c.unblocks, c.active), (100, 0, false));
+        assert_eq!((stats.blocks, stats.unblocks), (4, 3));
+        assert_eq!(
+            stats.average_block_seconds,
+            Some((300 + 100 + 500 + 100) / 4)
+        );
+        assert_eq!(stats.most_unblocked, vec!["a.com", "b.com"]);
+
+        // Only what happened from 450 on; a.com's block from 100 to 400 is left out
+        let recent = build_stats(&records, Some(450), 1_000);
+        let a = domain(&recent, "a.com");
+        assert_eq!((a.blocks, a.unblocks, a.blocked_seconds), (1, 1, 100));
+        // b.com's block started before the period, so only its part in the period counts
+        assert_eq!(domain(&recent, "b.com").blocked_seconds, 150);
+        assert_eq!(recent.most_unblocked, vec!["a.com", "b.com"]);
+    }
+
+    #[test]
+    fn test_stats_handle_an_incomplete_journal() {
+        let records = vec![
+            // Its block was rotated out of the journal
+            at(
+                HistoryAction::Unblock,
+                100,
+                &["old.com"],
+                &["0.0.0.0 old.com # Blocked by gwd"],
+            ),
+            // Never unblocked: still in effect
+            at(
+                HistoryAction::Block,
+                200,
+                &["still.com"],
+                &["0.0.0.0 still.com # Blocked by gwd"],
+            ),
+            // Locked until after now: still in effect too
+            at(
+                HistoryAction::Block,
+                300,
+                &["locked.com"],
+                &["0.0.0.0 locked.com # Blocked by gwd until 5000"],
+            ),
+        ];
+
+        let stats = build_stats(&records, None, 1_000);
+        let old = domain(&stats, "old.com");
+        assert_eq!((old.unblocks, old.blocked_seconds), (1, 0));
+        assert_eq!(old.average_block_seconds, None);
+        let still = domain(&stats, "still.com");
+        assert!(still.active);
+        assert_eq!(still.blocked_seconds, 800);
+        assert_eq!(still.average_block_seconds, None);
+        assert!(domain(&stats, "locked.com").active);
+        assert_eq!(stats.average_block_seconds, None);
+        assert_eq!(stats.most_unblocked, vec!["old.com"]);
+
+        assert_eq!(
+            build_stats(&[], None, 1_000),
+            Stats {
+                until: 1_000,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_stats_keep_the_longer_lock_of_a_repeated_block() {
+        let records = vec![
+            // Blocked indefinitely, then again --for a while: still blocked after that runs out
+            at(
+                HistoryAction::Block,
+                100,
+                &["a.com"],
+                &["0.0.0.0 a.com # Blocked by gwd"],
+            ),
+            at(
+                HistoryAction::Block,
+                200,
+                &["a.com"],
+                &["0.0.0.0 a.com # Blocked by gwd until 300"],
+            ),
+            // Blocked --for a while, then indefinitely: the lock no longer ends it
+            at(
+                HistoryAction::Block,
+                100,
+                &["b.com"],
+                &["0.0.0.0 b.com # Blocked by gwd until 300"],
+            ),
+            at(
+                HistoryAction::Block,
+                200,
+                &["b.com"],
+                &["0.0.0.0 b.com # Blocked by gwd"],
+            ),
+            // Two locks: the later one ends it
+            at(
+                HistoryAction::Block,
+                100,
+                &["c.com"],
+                &["0.0.0.0 c.com # Blocked by gwd until 600"],
+            ),
+            at(
+                HistoryAction::Block,
+                200,
+                &["c.com"],
+                &["0.0.0.0 c.com # Blocked by gwd until 300"],
+            ),
+        ];
+
+        let stats = build_stats(&records, None, 1_000);
+        for name in ["a.com", "b.com"] {
+            let blocked = domain(&stats, name);
+            assert!(blocked.active, "{}", name);
+            assert_eq!(blocked.blocked_seconds, 900, "{}", name);
+        }
+        let c = domain(&stats, "c.com");
+        assert!(!c.active);
+        assert_eq!(c.blocked_seconds, 500);
+
+        assert_eq!(later_lock(Some(300), Some(600)), Some(600));
+        assert_eq!(later_lock(None, Some(600)), None);
+        assert_eq!(later_lock(Some(600), None), None);
+    }
+}
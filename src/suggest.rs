@@ -0,0 +1,66 @@
+// Largest edit distance at which a blocked domain is offered as "did you mean"; anything further
+// away is more likely a different site than a typo
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+// The blocked domain closest to `domain`, if one is within a couple of typos of it (e.g.
+// "exmaple.com" -> "example.com"). Ties go to the earlier domain in `blocked`.
+pub fn did_you_mean<'a>(domain: &str, blocked: &'a [String]) -> Option<&'a str> {
+    blocked
+        .iter()
+        .map(|candidate| (edit_distance(domain, candidate), candidate))
+        .filter(|(distance, _)| (1..=MAX_SUGGESTION_DISTANCE).contains(distance))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+// Levenshtein distance, counting a swap of two neighbouring characters as one edit since that is
+// the most common typo
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances[i][j]: edits turning the first i characters of a into the first j of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + substitution);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("example.com", "example.com"), 0);
+        assert_eq!(edit_distance("exmaple.com", "example.com"), 1);
+        assert_eq!(edit_distance("exampl.com", "example.com"), 1);
+        assert_eq!(edit_distance("reddit.co", "reddit.com"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_did_you_mean_only_suggests_close_matches() {
+        let blocked = ["reddit.com".to_string(), "example.com".to_string()];
+        assert_eq!(did_you_mean("exmaple.com", &blocked), Some("example.com"));
+        assert_eq!(did_you_mean("redit.com", &blocked), Some("reddit.com"));
+        assert_eq!(did_you_mean("youtube.com", &blocked), None);
+        // An exact match is blocked, not a typo
+        assert_eq!(did_you_mean("reddit.com", &blocked), None);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::error::{AppError, Result};
+use std::io::{self, BufRead, Read};
+
+// Keeps what the user types off the screen (unblock --challenge-hidden) until it's dropped,
+// which puts the terminal back the way it was, on errors and early returns alike
+pub struct HiddenInput {
+    #[cfg(unix)]
+    saved: nix::sys::termios::Termios,
+}
+
+impl HiddenInput {
+    // Turns off echo on stdin. Returns None when stdin isn't a terminal, or on platforms where
+    // gwd can't change the terminal mode (Windows); the input is then read as usual.
+    //
+    // Besides echo, line editing and signal keys are switched off too, so a Ctrl-C can't kill gwd
+    // before the terminal is restored: read_hidden_line sees it as a key instead.
+    #[cfg(unix)]
+    pub fn enable() -> Result<Option<HiddenInput>> {
+        use nix::sys::termios::{
+            tcgetattr, tcsetattr, LocalFlags, SetArg, SpecialCharacterIndices,
+        };
+        use std::io::IsTerminal;
+
+        let stdin = io::stdin();
+        if !stdin.is_terminal() {
+            return Ok(None);
+        }
+        let saved = tcgetattr(&stdin).map_err(terminal_error)?;
+        let mut hidden = saved.clone();
+        hidden
+            .local_flags
+            .remove(LocalFlags::ECHO | LocalFlags::ICANON | LocalFlags::ISIG);
+        hidden.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+        hidden.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+        tcsetattr(&stdin, SetArg::TCSAFLUSH, &hidden).map_err(terminal_error)?;
+        Ok(Some(HiddenInput { saved }))
+    }
+
+    #[cfg(not(unix))]
+    pub fn enable() -> Result<Option<HiddenInput>> {
+        Ok(None)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for HiddenInput {
+    fn drop(&mut self) {
+        use nix::sys::termios::{tcsetattr, SetArg};
+        if let Err(e) = tcsetattr(io::stdin(), SetArg::TCSANOW, &self.saved) {
+            eprintln!("Warning: could not restore the terminal: {}", e);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn terminal_error(e: nix::Error) -> AppError {
+    AppError::Io(format!("Failed to change the terminal mode: {}", e))
+}
+
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+const CTRL_U: u8 = 0x15;
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+
+// Reads one line typed while HiddenInput is active. With line editing off, gwd does the editing
+// itself: Backspace deletes a character, Ctrl-U the whole line, and Enter or Ctrl-D (or the end
+// of the input) finishes it. Ctrl-C gives AppError::Interrupted.
+pub fn read_hidden_line(input: &mut impl BufRead) -> Result<String> {
+    let mut line: Vec<u8> = Vec::new();
+    for byte in input.by_ref().bytes() {
+        match byte? {
+            b'\r' | b'\n' | CTRL_D => break,
+            CTRL_C => return Err(AppError::Interrupted),
+            CTRL_U => line.clear(),
+            BACKSPACE | DELETE => {
+                // Drop a whole UTF-8 character, not just its last byte
+                while let Some(last) = line.pop() {
+                    if last & 0b1100_0000 != 0b1000_0000 {
+                        break;
+                    }
+                }
+            }
+            byte => line.push(byte),
+        }
+    }
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_hidden_line_edits_like_a_terminal() {
+        let read = |input: &[u8]| read_hidden_line(&mut &input[..]);
+        assert_eq!(read(b"apple pear\rrest").unwrap(), "apple pear");
+        assert_eq!(read(b"applx\x7fe\n").unwrap(), "apple");
+        assert_eq!(read(b"wrong\x15right\r").unwrap(), "right");
+        assert_eq!(read("café\x7f\x08\r".as_bytes()).unwrap(), "ca");
+        assert_eq!(read(b"partial").unwrap(), "partial");
+        assert_eq!(read(b"\x04").unwrap(), "");
+        assert!(matches!(read(b"app\x03le\r"), Err(AppError::Interrupted)));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::error::{AppError, Result};
+use chrono::{DateTime, Local, NaiveTime, SecondsFormat, TimeZone, Utc};
+use std::time::Duration;
+
+// Parses a compact duration such as "2h", "30m", "1h30m" or "45s".
+// A bare number is treated as minutes.
+pub fn parse_duration(input: &str) -> Result<Duration> {
+    let invalid = || AppError::InvalidDuration(input.to_string());
+    let trimmed = input.trim().to_lowercase();
+    if trimmed.is_empty() {
+        return Err(invalid());
+    }
+    if let Ok(minutes) = trimmed.parse::<u64>() {
+        return Ok(Duration::from_secs(minutes * 60));
+    }
+
+    let mut total_secs: u64 = 0;
+    let mut number = String::new();
+    for c in trimmed.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit_secs = match c {
+            'd' => 86_400,
+            'h' => 3_600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = number.parse().map_err(|_| invalid())?;
+        total_secs += value * unit_secs;
+        number.clear();
+    }
+    // Reject trailing digits without a unit (e.g. "1h30")
+    if !number.is_empty() || total_secs == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(total_secs))
+}
+
+// Resolves a wall-clock time such as "17:00" to the next occurrence in local time
+// (today if it's still ahead, otherwise tomorrow).
+pub fn resolve_until(input: &str, now: DateTime<Local>) -> Result<DateTime<Local>> {
+    let time = NaiveTime::parse_from_str(input.trim(), "%H:%M")
+        .map_err(|_| AppError::InvalidDuration(input.to_string()))?;
+    let mut date = now.date_naive();
+    if time <= now.time() {
+        date = date
+            .succ_opt()
+            .ok_or_else(|| AppError::InvalidDuration(input.to_string()))?;
+    }
+    Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .ok_or_else(|| AppError::InvalidDuration(input.to_string()))
+}
+
+// Current time as a Unix timestamp (seconds)
+pub fn now_timestamp() -> i64 {
+    Utc::now().timestamp()
+}
+
+// Formats a duration compactly, e.g. "1h23m", "5m", "42s"
+pub fn format_duration(duration: &Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes) = (secs / 86_400, (secs % 86_400) / 3_600, (secs % 3_600) / 60);
+    if days > 0 {
+        format!("{}d{}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h{}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+// Formats a duration as a countdown clock, e.g. "24:59" or "1:05:00"
+pub fn format_clock(duration: &Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3_600, (secs % 3_600) / 60, secs % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+// Formats a Unix timestamp as an RFC 3339 UTC time, e.g. "2024-01-15T09:30:00Z"
+pub fn format_rfc3339(timestamp: i64) -> Option<String> {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+// Parses an RFC 3339 time (any offset) into a Unix timestamp
+pub fn parse_rfc3339(input: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(input)
+        .ok()
+        .map(|time| time.timestamp())
+}
+
+// Describes how long ago something happened in the largest whole unit, e.g. "3 days ago"
+pub fn format_age(seconds: i64) -> String {
+    let (count, unit) = match seconds.max(0) {
+        s if s < 60 => return "just now".to_string(),
+        s if s < 3_600 => (s / 60, "minute"),
+        s if s < 86_400 => (s / 3_600, "hour"),
+        s => (s / 86_400, "day"),
+    };
+    format!(
+        "{} {}{} ago",
+        count,
+        unit,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+// When the system last booted, as a Unix timestamp, for dropping block --until-reboot entries.
+// None where gwd can't find out (other platforms, or the source isn't readable); such entries
+// then stay until they are unblocked.
+#[cfg(target_os = "linux")]
+pub fn boot_time() -> Option<i64> {
+    parse_proc_stat_btime(&std::fs::read_to_string("/proc/stat").ok()?)
+}
+
+#[cfg(target_os = "macos")]
+pub fn boot_time() -> Option<i64> {
+    let output = std::process::Command::new("sysctl")
+        .args(["-n", "kern.boottime"])
+        .output()
+        .ok()?;
+    parse_kern_boottime(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(target_os = "windows")]
+pub fn boot_time() -> Option<i64> {
+    // Environment.TickCount64 is GetTickCount64: milliseconds since the system started
+    let output = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-Command", "[Environment]::TickCount64"])
+        .output()
+        .ok()?;
+    let uptime_ms: i64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()?;
+    Some(now_timestamp() - uptime_ms / 1_000)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn boot_time() -> Option<i64> {
+    None
+}
+
+// The "btime 1699999999" line of /proc/stat
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_proc_stat_btime(content: &str) -> Option<i64> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|value| value.trim().parse().ok())
+}
+
+// The output of `sysctl -n kern.boottime`, e.g. "{ sec = 1699999999, usec = 12345 } Tue Nov 14"
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_kern_boottime(output: &str) -> Option<i64> {
+    let (_, rest) = output.split_once("sec =")?;
+    rest.split(',').next()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7_200));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1_800));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5_400));
+        assert_eq!(parse_duration("45s").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86_400));
+        assert_eq!(parse_duration("25").unwrap(), Duration::from_secs(1_500));
+    }
+
+    #[test]
+    fn test_parse_duration_invalid() {
+        for input in ["", "h", "1h30", "2x", "0m", "-5m"] {
+            assert!(parse_duration(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_resolve_until() {
+        let now = Local.with_ymd_and_hms(2024, 1, 15, 9, 30, 0).unwrap();
+        assert_eq!(
+            resolve_until("17:00", now).unwrap(),
+            Local.with_ymd_and_hms(2024, 1, 15, 17, 0, 0).unwrap()
+        );
+        // Times already past roll over to tomorrow
+        assert_eq!(
+            resolve_until("08:00", now).unwrap(),
+            Local.with_ymd_and_hms(2024, 1, 16, 8, 0, 0).unwrap()
+        );
+        assert!(resolve_until("25:00", now).is_err());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(&Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(&Duration::from_secs(300)), "5m");
+        assert_eq!(format_duration(&Duration::from_secs(4_980)), "1h23m");
+        assert_eq!(format_duration(&Duration::from_secs(90_000)), "1d1h");
+    }
+
+    #[test]
+    fn test_format_clock() {
+        assert_eq!(format_clock(&Duration::from_secs(1_499)), "24:59");
+        assert_eq!(format_clock(&Duration::from_secs(5)), "00:05");
+        assert_eq!(format_clock(&Duration::from_secs(3_900)), "1:05:00");
+    }
+
+    #[test]
+    fn test_rfc3339_round_trip() {
+        assert_eq!(
+            format_rfc3339(1_705_311_000).as_deref(),
+            Some("2024-01-15T09:30:00Z")
+        );
+        assert_eq!(parse_rfc3339("2024-01-15T09:30:00Z"), Some(1_705_311_000));
+        assert_eq!(
+            parse_rfc3339("2024-01-15T10:30:00+01:00"),
+            Some(1_705_311_000)
+        );
+        assert_eq!(parse_rfc3339("yesterday"), None);
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(5), "just now");
+        assert_eq!(format_age(60), "1 minute ago");
+        assert_eq!(format_age(7_200), "2 hours ago");
+        assert_eq!(format_age(3 * 86_400 + 5), "3 days ago");
+    }
+
+    #[test]
+    fn test_parse_boot_time() {
+        assert_eq!(
+            parse_proc_stat_btime("cpu  10 0 5 100\nintr 42\nbtime 1699999999\nprocesses 7\n"),
+            Some(1_699_999_999)
+        );
+        assert_eq!(parse_proc_stat_btime("cpu  10 0 5 100\n"), None);
+        assert_eq!(
+            parse_kern_boottime("{ sec = 1699999999, usec = 12345 } Tue Nov 14 22:13:19 2023\n"),
+            Some(1_699_999_999)
+        );
+        assert_eq!(parse_kern_boottime(""), None);
+    }
+}
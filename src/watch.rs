@@ -0,0 +1,102 @@
+use crate::error::{AppError, Result};
+use crate::interrupt::InterruptGuard;
+use notify::{Event, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+// How often the wait for a change looks for a Ctrl-C
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+// Changes this close together are handled as one: an editor saving the file, or gwd replacing it
+// through a temp file, fires several events
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+// Calls `on_change` after every change to the file at `path` (gwd watch), until Ctrl-C. The
+// directory is watched rather than the file itself, since a file replaced by a rename (as editors
+// and gwd do) would end a watch on the old one. An error from `on_change` ends the watch, except
+// AppError::Interrupted, which is a Ctrl-C that arrived while it ran.
+pub fn watch_file(path: &Path, mut on_change: impl FnMut() -> Result<()>) -> Result<()> {
+    let directory = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        // The receiver only goes away once watching is over
+        let _ = sender.send(event);
+    })
+    .map_err(|e| watch_error(path, e))?;
+    watcher
+        .watch(directory, RecursiveMode::NonRecursive)
+        .map_err(|e| watch_error(path, e))?;
+
+    let interrupt = InterruptGuard::install()?;
+    while !interrupt.interrupted() {
+        match receiver.recv_timeout(INTERRUPT_POLL_INTERVAL) {
+            Ok(Ok(event)) if concerns(&event, path) => {
+                while receiver.recv_timeout(SETTLE_TIME).is_ok() {}
+                if interrupt.interrupted() {
+                    break;
+                }
+                match on_change() {
+                    Err(AppError::Interrupted) => break,
+                    result => result?,
+                }
+            }
+            Ok(Ok(_)) | Err(RecvTimeoutError::Timeout) => {}
+            Ok(Err(e)) => return Err(watch_error(path, e)),
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(AppError::Io(format!("Stopped watching {:?}", path)))
+            }
+        }
+    }
+    Ok(())
+}
+
+// Whether an event in the watched directory is about the watched file. Only the names are
+// compared, as the event paths are built from the directory as it was given.
+fn concerns(event: &Event, path: &Path) -> bool {
+    event
+        .paths
+        .iter()
+        .any(|changed| changed.file_name() == path.file_name())
+}
+
+fn watch_error(path: &Path, e: notify::Error) -> AppError {
+    AppError::Io(format!("Failed to watch {:?}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interrupt::simulate_interrupt;
+    use std::fs;
+
+    #[test]
+    fn test_watch_reports_changes_to_the_file_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts");
+        fs::write(&path, "127.0.0.1 localhost\n").unwrap();
+
+        let writer = {
+            let dir = dir.path().to_path_buf();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(300));
+                fs::write(dir.join("unrelated"), "x").unwrap();
+                std::thread::sleep(Duration::from_millis(800));
+                fs::write(dir.join("hosts"), "").unwrap();
+            })
+        };
+        let mut changes = Vec::new();
+        let result = watch_file(&path, || {
+            changes.push(fs::read_to_string(&path).unwrap());
+            // Stands in for the Ctrl-C that ends a real watch
+            simulate_interrupt(true);
+            Ok(())
+        });
+        simulate_interrupt(false);
+        writer.join().unwrap();
+        result.unwrap();
+        assert_eq!(changes, vec![""]);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::error::{AppError, Result};
+use crate::hosts::{read_domain_file, validate_domain};
+use crate::profile::config_dir;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+// User-maintained list of known hostnames that patterns are expanded against, next to config.toml
+const SUBDOMAINS_FILE_NAME: &str = "subdomains.txt";
+
+// Subdomains tried under the base of every "*.example.com" pattern. The hosts file can't match
+// wildcards, so a pattern only ever covers the names gwd knows about; this list catches the usual
+// web, app and ad/tracking hosts.
+const COMMON_SUBDOMAINS: &[&str] = &[
+    "www",
+    "m",
+    "mobile",
+    "app",
+    "api",
+    "cdn",
+    "static",
+    "assets",
+    "img",
+    "images",
+    "media",
+    "video",
+    "login",
+    "accounts",
+    "ad",
+    "ads",
+    "adserver",
+    "adservice",
+    "pagead",
+    "pagead2",
+    "pixel",
+    "track",
+    "tracking",
+    "stats",
+    "analytics",
+    "metrics",
+    "telemetry",
+    "events",
+    "log",
+    "logs",
+];
+
+// Whether a command-line domain is a pattern to expand rather than a single hostname
+pub fn is_pattern(domain: &str) -> bool {
+    domain.contains('*')
+}
+
+// Location of the known-hostnames list (one hostname per line, '#' starts a comment)
+pub fn subdomains_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join(SUBDOMAINS_FILE_NAME))
+}
+
+// Hostnames from subdomains.txt; a missing file just means the user hasn't listed any
+pub fn load_known_hostnames() -> Result<Vec<String>> {
+    load_known_hostnames_at(&subdomains_path()?)
+}
+
+pub fn load_known_hostnames_at(path: &Path) -> Result<Vec<String>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(read_domain_file(path, false)?.domains)
+}
+
+// The label shared by every entry a pattern expands to, so `unblock --label` (or unblocking the
+// pattern itself) removes them as a unit, e.g. "*.doubleclick.net" -> "wildcard-doubleclick-net"
+pub fn pattern_label(pattern: &str) -> String {
+    let name = normalize(pattern)
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    format!("wildcard-{}", name)
+}
+
+// Expands a pattern into the concrete hostnames to block. "*.example.com" gives example.com
+// itself, the common subdomains above and every known hostname under it; any other pattern
+// (e.g. "ads*.example.com") is only matched against the known hostnames. '*' matches one or
+// more characters, dots included. Errors when the pattern is too broad or matches nothing.
+pub fn expand_pattern(pattern: &str, known: &[String]) -> Result<Vec<String>> {
+    let normalized = normalize(pattern);
+    let invalid = |reason: &str| AppError::InvalidDomain(format!("'{}' ({})", pattern, reason));
+    if let Some(c) = normalized
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '*')))
+    {
+        return Err(invalid(&format!(
+            "'{}' is not allowed in a pattern; use letters, digits, '-', '.' and '*'",
+            c
+        )));
+    }
+    // Everything after the last '*' must name at least a registrable domain, so "*.com" or "*"
+    // can't sweep in half the internet
+    let suffix = normalized.rsplit('*').next().unwrap_or("");
+    if !suffix.trim_start_matches('.').contains('.') {
+        return Err(invalid(
+            "a pattern needs a fixed domain after the last '*', e.g. *.example.com",
+        ));
+    }
+
+    let mut hostnames = Vec::new();
+    if let Some(base) = normalized
+        .strip_prefix("*.")
+        .filter(|base| !is_pattern(base))
+    {
+        hostnames.push(base.to_string());
+        hostnames.extend(
+            COMMON_SUBDOMAINS
+                .iter()
+                .map(|sub| format!("{}.{}", sub, base)),
+        );
+    }
+    let matcher = glob_regex(&normalized)?;
+    hostnames.extend(known.iter().filter(|host| matcher.is_match(host)).cloned());
+
+    let mut expanded: Vec<String> = Vec::new();
+    for hostname in hostnames {
+        let hostname = validate_domain(&hostname, false)?;
+        if !expanded.contains(&hostname) {
+            expanded.push(hostname);
+        }
+    }
+    if expanded.is_empty() {
+        return Err(invalid(&format!(
+            "no known hostname matches; list them in {}",
+            SUBDOMAINS_FILE_NAME
+        )));
+    }
+    Ok(expanded)
+}
+
+fn normalize(pattern: &str) -> String {
+    pattern.trim().trim_end_matches('.').to_lowercase()
+}
+
+fn glob_regex(pattern: &str) -> Result<Regex> {
+    let body = pattern
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join("[a-z0-9.-]+");
+    Regex::new(&format!("^{}$", body))
+        .map_err(|e| AppError::InvalidDomain(format!("'{}' ({})", pattern, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn known() -> Vec<String> {
+        ["stats.g.doubleclick.net", "ads1.example.com", "example.com"]
+            .iter()
+            .map(|host| host.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_leading_wildcard() {
+        let expanded = expand_pattern("*.DoubleClick.net.", &known()).unwrap();
+        assert_eq!(expanded[0], "doubleclick.net");
+        assert!(expanded.contains(&"www.doubleclick.net".to_string()));
+        assert!(expanded.contains(&"ads.doubleclick.net".to_string()));
+        assert!(expanded.contains(&"stats.g.doubleclick.net".to_string()));
+        assert_eq!(expanded.len(), COMMON_SUBDOMAINS.len() + 2);
+    }
+
+    #[test]
+    fn test_expand_inner_wildcard_uses_known_hostnames_only() {
+        assert_eq!(
+            expand_pattern("ads*.example.com", &known()).unwrap(),
+            vec!["ads1.example.com"]
+        );
+        assert!(expand_pattern("ads*.example.org", &known()).is_err());
+    }
+
+    #[test]
+    fn test_expand_rejects_broad_or_invalid_patterns() {
+        for pattern in [
+            "*",
+            "*.com",
+            "example.*",
+            "*.exa mple.com",
+            "*.ex_ample.com",
+        ] {
+            assert!(expand_pattern(pattern, &known()).is_err(), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_pattern_label() {
+        assert_eq!(
+            pattern_label("*.doubleclick.net"),
+            "wildcard-doubleclick-net"
+        );
+        assert_eq!(
+            pattern_label("ads*.Example.com"),
+            "wildcard-ads-example-com"
+        );
+        assert!(is_pattern("*.x.com"));
+        assert!(!is_pattern("x.com"));
+    }
+
+    #[test]
+    fn test_missing_subdomains_file_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SUBDOMAINS_FILE_NAME);
+        assert!(load_known_hostnames_at(&path).unwrap().is_empty());
+        std::fs::write(&path, "# trackers\nstats.g.doubleclick.net\nnot a host\n").unwrap();
+        assert_eq!(
+            load_known_hostnames_at(&path).unwrap(),
+            vec!["stats.g.doubleclick.net"]
+        );
+    }
+}